@@ -1,13 +1,25 @@
 pub mod debug;
+mod opll;
+mod state;
 mod synth;
 
 use std::cell::RefCell;
 use std::rc::Rc;
 
+use serde::{Deserialize, Serialize};
+
 use crate::emulator::clock::Ticker;
 use crate::emulator::memory::{Reader, Writer};
+use crate::emulator::region::Region;
+
+use self::synth::{DMC, Noise, Sweep, Triangle};
+
+// Expansion audio reuses the pulse channel.
+pub use self::synth::Pulse;
 
-use self::synth::{DMC, Noise, Pulse, Sweep, Triangle};
+// FM synthesis for the VRC7.
+pub use self::opll::EnvelopePhase as OpllEnvelopePhase;
+pub use self::opll::{Opll, VRC7_PATCHES};
 
 pub trait AudioOut {
     fn emit(&mut self, sample: f32);
@@ -19,19 +31,32 @@ impl<A: AudioOut> AudioOut for Rc<RefCell<A>> {
     }
 }
 
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
-enum SequenceMode {
+// Sound channels on the cartridge, which are mixed in with the APU's own.
+pub trait ExpansionAudio {
+    // Called once per APU cycle.  Returns the channels' output.
+    fn clock(&mut self) -> f32;
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub enum SequenceMode {
     FourStep,
     FiveStep,
 }
 
-const LENGTH_COUNTER_LOOKUP: [u8; 0x20] = [
+pub const LENGTH_COUNTER_LOOKUP: [u8; 0x20] = [
     10, 254, 20, 2, 40, 4, 80, 6, 160, 8, 60, 10, 14, 12, 26, 14, 12, 16, 24, 18, 48, 20, 96, 22,
     192, 24, 72, 26, 16, 28, 32, 30,
 ];
 
+// Frame sequencer step timings.
+// The first 4 steps make up the 4-step sequence, and the 5-step sequence skips the 4th.
+const NTSC_FRAME_STEPS: [u64; 5] = [3729, 7457, 11186, 14915, 18641];
+const PAL_FRAME_STEPS: [u64; 5] = [4156, 8313, 12469, 16626, 20782];
+
 pub struct APU {
     output: Box<dyn AudioOut>,
+    expansion_audio: Box<dyn ExpansionAudio>,
+    region: Region,
 
     sequence_mode: SequenceMode,
     cycle_counter: u64,
@@ -46,9 +71,16 @@ pub struct APU {
 }
 
 impl APU {
-    pub fn new(output: Box<dyn AudioOut>, prg_rom: Box<dyn Reader>) -> APU {
+    pub fn new(
+        output: Box<dyn AudioOut>,
+        prg_rom: Box<dyn Reader>,
+        expansion_audio: Box<dyn ExpansionAudio>,
+        region: Region,
+    ) -> APU {
         APU {
             output,
+            expansion_audio,
+            region,
 
             sequence_mode: SequenceMode::FourStep,
             cycle_counter: 0,
@@ -80,20 +112,42 @@ impl APU {
         self.triangle.clock_length();
         self.noise.clock_length();
     }
+
+    // Dendy uses the NTSC APU, it's just clocked faster.
+    fn frame_steps(&self) -> &'static [u64; 5] {
+        match self.region {
+            Region::PAL => &PAL_FRAME_STEPS,
+            Region::NTSC | Region::Dendy => &NTSC_FRAME_STEPS,
+        }
+    }
+
+    fn noise_period(&self, ix: u8) -> u16 {
+        match self.region {
+            Region::PAL => Noise::PAL_PERIOD_LOOKUP[ix as usize],
+            Region::NTSC | Region::Dendy => Noise::PERIOD_LOOKUP[ix as usize],
+        }
+    }
+
+    fn dmc_period(&self, ix: u8) -> u16 {
+        match self.region {
+            Region::PAL => DMC::PAL_PERIOD_LOOKUP[ix as usize],
+            Region::NTSC | Region::Dendy => DMC::PERIOD_LOOKUP[ix as usize],
+        }
+    }
 }
 
 impl Ticker for APU {
     fn tick(&mut self) -> u32 {
         self.cycle_counter += 1;
+        let steps = self.frame_steps();
         match self.sequence_mode {
             SequenceMode::FourStep => match self.cycle_counter {
-                3729 => self.clock_linear_and_envelope(),
-                7457 => {
+                c if c == steps[0] || c == steps[2] => self.clock_linear_and_envelope(),
+                c if c == steps[1] => {
                     self.clock_linear_and_envelope();
                     self.clock_length_counters();
                 }
-                11186 => self.clock_linear_and_envelope(),
-                14915 => {
+                c if c == steps[3] => {
                     self.clock_linear_and_envelope();
                     self.clock_length_counters();
                     self.cycle_counter = 0;
@@ -102,13 +156,12 @@ impl Ticker for APU {
                 _ => (),
             },
             SequenceMode::FiveStep => match self.cycle_counter {
-                3729 => self.clock_linear_and_envelope(),
-                7457 => {
+                c if c == steps[0] || c == steps[2] => self.clock_linear_and_envelope(),
+                c if c == steps[1] => {
                     self.clock_linear_and_envelope();
                     self.clock_length_counters();
                 }
-                11186 => self.clock_linear_and_envelope(),
-                18641 => {
+                c if c == steps[4] => {
                     self.clock_linear_and_envelope();
                     self.clock_length_counters();
                     self.cycle_counter = 0;
@@ -135,7 +188,8 @@ impl Ticker for APU {
 
         let pulse_out = 0.00752 * (p1 + p2);
         let tnd_out = (0.00851 * t) + (0.00494 * n) + (0.00335 * dmc);
-        self.output.emit(pulse_out + tnd_out);
+        let expansion_out = self.expansion_audio.clock();
+        self.output.emit(pulse_out + tnd_out + expansion_out);
         1
     }
 }
@@ -192,9 +246,8 @@ impl Writer for APU {
             }
             0x400E => {
                 self.noise.mode = byte & 0x80 != 0;
-                self.noise
-                    .timer
-                    .set_period(Noise::PERIOD_LOOKUP[(byte & 0x0F) as usize]);
+                let period = self.noise_period(byte & 0x0F);
+                self.noise.timer.set_period(period);
             }
             0x400F => {
                 self.noise.length = LENGTH_COUNTER_LOOKUP[(byte >> 3) as usize];
@@ -203,9 +256,8 @@ impl Writer for APU {
             0x4010 => {
                 self.dmc.irq_enabled = byte & 0x80 != 0;
                 self.dmc.loop_flag = byte & 0x40 != 0;
-                self.dmc
-                    .timer
-                    .set_period(DMC::PERIOD_LOOKUP[(byte & 0x0F) as usize]);
+                let period = self.dmc_period(byte & 0x0F);
+                self.dmc.timer.set_period(period);
             }
             0x4011 => {
                 self.dmc.volume = byte & 0x7F;
@@ -294,12 +346,12 @@ impl Reader for APU {
                 if self.dmc.bytes_remaining != 0 {
                     status |= 1 << 4
                 };
-                if self.dmc.irq_flag {
-                    status |= 1 << 5
-                };
                 if self.irq_flag {
                     status |= 1 << 6
                 };
+                if self.dmc.irq_flag {
+                    status |= 1 << 7
+                };
 
                 self.irq_flag = false;
                 status
@@ -309,7 +361,7 @@ impl Reader for APU {
     }
 }
 
-fn write_first_pulse_register(pulse: &mut Pulse, byte: u8) {
+pub fn write_first_pulse_register(pulse: &mut Pulse, byte: u8) {
     pulse.sequence = byte >> 6;
     // These 2 flags share the same bit.
     pulse.envelope.loop_flag = (byte & 0x20) != 0;
@@ -319,19 +371,19 @@ fn write_first_pulse_register(pulse: &mut Pulse, byte: u8) {
     pulse.envelope.restart();
 }
 
-fn write_sweep_register(sweep: &mut Sweep, byte: u8) {
+pub fn write_sweep_register(sweep: &mut Sweep, byte: u8) {
     sweep.enabled = byte & 0x80 != 0;
     sweep.divider.set_period(((byte & 0x70) >> 4) as u16);
     sweep.negate_flag = byte & 0x08 != 0;
     sweep.shift_count = byte & 0x07;
 }
 
-fn write_second_pulse_register(pulse: &mut Pulse, byte: u8) {
+pub fn write_second_pulse_register(pulse: &mut Pulse, byte: u8) {
     let new_period = (pulse.timer.period() & 0xFF00) | (byte as u16);
     pulse.timer.set_period(new_period);
 }
 
-fn write_third_pulse_register(pulse: &mut Pulse, byte: u8) {
+pub fn write_third_pulse_register(pulse: &mut Pulse, byte: u8) {
     pulse.length = LENGTH_COUNTER_LOOKUP[(byte >> 3) as usize];
     let new_period = (pulse.timer.period() & 0x00FF) | (((byte & 0x7) as u16) << 8);
     pulse.timer.set_period(new_period);
@@ -0,0 +1,473 @@
+use std::f32::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use crate::emulator::state::{OpllChannelState, OpllOperatorState, OpllState, SaveState};
+
+// A YM2413 (OPLL) compatible FM synthesizer, as used in the VRC7.
+//
+// Each channel is a modulator operator which feeds the phase of a carrier operator.  Instruments
+// come from a ROM of fixed patches, plus one custom patch set through registers $00-$07.
+//
+// The OPLL's logarithmic lookup tables are replaced by the formulas they approximate, so this
+// isn't bit exact.  Rhythm mode isn't implemented, since the VRC7 doesn't have it.
+//
+// Call `sample` at the chip's sample rate, which is its clock divided by 72.
+pub struct Opll {
+    patches: &'static [[u8; 8]; 15],
+    custom_patch: [u8; 8],
+    channels: Vec<Channel>,
+
+    // Low frequency oscillators for tremolo and vibrato, counted in samples.
+    am_counter: u32,
+    pm_counter: u32,
+}
+
+// The VRC7's instrument ROM, for instruments 1-15.
+pub const VRC7_PATCHES: [[u8; 8]; 15] = [
+    [0x03, 0x21, 0x05, 0x06, 0xE8, 0x81, 0x42, 0x27],
+    [0x13, 0x41, 0x14, 0x0D, 0xD8, 0xF6, 0x23, 0x12],
+    [0x11, 0x11, 0x08, 0x08, 0xFA, 0xB2, 0x20, 0x12],
+    [0x31, 0x61, 0x0C, 0x07, 0xA8, 0x64, 0x61, 0x27],
+    [0x32, 0x21, 0x1E, 0x06, 0xE1, 0x76, 0x01, 0x28],
+    [0x02, 0x01, 0x06, 0x00, 0xA3, 0xE2, 0xF4, 0xF4],
+    [0x21, 0x61, 0x1D, 0x07, 0x82, 0x81, 0x11, 0x07],
+    [0x23, 0x21, 0x22, 0x17, 0xA2, 0x72, 0x01, 0x17],
+    [0x35, 0x11, 0x25, 0x00, 0x40, 0x73, 0x72, 0x01],
+    [0xB5, 0x01, 0x0F, 0x0F, 0xA8, 0xA5, 0x51, 0x02],
+    [0x17, 0xC1, 0x24, 0x07, 0xF8, 0xF8, 0x22, 0x12],
+    [0x71, 0x23, 0x11, 0x06, 0x65, 0x74, 0x18, 0x16],
+    [0x01, 0x02, 0xD3, 0x05, 0xC9, 0x95, 0x03, 0x02],
+    [0x61, 0x63, 0x0C, 0x00, 0x94, 0xC0, 0x33, 0xF6],
+    [0x21, 0x72, 0x0D, 0x00, 0xC1, 0xD5, 0x56, 0x06],
+];
+
+// Phases are 19-bit fractions of a cycle.
+const PHASE_BITS: u32 = 19;
+
+// Attenuation from the envelope is in 0.375dB steps, and 128 steps is silent.
+const ENVELOPE_STEP_DB: f32 = 0.375;
+const ENVELOPE_SILENT: f32 = 128.0;
+
+// How far the carrier's phase moves, in cycles, for a modulator at full volume.
+const MODULATION_CYCLES: f32 = 2.0;
+
+// Frequency multipliers, doubled so that they're all whole numbers.
+const MULTIPLIERS_X2: [u32; 16] = [1, 2, 4, 6, 8, 10, 12, 14, 16, 18, 20, 20, 24, 24, 30, 30];
+
+// Key scale level attenuation in dB by the top 4 bits of the F-number, for octave 7 at 6dB/octave.
+const KEY_SCALE_DB: [f32; 16] = [
+    0.0, 18.0, 24.0, 27.75, 30.0, 32.25, 33.75, 35.25, 36.0, 37.5, 38.25, 39.0, 39.75, 40.5, 41.25,
+    42.0,
+];
+
+// Vibrato offsets to the F-number, by its top 3 bits and the vibrato step.
+const VIBRATO: [[i32; 8]; 8] = [
+    [0, 0, 0, 0, 0, 0, 0, 0],
+    [0, 0, 1, 0, 0, 0, -1, 0],
+    [0, 1, 2, 1, 0, -1, -2, -1],
+    [0, 1, 3, 1, 0, -1, -3, -1],
+    [0, 2, 4, 2, 0, -2, -4, -2],
+    [0, 2, 5, 2, 0, -2, -5, -2],
+    [0, 3, 6, 3, 0, -3, -6, -3],
+    [0, 3, 7, 3, 0, -3, -7, -3],
+];
+
+// Tremolo is a triangle wave of 0 to 13 envelope steps, lasting this many samples.
+const TREMOLO_PERIOD: u32 = 13440;
+// Vibrato steps every this many samples.
+const VIBRATO_STEP_SAMPLES: u32 = 1024;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum EnvelopePhase {
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+}
+
+struct Operator {
+    phase: u32,
+    envelope_phase: EnvelopePhase,
+    envelope_level: f32,
+    output: f32,
+}
+
+struct Channel {
+    // $10-$18, $20-$28 and $30-$38.
+    fnum: u16,
+    block: u8,
+    key_on: bool,
+    sustain: bool,
+    instrument: u8,
+    volume: u8,
+
+    modulator: Operator,
+    carrier: Operator,
+    // The modulator's last 2 outputs, for feedback.
+    feedback: [f32; 2],
+}
+
+// One operator's half of a patch.
+struct OperatorPatch {
+    tremolo: bool,
+    vibrato: bool,
+    sustained: bool,
+    key_scale_rate: bool,
+    multiplier: u8,
+    key_scale_level: u8,
+    rectified: bool,
+    attack_rate: u8,
+    decay_rate: u8,
+    sustain_level: u8,
+    release_rate: u8,
+}
+
+impl OperatorPatch {
+    fn new(patch: &[u8; 8], carrier: bool) -> OperatorPatch {
+        let ix = carrier as usize;
+        OperatorPatch {
+            tremolo: patch[ix] & 0x80 != 0,
+            vibrato: patch[ix] & 0x40 != 0,
+            sustained: patch[ix] & 0x20 != 0,
+            key_scale_rate: patch[ix] & 0x10 != 0,
+            multiplier: patch[ix] & 0x0F,
+            key_scale_level: patch[2 + ix] >> 6,
+            rectified: patch[3] & (if carrier { 0x10 } else { 0x08 }) != 0,
+            attack_rate: patch[4 + ix] >> 4,
+            decay_rate: patch[4 + ix] & 0x0F,
+            sustain_level: patch[6 + ix] >> 4,
+            release_rate: patch[6 + ix] & 0x0F,
+        }
+    }
+}
+
+impl Operator {
+    fn new() -> Operator {
+        Operator {
+            phase: 0,
+            envelope_phase: EnvelopePhase::Release,
+            envelope_level: ENVELOPE_SILENT,
+            output: 0.0,
+        }
+    }
+
+    fn key_on(&mut self) {
+        self.phase = 0;
+        self.envelope_phase = EnvelopePhase::Attack;
+    }
+
+    fn key_off(&mut self) {
+        self.envelope_phase = EnvelopePhase::Release;
+    }
+
+    // How many envelope steps a rate moves per sample.  Each increase in the rate is a quarter
+    // of an octave faster.
+    fn envelope_steps(rate: u8, key_scale: u8) -> f32 {
+        if rate == 0 {
+            return 0.0;
+        }
+        let rate = (rate * 4 + key_scale).min(63) as i32;
+        (4 + (rate & 0x3)) as f32 / 4.0 * 2f32.powi((rate >> 2) - 13)
+    }
+
+    fn clock_envelope(&mut self, patch: &OperatorPatch, key_scale: u8, channel_sustain: bool) {
+        match self.envelope_phase {
+            EnvelopePhase::Attack => {
+                if patch.attack_rate == 15 {
+                    self.envelope_level = 0.0;
+                } else {
+                    // Attack is exponential.
+                    let steps = Operator::envelope_steps(patch.attack_rate, key_scale);
+                    self.envelope_level -= steps * (self.envelope_level / 8.0 + 1.0);
+                }
+                if self.envelope_level <= 0.0 {
+                    self.envelope_level = 0.0;
+                    self.envelope_phase = EnvelopePhase::Decay;
+                }
+            }
+            EnvelopePhase::Decay => {
+                self.envelope_level += Operator::envelope_steps(patch.decay_rate, key_scale);
+                // The sustain level is in 3dB units.
+                let sustain_level = patch.sustain_level as f32 * 8.0;
+                if self.envelope_level >= sustain_level {
+                    self.envelope_level = sustain_level;
+                    self.envelope_phase = EnvelopePhase::Sustain;
+                }
+            }
+            // Sustained instruments hold here until key off.  Percussive ones carry on fading.
+            EnvelopePhase::Sustain if patch.sustained => (),
+            EnvelopePhase::Sustain => {
+                self.envelope_level += Operator::envelope_steps(patch.release_rate, key_scale);
+            }
+            EnvelopePhase::Release => {
+                let rate = if channel_sustain {
+                    5
+                } else if patch.sustained {
+                    patch.release_rate
+                } else {
+                    7
+                };
+                self.envelope_level += Operator::envelope_steps(rate, key_scale);
+            }
+        }
+        self.envelope_level = self.envelope_level.min(ENVELOPE_SILENT);
+    }
+
+    // Advances the phase, and outputs the wave at it plus `modulation` cycles, attenuated by
+    // `attenuation_db` on top of the envelope.
+    fn clock(
+        &mut self,
+        phase_step: u32,
+        modulation: f32,
+        attenuation_db: f32,
+        rectified: bool,
+    ) -> f32 {
+        self.phase = (self.phase + phase_step) & ((1 << PHASE_BITS) - 1);
+
+        if self.envelope_level >= ENVELOPE_SILENT {
+            self.output = 0.0;
+            return 0.0;
+        }
+
+        let phase = self.phase as f32 / (1 << PHASE_BITS) as f32 + modulation;
+        let mut wave = (2.0 * PI * phase).sin();
+        if rectified && wave < 0.0 {
+            wave = 0.0;
+        }
+
+        let attenuation_db = attenuation_db + self.envelope_level * ENVELOPE_STEP_DB;
+        self.output = wave * 10f32.powf(-attenuation_db / 20.0);
+        self.output
+    }
+}
+
+impl Channel {
+    fn new() -> Channel {
+        Channel {
+            fnum: 0,
+            block: 0,
+            key_on: false,
+            sustain: false,
+            instrument: 0,
+            volume: 0,
+            modulator: Operator::new(),
+            carrier: Operator::new(),
+            feedback: [0.0; 2],
+        }
+    }
+
+    fn phase_step(&self, patch: &OperatorPatch, vibrato_step: usize) -> u32 {
+        let mut fnum = self.fnum as i32;
+        if patch.vibrato {
+            fnum += VIBRATO[(self.fnum >> 6) as usize][vibrato_step];
+        }
+        ((fnum.max(0) as u32 * MULTIPLIERS_X2[patch.multiplier as usize]) << self.block) >> 1
+    }
+
+    fn key_scale_rate(&self, patch: &OperatorPatch) -> u8 {
+        let key_code = (self.block << 1) | (self.fnum >> 8) as u8;
+        if patch.key_scale_rate {
+            key_code
+        } else {
+            key_code >> 2
+        }
+    }
+
+    fn key_scale_level_db(&self, patch: &OperatorPatch) -> f32 {
+        if patch.key_scale_level == 0 {
+            return 0.0;
+        }
+        let db = KEY_SCALE_DB[(self.fnum >> 5) as usize] - 6.0 * (7 - self.block) as f32;
+        db.max(0.0) / (1 << (3 - patch.key_scale_level)) as f32
+    }
+}
+
+impl Opll {
+    pub fn new(patches: &'static [[u8; 8]; 15], num_channels: usize) -> Opll {
+        Opll {
+            patches,
+            custom_patch: [0; 8],
+            channels: (0..num_channels).map(|_| Channel::new()).collect(),
+            am_counter: 0,
+            pm_counter: 0,
+        }
+    }
+
+    pub fn write(&mut self, register: u8, byte: u8) {
+        let ix = (register & 0x0F) as usize;
+        if register < 0x08 {
+            self.custom_patch[ix] = byte;
+            return;
+        }
+        let channel = match self.channels.get_mut(ix) {
+            Some(channel) => channel,
+            None => return,
+        };
+
+        match register & 0xF0 {
+            0x10 => channel.fnum = (channel.fnum & 0x100) | byte as u16,
+            0x20 => {
+                channel.fnum = (channel.fnum & 0xFF) | (((byte & 0x01) as u16) << 8);
+                channel.block = (byte >> 1) & 0x07;
+                channel.sustain = byte & 0x20 != 0;
+
+                let key_on = byte & 0x10 != 0;
+                if key_on && !channel.key_on {
+                    channel.modulator.key_on();
+                    channel.carrier.key_on();
+                } else if !key_on && channel.key_on {
+                    channel.modulator.key_off();
+                    channel.carrier.key_off();
+                }
+                channel.key_on = key_on;
+            }
+            0x30 => {
+                channel.instrument = byte >> 4;
+                channel.volume = byte & 0x0F;
+            }
+            _ => (),
+        }
+    }
+
+    // Produces the next sample.  Each channel outputs between -1 and 1, and they're added up.
+    pub fn sample(&mut self) -> f32 {
+        self.am_counter = (self.am_counter + 1) % TREMOLO_PERIOD;
+        self.pm_counter = self.pm_counter.wrapping_add(1);
+
+        let am_position = self.am_counter * 2 / (TREMOLO_PERIOD / 13);
+        let tremolo_db = if am_position <= 13 {
+            am_position
+        } else {
+            26 - am_position
+        } as f32
+            * ENVELOPE_STEP_DB;
+        let vibrato_step = ((self.pm_counter / VIBRATO_STEP_SAMPLES) & 0x7) as usize;
+
+        let mut output = 0.0;
+        for channel in self.channels.iter_mut() {
+            let patch = match channel.instrument {
+                0 => &self.custom_patch,
+                instrument => &self.patches[instrument as usize - 1],
+            };
+            let feedback_level = patch[3] & 0x07;
+            let modulator_tl_db = (patch[2] & 0x3F) as f32 * 0.75;
+            let modulator_patch = OperatorPatch::new(patch, false);
+            let carrier_patch = OperatorPatch::new(patch, true);
+
+            let sustain = channel.sustain;
+            let key_scale = channel.key_scale_rate(&modulator_patch);
+            channel
+                .modulator
+                .clock_envelope(&modulator_patch, key_scale, sustain);
+            let key_scale = channel.key_scale_rate(&carrier_patch);
+            channel
+                .carrier
+                .clock_envelope(&carrier_patch, key_scale, sustain);
+
+            let feedback = if feedback_level == 0 {
+                0.0
+            } else {
+                (channel.feedback[0] + channel.feedback[1]) / 2.0 * (1 << feedback_level) as f32
+                    / 64.0
+            };
+            let attenuation_db = modulator_tl_db
+                + channel.key_scale_level_db(&modulator_patch)
+                + if modulator_patch.tremolo {
+                    tremolo_db
+                } else {
+                    0.0
+                };
+            let phase_step = channel.phase_step(&modulator_patch, vibrato_step);
+            let modulation = channel.modulator.clock(
+                phase_step,
+                feedback,
+                attenuation_db,
+                modulator_patch.rectified,
+            );
+            channel.feedback = [channel.feedback[1], modulation];
+
+            let attenuation_db = channel.volume as f32 * 3.0
+                + channel.key_scale_level_db(&carrier_patch)
+                + if carrier_patch.tremolo {
+                    tremolo_db
+                } else {
+                    0.0
+                };
+            let phase_step = channel.phase_step(&carrier_patch, vibrato_step);
+            output += channel.carrier.clock(
+                phase_step,
+                modulation * MODULATION_CYCLES,
+                attenuation_db,
+                carrier_patch.rectified,
+            );
+        }
+        output
+    }
+}
+
+impl<'de> SaveState<'de, OpllOperatorState> for Operator {
+    fn freeze(&mut self) -> OpllOperatorState {
+        OpllOperatorState {
+            phase: self.phase,
+            envelope_phase: self.envelope_phase,
+            envelope_level: self.envelope_level,
+            output: self.output,
+        }
+    }
+
+    fn hydrate(&mut self, state: OpllOperatorState) {
+        self.phase = state.phase;
+        self.envelope_phase = state.envelope_phase;
+        self.envelope_level = state.envelope_level;
+        self.output = state.output;
+    }
+}
+
+impl<'de> SaveState<'de, OpllChannelState> for Channel {
+    fn freeze(&mut self) -> OpllChannelState {
+        OpllChannelState {
+            fnum: self.fnum,
+            block: self.block,
+            key_on: self.key_on,
+            sustain: self.sustain,
+            instrument: self.instrument,
+            volume: self.volume,
+            modulator: self.modulator.freeze(),
+            carrier: self.carrier.freeze(),
+            feedback: self.feedback.to_vec(),
+        }
+    }
+
+    fn hydrate(&mut self, state: OpllChannelState) {
+        self.fnum = state.fnum;
+        self.block = state.block;
+        self.key_on = state.key_on;
+        self.sustain = state.sustain;
+        self.instrument = state.instrument;
+        self.volume = state.volume;
+        self.modulator.hydrate(state.modulator);
+        self.carrier.hydrate(state.carrier);
+        self.feedback.copy_from_slice(&state.feedback);
+    }
+}
+
+impl<'de> SaveState<'de, OpllState> for Opll {
+    fn freeze(&mut self) -> OpllState {
+        OpllState {
+            custom_patch: self.custom_patch.to_vec(),
+            channels: self.channels.iter_mut().map(|c| c.freeze()).collect(),
+            am_counter: self.am_counter,
+            pm_counter: self.pm_counter,
+        }
+    }
+
+    fn hydrate(&mut self, state: OpllState) {
+        self.custom_patch.copy_from_slice(&state.custom_patch);
+        for (channel, channel_state) in self.channels.iter_mut().zip(state.channels) {
+            channel.hydrate(channel_state);
+        }
+        self.am_counter = state.am_counter;
+        self.pm_counter = state.pm_counter;
+    }
+}
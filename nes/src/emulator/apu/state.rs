@@ -0,0 +1,30 @@
+use crate::emulator::apu::APU;
+use crate::emulator::state::{APUState, SaveState};
+
+impl<'de> SaveState<'de, APUState> for APU {
+    fn freeze(&mut self) -> APUState {
+        APUState {
+            sequence_mode: self.sequence_mode,
+            cycle_counter: self.cycle_counter,
+            irq_flag: self.irq_flag,
+            irq_enabled: self.irq_enabled,
+            pulse_1: self.pulse_1.freeze(),
+            pulse_2: self.pulse_2.freeze(),
+            triangle: self.triangle.freeze(),
+            noise: self.noise.freeze(),
+            dmc: self.dmc.freeze(),
+        }
+    }
+
+    fn hydrate(&mut self, state: APUState) {
+        self.sequence_mode = state.sequence_mode;
+        self.cycle_counter = state.cycle_counter;
+        self.irq_flag = state.irq_flag;
+        self.irq_enabled = state.irq_enabled;
+        self.pulse_1.hydrate(state.pulse_1);
+        self.pulse_2.hydrate(state.pulse_2);
+        self.triangle.hydrate(state.triangle);
+        self.noise.hydrate(state.noise);
+        self.dmc.hydrate(state.dmc);
+    }
+}
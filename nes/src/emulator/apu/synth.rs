@@ -1,4 +1,8 @@
 use crate::emulator::memory::Reader;
+use crate::emulator::state::{
+    DMCState, DividerState, EnvelopeState, NoiseState, PulseState, SaveState, SweepState,
+    TriangleState,
+};
 
 pub struct Divider {
     period: u16,
@@ -162,6 +166,9 @@ pub struct Pulse {
     sequence_ix: u8,
     pub envelope: Envelope,
     pub sweep: Sweep,
+
+    // Expansion audio pulse channels have no sweep unit, so nothing mutes them.
+    has_sweep: bool,
 }
 
 impl Pulse {
@@ -182,6 +189,14 @@ impl Pulse {
             envelope: Envelope::new(),
             sweep,
             sequence_ix: 0,
+            has_sweep: true,
+        }
+    }
+
+    pub fn without_sweep() -> Pulse {
+        Pulse {
+            has_sweep: false,
+            ..Pulse::new(Sweep::new(false))
         }
     }
 
@@ -211,7 +226,7 @@ impl Pulse {
             return 0;
         }
 
-        if self.timer.counter() < 8 {
+        if self.has_sweep && self.timer.counter() < 8 {
             return 0;
         }
 
@@ -223,7 +238,7 @@ impl Pulse {
             return 0;
         }
 
-        if self.sweep.is_muting(self.timer.period()) {
+        if self.has_sweep && self.sweep.is_muting(self.timer.period()) {
             return 0;
         }
 
@@ -319,6 +334,9 @@ impl Noise {
     pub const PERIOD_LOOKUP: [u16; 16] = [
         4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068,
     ];
+    pub const PAL_PERIOD_LOOKUP: [u16; 16] = [
+        4, 8, 14, 30, 60, 88, 118, 148, 188, 236, 354, 472, 708, 944, 1890, 3778,
+    ];
 
     pub fn new() -> Noise {
         Noise {
@@ -395,6 +413,9 @@ impl DMC {
     pub const PERIOD_LOOKUP: [u16; 16] = [
         428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54,
     ];
+    pub const PAL_PERIOD_LOOKUP: [u16; 16] = [
+        398, 354, 316, 298, 276, 236, 210, 198, 176, 148, 132, 118, 98, 78, 66, 50,
+    ];
 
     pub fn new(prg_rom: Box<dyn Reader>) -> DMC {
         DMC {
@@ -475,3 +496,177 @@ impl DMC {
         }
     }
 }
+
+impl<'de> SaveState<'de, DividerState> for Divider {
+    fn freeze(&mut self) -> DividerState {
+        DividerState {
+            period: self.period,
+            counter: self.counter,
+        }
+    }
+
+    fn hydrate(&mut self, state: DividerState) {
+        self.period = state.period;
+        self.counter = state.counter;
+    }
+}
+
+impl<'de> SaveState<'de, EnvelopeState> for Envelope {
+    fn freeze(&mut self) -> EnvelopeState {
+        EnvelopeState {
+            start_flag: self.start_flag,
+            decay_level: self.decay_level,
+            divider: self.divider.freeze(),
+            loop_flag: self.loop_flag,
+            constant_volume: self.constant_volume,
+            volume: self.volume,
+        }
+    }
+
+    fn hydrate(&mut self, state: EnvelopeState) {
+        self.start_flag = state.start_flag;
+        self.decay_level = state.decay_level;
+        self.divider.hydrate(state.divider);
+        self.loop_flag = state.loop_flag;
+        self.constant_volume = state.constant_volume;
+        self.volume = state.volume;
+    }
+}
+
+impl<'de> SaveState<'de, SweepState> for Sweep {
+    fn freeze(&mut self) -> SweepState {
+        SweepState {
+            enabled: self.enabled,
+            divider: self.divider.freeze(),
+            negate_flag: self.negate_flag,
+            shift_count: self.shift_count,
+            reload_flag: self.reload_flag,
+            target_period: self.target_period,
+        }
+    }
+
+    fn hydrate(&mut self, state: SweepState) {
+        self.enabled = state.enabled;
+        self.divider.hydrate(state.divider);
+        self.negate_flag = state.negate_flag;
+        self.shift_count = state.shift_count;
+        self.reload_flag = state.reload_flag;
+        self.target_period = state.target_period;
+    }
+}
+
+impl<'de> SaveState<'de, PulseState> for Pulse {
+    fn freeze(&mut self) -> PulseState {
+        PulseState {
+            enabled: self.enabled,
+            timer: self.timer.freeze(),
+            length: self.length,
+            halt_length: self.halt_length,
+            sequence: self.sequence,
+            sequence_ix: self.sequence_ix,
+            envelope: self.envelope.freeze(),
+            sweep: self.sweep.freeze(),
+        }
+    }
+
+    fn hydrate(&mut self, state: PulseState) {
+        self.enabled = state.enabled;
+        self.timer.hydrate(state.timer);
+        self.length = state.length;
+        self.halt_length = state.halt_length;
+        self.sequence = state.sequence;
+        self.sequence_ix = state.sequence_ix;
+        self.envelope.hydrate(state.envelope);
+        self.sweep.hydrate(state.sweep);
+    }
+}
+
+impl<'de> SaveState<'de, TriangleState> for Triangle {
+    fn freeze(&mut self) -> TriangleState {
+        TriangleState {
+            enabled: self.enabled,
+            timer: self.timer.freeze(),
+            linear: self.linear,
+            length: self.length,
+            halt_length: self.halt_length,
+            linear_reload_flag: self.linear_reload_flag,
+            linear_reload_value: self.linear_reload_value,
+            control_flag: self.control_flag,
+            sequence_ix: self.sequence_ix,
+        }
+    }
+
+    fn hydrate(&mut self, state: TriangleState) {
+        self.enabled = state.enabled;
+        self.timer.hydrate(state.timer);
+        self.linear = state.linear;
+        self.length = state.length;
+        self.halt_length = state.halt_length;
+        self.linear_reload_flag = state.linear_reload_flag;
+        self.linear_reload_value = state.linear_reload_value;
+        self.control_flag = state.control_flag;
+        self.sequence_ix = state.sequence_ix;
+    }
+}
+
+impl<'de> SaveState<'de, NoiseState> for Noise {
+    fn freeze(&mut self) -> NoiseState {
+        NoiseState {
+            enabled: self.enabled,
+            envelope: self.envelope.freeze(),
+            shift_register: self.shift_register,
+            length: self.length,
+            halt_length: self.halt_length,
+            mode: self.mode,
+            timer: self.timer.freeze(),
+        }
+    }
+
+    fn hydrate(&mut self, state: NoiseState) {
+        self.enabled = state.enabled;
+        self.envelope.hydrate(state.envelope);
+        self.shift_register = state.shift_register;
+        self.length = state.length;
+        self.halt_length = state.halt_length;
+        self.mode = state.mode;
+        self.timer.hydrate(state.timer);
+    }
+}
+
+impl<'de> SaveState<'de, DMCState> for DMC {
+    fn freeze(&mut self) -> DMCState {
+        DMCState {
+            enabled: self.enabled,
+            irq_enabled: self.irq_enabled,
+            loop_flag: self.loop_flag,
+            silence_flag: self.silence_flag,
+            timer: self.timer.freeze(),
+            volume: self.volume,
+            sample_addr: self.sample_addr,
+            sample_len: self.sample_len,
+            sample_buffer: self.sample_buffer,
+            current_addr: self.current_addr,
+            bytes_remaining: self.bytes_remaining,
+            irq_flag: self.irq_flag,
+            shift_register: self.shift_register,
+            bits_remaining: self.bits_remaining,
+        }
+    }
+
+    fn hydrate(&mut self, state: DMCState) {
+        self.enabled = state.enabled;
+        self.irq_enabled = state.irq_enabled;
+        self.loop_flag = state.loop_flag;
+        self.silence_flag = state.silence_flag;
+        self.timer.hydrate(state.timer);
+        self.volume = state.volume;
+        self.sample_addr = state.sample_addr;
+        self.sample_len = state.sample_len;
+        self.sample_buffer = state.sample_buffer;
+        self.current_addr = state.current_addr;
+        self.bytes_remaining = state.bytes_remaining;
+        self.irq_flag = state.irq_flag;
+        self.shift_register = state.shift_register;
+        self.bits_remaining = state.bits_remaining;
+    }
+}
@@ -4,6 +4,8 @@ use std::collections::BinaryHeap;
 use std::rc::Rc;
 use std::vec::Vec;
 
+use crate::emulator::state::{ClockState, SaveState};
+
 pub trait Ticker {
     // Returns how many master clock cycles while ticking.
     fn tick(&mut self) -> u32;
@@ -76,6 +78,33 @@ impl Clock {
     }
 }
 
+impl<'de> SaveState<'de, ClockState> for Clock {
+    fn freeze(&mut self) -> ClockState {
+        let mut next_tick_cycles = vec![0; self.tickers.len()];
+        for node in self.turn_order.iter() {
+            next_tick_cycles[node.ticker_ix] = node.next_tick_cycle;
+        }
+
+        ClockState {
+            elapsed_cycles: self.elapsed_cycles,
+            next_tick_cycles,
+        }
+    }
+
+    fn hydrate(&mut self, state: ClockState) {
+        self.elapsed_cycles = state.elapsed_cycles;
+        self.turn_order = state
+            .next_tick_cycles
+            .iter()
+            .enumerate()
+            .map(|(ticker_ix, next_tick_cycle)| TickNode {
+                ticker_ix,
+                next_tick_cycle: *next_tick_cycle,
+            })
+            .collect();
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 struct TickNode {
     ticker_ix: usize,
@@ -47,6 +47,11 @@ impl Controller {
             register: 0,
         }
     }
+
+    // Press or release a button directly, bypassing the keymap.
+    pub fn set_pressed(&mut self, button: Button, pressed: bool) {
+        self.keystate.insert(button, pressed);
+    }
 }
 
 impl EventHandler for Controller {
@@ -1,50 +1,52 @@
 use crate::emulator::cpu;
+use crate::emulator::memory::BusAccess;
 use crate::emulator::util;
 
 // An addressing mode calculates the final operand address, and returns it along with any extra
-// cycles it too, e.g. as the result of crossing a page boundary.
+// cycles it too, e.g. as the result of crossing a page boundary, and the kind of bus access that
+// reading the operand is.
 // After finding the address, the function should leave the PC pointing at the next opcode.
-pub type AddressingMode = fn(cpu: &mut cpu::CPU) -> (u16, u32);
+pub type AddressingMode = fn(cpu: &mut cpu::CPU) -> (u16, u32, BusAccess);
 
 fn load_memory_from_pc(cpu: &mut cpu::CPU) -> u8 {
     let addr = cpu.pc;
-    cpu.load_memory(addr)
+    cpu.read_memory(addr, BusAccess::Operand)
 }
 
 // Implied: no operand.
 // Due to a quirk in the nature of the processor, even when doing implied addressing,
 // the CPU will read the next byte of memory and then discard it.
-pub fn implied(cpu: &mut cpu::CPU) -> (u16, u32) {
+pub fn implied(cpu: &mut cpu::CPU) -> (u16, u32, BusAccess) {
     let _ = load_memory_from_pc(cpu);
-    (0, 0)
+    (0, 0, BusAccess::Data)
 }
 
 // Immediate: one byte literal operand.
-pub fn immediate(cpu: &mut cpu::CPU) -> (u16, u32) {
+pub fn immediate(cpu: &mut cpu::CPU) -> (u16, u32, BusAccess) {
     let addr = cpu.pc;
     cpu.pc += 1;
-    (addr, 0)
+    (addr, 0, BusAccess::Operand)
 }
 
 // Absolute: two byte operand indicates memory address.
-pub fn absolute(cpu: &mut cpu::CPU) -> (u16, u32) {
+pub fn absolute(cpu: &mut cpu::CPU) -> (u16, u32, BusAccess) {
     let low_byte = load_memory_from_pc(cpu);
     cpu.pc += 1;
     let high_byte = load_memory_from_pc(cpu);
     cpu.pc += 1;
-    (util::combine_bytes(high_byte, low_byte), 0)
+    (util::combine_bytes(high_byte, low_byte), 0, BusAccess::Data)
 }
 
 // Zero page: one byte operand indicates address in page 0 of memory.
-pub fn zero_page(cpu: &mut cpu::CPU) -> (u16, u32) {
+pub fn zero_page(cpu: &mut cpu::CPU) -> (u16, u32, BusAccess) {
     let low_byte = load_memory_from_pc(cpu);
     cpu.pc += 1;
-    (low_byte as u16, 0)
+    (low_byte as u16, 0, BusAccess::Data)
 }
 
 // Relative: one byte operand indicates address relative to PC.
 // Only used by branch instructions.
-pub fn relative(cpu: &mut cpu::CPU) -> (u16, u32) {
+pub fn relative(cpu: &mut cpu::CPU) -> (u16, u32, BusAccess) {
     let offset: u8 = load_memory_from_pc(cpu);
     cpu.pc += 1;
 
@@ -63,15 +65,15 @@ pub fn relative(cpu: &mut cpu::CPU) -> (u16, u32) {
 
     // One extra cycle if we crossed a page boundary.
     if (addr & 0xFF00) != (cpu.pc & 0xFF00) {
-        (addr, 1)
+        (addr, 1, BusAccess::Data)
     } else {
-        (addr, 0)
+        (addr, 0, BusAccess::Data)
     }
 }
 
 // Absolute indexed: same as absolute addressing, but adds an index register to the
 // address.
-fn absolute_indexed_load(cpu: &mut cpu::CPU, offset: u8) -> (u16, u32) {
+fn absolute_indexed_load(cpu: &mut cpu::CPU, offset: u8) -> (u16, u32, BusAccess) {
     let bal = load_memory_from_pc(cpu);
     cpu.pc += 1;
     let bah = load_memory_from_pc(cpu);
@@ -83,18 +85,18 @@ fn absolute_indexed_load(cpu: &mut cpu::CPU, offset: u8) -> (u16, u32) {
         let _ = cpu.load_memory(util::combine_bytes(bah, adl));
 
         let (adh, _) = bah.overflowing_add(1);
-        (util::combine_bytes(adh, adl), 1)
+        (util::combine_bytes(adh, adl), 1, BusAccess::Data)
     } else {
-        (util::combine_bytes(bah, adl), 0)
+        (util::combine_bytes(bah, adl), 0, BusAccess::Data)
     }
 }
 
-pub fn absolute_indexed_x(cpu: &mut cpu::CPU) -> (u16, u32) {
+pub fn absolute_indexed_x(cpu: &mut cpu::CPU) -> (u16, u32, BusAccess) {
     let offset = cpu.x;
     absolute_indexed_load(cpu, offset)
 }
 
-pub fn absolute_indexed_y(cpu: &mut cpu::CPU) -> (u16, u32) {
+pub fn absolute_indexed_y(cpu: &mut cpu::CPU) -> (u16, u32, BusAccess) {
     let offset = cpu.y;
     absolute_indexed_load(cpu, offset)
 }
@@ -102,7 +104,7 @@ pub fn absolute_indexed_y(cpu: &mut cpu::CPU) -> (u16, u32) {
 // Zero page indexed: same as zero page, but adds an index register to the address.
 // Only supported for index X except for LDX and STX.
 // If the resulting value is greated than 255, the address wraps within page 0.
-fn zero_page_indexed_load(cpu: &mut cpu::CPU, offset: u8) -> (u16, u32) {
+fn zero_page_indexed_load(cpu: &mut cpu::CPU, offset: u8) -> (u16, u32, BusAccess) {
     let low_byte = load_memory_from_pc(cpu);
     cpu.pc += 1;
 
@@ -110,16 +112,16 @@ fn zero_page_indexed_load(cpu: &mut cpu::CPU, offset: u8) -> (u16, u32) {
     let _ = cpu.load_memory(low_byte as u16);
 
     let adjusted = (low_byte as u16) + (offset as u16);
-    (adjusted & 0x00FF, 0)
+    (adjusted & 0x00FF, 0, BusAccess::Data)
 }
 
-pub fn zero_page_indexed(cpu: &mut cpu::CPU) -> (u16, u32) {
+pub fn zero_page_indexed(cpu: &mut cpu::CPU) -> (u16, u32, BusAccess) {
     let offset = cpu.x;
     zero_page_indexed_load(cpu, offset)
 }
 
 // Y-indexed version.  Only supported for LDX, STX.
-pub fn zero_page_indexed_y(cpu: &mut cpu::CPU) -> (u16, u32) {
+pub fn zero_page_indexed_y(cpu: &mut cpu::CPU) -> (u16, u32, BusAccess) {
     let offset = cpu.y;
     zero_page_indexed_load(cpu, offset)
 }
@@ -149,7 +151,7 @@ fn load_addr_within_page(cpu: &mut cpu::CPU, addr: u16) -> u16 {
     util::combine_bytes(cpu.load_memory(addr_2), cpu.load_memory(addr))
 }
 
-pub fn indexed_indirect(cpu: &mut cpu::CPU) -> (u16, u32) {
+pub fn indexed_indirect(cpu: &mut cpu::CPU) -> (u16, u32, BusAccess) {
     let bal = load_memory_from_pc(cpu);
     cpu.pc += 1;
 
@@ -159,10 +161,10 @@ pub fn indexed_indirect(cpu: &mut cpu::CPU) -> (u16, u32) {
     // Wrap within page 0.
     let addr = ((bal as u16) + (cpu.x as u16)) & 0x00FF;
     let target = load_addr_within_page(cpu, addr);
-    (target, 0)
+    (target, 0, BusAccess::Data)
 }
 
-pub fn indirect_indexed(cpu: &mut cpu::CPU) -> (u16, u32) {
+pub fn indirect_indexed(cpu: &mut cpu::CPU) -> (u16, u32, BusAccess) {
     let ial = load_memory_from_pc(cpu);
     cpu.pc += 1;
     let bal = load_byte_from_page_zero(cpu, ial as u16);
@@ -174,13 +176,13 @@ pub fn indirect_indexed(cpu: &mut cpu::CPU) -> (u16, u32) {
         let _ = cpu.load_memory(util::combine_bytes(bah, adl));
 
         let (adh, _) = bah.overflowing_add(1);
-        (util::combine_bytes(adh, adl), 1)
+        (util::combine_bytes(adh, adl), 1, BusAccess::Data)
     } else {
-        (util::combine_bytes(bah, adl), 0)
+        (util::combine_bytes(bah, adl), 0, BusAccess::Data)
     }
 }
 
-pub fn indirect(cpu: &mut cpu::CPU) -> (u16, u32) {
+pub fn indirect(cpu: &mut cpu::CPU) -> (u16, u32, BusAccess) {
     let ial = load_memory_from_pc(cpu);
     cpu.pc += 1;
     let iah = load_memory_from_pc(cpu);
@@ -188,5 +190,5 @@ pub fn indirect(cpu: &mut cpu::CPU) -> (u16, u32) {
 
     let addr = util::combine_bytes(iah, ial);
     let target = load_addr_within_page(cpu, addr);
-    (target, 0)
+    (target, 0, BusAccess::Data)
 }
@@ -30,8 +30,8 @@ fn load_status_from_stack(cpu: &mut cpu::CPU) {
 // LDA: Load Accumulator with Memory
 // A -> M
 pub fn lda(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u32 {
-    let (addr, addr_cycles) = load_addr(cpu);
-    let res = cpu.load_memory(addr);
+    let (addr, addr_cycles, access) = load_addr(cpu);
+    let res = cpu.read_memory(addr, access);
     update_zero_flag(cpu, res);
     update_negative_flag(cpu, res);
     cpu.a = res;
@@ -42,7 +42,7 @@ pub fn lda(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u3
 // STA: Store Accumulator in Memory
 // M -> A
 pub fn sta(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u32 {
-    let (addr, _) = load_addr(cpu);
+    let (addr, _, _) = load_addr(cpu);
     let byte = cpu.a;
     cpu.store_memory(addr, byte);
     // STA doesn't incur the extra "oops" cycle.
@@ -56,9 +56,13 @@ pub fn sta(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u3
 // ADC: Add Memory to Accumulator with Carry
 // A + M + C -> A, C
 pub fn adc(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u32 {
-    let (addr, addr_cycles) = load_addr(cpu);
-    let mem = cpu.load_memory(addr);
+    let (addr, addr_cycles, access) = load_addr(cpu);
+    let mem = cpu.read_memory(addr, access);
+    add_with_carry(cpu, mem);
+    addr_cycles
+}
 
+fn add_with_carry(cpu: &mut cpu::CPU, mem: u8) {
     let carry_val: u8 = if cpu.p.is_set(cpu::flags::Flag::C) {
         1
     } else {
@@ -106,16 +110,19 @@ pub fn adc(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u3
     update_negative_flag(cpu, res);
 
     cpu.a = res;
-    addr_cycles
 }
 
 // SBC: Subtract Memory from Accumulator with Borrow
 // A - M - ~C -> A
 // Borrow = Complement of carry
 pub fn sbc(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u32 {
-    let (addr, addr_cycles) = load_addr(cpu);
-    let mem = cpu.load_memory(addr);
+    let (addr, addr_cycles, access) = load_addr(cpu);
+    let mem = cpu.read_memory(addr, access);
+    subtract_with_borrow(cpu, mem);
+    addr_cycles
+}
 
+fn subtract_with_borrow(cpu: &mut cpu::CPU, mem: u8) {
     let carry_val: u8 = if cpu.p.is_set(cpu::flags::Flag::C) {
         1
     } else {
@@ -163,14 +170,13 @@ pub fn sbc(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u3
     update_negative_flag(cpu, res);
 
     cpu.a = res;
-    addr_cycles
 }
 
 // AND: Bitwise AND Memory with Accumulator
 // A /\ M -> A
 pub fn and(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u32 {
-    let (addr, addr_cycles) = load_addr(cpu);
-    let mem = cpu.load_memory(addr);
+    let (addr, addr_cycles, access) = load_addr(cpu);
+    let mem = cpu.read_memory(addr, access);
     let res = mem & cpu.a;
     update_zero_flag(cpu, res);
     update_negative_flag(cpu, res);
@@ -181,8 +187,8 @@ pub fn and(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u3
 // ORA: Bitwise OR Memory with Accumulator
 // A \/ M -> A
 pub fn ora(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u32 {
-    let (addr, addr_cycles) = load_addr(cpu);
-    let mem = cpu.load_memory(addr);
+    let (addr, addr_cycles, access) = load_addr(cpu);
+    let mem = cpu.read_memory(addr, access);
     let res = mem | cpu.a;
     update_zero_flag(cpu, res);
     update_negative_flag(cpu, res);
@@ -193,8 +199,8 @@ pub fn ora(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u3
 // EOR: Bitwise Exclusive OR Memory with Accumulator
 // A \-/ M -> A
 pub fn eor(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u32 {
-    let (addr, addr_cycles) = load_addr(cpu);
-    let mem = cpu.load_memory(addr);
+    let (addr, addr_cycles, access) = load_addr(cpu);
+    let mem = cpu.read_memory(addr, access);
     let res = mem ^ cpu.a;
     update_zero_flag(cpu, res);
     update_negative_flag(cpu, res);
@@ -258,7 +264,7 @@ pub fn clv(cpu: &mut cpu::CPU, _: cpu::addressing::AddressingMode) -> u32 {
 // JMP: Jump to New Location
 // (PC + 1) -> PCL, (PC + 2) -> PCH
 pub fn jmp(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u32 {
-    let (addr, addr_cycles) = load_addr(cpu);
+    let (addr, addr_cycles, _) = load_addr(cpu);
     cpu.pc = addr;
     addr_cycles
 }
@@ -269,7 +275,7 @@ fn branch_if(
     load_addr: cpu::addressing::AddressingMode,
     should_branch: bool,
 ) -> u32 {
-    let (addr, addr_cycles) = load_addr(cpu);
+    let (addr, addr_cycles, _) = load_addr(cpu);
     if should_branch {
         cpu.pc = addr;
         addr_cycles + 1
@@ -332,9 +338,13 @@ fn compare_instruction(
     load_addr: cpu::addressing::AddressingMode,
     compare_with: u8,
 ) -> u32 {
-    let (addr, addr_cycles) = load_addr(cpu);
-    let mem = cpu.load_memory(addr);
+    let (addr, addr_cycles, access) = load_addr(cpu);
+    let mem = cpu.read_memory(addr, access);
+    compare_values(cpu, compare_with, mem);
+    addr_cycles
+}
 
+fn compare_values(cpu: &mut cpu::CPU, compare_with: u8, mem: u8) {
     let diff = compare_with.wrapping_sub(mem);
     update_zero_flag(cpu, diff);
     update_negative_flag(cpu, diff);
@@ -344,8 +354,6 @@ fn compare_instruction(
     } else {
         cpu.p.set(cpu::flags::Flag::C);
     }
-
-    addr_cycles
 }
 
 // CMP - Compare Memory and Accumulator
@@ -358,8 +366,8 @@ pub fn cmp(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u3
 // BIT: Test Bits in Memory with Accumulator
 // M /\ A, M7 -> N, M6 -> V
 pub fn bit(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u32 {
-    let (addr, addr_cycles) = load_addr(cpu);
-    let mem = cpu.load_memory(addr);
+    let (addr, addr_cycles, access) = load_addr(cpu);
+    let mem = cpu.read_memory(addr, access);
 
     // N is set to bit 7 of the memory being tested.
     update_negative_flag(cpu, mem);
@@ -386,8 +394,8 @@ pub fn bit(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u3
 // LDX: Load Index Register X from Memory
 // M -> X
 pub fn ldx(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u32 {
-    let (addr, addr_cycles) = load_addr(cpu);
-    let mem = cpu.load_memory(addr);
+    let (addr, addr_cycles, access) = load_addr(cpu);
+    let mem = cpu.read_memory(addr, access);
 
     update_zero_flag(cpu, mem);
     update_negative_flag(cpu, mem);
@@ -399,8 +407,8 @@ pub fn ldx(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u3
 // LDY: Load Index Register Y from Memory
 // M -> Y
 pub fn ldy(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u32 {
-    let (addr, addr_cycles) = load_addr(cpu);
-    let mem = cpu.load_memory(addr);
+    let (addr, addr_cycles, access) = load_addr(cpu);
+    let mem = cpu.read_memory(addr, access);
 
     update_zero_flag(cpu, mem);
     update_negative_flag(cpu, mem);
@@ -412,7 +420,7 @@ pub fn ldy(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u3
 // STX: Store Index Register X in Memory
 // X -> M
 pub fn stx(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u32 {
-    let (addr, addr_cycles) = load_addr(cpu);
+    let (addr, addr_cycles, _) = load_addr(cpu);
     let byte = cpu.x;
     cpu.store_memory(addr, byte);
     addr_cycles
@@ -421,7 +429,7 @@ pub fn stx(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u3
 // STY: Store Index Register Y in Memory
 // Y -> M
 pub fn sty(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u32 {
-    let (addr, addr_cycles) = load_addr(cpu);
+    let (addr, addr_cycles, _) = load_addr(cpu);
     let byte = cpu.y;
     cpu.store_memory(addr, byte);
     addr_cycles
@@ -526,7 +534,7 @@ pub fn tya(cpu: &mut cpu::CPU, _: cpu::addressing::AddressingMode) -> u32 {
 // JSR: Jump to Subroutine
 // PC + 2v, (PC + 1) -> PCL, (PC + 2) -> PCH
 pub fn jsr(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u32 {
-    let (addr, addr_cycles) = load_addr(cpu);
+    let (addr, addr_cycles, _) = load_addr(cpu);
 
     // load_addr will leave the PC pointing at the next opcode.
     // JSR is actually supposed to store the previous address.
@@ -669,11 +677,11 @@ fn shift_set_flags(cpu: &mut cpu::CPU, res: u8, carry: bool) {
 
 // LSR: Logical Shift Right
 pub fn lsr(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u32 {
-    let (addr, _) = load_addr(cpu);
-    let byte = cpu.load_memory(addr);
+    let (addr, _, access) = load_addr(cpu);
+    let byte = cpu.read_memory(addr, access);
     let (res, carry) = util::shift_right(byte);
     shift_set_flags(cpu, res, carry);
-    cpu.store_memory(addr, res);
+    cpu.store_memory_rmw(addr, byte, res);
 
     // Ignore whether the addressing added a whoops cycle since it's always triggered on write
     // instructions.
@@ -690,11 +698,11 @@ pub fn lsra(cpu: &mut cpu::CPU, _: cpu::addressing::AddressingMode) -> u32 {
 
 // ASL: Arithmetic Shift Left
 pub fn asl(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u32 {
-    let (addr, _) = load_addr(cpu);
-    let byte = cpu.load_memory(addr);
+    let (addr, _, access) = load_addr(cpu);
+    let byte = cpu.read_memory(addr, access);
     let (res, carry) = util::shift_left(byte);
     shift_set_flags(cpu, res, carry);
-    cpu.store_memory(addr, res);
+    cpu.store_memory_rmw(addr, byte, res);
 
     // Ignore whether the addressing added a whoops cycle since it's always triggered on write
     // instructions.
@@ -711,11 +719,11 @@ pub fn asla(cpu: &mut cpu::CPU, _: cpu::addressing::AddressingMode) -> u32 {
 
 // ROR: Rotate Right
 pub fn ror(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u32 {
-    let (addr, _) = load_addr(cpu);
-    let byte = cpu.load_memory(addr);
+    let (addr, _, access) = load_addr(cpu);
+    let byte = cpu.read_memory(addr, access);
     let (res, carry) = util::rotate_right(byte, cpu.p.is_set(cpu::flags::Flag::C));
     shift_set_flags(cpu, res, carry);
-    cpu.store_memory(addr, res);
+    cpu.store_memory_rmw(addr, byte, res);
 
     // Ignore whether the addressing added a whoops cycle since it's always triggered on write
     // instructions.
@@ -732,11 +740,11 @@ pub fn rora(cpu: &mut cpu::CPU, _: cpu::addressing::AddressingMode) -> u32 {
 
 // ROL: Rotate Left
 pub fn rol(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u32 {
-    let (addr, _) = load_addr(cpu);
-    let byte = cpu.load_memory(addr);
+    let (addr, _, access) = load_addr(cpu);
+    let byte = cpu.read_memory(addr, access);
     let (res, carry) = util::rotate_left(byte, cpu.p.is_set(cpu::flags::Flag::C));
     shift_set_flags(cpu, res, carry);
-    cpu.store_memory(addr, res);
+    cpu.store_memory_rmw(addr, byte, res);
 
     // Ignore whether the addressing added a whoops cycle since it's always triggered on write
     // instructions.
@@ -754,10 +762,10 @@ pub fn rola(cpu: &mut cpu::CPU, _: cpu::addressing::AddressingMode) -> u32 {
 // INC: Increment Memory by One
 // M + 1 -> M
 pub fn inc(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u32 {
-    let (addr, _) = load_addr(cpu);
-    let byte = cpu.load_memory(addr);
+    let (addr, _, access) = load_addr(cpu);
+    let byte = cpu.read_memory(addr, access);
     let res = byte.wrapping_add(1);
-    cpu.store_memory(addr, res);
+    cpu.store_memory_rmw(addr, byte, res);
     update_zero_flag(cpu, res);
     update_negative_flag(cpu, res);
 
@@ -769,10 +777,10 @@ pub fn inc(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u3
 // DEC: Decrement Memory by One
 // M - 1 -> M
 pub fn dec(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u32 {
-    let (addr, _) = load_addr(cpu);
-    let byte = cpu.load_memory(addr);
+    let (addr, _, access) = load_addr(cpu);
+    let byte = cpu.read_memory(addr, access);
     let res = byte.wrapping_sub(1);
-    cpu.store_memory(addr, res);
+    cpu.store_memory_rmw(addr, byte, res);
     update_zero_flag(cpu, res);
     update_negative_flag(cpu, res);
 
@@ -785,3 +793,267 @@ pub fn dec(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u3
 pub fn nop(_: &mut cpu::CPU, _: cpu::addressing::AddressingMode) -> u32 {
     0
 }
+
+/* Unofficial Instructions */
+
+// The following opcodes are not documented by the manufacturer, but fall out of the way the
+// instruction decoder combines the official ones.  Most are combinations of a read-modify-write
+// instruction with an accumulator operation.  Some NES games rely on them.
+
+// IGN: Ignore Memory
+// The multi-byte NOPs.  Unlike NOP these still read their operand, which matters for registers with
+// read side effects.
+pub fn ign(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u32 {
+    let (addr, addr_cycles, access) = load_addr(cpu);
+    let _ = cpu.read_memory(addr, access);
+    addr_cycles
+}
+
+// KIL: Halt the processor
+// The CPU locks up and stops fetching instructions until reset.  We emulate this by leaving the PC
+// pointing at the KIL instruction so that it executes forever.
+pub fn kil(cpu: &mut cpu::CPU, _: cpu::addressing::AddressingMode) -> u32 {
+    cpu.pc = cpu.pc.wrapping_sub(1);
+    0
+}
+
+// LAX: Load Accumulator and Index Register X from Memory
+// M -> A, X
+pub fn lax(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u32 {
+    let (addr, addr_cycles, access) = load_addr(cpu);
+    let res = cpu.read_memory(addr, access);
+    update_zero_flag(cpu, res);
+    update_negative_flag(cpu, res);
+    cpu.a = res;
+    cpu.x = res;
+    addr_cycles
+}
+
+// SAX: Store Accumulator AND Index Register X in Memory
+// A /\ X -> M
+pub fn sax(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u32 {
+    let (addr, _, _) = load_addr(cpu);
+    let byte = cpu.a & cpu.x;
+    cpu.store_memory(addr, byte);
+    0
+}
+
+// SLO: Arithmetic Shift Left then OR Memory with Accumulator
+// M << 1 -> M, A \/ M -> A
+pub fn slo(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u32 {
+    let (addr, _, access) = load_addr(cpu);
+    let byte = cpu.read_memory(addr, access);
+    let (shifted, carry) = util::shift_left(byte);
+    cpu.store_memory_rmw(addr, byte, shifted);
+    let res = cpu.a | shifted;
+    shift_set_flags(cpu, res, carry);
+    cpu.a = res;
+    0
+}
+
+// RLA: Rotate Left then AND Memory with Accumulator
+// M << 1 -> M, A /\ M -> A
+pub fn rla(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u32 {
+    let (addr, _, access) = load_addr(cpu);
+    let byte = cpu.read_memory(addr, access);
+    let (rotated, carry) = util::rotate_left(byte, cpu.p.is_set(cpu::flags::Flag::C));
+    cpu.store_memory_rmw(addr, byte, rotated);
+    let res = cpu.a & rotated;
+    shift_set_flags(cpu, res, carry);
+    cpu.a = res;
+    0
+}
+
+// SRE: Logical Shift Right then Exclusive OR Memory with Accumulator
+// M >> 1 -> M, A \-/ M -> A
+pub fn sre(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u32 {
+    let (addr, _, access) = load_addr(cpu);
+    let byte = cpu.read_memory(addr, access);
+    let (shifted, carry) = util::shift_right(byte);
+    cpu.store_memory_rmw(addr, byte, shifted);
+    let res = cpu.a ^ shifted;
+    shift_set_flags(cpu, res, carry);
+    cpu.a = res;
+    0
+}
+
+// RRA: Rotate Right then Add Memory to Accumulator with Carry
+// M >> 1 -> M, A + M + C -> A, C
+pub fn rra(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u32 {
+    let (addr, _, access) = load_addr(cpu);
+    let byte = cpu.read_memory(addr, access);
+    let (rotated, carry) = util::rotate_right(byte, cpu.p.is_set(cpu::flags::Flag::C));
+    cpu.store_memory_rmw(addr, byte, rotated);
+    shift_set_flags(cpu, rotated, carry);
+    add_with_carry(cpu, rotated);
+    0
+}
+
+// DCP: Decrement Memory by One then Compare with Accumulator
+// M - 1 -> M, A - M
+pub fn dcp(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u32 {
+    let (addr, _, access) = load_addr(cpu);
+    let byte = cpu.read_memory(addr, access);
+    let res = byte.wrapping_sub(1);
+    cpu.store_memory_rmw(addr, byte, res);
+    let a = cpu.a;
+    compare_values(cpu, a, res);
+    0
+}
+
+// ISC: Increment Memory by One then Subtract from Accumulator with Borrow
+// M + 1 -> M, A - M - ~C -> A
+pub fn isc(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u32 {
+    let (addr, _, access) = load_addr(cpu);
+    let byte = cpu.read_memory(addr, access);
+    let res = byte.wrapping_add(1);
+    cpu.store_memory_rmw(addr, byte, res);
+    subtract_with_borrow(cpu, res);
+    0
+}
+
+// ANC: AND Memory with Accumulator then Copy N to C
+// A /\ M -> A, N -> C
+pub fn anc(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u32 {
+    let cycles = and(cpu, load_addr);
+    if cpu.p.is_set(cpu::flags::Flag::N) {
+        cpu.p.set(cpu::flags::Flag::C);
+    } else {
+        cpu.p.clear(cpu::flags::Flag::C);
+    }
+    cycles
+}
+
+// ALR: AND Memory with Accumulator then Logical Shift Right Accumulator
+// (A /\ M) >> 1 -> A
+pub fn alr(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u32 {
+    let cycles = and(cpu, load_addr);
+    lsra(cpu, load_addr);
+    cycles
+}
+
+// ARR: AND Memory with Accumulator then Rotate Right Accumulator
+// (A /\ M) >> 1 -> A, A6 -> C, A6 \-/ A5 -> V
+pub fn arr(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u32 {
+    let (addr, addr_cycles, access) = load_addr(cpu);
+    let mem = cpu.read_memory(addr, access);
+    let (res, _) = util::rotate_right(cpu.a & mem, cpu.p.is_set(cpu::flags::Flag::C));
+
+    // The carry and overflow flags come from the adder, which sees bits 6 and 5 of the result.
+    let bit_6 = (res & 0b0100_0000) != 0;
+    let bit_5 = (res & 0b0010_0000) != 0;
+    shift_set_flags(cpu, res, bit_6);
+    if bit_6 != bit_5 {
+        cpu.p.set(cpu::flags::Flag::V);
+    } else {
+        cpu.p.clear(cpu::flags::Flag::V);
+    }
+
+    cpu.a = res;
+    addr_cycles
+}
+
+// AXS: Subtract Memory from Accumulator AND Index Register X
+// (A /\ X) - M -> X
+// Sets flags like CMP, ignoring the carry and decimal flags.
+pub fn axs(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u32 {
+    let (addr, addr_cycles, access) = load_addr(cpu);
+    let mem = cpu.read_memory(addr, access);
+    let a_and_x = cpu.a & cpu.x;
+    compare_values(cpu, a_and_x, mem);
+    cpu.x = a_and_x.wrapping_sub(mem);
+    addr_cycles
+}
+
+// Value ORed into the accumulator by the unstable XAA instruction.
+// This depends on the individual chip, temperature etc., but 0xEE is the most commonly observed.
+const XAA_MAGIC: u8 = 0xEE;
+
+// XAA: Transfer Index X to Accumulator then AND with Memory (unstable)
+// (A \/ magic) /\ X /\ M -> A
+pub fn xaa(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u32 {
+    let (addr, addr_cycles, access) = load_addr(cpu);
+    let mem = cpu.read_memory(addr, access);
+    let res = (cpu.a | XAA_MAGIC) & cpu.x & mem;
+    update_zero_flag(cpu, res);
+    update_negative_flag(cpu, res);
+    cpu.a = res;
+    addr_cycles
+}
+
+// LXA: Load Accumulator and Index Register X from Memory (unstable)
+// M -> A, X
+// Suffers from the same instability as XAA, but on the NES the magic value is effectively 0xFF so
+// the accumulator drops out.
+pub fn lxa(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u32 {
+    let (addr, addr_cycles, access) = load_addr(cpu);
+    let res = cpu.read_memory(addr, access);
+    update_zero_flag(cpu, res);
+    update_negative_flag(cpu, res);
+    cpu.a = res;
+    cpu.x = res;
+    addr_cycles
+}
+
+// LAS: Load Accumulator, Index Register X and Stack Pointer from Memory AND Stack Pointer
+// M /\ S -> A, X, S
+pub fn las(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u32 {
+    let (addr, addr_cycles, access) = load_addr(cpu);
+    let mem = cpu.read_memory(addr, access);
+    let res = mem & cpu.sp;
+    update_zero_flag(cpu, res);
+    update_negative_flag(cpu, res);
+    cpu.a = res;
+    cpu.x = res;
+    cpu.sp = res;
+    addr_cycles
+}
+
+// Common functionality for the unstable indexed stores, which AND the stored value with the high
+// byte of the base address plus one.
+// When indexing crosses a page boundary the high byte of the target address is also replaced with
+// the stored value.
+fn store_and_high_byte(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode, byte: u8) {
+    let (addr, page_crossed, _) = load_addr(cpu);
+    let (high, low) = util::split_word(addr);
+    if page_crossed == 0 {
+        let res = byte & high.wrapping_add(1);
+        cpu.store_memory(addr, res);
+    } else {
+        let res = byte & high;
+        cpu.store_memory(util::combine_bytes(res, low), res);
+    }
+}
+
+// SHA: Store Accumulator AND Index Register X AND High Byte in Memory (unstable)
+// A /\ X /\ (H + 1) -> M
+pub fn sha(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u32 {
+    let byte = cpu.a & cpu.x;
+    store_and_high_byte(cpu, load_addr, byte);
+    0
+}
+
+// SHX: Store Index Register X AND High Byte in Memory (unstable)
+// X /\ (H + 1) -> M
+pub fn shx(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u32 {
+    let byte = cpu.x;
+    store_and_high_byte(cpu, load_addr, byte);
+    0
+}
+
+// SHY: Store Index Register Y AND High Byte in Memory (unstable)
+// Y /\ (H + 1) -> M
+pub fn shy(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u32 {
+    let byte = cpu.y;
+    store_and_high_byte(cpu, load_addr, byte);
+    0
+}
+
+// TAS: Transfer Accumulator AND Index Register X to Stack Pointer then Store (unstable)
+// A /\ X -> S, S /\ (H + 1) -> M
+pub fn tas(cpu: &mut cpu::CPU, load_addr: cpu::addressing::AddressingMode) -> u32 {
+    cpu.sp = cpu.a & cpu.x;
+    let byte = cpu.sp;
+    store_and_high_byte(cpu, load_addr, byte);
+    0
+}
@@ -13,7 +13,7 @@ use std::time::Instant;
 use crate::emulator::clock;
 use crate::emulator::components::bitfield::BitField;
 use crate::emulator::components::ringbuffer::RingBuffer;
-use crate::emulator::memory::ReadWriter;
+use crate::emulator::memory::{BusAccess, ReadWriter};
 use crate::emulator::state;
 use crate::emulator::util;
 
@@ -27,6 +27,7 @@ pub const NMI_VECTOR: u16 = 0xFFFA;
 const TRACE_FRAME_SIZE: usize = 10;
 const MAX_TRACE_FRAMES: usize = 2_000_000 * TRACE_FRAME_SIZE;
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Flag {
     N = 1 << 7, // Negative
     V = 1 << 6, // Overflow
@@ -43,6 +44,17 @@ impl Into<u8> for Flag {
     }
 }
 
+// A snapshot of the CPU registers, for inspecting and editing in the debugger.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Registers {
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub sp: u8,
+    pub pc: u16,
+    pub p: u8,
+}
+
 pub struct CPU {
     // Connection to main memory.
     memory: Box<dyn ReadWriter>,
@@ -73,10 +85,16 @@ pub struct CPU {
     // NMI triggered?
     nmi_flip_flop: bool,
 
+    // The last value on the data bus, which is read back from anywhere nothing drives it.
+    data_bus: u8,
+
     // Debug tracing execution.
     // Format: a x y sp pch pcl p opcode arg1 arg2
     is_tracing: bool,
     trace_buffer: RingBuffer<u8>,
+
+    // Number of instructions executed, so the debugger can tell when a new one starts.
+    instruction_count: u64,
 }
 
 pub fn new(memory: Box<dyn ReadWriter>) -> CPU {
@@ -93,8 +111,10 @@ pub fn new(memory: Box<dyn ReadWriter>) -> CPU {
         dec_arith_on: true,
         irq_flip_flop: false,
         nmi_flip_flop: false,
+        data_bus: 0,
         is_tracing: false,
         trace_buffer: RingBuffer::new(MAX_TRACE_FRAMES),
+        instruction_count: 0,
     }
 }
 
@@ -151,20 +171,22 @@ impl CPU {
         // to figure out which bytes form the next instruction.
         // Should probably refactor addressing modes so we can just query how many bytes it is.
         let saved_pc = self.pc;
-        let opcode = self.memory.read(self.pc);
+        let saved_data_bus = self.data_bus;
+        let opcode = self.peek_memory(self.pc);
         let (_, addressing_mode, _) = CPU::decode_instruction(opcode);
-        let (_, _) = addressing_mode(self);
+        let (_, _, _) = addressing_mode(self);
         let num_bytes = self.pc - saved_pc;
         self.pc = saved_pc;
+        self.data_bus = saved_data_bus;
 
         // Now we have the number of bytes, lets trace out the instruction.
         let b1 = if num_bytes > 0 {
-            Some(self.memory.read(self.pc + 1))
+            Some(self.peek_memory(self.pc + 1))
         } else {
             None
         };
         let b2 = if num_bytes > 1 {
-            Some(self.memory.read(self.pc + 2))
+            Some(self.peek_memory(self.pc + 2))
         } else {
             None
         };
@@ -176,11 +198,13 @@ impl CPU {
     fn execute_next_instruction(&mut self) -> u32 {
         self.trace_registers();
 
-        let opcode = self.memory.read(self.pc);
+        let pc = self.pc;
+        let opcode = self.read_memory(pc, BusAccess::Fetch);
         self.trace_byte(opcode);
         self.trace_args();
 
         self.pc += 1;
+        self.instruction_count += 1;
         let (operation, addressing_mode, cycles) = CPU::decode_instruction(opcode);
         let extra_cycles = operation(self, addressing_mode);
 
@@ -450,18 +474,173 @@ impl CPU {
             opcodes::TSX => (instructions::tsx, addressing::implied, 2),
             opcodes::TXS => (instructions::txs, addressing::implied, 2),
 
-            _ => panic!("Unknown opcode: {:X}", opcode),
+            // Unofficial opcodes.
+            // ALR, ANC, ARR, AXS
+            opcodes::ALR_IMM => (instructions::alr, addressing::immediate, 2),
+            opcodes::ANC_IMM => (instructions::anc, addressing::immediate, 2),
+            opcodes::ANC_IMM_2B => (instructions::anc, addressing::immediate, 2),
+            opcodes::ARR_IMM => (instructions::arr, addressing::immediate, 2),
+            opcodes::AXS_IMM => (instructions::axs, addressing::immediate, 2),
+
+            // DCP
+            opcodes::DCP_ZPG => (instructions::dcp, addressing::zero_page, 5),
+            opcodes::DCP_ZPG_X => (instructions::dcp, addressing::zero_page_indexed, 6),
+            opcodes::DCP_ABS => (instructions::dcp, addressing::absolute, 6),
+            opcodes::DCP_ABS_X => (instructions::dcp, addressing::absolute_indexed_x, 7),
+            opcodes::DCP_ABS_Y => (instructions::dcp, addressing::absolute_indexed_y, 7),
+            opcodes::DCP_IX_IND => (instructions::dcp, addressing::indexed_indirect, 8),
+            opcodes::DCP_IND_IX => (instructions::dcp, addressing::indirect_indexed, 8),
+
+            // IGN
+            opcodes::IGN_ZPG => (instructions::ign, addressing::zero_page, 3),
+            opcodes::IGN_ZPG_44 => (instructions::ign, addressing::zero_page, 3),
+            opcodes::IGN_ZPG_64 => (instructions::ign, addressing::zero_page, 3),
+            opcodes::IGN_ZPG_X => (instructions::ign, addressing::zero_page_indexed, 4),
+            opcodes::IGN_ZPG_X_34 => (instructions::ign, addressing::zero_page_indexed, 4),
+            opcodes::IGN_ZPG_X_54 => (instructions::ign, addressing::zero_page_indexed, 4),
+            opcodes::IGN_ZPG_X_74 => (instructions::ign, addressing::zero_page_indexed, 4),
+            opcodes::IGN_ZPG_X_D4 => (instructions::ign, addressing::zero_page_indexed, 4),
+            opcodes::IGN_ZPG_X_F4 => (instructions::ign, addressing::zero_page_indexed, 4),
+            opcodes::IGN_ABS => (instructions::ign, addressing::absolute, 4),
+            opcodes::IGN_ABS_X => (instructions::ign, addressing::absolute_indexed_x, 4),
+            opcodes::IGN_ABS_X_3C => (instructions::ign, addressing::absolute_indexed_x, 4),
+            opcodes::IGN_ABS_X_5C => (instructions::ign, addressing::absolute_indexed_x, 4),
+            opcodes::IGN_ABS_X_7C => (instructions::ign, addressing::absolute_indexed_x, 4),
+            opcodes::IGN_ABS_X_DC => (instructions::ign, addressing::absolute_indexed_x, 4),
+            opcodes::IGN_ABS_X_FC => (instructions::ign, addressing::absolute_indexed_x, 4),
+
+            // ISC
+            opcodes::ISC_ZPG => (instructions::isc, addressing::zero_page, 5),
+            opcodes::ISC_ZPG_X => (instructions::isc, addressing::zero_page_indexed, 6),
+            opcodes::ISC_ABS => (instructions::isc, addressing::absolute, 6),
+            opcodes::ISC_ABS_X => (instructions::isc, addressing::absolute_indexed_x, 7),
+            opcodes::ISC_ABS_Y => (instructions::isc, addressing::absolute_indexed_y, 7),
+            opcodes::ISC_IX_IND => (instructions::isc, addressing::indexed_indirect, 8),
+            opcodes::ISC_IND_IX => (instructions::isc, addressing::indirect_indexed, 8),
+
+            // KIL
+            opcodes::KIL => (instructions::kil, addressing::implied, 2),
+            opcodes::KIL_12 => (instructions::kil, addressing::implied, 2),
+            opcodes::KIL_22 => (instructions::kil, addressing::implied, 2),
+            opcodes::KIL_32 => (instructions::kil, addressing::implied, 2),
+            opcodes::KIL_42 => (instructions::kil, addressing::implied, 2),
+            opcodes::KIL_52 => (instructions::kil, addressing::implied, 2),
+            opcodes::KIL_62 => (instructions::kil, addressing::implied, 2),
+            opcodes::KIL_72 => (instructions::kil, addressing::implied, 2),
+            opcodes::KIL_92 => (instructions::kil, addressing::implied, 2),
+            opcodes::KIL_B2 => (instructions::kil, addressing::implied, 2),
+            opcodes::KIL_D2 => (instructions::kil, addressing::implied, 2),
+            opcodes::KIL_F2 => (instructions::kil, addressing::implied, 2),
+
+            // LAS, LAX, LXA
+            opcodes::LAS_ABS_Y => (instructions::las, addressing::absolute_indexed_y, 4),
+            opcodes::LAX_ZPG => (instructions::lax, addressing::zero_page, 3),
+            opcodes::LAX_ZPG_Y => (instructions::lax, addressing::zero_page_indexed_y, 4),
+            opcodes::LAX_ABS => (instructions::lax, addressing::absolute, 4),
+            opcodes::LAX_ABS_Y => (instructions::lax, addressing::absolute_indexed_y, 4),
+            opcodes::LAX_IX_IND => (instructions::lax, addressing::indexed_indirect, 6),
+            opcodes::LAX_IND_IX => (instructions::lax, addressing::indirect_indexed, 5),
+            opcodes::LXA_IMM => (instructions::lxa, addressing::immediate, 2),
+
+            // NOP (unofficial)
+            opcodes::NOP_1A => (instructions::nop, addressing::implied, 2),
+            opcodes::NOP_3A => (instructions::nop, addressing::implied, 2),
+            opcodes::NOP_5A => (instructions::nop, addressing::implied, 2),
+            opcodes::NOP_7A => (instructions::nop, addressing::implied, 2),
+            opcodes::NOP_DA => (instructions::nop, addressing::implied, 2),
+            opcodes::NOP_FA => (instructions::nop, addressing::implied, 2),
+
+            // RLA
+            opcodes::RLA_ZPG => (instructions::rla, addressing::zero_page, 5),
+            opcodes::RLA_ZPG_X => (instructions::rla, addressing::zero_page_indexed, 6),
+            opcodes::RLA_ABS => (instructions::rla, addressing::absolute, 6),
+            opcodes::RLA_ABS_X => (instructions::rla, addressing::absolute_indexed_x, 7),
+            opcodes::RLA_ABS_Y => (instructions::rla, addressing::absolute_indexed_y, 7),
+            opcodes::RLA_IX_IND => (instructions::rla, addressing::indexed_indirect, 8),
+            opcodes::RLA_IND_IX => (instructions::rla, addressing::indirect_indexed, 8),
+
+            // RRA
+            opcodes::RRA_ZPG => (instructions::rra, addressing::zero_page, 5),
+            opcodes::RRA_ZPG_X => (instructions::rra, addressing::zero_page_indexed, 6),
+            opcodes::RRA_ABS => (instructions::rra, addressing::absolute, 6),
+            opcodes::RRA_ABS_X => (instructions::rra, addressing::absolute_indexed_x, 7),
+            opcodes::RRA_ABS_Y => (instructions::rra, addressing::absolute_indexed_y, 7),
+            opcodes::RRA_IX_IND => (instructions::rra, addressing::indexed_indirect, 8),
+            opcodes::RRA_IND_IX => (instructions::rra, addressing::indirect_indexed, 8),
+
+            // SAX
+            opcodes::SAX_ZPG => (instructions::sax, addressing::zero_page, 3),
+            opcodes::SAX_ZPG_Y => (instructions::sax, addressing::zero_page_indexed_y, 4),
+            opcodes::SAX_ABS => (instructions::sax, addressing::absolute, 4),
+            opcodes::SAX_IX_IND => (instructions::sax, addressing::indexed_indirect, 6),
+
+            // SBC (unofficial)
+            opcodes::SBC_IMM_EB => (instructions::sbc, addressing::immediate, 2),
+
+            // SHA, SHX, SHY
+            opcodes::SHA_ABS_Y => (instructions::sha, addressing::absolute_indexed_y, 5),
+            opcodes::SHA_IND_IX => (instructions::sha, addressing::indirect_indexed, 6),
+            opcodes::SHX_ABS_Y => (instructions::shx, addressing::absolute_indexed_y, 5),
+            opcodes::SHY_ABS_X => (instructions::shy, addressing::absolute_indexed_x, 5),
+
+            // SKB
+            opcodes::SKB_IMM => (instructions::ign, addressing::immediate, 2),
+            opcodes::SKB_IMM_82 => (instructions::ign, addressing::immediate, 2),
+            opcodes::SKB_IMM_89 => (instructions::ign, addressing::immediate, 2),
+            opcodes::SKB_IMM_C2 => (instructions::ign, addressing::immediate, 2),
+            opcodes::SKB_IMM_E2 => (instructions::ign, addressing::immediate, 2),
+
+            // SLO
+            opcodes::SLO_ZPG => (instructions::slo, addressing::zero_page, 5),
+            opcodes::SLO_ZPG_X => (instructions::slo, addressing::zero_page_indexed, 6),
+            opcodes::SLO_ABS => (instructions::slo, addressing::absolute, 6),
+            opcodes::SLO_ABS_X => (instructions::slo, addressing::absolute_indexed_x, 7),
+            opcodes::SLO_ABS_Y => (instructions::slo, addressing::absolute_indexed_y, 7),
+            opcodes::SLO_IX_IND => (instructions::slo, addressing::indexed_indirect, 8),
+            opcodes::SLO_IND_IX => (instructions::slo, addressing::indirect_indexed, 8),
+
+            // SRE
+            opcodes::SRE_ZPG => (instructions::sre, addressing::zero_page, 5),
+            opcodes::SRE_ZPG_X => (instructions::sre, addressing::zero_page_indexed, 6),
+            opcodes::SRE_ABS => (instructions::sre, addressing::absolute, 6),
+            opcodes::SRE_ABS_X => (instructions::sre, addressing::absolute_indexed_x, 7),
+            opcodes::SRE_ABS_Y => (instructions::sre, addressing::absolute_indexed_y, 7),
+            opcodes::SRE_IX_IND => (instructions::sre, addressing::indexed_indirect, 8),
+            opcodes::SRE_IND_IX => (instructions::sre, addressing::indirect_indexed, 8),
+
+            // TAS, XAA
+            opcodes::TAS_ABS_Y => (instructions::tas, addressing::absolute_indexed_y, 5),
+            opcodes::XAA_IMM => (instructions::xaa, addressing::immediate, 2),
         }
     }
 
     pub fn load_memory(&mut self, address: u16) -> u8 {
-        self.memory.read(address)
+        self.read_memory(address, BusAccess::Data)
+    }
+
+    pub fn read_memory(&mut self, address: u16, access: BusAccess) -> u8 {
+        self.data_bus = self.memory.read_bus(address, self.data_bus, access);
+        self.data_bus
+    }
+
+    // Reads memory for the debugger or tracer, leaving the data bus as it was.
+    pub fn peek_memory(&mut self, address: u16) -> u8 {
+        self.memory
+            .read_bus(address, self.data_bus, BusAccess::Fetch)
     }
 
     pub fn store_memory(&mut self, address: u16, byte: u8) {
+        self.data_bus = byte;
         self.memory.write(address, byte);
     }
 
+    // Read-modify-write instructions write the unmodified value back on the cycle before the
+    // result, which registers with side effects can see.
+    pub fn store_memory_rmw(&mut self, address: u16, original: u8, result: u8) {
+        self.store_memory(address, original);
+        self.store_memory(address, result);
+    }
+
     fn stack_push(&mut self, byte: u8) {
         let addr = 0x0100 | (self.sp as u16);
         self.sp = self.sp.wrapping_sub(1);
@@ -508,9 +687,9 @@ impl CPU {
             // decoder will ignore them.
             // TODO: Trace these actually as we read them so we don't double-read.
             let pc = self.pc;
-            let arg1 = self.load_memory(pc + 1);
+            let arg1 = self.peek_memory(pc + 1);
             self.trace_buffer.push(arg1);
-            let arg2 = self.load_memory(pc + 2);
+            let arg2 = self.peek_memory(pc + 2);
             self.trace_buffer.push(arg2);
         }
     }
@@ -554,6 +733,73 @@ impl CPU {
     }
 }
 
+// CPU Debugger functions.
+impl CPU {
+    pub fn instruction_count(&self) -> u64 {
+        self.instruction_count
+    }
+
+    pub fn registers(&self) -> Registers {
+        Registers {
+            a: self.a,
+            x: self.x,
+            y: self.y,
+            sp: self.sp,
+            pc: self.pc,
+            p: self.p.as_byte(),
+        }
+    }
+
+    pub fn set_registers(&mut self, registers: Registers) {
+        self.a = registers.a;
+        self.x = registers.x;
+        self.y = registers.y;
+        self.sp = registers.sp;
+        self.pc = registers.pc;
+        self.p.load_byte(registers.p);
+    }
+
+    pub fn is_flag_set(&self, flag: Flag) -> bool {
+        self.p.is_set(flag)
+    }
+
+    pub fn set_flag(&mut self, flag: Flag, on: bool) {
+        if on {
+            self.p.set(flag);
+        } else {
+            self.p.clear(flag);
+        }
+    }
+
+    // Whether the next instruction is a JSR, which the debugger can step over.
+    pub fn next_instruction_is_call(&mut self) -> bool {
+        let pc = self.pc;
+        self.peek_memory(pc) == opcodes::JSR
+    }
+
+    // Formats the next instruction and current registers in the same way as the trace.
+    pub fn format_next_instruction(&mut self) -> String {
+        let pc = self.pc;
+        let (pch, pcl) = util::split_word(pc);
+        let frame = [
+            self.a,
+            self.x,
+            self.y,
+            self.sp,
+            pch,
+            pcl,
+            self.p.as_byte(),
+            self.peek_memory(pc),
+            self.peek_memory(pc.wrapping_add(1)),
+            self.peek_memory(pc.wrapping_add(2)),
+        ];
+
+        let mut buf = vec![];
+        trace::write_trace_frame(&mut buf, &frame);
+        String::from_utf8_lossy(&buf).into_owned()
+    }
+}
+
 // CPU Save State functionality.
 impl<'de> state::SaveState<'de, state::CPUState> for CPU {
     fn freeze(&mut self) -> state::CPUState {
@@ -567,6 +813,7 @@ impl<'de> state::SaveState<'de, state::CPUState> for CPU {
             dec_arith_on: self.dec_arith_on,
             irq_flip_flop: self.irq_flip_flop,
             nmi_flip_flop: self.nmi_flip_flop,
+            data_bus: self.data_bus,
         }
     }
 
@@ -580,5 +827,6 @@ impl<'de> state::SaveState<'de, state::CPUState> for CPU {
         self.dec_arith_on = s.dec_arith_on;
         self.irq_flip_flop = s.irq_flip_flop;
         self.nmi_flip_flop = s.nmi_flip_flop;
+        self.data_bus = s.data_bus;
     }
 }
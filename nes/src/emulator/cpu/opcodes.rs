@@ -187,3 +187,134 @@ opcode!(TAY, 0xA8);
 opcode!(TYA, 0x98);
 opcode!(TSX, 0xBA);
 opcode!(TXS, 0x9A);
+
+// Unofficial opcodes.
+
+opcode!(ALR_IMM, 0x4B);
+
+opcode!(ANC_IMM, 0x0B);
+opcode!(ANC_IMM_2B, 0x2B);
+
+opcode!(ARR_IMM, 0x6B);
+
+opcode!(AXS_IMM, 0xCB);
+
+opcode!(DCP_ZPG, 0xC7);
+opcode!(DCP_ZPG_X, 0xD7);
+opcode!(DCP_ABS, 0xCF);
+opcode!(DCP_ABS_X, 0xDF);
+opcode!(DCP_ABS_Y, 0xDB);
+opcode!(DCP_IX_IND, 0xC3);
+opcode!(DCP_IND_IX, 0xD3);
+
+opcode!(IGN_ZPG, 0x04);
+opcode!(IGN_ZPG_44, 0x44);
+opcode!(IGN_ZPG_64, 0x64);
+opcode!(IGN_ZPG_X, 0x14);
+opcode!(IGN_ZPG_X_34, 0x34);
+opcode!(IGN_ZPG_X_54, 0x54);
+opcode!(IGN_ZPG_X_74, 0x74);
+opcode!(IGN_ZPG_X_D4, 0xD4);
+opcode!(IGN_ZPG_X_F4, 0xF4);
+opcode!(IGN_ABS, 0x0C);
+opcode!(IGN_ABS_X, 0x1C);
+opcode!(IGN_ABS_X_3C, 0x3C);
+opcode!(IGN_ABS_X_5C, 0x5C);
+opcode!(IGN_ABS_X_7C, 0x7C);
+opcode!(IGN_ABS_X_DC, 0xDC);
+opcode!(IGN_ABS_X_FC, 0xFC);
+
+opcode!(ISC_ZPG, 0xE7);
+opcode!(ISC_ZPG_X, 0xF7);
+opcode!(ISC_ABS, 0xEF);
+opcode!(ISC_ABS_X, 0xFF);
+opcode!(ISC_ABS_Y, 0xFB);
+opcode!(ISC_IX_IND, 0xE3);
+opcode!(ISC_IND_IX, 0xF3);
+
+opcode!(KIL, 0x02);
+opcode!(KIL_12, 0x12);
+opcode!(KIL_22, 0x22);
+opcode!(KIL_32, 0x32);
+opcode!(KIL_42, 0x42);
+opcode!(KIL_52, 0x52);
+opcode!(KIL_62, 0x62);
+opcode!(KIL_72, 0x72);
+opcode!(KIL_92, 0x92);
+opcode!(KIL_B2, 0xB2);
+opcode!(KIL_D2, 0xD2);
+opcode!(KIL_F2, 0xF2);
+
+opcode!(LAS_ABS_Y, 0xBB);
+
+opcode!(LAX_ZPG, 0xA7);
+opcode!(LAX_ZPG_Y, 0xB7);
+opcode!(LAX_ABS, 0xAF);
+opcode!(LAX_ABS_Y, 0xBF);
+opcode!(LAX_IX_IND, 0xA3);
+opcode!(LAX_IND_IX, 0xB3);
+
+opcode!(LXA_IMM, 0xAB);
+
+opcode!(NOP_1A, 0x1A);
+opcode!(NOP_3A, 0x3A);
+opcode!(NOP_5A, 0x5A);
+opcode!(NOP_7A, 0x7A);
+opcode!(NOP_DA, 0xDA);
+opcode!(NOP_FA, 0xFA);
+
+opcode!(RLA_ZPG, 0x27);
+opcode!(RLA_ZPG_X, 0x37);
+opcode!(RLA_ABS, 0x2F);
+opcode!(RLA_ABS_X, 0x3F);
+opcode!(RLA_ABS_Y, 0x3B);
+opcode!(RLA_IX_IND, 0x23);
+opcode!(RLA_IND_IX, 0x33);
+
+opcode!(RRA_ZPG, 0x67);
+opcode!(RRA_ZPG_X, 0x77);
+opcode!(RRA_ABS, 0x6F);
+opcode!(RRA_ABS_X, 0x7F);
+opcode!(RRA_ABS_Y, 0x7B);
+opcode!(RRA_IX_IND, 0x63);
+opcode!(RRA_IND_IX, 0x73);
+
+opcode!(SAX_ZPG, 0x87);
+opcode!(SAX_ZPG_Y, 0x97);
+opcode!(SAX_ABS, 0x8F);
+opcode!(SAX_IX_IND, 0x83);
+
+opcode!(SBC_IMM_EB, 0xEB);
+
+opcode!(SHA_ABS_Y, 0x9F);
+opcode!(SHA_IND_IX, 0x93);
+
+opcode!(SHX_ABS_Y, 0x9E);
+
+opcode!(SHY_ABS_X, 0x9C);
+
+opcode!(SKB_IMM, 0x80);
+opcode!(SKB_IMM_82, 0x82);
+opcode!(SKB_IMM_89, 0x89);
+opcode!(SKB_IMM_C2, 0xC2);
+opcode!(SKB_IMM_E2, 0xE2);
+
+opcode!(SLO_ZPG, 0x07);
+opcode!(SLO_ZPG_X, 0x17);
+opcode!(SLO_ABS, 0x0F);
+opcode!(SLO_ABS_X, 0x1F);
+opcode!(SLO_ABS_Y, 0x1B);
+opcode!(SLO_IX_IND, 0x03);
+opcode!(SLO_IND_IX, 0x13);
+
+opcode!(SRE_ZPG, 0x47);
+opcode!(SRE_ZPG_X, 0x57);
+opcode!(SRE_ABS, 0x4F);
+opcode!(SRE_ABS_X, 0x5F);
+opcode!(SRE_ABS_Y, 0x5B);
+opcode!(SRE_IX_IND, 0x43);
+opcode!(SRE_IND_IX, 0x53);
+
+opcode!(TAS_ABS_Y, 0x9B);
+
+opcode!(XAA_IMM, 0x8B);
@@ -15,7 +15,7 @@ fn test_nestest() {
 
     load_rom(&mut cpu);
 
-    let mut trace_lines = load_trace();
+    let trace_lines = load_trace();
 
     cpu.startup_sequence();
 
@@ -25,15 +25,17 @@ fn test_nestest() {
 
     let mut cycles: u64 = 0;
 
-    // At instruction 5004 it starts testing undocumented opcodes which we don't support.
-    for _ in 0..5003 {
-        // We know there are more than 5000 lines.
-        let line = trace_lines.next().unwrap();
+    // From instruction 5004 onwards it tests the unofficial opcodes.
+    for line in trace_lines {
         assert_state(&mut cpu, cycles, line);
 
         let new_cycles = cpu.tick();
         cycles += new_cycles as u64;
     }
+
+    // nestest stores the number of the first failing official and unofficial test at $02 and $03.
+    assert_eq!(cpu.load_memory(0x0002), 0x00);
+    assert_eq!(cpu.load_memory(0x0003), 0x00);
 }
 
 fn assert_state(cpu: &mut cpu::CPU, cycles: u64, line: String) {
@@ -285,7 +285,143 @@ pub fn format_instruction(opcode: u8, b1: u8, b2: u8) -> String {
         opcodes::TSX => ("TSX", 0, format_implied()),
         opcodes::TXS => ("TXS", 0, format_implied()),
 
-        _ => panic!("Unknown opcode: {:X}", opcode),
+        // Unofficial opcodes, marked with a * as in the nestest log.
+        // ALR, ANC, ARR, AXS
+        opcodes::ALR_IMM => ("*ALR", 1, format_immediate(b1)),
+        opcodes::ANC_IMM => ("*ANC", 1, format_immediate(b1)),
+        opcodes::ANC_IMM_2B => ("*ANC", 1, format_immediate(b1)),
+        opcodes::ARR_IMM => ("*ARR", 1, format_immediate(b1)),
+        opcodes::AXS_IMM => ("*AXS", 1, format_immediate(b1)),
+
+        // DCP
+        opcodes::DCP_ZPG => ("*DCP", 1, format_zero_page(b1)),
+        opcodes::DCP_ZPG_X => ("*DCP", 1, format_zero_page_x(b1)),
+        opcodes::DCP_ABS => ("*DCP", 2, format_absolute(b2, b1)),
+        opcodes::DCP_ABS_X => ("*DCP", 2, format_absolute_x(b2, b1)),
+        opcodes::DCP_ABS_Y => ("*DCP", 2, format_absolute_y(b2, b1)),
+        opcodes::DCP_IX_IND => ("*DCP", 1, format_indexed_indirect(b1)),
+        opcodes::DCP_IND_IX => ("*DCP", 1, format_indirect_indexed(b1)),
+
+        // IGN
+        opcodes::IGN_ZPG => ("*NOP", 1, format_zero_page(b1)),
+        opcodes::IGN_ZPG_44 => ("*NOP", 1, format_zero_page(b1)),
+        opcodes::IGN_ZPG_64 => ("*NOP", 1, format_zero_page(b1)),
+        opcodes::IGN_ZPG_X => ("*NOP", 1, format_zero_page_x(b1)),
+        opcodes::IGN_ZPG_X_34 => ("*NOP", 1, format_zero_page_x(b1)),
+        opcodes::IGN_ZPG_X_54 => ("*NOP", 1, format_zero_page_x(b1)),
+        opcodes::IGN_ZPG_X_74 => ("*NOP", 1, format_zero_page_x(b1)),
+        opcodes::IGN_ZPG_X_D4 => ("*NOP", 1, format_zero_page_x(b1)),
+        opcodes::IGN_ZPG_X_F4 => ("*NOP", 1, format_zero_page_x(b1)),
+        opcodes::IGN_ABS => ("*NOP", 2, format_absolute(b2, b1)),
+        opcodes::IGN_ABS_X => ("*NOP", 2, format_absolute_x(b2, b1)),
+        opcodes::IGN_ABS_X_3C => ("*NOP", 2, format_absolute_x(b2, b1)),
+        opcodes::IGN_ABS_X_5C => ("*NOP", 2, format_absolute_x(b2, b1)),
+        opcodes::IGN_ABS_X_7C => ("*NOP", 2, format_absolute_x(b2, b1)),
+        opcodes::IGN_ABS_X_DC => ("*NOP", 2, format_absolute_x(b2, b1)),
+        opcodes::IGN_ABS_X_FC => ("*NOP", 2, format_absolute_x(b2, b1)),
+
+        // ISC
+        opcodes::ISC_ZPG => ("*ISB", 1, format_zero_page(b1)),
+        opcodes::ISC_ZPG_X => ("*ISB", 1, format_zero_page_x(b1)),
+        opcodes::ISC_ABS => ("*ISB", 2, format_absolute(b2, b1)),
+        opcodes::ISC_ABS_X => ("*ISB", 2, format_absolute_x(b2, b1)),
+        opcodes::ISC_ABS_Y => ("*ISB", 2, format_absolute_y(b2, b1)),
+        opcodes::ISC_IX_IND => ("*ISB", 1, format_indexed_indirect(b1)),
+        opcodes::ISC_IND_IX => ("*ISB", 1, format_indirect_indexed(b1)),
+
+        // KIL
+        opcodes::KIL => ("*KIL", 0, format_implied()),
+        opcodes::KIL_12 => ("*KIL", 0, format_implied()),
+        opcodes::KIL_22 => ("*KIL", 0, format_implied()),
+        opcodes::KIL_32 => ("*KIL", 0, format_implied()),
+        opcodes::KIL_42 => ("*KIL", 0, format_implied()),
+        opcodes::KIL_52 => ("*KIL", 0, format_implied()),
+        opcodes::KIL_62 => ("*KIL", 0, format_implied()),
+        opcodes::KIL_72 => ("*KIL", 0, format_implied()),
+        opcodes::KIL_92 => ("*KIL", 0, format_implied()),
+        opcodes::KIL_B2 => ("*KIL", 0, format_implied()),
+        opcodes::KIL_D2 => ("*KIL", 0, format_implied()),
+        opcodes::KIL_F2 => ("*KIL", 0, format_implied()),
+
+        // LAS, LAX, LXA
+        opcodes::LAS_ABS_Y => ("*LAS", 2, format_absolute_y(b2, b1)),
+        opcodes::LAX_ZPG => ("*LAX", 1, format_zero_page(b1)),
+        opcodes::LAX_ZPG_Y => ("*LAX", 1, format_zero_page_y(b1)),
+        opcodes::LAX_ABS => ("*LAX", 2, format_absolute(b2, b1)),
+        opcodes::LAX_ABS_Y => ("*LAX", 2, format_absolute_y(b2, b1)),
+        opcodes::LAX_IX_IND => ("*LAX", 1, format_indexed_indirect(b1)),
+        opcodes::LAX_IND_IX => ("*LAX", 1, format_indirect_indexed(b1)),
+        opcodes::LXA_IMM => ("*LXA", 1, format_immediate(b1)),
+
+        // NOP (unofficial)
+        opcodes::NOP_1A => ("*NOP", 0, format_implied()),
+        opcodes::NOP_3A => ("*NOP", 0, format_implied()),
+        opcodes::NOP_5A => ("*NOP", 0, format_implied()),
+        opcodes::NOP_7A => ("*NOP", 0, format_implied()),
+        opcodes::NOP_DA => ("*NOP", 0, format_implied()),
+        opcodes::NOP_FA => ("*NOP", 0, format_implied()),
+
+        // RLA
+        opcodes::RLA_ZPG => ("*RLA", 1, format_zero_page(b1)),
+        opcodes::RLA_ZPG_X => ("*RLA", 1, format_zero_page_x(b1)),
+        opcodes::RLA_ABS => ("*RLA", 2, format_absolute(b2, b1)),
+        opcodes::RLA_ABS_X => ("*RLA", 2, format_absolute_x(b2, b1)),
+        opcodes::RLA_ABS_Y => ("*RLA", 2, format_absolute_y(b2, b1)),
+        opcodes::RLA_IX_IND => ("*RLA", 1, format_indexed_indirect(b1)),
+        opcodes::RLA_IND_IX => ("*RLA", 1, format_indirect_indexed(b1)),
+
+        // RRA
+        opcodes::RRA_ZPG => ("*RRA", 1, format_zero_page(b1)),
+        opcodes::RRA_ZPG_X => ("*RRA", 1, format_zero_page_x(b1)),
+        opcodes::RRA_ABS => ("*RRA", 2, format_absolute(b2, b1)),
+        opcodes::RRA_ABS_X => ("*RRA", 2, format_absolute_x(b2, b1)),
+        opcodes::RRA_ABS_Y => ("*RRA", 2, format_absolute_y(b2, b1)),
+        opcodes::RRA_IX_IND => ("*RRA", 1, format_indexed_indirect(b1)),
+        opcodes::RRA_IND_IX => ("*RRA", 1, format_indirect_indexed(b1)),
+
+        // SAX
+        opcodes::SAX_ZPG => ("*SAX", 1, format_zero_page(b1)),
+        opcodes::SAX_ZPG_Y => ("*SAX", 1, format_zero_page_y(b1)),
+        opcodes::SAX_ABS => ("*SAX", 2, format_absolute(b2, b1)),
+        opcodes::SAX_IX_IND => ("*SAX", 1, format_indexed_indirect(b1)),
+
+        // SBC (unofficial)
+        opcodes::SBC_IMM_EB => ("*SBC", 1, format_immediate(b1)),
+
+        // SHA, SHX, SHY
+        opcodes::SHA_ABS_Y => ("*SHA", 2, format_absolute_y(b2, b1)),
+        opcodes::SHA_IND_IX => ("*SHA", 1, format_indirect_indexed(b1)),
+        opcodes::SHX_ABS_Y => ("*SHX", 2, format_absolute_y(b2, b1)),
+        opcodes::SHY_ABS_X => ("*SHY", 2, format_absolute_x(b2, b1)),
+
+        // SKB
+        opcodes::SKB_IMM => ("*NOP", 1, format_immediate(b1)),
+        opcodes::SKB_IMM_82 => ("*NOP", 1, format_immediate(b1)),
+        opcodes::SKB_IMM_89 => ("*NOP", 1, format_immediate(b1)),
+        opcodes::SKB_IMM_C2 => ("*NOP", 1, format_immediate(b1)),
+        opcodes::SKB_IMM_E2 => ("*NOP", 1, format_immediate(b1)),
+
+        // SLO
+        opcodes::SLO_ZPG => ("*SLO", 1, format_zero_page(b1)),
+        opcodes::SLO_ZPG_X => ("*SLO", 1, format_zero_page_x(b1)),
+        opcodes::SLO_ABS => ("*SLO", 2, format_absolute(b2, b1)),
+        opcodes::SLO_ABS_X => ("*SLO", 2, format_absolute_x(b2, b1)),
+        opcodes::SLO_ABS_Y => ("*SLO", 2, format_absolute_y(b2, b1)),
+        opcodes::SLO_IX_IND => ("*SLO", 1, format_indexed_indirect(b1)),
+        opcodes::SLO_IND_IX => ("*SLO", 1, format_indirect_indexed(b1)),
+
+        // SRE
+        opcodes::SRE_ZPG => ("*SRE", 1, format_zero_page(b1)),
+        opcodes::SRE_ZPG_X => ("*SRE", 1, format_zero_page_x(b1)),
+        opcodes::SRE_ABS => ("*SRE", 2, format_absolute(b2, b1)),
+        opcodes::SRE_ABS_X => ("*SRE", 2, format_absolute_x(b2, b1)),
+        opcodes::SRE_ABS_Y => ("*SRE", 2, format_absolute_y(b2, b1)),
+        opcodes::SRE_IX_IND => ("*SRE", 1, format_indexed_indirect(b1)),
+        opcodes::SRE_IND_IX => ("*SRE", 1, format_indirect_indexed(b1)),
+
+        // TAS, XAA
+        opcodes::TAS_ABS_Y => ("*TAS", 2, format_absolute_y(b2, b1)),
+        opcodes::XAA_IMM => ("*XAA", 1, format_immediate(b1)),
     };
 
     let mut output = format!("{:02X} ", opcode);
@@ -296,12 +432,12 @@ pub fn format_instruction(opcode: u8, b1: u8, b2: u8) -> String {
     };
     output.push_str(&b1_str);
     let b2_str = if num_args >= 2 {
-        format!("{:02X}  ", b2)
+        format!("{:02X} ", b2)
     } else {
-        String::from("    ")
+        String::from("   ")
     };
     output.push_str(&b2_str);
-    output.push_str(&format!("{} {:<28}", opstring, human));
+    output.push_str(&format!("{:>4} {:<28}", opstring, human));
 
     output
 }
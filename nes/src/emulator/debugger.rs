@@ -0,0 +1,209 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+// Debugger state shared between the NES, which checks breakpoints between instructions, and
+// CPUMemory, which checks read and write watchpoints on every access.
+//
+// When a breakpoint or watchpoint fires, the reason is recorded and the NES stops ticking until
+// it's been collected with `NES::take_break`.
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Access {
+    Read,
+    Write,
+    Execute,
+}
+
+// Watches an inclusive range of CPU addresses for a kind of access.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Watchpoint {
+    pub start: u16,
+    pub end: u16,
+    pub access: Access,
+}
+
+impl Watchpoint {
+    pub fn new(start: u16, end: u16, access: Access) -> Watchpoint {
+        Watchpoint { start, end, access }
+    }
+
+    fn matches(&self, address: u16, access: Access) -> bool {
+        self.access == access && address >= self.start && address <= self.end
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BreakReason {
+    Breakpoint(u16),
+    Watchpoint {
+        address: u16,
+        access: Access,
+        value: u8,
+    },
+    // A step, step over, step out or run to scanline finished.
+    Step,
+}
+
+impl fmt::Display for BreakReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BreakReason::Breakpoint(pc) => write!(f, "Breakpoint at ${:04X}", pc),
+            BreakReason::Watchpoint {
+                address,
+                access: Access::Read,
+                value,
+            } => write!(f, "Read ${:02X} from ${:04X}", value, address),
+            BreakReason::Watchpoint {
+                address,
+                access: Access::Write,
+                value,
+            } => write!(f, "Wrote ${:02X} to ${:04X}", value, address),
+            BreakReason::Watchpoint {
+                address,
+                access: Access::Execute,
+                ..
+            } => write!(f, "Executing ${:04X}", address),
+            BreakReason::Step => write!(f, "Stepped"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StepMode {
+    // Stop before the next instruction.
+    Instruction,
+    // Stop when execution reaches this PC with the stack pointer at least this high, i.e. once a
+    // subroutine has returned.
+    Until { pc: u16, sp: u8 },
+    // Stop once the stack pointer rises above this, i.e. the current subroutine returns.
+    Out { sp: u8 },
+    // Stop when the PPU starts this scanline.
+    Scanline(u16),
+}
+
+#[derive(Default)]
+pub struct Debugger {
+    breakpoints: BTreeSet<u16>,
+    watchpoints: Vec<Watchpoint>,
+    step: Option<StepMode>,
+    break_reason: Option<BreakReason>,
+
+    // Set while the debugger itself is poking at memory, so that it doesn't trip watchpoints.
+    suspended: bool,
+}
+
+impl Debugger {
+    pub fn new() -> Debugger {
+        Debugger::default()
+    }
+
+    // Whether there's anything to check.  Lets the NES skip the checks in the common case.
+    pub fn is_active(&self) -> bool {
+        !self.breakpoints.is_empty() || !self.watchpoints.is_empty() || self.step.is_some()
+    }
+
+    pub fn add_breakpoint(&mut self, pc: u16) {
+        self.breakpoints.insert(pc);
+    }
+
+    pub fn remove_breakpoint(&mut self, pc: u16) -> bool {
+        self.breakpoints.remove(&pc)
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.breakpoints.iter().cloned()
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        if !self.watchpoints.contains(&watchpoint) {
+            self.watchpoints.push(watchpoint);
+        }
+    }
+
+    // Removes all watchpoints covering the address.
+    pub fn remove_watchpoints(&mut self, address: u16) -> bool {
+        let before = self.watchpoints.len();
+        self.watchpoints
+            .retain(|w| address < w.start || address > w.end);
+        self.watchpoints.len() != before
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    pub fn set_step(&mut self, step: Option<StepMode>) {
+        self.step = step;
+    }
+
+    pub fn step(&self) -> Option<StepMode> {
+        self.step
+    }
+
+    pub fn set_suspended(&mut self, suspended: bool) {
+        self.suspended = suspended;
+    }
+
+    pub fn is_breaking(&self) -> bool {
+        self.break_reason.is_some()
+    }
+
+    pub fn take_break(&mut self) -> Option<BreakReason> {
+        self.break_reason.take()
+    }
+
+    // Stop as soon as possible.  Any step in progress is cancelled.
+    pub fn trigger_break(&mut self, reason: BreakReason) {
+        if self.break_reason.is_none() {
+            self.break_reason = Some(reason);
+        }
+        self.step = None;
+    }
+
+    // Called by CPUMemory on each read and write.
+    #[inline]
+    pub fn check_access(&mut self, address: u16, access: Access, value: u8) {
+        if self.watchpoints.is_empty() || self.suspended {
+            return;
+        }
+
+        if self.watchpoints.iter().any(|w| w.matches(address, access)) {
+            self.trigger_break(BreakReason::Watchpoint {
+                address,
+                access,
+                value,
+            });
+        }
+    }
+
+    // Called by the NES before each instruction is executed.
+    pub fn check_instruction(&mut self, pc: u16, sp: u8) {
+        if self.breakpoints.contains(&pc) {
+            self.trigger_break(BreakReason::Breakpoint(pc));
+            return;
+        }
+
+        self.check_access(pc, Access::Execute, 0);
+
+        let step_finished = match self.step {
+            Some(StepMode::Instruction) => true,
+            Some(StepMode::Until {
+                pc: target_pc,
+                sp: target_sp,
+            }) => pc == target_pc && sp >= target_sp,
+            Some(StepMode::Out { sp: start_sp }) => sp > start_sp,
+            _ => false,
+        };
+
+        if step_finished {
+            self.trigger_break(BreakReason::Step);
+        }
+    }
+
+    // Called by the NES whenever the PPU moves to a new scanline.
+    pub fn check_scanline(&mut self, scanline: u16) {
+        if self.step == Some(StepMode::Scanline(scanline)) {
+            self.trigger_break(BreakReason::Step);
+        }
+    }
+}
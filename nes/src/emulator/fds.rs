@@ -0,0 +1,87 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use crate::emulator::ines::RomError;
+
+// Famicom Disk System disk images.
+//
+// A .fds file is each disk side's 65500 bytes one after the other, optionally behind a 16 byte
+// header from fwNES.  The files leave out the gaps between blocks and their CRCs, which the drive
+// needs, so those get put back in when the disk is inserted.  See mappers/fds.rs.
+//
+// Games are booted by the Disk System's BIOS, which Nintendo never released, so it has to be
+// supplied separately.
+
+const HEADER_SIZE: usize = 16;
+const MAGIC: [u8; 4] = [b'F', b'D', b'S', 0x1A];
+pub const SIDE_SIZE: usize = 65500;
+pub const BIOS_SIZE: usize = 0x2000;
+
+// Every side starts with a disk info block, which begins like this.
+const DISK_INFO_BLOCK: &[u8] = b"\x01*NINTENDO-HVC*";
+
+pub struct Disk {
+    sides: Vec<Vec<u8>>,
+    bios: Vec<u8>,
+}
+
+impl Disk {
+    pub fn load<P: AsRef<Path>, Q: AsRef<Path>>(path: P, bios_path: Q) -> Result<Disk, RomError> {
+        let data = read_file(path)?;
+        let bios = read_file(bios_path).map_err(|cause| match cause {
+            RomError::Io(cause) => RomError::MissingBios(cause),
+            cause => cause,
+        })?;
+        Disk::from_bytes(data, bios)
+    }
+
+    pub fn from_bytes(data: Vec<u8>, bios: Vec<u8>) -> Result<Disk, RomError> {
+        if bios.len() != BIOS_SIZE {
+            return Err(RomError::BadBiosSize(bios.len()));
+        }
+
+        let data = if data.starts_with(&MAGIC) {
+            if data.len() < HEADER_SIZE {
+                return Err(RomError::TruncatedHeader);
+            }
+            &data[HEADER_SIZE..]
+        } else {
+            &data[..]
+        };
+
+        let sides: Vec<Vec<u8>> = data
+            .chunks(SIDE_SIZE)
+            .map(|side| {
+                let mut side = side.to_vec();
+                side.resize(SIDE_SIZE, 0);
+                side
+            })
+            .collect();
+        if sides.is_empty() || !sides.iter().all(|side| side.starts_with(DISK_INFO_BLOCK)) {
+            return Err(RomError::BadDiskImage);
+        }
+
+        Ok(Disk { sides, bios })
+    }
+
+    // Whether the data looks like a disk image rather than an iNES ROM.
+    pub fn is_disk_image(data: &[u8]) -> bool {
+        data.starts_with(&MAGIC) || data.starts_with(DISK_INFO_BLOCK)
+    }
+
+    pub fn sides(&self) -> &[Vec<u8>] {
+        &self.sides
+    }
+
+    pub fn bios(&self) -> &[u8] {
+        &self.bios
+    }
+}
+
+fn read_file<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, RomError> {
+    let mut file = File::open(path)?;
+    let mut contents = vec![];
+    file.read_to_end(&mut contents)?;
+    Ok(contents)
+}
@@ -1,51 +1,223 @@
 use std::cell::RefCell;
+use std::error::Error;
+use std::fmt;
 use std::fs::File;
+use std::io;
 use std::io::Read;
 use std::path::Path;
 use std::rc::Rc;
+use std::sync::Arc;
 use std::vec::Vec;
 
-use crate::emulator::mappers;
+use crate::emulator::mappers::MapperRegistry;
 use crate::emulator::memory::{Mapper, Memory};
 use crate::emulator::ppu;
 
+const HEADER_SIZE: usize = 16;
+const TRAINER_SIZE: usize = 512;
+const MAGIC: [u8; 4] = [b'N', b'E', b'S', 0x1A];
+
+// Sizes used when the header doesn't specify them, i.e. for iNES 1.0 ROMs.
+const DEFAULT_PRG_RAM_SIZE: usize = 0x2000;
+const MMC5_MAX_PRG_RAM_SIZE: usize = 0x10000;
+const MMC1_SXROM_PRG_RAM_SIZE: usize = 0x8000;
+const DEFAULT_CHR_RAM_SIZE: usize = 0x2000;
+
+// Bandai's serial EEPROMs, which are kept in PRG RAM.
+const EEPROM_24C01_SIZE: usize = 0x80;
+const EEPROM_24C02_SIZE: usize = 0x100;
+
+// The console always has 2KB of nametable RAM.
+const NAMETABLE_RAM_SIZE: usize = 0x800;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Timing {
+    NTSC,
+    PAL,
+    MultiRegion,
+    Dendy,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ConsoleType {
+    NES,
+    VsSystem,
+    Playchoice10,
+    Extended(u8),
+}
+
+#[derive(Debug)]
+pub enum RomError {
+    Io(io::Error),
+    TruncatedHeader,
+    BadMagic,
+    TruncatedTrainer,
+    MissingPrgRom,
+    TruncatedPrgRom { expected: usize, actual: usize },
+    TruncatedChrRom { expected: usize, actual: usize },
+    UnsupportedMapper(u16),
+    BadDiskImage,
+    MissingBios(io::Error),
+    BadBiosSize(usize),
+    BadNsf(String),
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RomError::Io(cause) => write!(f, "couldn't read ROM file: {}", cause),
+            RomError::TruncatedHeader => write!(f, "file is too short to contain an iNES header"),
+            RomError::BadMagic => write!(f, "not an iNES ROM (bad magic number)"),
+            RomError::TruncatedTrainer => write!(f, "trainer is truncated"),
+            RomError::MissingPrgRom => write!(f, "ROM contains no PRG ROM"),
+            RomError::TruncatedPrgRom { expected, actual } => write!(
+                f,
+                "PRG ROM is truncated: expected {} bytes, found {}",
+                expected, actual
+            ),
+            RomError::TruncatedChrRom { expected, actual } => write!(
+                f,
+                "CHR ROM is truncated: expected {} bytes, found {}",
+                expected, actual
+            ),
+            RomError::UnsupportedMapper(number) => write!(f, "unsupported mapper: {}", number),
+            RomError::BadDiskImage => write!(f, "not a Famicom Disk System disk image"),
+            RomError::MissingBios(cause) => {
+                write!(f, "couldn't read Famicom Disk System BIOS: {}", cause)
+            }
+            RomError::BadBiosSize(size) => write!(
+                f,
+                "Famicom Disk System BIOS should be 8192 bytes, found {}",
+                size
+            ),
+            RomError::BadNsf(reason) => write!(f, "bad NSF file: {}", reason),
+        }
+    }
+}
+
+impl Error for RomError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RomError::Io(cause) | RomError::MissingBios(cause) => Some(cause),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for RomError {
+    fn from(cause: io::Error) -> RomError {
+        RomError::Io(cause)
+    }
+}
+
 pub struct ROM {
     data: Vec<u8>,
+    mapper_registry: Option<Arc<MapperRegistry>>,
 }
 
 impl ROM {
-    pub fn load<P: AsRef<Path>>(path: P) -> ROM {
-        let mut file = match File::open(path) {
-            Err(cause) => panic!("Couldn't open file: {}", cause),
-            Ok(file) => file,
-        };
-
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<ROM, RomError> {
+        let mut file = File::open(path)?;
         let mut contents = vec![];
-        match file.read_to_end(&mut contents) {
-            Err(cause) => panic!("Couldn't read file: {}", cause),
-            Ok(_) => (),
+        file.read_to_end(&mut contents)?;
+        ROM::from_bytes(contents)
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> Result<ROM, RomError> {
+        if data.len() < HEADER_SIZE {
+            return Err(RomError::TruncatedHeader);
+        }
+
+        if data[0..4] != MAGIC {
+            return Err(RomError::BadMagic);
+        }
+
+        let rom = ROM {
+            data,
+            mapper_registry: None,
         };
+        let prg_start = rom.prg_rom_start();
+        if rom.data.len() < prg_start {
+            return Err(RomError::TruncatedTrainer);
+        }
 
-        ROM::from_bytes(contents)
+        let prg_size = rom.prg_rom_size_bytes();
+        if prg_size == 0 {
+            return Err(RomError::MissingPrgRom);
+        }
+
+        let available = rom.data.len() - prg_start;
+        if available < prg_size {
+            return Err(RomError::TruncatedPrgRom {
+                expected: prg_size,
+                actual: available,
+            });
+        }
+
+        let chr_size = rom.chr_rom_size_bytes();
+        if available - prg_size < chr_size {
+            return Err(RomError::TruncatedChrRom {
+                expected: chr_size,
+                actual: available - prg_size,
+            });
+        }
+
+        Ok(rom)
+    }
+
+    // NES 2.0 is identified by bits 2-3 of byte 7 being 0b10.
+    pub fn is_nes2(&self) -> bool {
+        self.data[7] & 0x0C == 0x08
+    }
+
+    pub fn mapper_number(&self) -> u16 {
+        let low = ((self.data[6] & 0xF0) >> 4) as u16;
+        if self.is_nes2() {
+            low | (self.data[7] & 0xF0) as u16 | ((self.data[8] & 0x0F) as u16) << 8
+        } else if self.data[12..16].iter().any(|b| *b != 0) {
+            // Old dumping tools wrote junk like "DiskDude!" into the unused header bytes, which
+            // corrupts the upper mapper nibble.  In that case only trust the lower nibble.
+            low
+        } else {
+            low | (self.data[7] & 0xF0) as u16
+        }
     }
 
-    pub fn from_bytes(data: Vec<u8>) -> ROM {
-        ROM { data }
+    pub fn submapper(&self) -> u8 {
+        if self.is_nes2() { self.data[8] >> 4 } else { 0 }
     }
 
-    pub fn mapper_number(&self) -> u8 {
-        ((self.data[6] & 0xF0) >> 4) | (self.data[7] & 0xF0)
+    // The trainer, if present, sits between the header and PRG ROM.
+    pub fn trainer(&self) -> Option<&[u8]> {
+        if self.has_trainer() {
+            Some(&self.data[HEADER_SIZE..HEADER_SIZE + TRAINER_SIZE])
+        } else {
+            None
+        }
+    }
+
+    fn prg_rom_start(&self) -> usize {
+        if self.has_trainer() {
+            HEADER_SIZE + TRAINER_SIZE
+        } else {
+            HEADER_SIZE
+        }
     }
 
     pub fn prg_rom(&self) -> Memory {
         let size = self.prg_rom_size_bytes();
-        let start = 16 as usize;
-        let end = start + size as usize;
+        let start = self.prg_rom_start();
+        let end = start + size;
         Memory::new_rom(self.data[start..end].to_vec())
     }
 
-    pub fn prg_rom_size_bytes(&self) -> u32 {
-        (self.data[4] as u32) * 16384
+    pub fn prg_rom_size_bytes(&self) -> usize {
+        let msb = if self.is_nes2() {
+            self.data[9] & 0x0F
+        } else {
+            0
+        };
+        rom_size_bytes(self.data[4], msb, 0x4000)
     }
 
     pub fn chr_mem(&self) -> Memory {
@@ -53,57 +225,164 @@ impl ROM {
         let size = self.chr_rom_size_bytes();
 
         if size == 0 {
-            // Cartridge uses chr_ram.
-            Memory::new_ram(0x2000)
+            // Cartridge uses chr_ram.  Mappers assume there's at least 8KB of it.
+            Memory::new_ram(self.chr_ram_size_bytes().max(DEFAULT_CHR_RAM_SIZE))
         } else {
-            let start = (16 + prg_size) as usize;
-            let end = start + size as usize;
+            let start = self.prg_rom_start() + prg_size;
+            let end = start + size;
             Memory::new_rom(self.data[start..end].to_vec())
         }
     }
 
-    pub fn chr_rom_size_bytes(&self) -> u32 {
-        (self.data[5] as u32) * 8192
+    pub fn chr_rom_size_bytes(&self) -> usize {
+        let msb = if self.is_nes2() { self.data[9] >> 4 } else { 0 };
+        rom_size_bytes(self.data[5], msb, 0x2000)
+    }
+
+    // Total PRG RAM, including any battery-backed PRG NVRAM.
+    pub fn prg_ram_size_bytes(&self) -> usize {
+        if self.is_nes2() {
+            ram_size_bytes(self.data[10] & 0x0F) + ram_size_bytes(self.data[10] >> 4)
+        } else {
+            // Byte 8 of iNES 1.0 headers is meant to hold this, but is almost never set.
+            match self.mapper_number() {
+                // Give MMC5 games as much as they could want, since they vary a lot.
+                // 512kb MMC1 boards could be SUROM or SXROM, so allow for SXROM's 32kb.
+                1 if self.prg_rom_size_bytes() >= 0x80000 => MMC1_SXROM_PRG_RAM_SIZE,
+                5 => MMC5_MAX_PRG_RAM_SIZE,
+                16 => EEPROM_24C02_SIZE,
+                159 => EEPROM_24C01_SIZE,
+                _ => DEFAULT_PRG_RAM_SIZE,
+            }
+        }
+    }
+
+    pub fn prg_nvram_size_bytes(&self) -> usize {
+        if self.is_nes2() {
+            ram_size_bytes(self.data[10] >> 4)
+        } else if self.has_battery() {
+            DEFAULT_PRG_RAM_SIZE
+        } else {
+            0
+        }
+    }
+
+    // Total CHR RAM, including any battery-backed CHR NVRAM.
+    pub fn chr_ram_size_bytes(&self) -> usize {
+        if self.is_nes2() {
+            ram_size_bytes(self.data[11] & 0x0F) + ram_size_bytes(self.data[11] >> 4)
+        } else if self.chr_rom_size_bytes() == 0 {
+            DEFAULT_CHR_RAM_SIZE
+        } else {
+            0
+        }
+    }
+
+    pub fn chr_nvram_size_bytes(&self) -> usize {
+        if self.is_nes2() {
+            ram_size_bytes(self.data[11] >> 4)
+        } else {
+            0
+        }
+    }
+
+    // Four-screen cartridges provide another 2KB of nametable RAM on top of the console's.
+    pub fn nametable_ram_size_bytes(&self) -> usize {
+        match self.mirror_mode() {
+            ppu::MirrorMode::FourScreen => NAMETABLE_RAM_SIZE * 2,
+            _ => NAMETABLE_RAM_SIZE,
+        }
+    }
+
+    pub fn timing(&self) -> Timing {
+        if self.is_nes2() {
+            match self.data[12] & 0x03 {
+                0 => Timing::NTSC,
+                1 => Timing::PAL,
+                2 => Timing::MultiRegion,
+                _ => Timing::Dendy,
+            }
+        } else {
+            Timing::NTSC
+        }
+    }
+
+    pub fn console_type(&self) -> ConsoleType {
+        match self.data[7] & 0x03 {
+            0 => ConsoleType::NES,
+            1 => ConsoleType::VsSystem,
+            2 => ConsoleType::Playchoice10,
+            _ if self.is_nes2() => ConsoleType::Extended(self.data[13] & 0x0F),
+            _ => ConsoleType::NES,
+        }
+    }
+
+    // Default expansion device, as listed on the NESdev wiki.  0 means unspecified.
+    pub fn expansion_device(&self) -> u8 {
+        if self.is_nes2() {
+            self.data[15] & 0x3F
+        } else {
+            0
+        }
     }
 
     pub fn mirror_mode(&self) -> ppu::MirrorMode {
-        if self.data[6] & 0x1 == 0 {
+        if self.data[6] & 0x8 != 0 {
+            ppu::MirrorMode::FourScreen
+        } else if self.data[6] & 0x1 == 0 {
             ppu::MirrorMode::Horizontal
         } else {
             ppu::MirrorMode::Vertical
         }
     }
 
-    pub fn get_mapper(&self) -> Rc<RefCell<dyn Mapper>> {
-        let prg_rom = self.prg_rom();
-        let chr_mem = self.chr_mem();
-        let mirror_mode = self.mirror_mode();
-
-        match self.mapper_number() {
-            0 => Rc::new(RefCell::new(mappers::NROM::new(
-                prg_rom,
-                chr_mem,
-                mirror_mode,
-            ))),
-            1 => Rc::new(RefCell::new(mappers::MMC1::new(prg_rom, chr_mem))),
-            2 => Rc::new(RefCell::new(mappers::UXROM::new(
-                prg_rom,
-                chr_mem,
-                mirror_mode,
-            ))),
-            3 => Rc::new(RefCell::new(mappers::CNROM::new(
-                prg_rom,
-                chr_mem,
-                mirror_mode,
-            ))),
-            4 => Rc::new(RefCell::new(mappers::MMC3::new(prg_rom, chr_mem))),
-            7 => Rc::new(RefCell::new(mappers::AXROM::new(prg_rom, chr_mem))),
-            11 => Rc::new(RefCell::new(mappers::ColorDreams::new(
-                prg_rom,
-                chr_mem,
-                mirror_mode,
-            ))),
-            _ => panic!("Unknown mapper: {}", self.mapper_number()),
+    pub fn has_battery(&self) -> bool {
+        self.data[6] & 0x2 != 0
+    }
+
+    // Bandai's EEPROMs keep their contents without a battery, but are saved the same way.
+    pub fn has_eeprom(&self) -> bool {
+        matches!(self.mapper_number(), 16 | 159)
+            && self.submapper() != 4
+            && self.prg_ram_size_bytes() > 0
+    }
+
+    pub fn has_trainer(&self) -> bool {
+        self.data[6] & 0x4 != 0
+    }
+
+    // Uses the built-in mappers unless given a registry with `with_mapper_registry`.
+    pub fn get_mapper(&self) -> Result<Rc<RefCell<dyn Mapper>>, RomError> {
+        match self.mapper_registry {
+            Some(ref registry) => registry.create(self),
+            None => MapperRegistry::new().create(self),
         }
     }
+
+    // Build the mapper from this registry instead, e.g. for boards defined outside this crate.
+    pub fn with_mapper_registry(mut self, registry: Arc<MapperRegistry>) -> ROM {
+        self.mapper_registry = Some(registry);
+        self
+    }
+}
+
+// ROM sizes are given in units of 16KB for PRG and 8KB for CHR.  NES 2.0 adds a most significant
+// nibble, which when set to 0xF instead selects an exponent-multiplier notation: EEEEEEMM gives
+// 2^E * (MM * 2 + 1) bytes.
+fn rom_size_bytes(lsb: u8, msb: u8, unit: usize) -> usize {
+    if msb == 0x0F {
+        let exponent = (lsb >> 2) as u32;
+        let multiplier = ((lsb & 0x03) as usize) * 2 + 1;
+        1usize
+            .checked_shl(exponent)
+            .and_then(|size| size.checked_mul(multiplier))
+            .unwrap_or(usize::MAX)
+    } else {
+        (((msb as usize) << 8) | lsb as usize) * unit
+    }
+}
+
+// NES 2.0 RAM sizes are given as a shift count: 64 << n bytes, or none if 0.
+fn ram_size_bytes(shift: u8) -> usize {
+    if shift == 0 { 0 } else { 64 << shift }
 }
@@ -5,9 +5,9 @@ pub mod palette;
 use std::collections::VecDeque;
 use std::f32::consts::PI;
 
-use crate::emulator::NES_APU_CLOCK_FACTOR;
 use crate::emulator::apu;
 use crate::emulator::ppu;
+use crate::emulator::region::Region;
 use crate::emulator::state::{SaveState, ScreenState};
 
 pub trait Graphics {
@@ -84,6 +84,7 @@ impl<'de> SaveState<'de, ScreenState> for Screen {
 }
 
 pub struct SimpleAudioOut {
+    region: Region,
     buffer: Vec<f32>,
     counter: f32,
     fir_filter: FIRFilter,
@@ -94,10 +95,9 @@ pub struct SimpleAudioOut {
 }
 
 impl SimpleAudioOut {
-    const APU_CLOCK: f32 = 1_789_772.0 / 2.0;
-
     pub fn new(sample_rate: f32) -> SimpleAudioOut {
         SimpleAudioOut {
+            region: Region::NTSC,
             buffer: Vec::new(),
             counter: 0.0,
             // This FIR filter generated from http://t-filter.engineerjs.com/
@@ -322,7 +322,7 @@ impl SimpleAudioOut {
                 -0.00003081140855425942,
                 -0.000026911389850328808,
             ]),
-            low_pass_filter: LowPassFilter::new(35_000.0, SimpleAudioOut::APU_CLOCK),
+            low_pass_filter: LowPassFilter::new(35_000.0, SimpleAudioOut::apu_clock(Region::NTSC)),
             high_pass_filter_1: HighPassFilter::new(440.0, sample_rate),
             high_pass_filter_2: HighPassFilter::new(90.0, sample_rate),
             enabled: true,
@@ -346,7 +346,7 @@ impl SimpleAudioOut {
 
         // Need to downsample all the samples we collected this frame.
         let total = self.buffer.len();
-        let apu_cycles = master_cycles / (NES_APU_CLOCK_FACTOR as u64);
+        let apu_cycles = master_cycles / (self.region.apu_clock_factor() as u64);
         let step = (apu_cycles as f64) / (num_samples as f64);
 
         let mut counter = 0.0;
@@ -369,6 +369,18 @@ impl SimpleAudioOut {
         self.enabled = enabled;
     }
 
+    // Must match the region of the NES we're attached to, otherwise audio will be resampled at
+    // the wrong rate.
+    // The FIR filter is designed for the NTSC APU clock, but the PAL one is close enough.
+    pub fn set_region(&mut self, region: Region) {
+        self.region = region;
+        self.low_pass_filter = LowPassFilter::new(35_000.0, SimpleAudioOut::apu_clock(region));
+    }
+
+    fn apu_clock(region: Region) -> f32 {
+        (region.master_clock_hz() as f32) / (region.apu_clock_factor() as f32)
+    }
+
     fn queue_sample(&mut self, sample: f32) {
         self.buffer.push(sample);
     }
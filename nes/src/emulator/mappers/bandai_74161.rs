@@ -0,0 +1,108 @@
+use crate::emulator::memory::{Mapper, Memory};
+use crate::emulator::ppu::MirrorMode;
+use crate::emulator::state::{Bandai74161State, MapperState, SaveState};
+
+// iNES Mappers 70 and 152: Bandai 74161/7432
+// 16k switchable + 16k fixed PRG ROM.
+// Up to 16 switchable 8kb CHR ROM banks.
+//
+// Mapper 152 takes the top bit of the PRG bank for single-screen mirroring.
+pub struct Bandai74161 {
+    prg_rom: Memory,
+    chr_mem: Memory,
+    mirror_mode: MirrorMode,
+    single_screen: bool,
+    prg_bank: u8,
+    chr_bank: u8,
+}
+
+impl Bandai74161 {
+    pub fn new(
+        prg_rom: Memory,
+        chr_mem: Memory,
+        mirror_mode: MirrorMode,
+        single_screen: bool,
+    ) -> Bandai74161 {
+        Bandai74161 {
+            prg_rom,
+            chr_mem,
+            mirror_mode: if single_screen {
+                MirrorMode::SingleLower
+            } else {
+                mirror_mode
+            },
+            single_screen,
+            prg_bank: 0,
+            chr_bank: 0,
+        }
+    }
+
+    fn chr_address(&self, address: u16) -> usize {
+        ((self.chr_bank as usize) << 13 | address as usize) % self.chr_mem.len()
+    }
+}
+
+impl Mapper for Bandai74161 {
+    fn read_chr(&mut self, address: u16) -> u8 {
+        self.chr_mem.get(self.chr_address(address))
+    }
+
+    fn write_chr(&mut self, address: u16, byte: u8) {
+        let chr_address = self.chr_address(address);
+        self.chr_mem.put(chr_address, byte);
+    }
+
+    fn read_prg(&mut self, address: u16) -> u8 {
+        let bank = if address < 0xC000 {
+            self.prg_bank as usize
+        } else {
+            self.prg_rom.len() / 0x4000 - 1
+        };
+        let rel = (address & 0x3FFF) as usize;
+        self.prg_rom.get(((bank << 14) | rel) % self.prg_rom.len())
+    }
+
+    fn write_prg(&mut self, _address: u16, byte: u8) {
+        self.chr_bank = byte & 0x0F;
+        if self.single_screen {
+            self.prg_bank = (byte >> 4) & 0x07;
+            self.mirror_mode = if byte & 0x80 == 0 {
+                MirrorMode::SingleLower
+            } else {
+                MirrorMode::SingleUpper
+            };
+        } else {
+            self.prg_bank = byte >> 4;
+        }
+    }
+
+    fn mirror_mode(&self) -> MirrorMode {
+        self.mirror_mode
+    }
+}
+
+impl<'de> SaveState<'de, MapperState> for Bandai74161 {
+    fn freeze(&mut self) -> MapperState {
+        MapperState::Bandai74161(Bandai74161State {
+            mirror_mode: self.mirror_mode,
+            prg_bank: self.prg_bank,
+            chr_bank: self.chr_bank,
+            chr_mem: self.chr_mem.freeze(),
+        })
+    }
+
+    fn hydrate(&mut self, state: MapperState) {
+        match state {
+            MapperState::Bandai74161(s) => {
+                self.mirror_mode = s.mirror_mode;
+                self.prg_bank = s.prg_bank;
+                self.chr_bank = s.chr_bank;
+                self.chr_mem.hydrate(s.chr_mem);
+            }
+            _ => panic!(
+                "Incompatible mapper state for Bandai74161 mapper: {:?}",
+                state
+            ),
+        }
+    }
+}
@@ -0,0 +1,294 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::emulator::mappers::eeprom::{Chip, Eeprom};
+use crate::emulator::memory::{Mapper, Memory};
+use crate::emulator::ppu::MirrorMode;
+use crate::emulator::state::{BandaiFCGState, MapperState, SaveState};
+
+// iNES Mappers 16, 153 and 159: Bandai FCG-1, FCG-2 and LZ93D50
+// 1x 16kb switchable PRG ROM bank, plus 1 fixed to the last bank.
+// 8x 1kb switchable CHR ROM banks.
+// A 16-bit IRQ counter, clocked every CPU cycle.
+//
+// The LZ93D50 boards save to a serial EEPROM instead of SRAM: a 24C02 on mapper 16, or a 24C01 on
+// mapper 159.  Mapper 153 has 8kb of SRAM instead, and 8kb of CHR RAM, with bit 0 of the CHR bank
+// registers choosing which half of its 512kb PRG ROM to use.
+pub struct BandaiFCG {
+    prg_rom: Memory,
+    chr_mem: Memory,
+    prg_ram: Option<Rc<RefCell<Memory>>>,
+    variant: Variant,
+
+    chr_banks: [u8; 8],
+    prg_bank: u8,
+    mirror_mode: MirrorMode,
+    prg_ram_enabled: bool,
+
+    irq_enabled: bool,
+    irq_counter: u16,
+    irq_latch: u16,
+    irq_pending: bool,
+
+    eeprom: Option<Eeprom>,
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Variant {
+    // Registers at $6000-$7FFF, and the IRQ counter is written directly.
+    FCG,
+    // Registers at $8000-$FFFF, and the IRQ counter reloads from a latch when enabled.
+    LZ93D50_24C01,
+    LZ93D50_24C02,
+    LZ93D50_SRAM,
+    // iNES 1.0 headers don't say which chip is used, so mapper 16 acts as both.
+    FCG_16,
+}
+
+impl Variant {
+    pub fn from_ines(mapper_number: u16, submapper: u8) -> Variant {
+        match (mapper_number, submapper) {
+            (153, _) => Variant::LZ93D50_SRAM,
+            (159, _) => Variant::LZ93D50_24C01,
+            (_, 4) => Variant::FCG,
+            (_, 5) => Variant::LZ93D50_24C02,
+            (_, _) => Variant::FCG_16,
+        }
+    }
+
+    fn eeprom(self) -> Option<Chip> {
+        match self {
+            Variant::LZ93D50_24C01 => Some(Chip::X24C01),
+            Variant::LZ93D50_24C02 | Variant::FCG_16 => Some(Chip::C24C02),
+            _ => None,
+        }
+    }
+
+    fn has_registers_at(self, address: u16) -> bool {
+        match self {
+            Variant::FCG => address < 0x8000,
+            Variant::FCG_16 => true,
+            _ => address >= 0x8000,
+        }
+    }
+}
+
+impl BandaiFCG {
+    pub fn new(prg_rom: Memory, chr_mem: Memory, variant: Variant) -> BandaiFCG {
+        BandaiFCG {
+            prg_rom,
+            chr_mem,
+            prg_ram: None,
+            variant,
+            chr_banks: [0; 8],
+            prg_bank: 0,
+            mirror_mode: MirrorMode::Vertical,
+            prg_ram_enabled: false,
+            irq_enabled: false,
+            irq_counter: 0,
+            irq_latch: 0,
+            irq_pending: false,
+            eeprom: variant.eeprom().map(Eeprom::new),
+        }
+    }
+
+    fn chr_address(&self, address: u16) -> usize {
+        if self.variant == Variant::LZ93D50_SRAM {
+            return address as usize % self.chr_mem.len();
+        }
+
+        let bank = self.chr_banks[(address >> 10) as usize] as usize;
+        ((bank << 10) | (address & 0x03FF) as usize) % self.chr_mem.len()
+    }
+
+    // Mapper 153's 256kb half of PRG ROM.
+    fn prg_outer_bank(&self) -> usize {
+        if self.variant != Variant::LZ93D50_SRAM {
+            return 0;
+        }
+
+        let outer = self.chr_banks.iter().fold(0, |acc, bank| acc | bank & 0x01);
+        outer as usize * 0x40000
+    }
+
+    fn read_rom(&self, bank: u8, address: u16) -> u8 {
+        let offset =
+            self.prg_outer_bank() + (bank & 0x0F) as usize * 0x4000 + (address & 0x3FFF) as usize;
+        self.prg_rom.get(offset % self.prg_rom.len())
+    }
+
+    fn write_register(&mut self, address: u16, byte: u8) {
+        match address & 0x000F {
+            register @ 0x0..=0x7 => self.chr_banks[register as usize] = byte,
+            0x8 => self.prg_bank = byte & 0x0F,
+            0x9 => {
+                self.mirror_mode = match byte & 0x3 {
+                    0 => MirrorMode::Vertical,
+                    1 => MirrorMode::Horizontal,
+                    2 => MirrorMode::SingleLower,
+                    _ => MirrorMode::SingleUpper,
+                }
+            }
+            0xA => {
+                self.irq_enabled = byte & 0x01 != 0;
+                self.irq_pending = false;
+                if self.variant != Variant::FCG {
+                    self.irq_counter = self.irq_latch;
+                }
+            }
+            0xB => self.write_irq_counter((self.irq_latch & 0xFF00) | byte as u16),
+            0xC => self.write_irq_counter((self.irq_latch & 0x00FF) | (byte as u16) << 8),
+            0xD => {
+                self.prg_ram_enabled = byte & 0x20 != 0;
+                if let Some(ref mut eeprom) = self.eeprom
+                    && let Some(ref prg_ram) = self.prg_ram
+                {
+                    let (scl, sda) = (byte & 0x20 != 0, byte & 0x40 != 0);
+                    eeprom.write_lines(scl, sda, &mut prg_ram.borrow_mut());
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn write_irq_counter(&mut self, value: u16) {
+        self.irq_latch = value;
+        if matches!(self.variant, Variant::FCG | Variant::FCG_16) {
+            self.irq_counter = value;
+        }
+    }
+}
+
+impl Mapper for BandaiFCG {
+    fn read_chr(&mut self, address: u16) -> u8 {
+        let chr_address = self.chr_address(address);
+        self.chr_mem.get(chr_address)
+    }
+
+    fn write_chr(&mut self, address: u16, byte: u8) {
+        let chr_address = self.chr_address(address);
+        self.chr_mem.put(chr_address, byte);
+    }
+
+    fn read_prg(&mut self, address: u16) -> u8 {
+        match address {
+            0x8000..=0xBFFF => self.read_rom(self.prg_bank, address),
+            _ => self.read_rom(0x0F, address),
+        }
+    }
+
+    fn write_prg(&mut self, address: u16, byte: u8) {
+        if self.variant.has_registers_at(address) {
+            self.write_register(address, byte);
+        }
+    }
+
+    fn read_expansion(&mut self, address: u16) -> Option<u8> {
+        if address < 0x6000 {
+            return None;
+        }
+
+        // The EEPROM's data line comes back on bit 4, and the rest is open bus.
+        if let Some(ref eeprom) = self.eeprom {
+            return Some((eeprom.output() as u8) << 4);
+        }
+
+        match self.prg_ram {
+            Some(ref prg_ram) if self.variant == Variant::LZ93D50_SRAM && self.prg_ram_enabled => {
+                let prg_ram = prg_ram.borrow();
+                match prg_ram.len() {
+                    0 => None,
+                    len => Some(prg_ram.get((address & 0x1FFF) as usize % len)),
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn write_expansion(&mut self, address: u16, byte: u8) {
+        if address < 0x6000 {
+            return;
+        }
+
+        if self.variant.has_registers_at(address) {
+            self.write_register(address, byte);
+        } else if self.variant == Variant::LZ93D50_SRAM
+            && self.prg_ram_enabled
+            && let Some(ref prg_ram) = self.prg_ram
+        {
+            let mut prg_ram = prg_ram.borrow_mut();
+            let len = prg_ram.len();
+            if len > 0 {
+                prg_ram.put((address & 0x1FFF) as usize % len, byte);
+            }
+        }
+    }
+
+    fn attach_prg_ram(&mut self, prg_ram: Rc<RefCell<Memory>>) -> bool {
+        self.prg_ram = Some(prg_ram);
+        true
+    }
+
+    fn mirror_mode(&self) -> MirrorMode {
+        self.mirror_mode
+    }
+
+    fn irq_triggered(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn clock_cpu(&mut self, cycles: u32) {
+        if !self.irq_enabled {
+            return;
+        }
+
+        // The counter is checked before it's decremented, so a count of 0 fires straight away.
+        for _ in 0..cycles {
+            if self.irq_counter == 0 {
+                self.irq_pending = true;
+            }
+            self.irq_counter = self.irq_counter.wrapping_sub(1);
+        }
+    }
+}
+
+impl<'de> SaveState<'de, MapperState> for BandaiFCG {
+    fn freeze(&mut self) -> MapperState {
+        MapperState::BandaiFCG(BandaiFCGState {
+            chr_banks: self.chr_banks.to_vec(),
+            prg_bank: self.prg_bank,
+            mirror_mode: self.mirror_mode,
+            prg_ram_enabled: self.prg_ram_enabled,
+            irq_enabled: self.irq_enabled,
+            irq_counter: self.irq_counter,
+            irq_latch: self.irq_latch,
+            irq_pending: self.irq_pending,
+            eeprom: self.eeprom.as_mut().map(|eeprom| eeprom.freeze()),
+            chr_mem: self.chr_mem.freeze(),
+        })
+    }
+
+    fn hydrate(&mut self, state: MapperState) {
+        match state {
+            MapperState::BandaiFCG(s) => {
+                self.chr_banks.copy_from_slice(&s.chr_banks);
+                self.prg_bank = s.prg_bank;
+                self.mirror_mode = s.mirror_mode;
+                self.prg_ram_enabled = s.prg_ram_enabled;
+                self.irq_enabled = s.irq_enabled;
+                self.irq_counter = s.irq_counter;
+                self.irq_latch = s.irq_latch;
+                self.irq_pending = s.irq_pending;
+                if let (Some(eeprom), Some(eeprom_state)) = (self.eeprom.as_mut(), s.eeprom) {
+                    eeprom.hydrate(eeprom_state);
+                }
+                self.chr_mem.hydrate(s.chr_mem);
+            }
+            _ => panic!(
+                "Incompatible mapper state for BandaiFCG mapper: {:?}",
+                state
+            ),
+        }
+    }
+}
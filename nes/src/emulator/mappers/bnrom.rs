@@ -0,0 +1,132 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::emulator::memory::{Mapper, Memory};
+use crate::emulator::ppu::MirrorMode;
+use crate::emulator::state::{BNROMState, MapperState, SaveState};
+
+// iNES Mapper 34: BNROM and NINA-001
+// Switchable 32kb PRG ROM banks.
+//
+// BNROM has 8kb CHR RAM, and its register covers $8000-$FFFF.
+// NINA-001 has 2 switchable 4kb CHR ROM banks, and 8kb PRG RAM, with the registers sitting on top
+// of the last 3 bytes of it, at $7FFD-$7FFF.
+pub struct BNROM {
+    prg_rom: Memory,
+    chr_mem: Memory,
+    prg_ram: Option<Rc<RefCell<Memory>>>,
+    mirror_mode: MirrorMode,
+    nina_001: bool,
+    prg_bank: u8,
+    chr_banks: [u8; 2],
+}
+
+impl BNROM {
+    pub fn new(prg_rom: Memory, chr_mem: Memory, mirror_mode: MirrorMode, nina_001: bool) -> BNROM {
+        BNROM {
+            prg_rom,
+            chr_mem,
+            prg_ram: None,
+            mirror_mode,
+            nina_001,
+            prg_bank: 0,
+            chr_banks: [0, 1],
+        }
+    }
+
+    fn chr_address(&self, address: u16) -> usize {
+        if !self.nina_001 {
+            return address as usize % self.chr_mem.len();
+        }
+
+        let bank = self.chr_banks[(address >> 12) as usize & 0x01] as usize;
+        ((bank << 12) | (address & 0x0FFF) as usize) % self.chr_mem.len()
+    }
+}
+
+impl Mapper for BNROM {
+    fn read_chr(&mut self, address: u16) -> u8 {
+        self.chr_mem.get(self.chr_address(address))
+    }
+
+    fn write_chr(&mut self, address: u16, byte: u8) {
+        let chr_address = self.chr_address(address);
+        self.chr_mem.put(chr_address, byte);
+    }
+
+    fn read_prg(&mut self, address: u16) -> u8 {
+        let base = (self.prg_bank as usize) << 15;
+        let rel = (address & 0x7FFF) as usize;
+        self.prg_rom.get((base | rel) % self.prg_rom.len())
+    }
+
+    fn write_prg(&mut self, _address: u16, byte: u8) {
+        if !self.nina_001 {
+            self.prg_bank = byte;
+        }
+    }
+
+    fn read_expansion(&mut self, address: u16) -> Option<u8> {
+        match self.prg_ram {
+            Some(ref prg_ram) if address >= 0x6000 && prg_ram.borrow().len() > 0 => {
+                let prg_ram = prg_ram.borrow();
+                Some(prg_ram.get((address & 0x1FFF) as usize % prg_ram.len()))
+            }
+            _ => None,
+        }
+    }
+
+    fn write_expansion(&mut self, address: u16, byte: u8) {
+        if address < 0x6000 {
+            return;
+        }
+
+        match address {
+            0x7FFD => self.prg_bank = byte & 0x01,
+            0x7FFE => self.chr_banks[0] = byte & 0x0F,
+            0x7FFF => self.chr_banks[1] = byte & 0x0F,
+            _ => (),
+        }
+
+        if let Some(ref prg_ram) = self.prg_ram {
+            let mut prg_ram = prg_ram.borrow_mut();
+            let len = prg_ram.len();
+            if len > 0 {
+                prg_ram.put((address & 0x1FFF) as usize % len, byte);
+            }
+        }
+    }
+
+    // Only NINA-001 needs to see writes to $6000-$7FFF.
+    fn attach_prg_ram(&mut self, prg_ram: Rc<RefCell<Memory>>) -> bool {
+        if self.nina_001 {
+            self.prg_ram = Some(prg_ram);
+        }
+        self.nina_001
+    }
+
+    fn mirror_mode(&self) -> MirrorMode {
+        self.mirror_mode
+    }
+}
+
+impl<'de> SaveState<'de, MapperState> for BNROM {
+    fn freeze(&mut self) -> MapperState {
+        MapperState::BNROM(BNROMState {
+            prg_bank: self.prg_bank,
+            chr_banks: self.chr_banks.to_vec(),
+            chr_mem: self.chr_mem.freeze(),
+        })
+    }
+
+    fn hydrate(&mut self, state: MapperState) {
+        match state {
+            MapperState::BNROM(s) => {
+                self.prg_bank = s.prg_bank;
+                self.chr_banks.copy_from_slice(&s.chr_banks);
+                self.chr_mem.hydrate(s.chr_mem);
+            }
+            _ => panic!("Incompatible mapper state for BNROM mapper: {:?}", state),
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use crate::emulator::memory::{Mapper, Memory};
+use crate::emulator::ppu::MirrorMode;
+use crate::emulator::state::{CamericaState, MapperState, SaveState};
+
+// iNES Mapper 71: Camerica / Codemasters BF909x
+// 16k switchable + 16k fixed PRG ROM.
+// 8kb CHR RAM.
+//
+// Fire Hawk's board also has single-screen mirroring, controlled from $9000-$9FFF.  Other games
+// never write there, so the first write switches it over from the header's mirroring.
+pub struct Camerica {
+    prg_rom: Memory,
+    chr_mem: Memory,
+    mirror_mode: MirrorMode,
+    prg_bank: u8,
+}
+
+impl Camerica {
+    pub fn new(prg_rom: Memory, chr_mem: Memory, mirror_mode: MirrorMode) -> Camerica {
+        Camerica {
+            prg_rom,
+            chr_mem,
+            mirror_mode,
+            prg_bank: 0,
+        }
+    }
+}
+
+impl Mapper for Camerica {
+    fn read_chr(&mut self, address: u16) -> u8 {
+        self.chr_mem.get(address as usize % self.chr_mem.len())
+    }
+
+    fn write_chr(&mut self, address: u16, byte: u8) {
+        let len = self.chr_mem.len();
+        self.chr_mem.put(address as usize % len, byte);
+    }
+
+    fn read_prg(&mut self, address: u16) -> u8 {
+        let bank = if address < 0xC000 {
+            self.prg_bank as usize
+        } else {
+            self.prg_rom.len() / 0x4000 - 1
+        };
+        let rel = (address & 0x3FFF) as usize;
+        self.prg_rom.get(((bank << 14) | rel) % self.prg_rom.len())
+    }
+
+    fn write_prg(&mut self, address: u16, byte: u8) {
+        match address {
+            0x9000..=0x9FFF => {
+                self.mirror_mode = if byte & 0x10 == 0 {
+                    MirrorMode::SingleLower
+                } else {
+                    MirrorMode::SingleUpper
+                };
+            }
+            0xC000..=0xFFFF => self.prg_bank = byte & 0x0F,
+            _ => (),
+        }
+    }
+
+    fn mirror_mode(&self) -> MirrorMode {
+        self.mirror_mode
+    }
+}
+
+impl<'de> SaveState<'de, MapperState> for Camerica {
+    fn freeze(&mut self) -> MapperState {
+        MapperState::Camerica(CamericaState {
+            mirror_mode: self.mirror_mode,
+            prg_bank: self.prg_bank,
+            chr_mem: self.chr_mem.freeze(),
+        })
+    }
+
+    fn hydrate(&mut self, state: MapperState) {
+        match state {
+            MapperState::Camerica(s) => {
+                self.mirror_mode = s.mirror_mode;
+                self.prg_bank = s.prg_bank;
+                self.chr_mem.hydrate(s.chr_mem);
+            }
+            _ => panic!("Incompatible mapper state for Camerica mapper: {:?}", state),
+        }
+    }
+}
@@ -0,0 +1,247 @@
+use serde::{Deserialize, Serialize};
+
+use crate::emulator::memory::Memory;
+use crate::emulator::state::{EepromState, SaveState};
+
+// Serial EEPROMs, as used on Bandai's boards instead of battery-backed SRAM.
+//
+// The CPU bit-bangs the I2C bus: the EEPROM samples SDA when SCL rises, and changes its own output
+// while SCL is low.  SDA changing while SCL is high marks the start or end of a transfer.  Every
+// byte is followed by an acknowledge bit from whichever side received it.
+//
+// The contents are kept in PRG RAM, so that they're saved the same way as battery-backed SRAM.
+pub struct Eeprom {
+    chip: Chip,
+    mode: Mode,
+    next_mode: Mode,
+    address: u8,
+    data: u8,
+    // Bits of the current byte so far, with 8 meaning the acknowledge bit.
+    bit: u8,
+    acking: bool,
+    scl: bool,
+    sda: bool,
+    output: bool,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Chip {
+    // 128 bytes.  No device address, and bits are sent least significant first.
+    X24C01,
+    // 256 bytes, at device address $A0.
+    C24C02,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum Mode {
+    Idle,
+    DeviceAddress,
+    Address,
+    Read,
+    Write,
+}
+
+impl Chip {
+    fn size(self) -> usize {
+        match self {
+            Chip::X24C01 => 0x80,
+            Chip::C24C02 => 0x100,
+        }
+    }
+
+    // Writes wrap around within a page, rather than carrying into the next one.
+    fn page_size(self) -> u8 {
+        match self {
+            Chip::X24C01 => 4,
+            Chip::C24C02 => 8,
+        }
+    }
+}
+
+impl Eeprom {
+    pub fn new(chip: Chip) -> Eeprom {
+        Eeprom {
+            chip,
+            mode: Mode::Idle,
+            next_mode: Mode::Idle,
+            address: 0,
+            data: 0,
+            bit: 0,
+            acking: false,
+            scl: false,
+            sda: false,
+            output: true,
+        }
+    }
+
+    // What the EEPROM is putting on SDA.  The line is pulled high when nothing drives it.
+    pub fn output(&self) -> bool {
+        self.output
+    }
+
+    pub fn write_lines(&mut self, scl: bool, sda: bool, memory: &mut Memory) {
+        if self.scl && scl {
+            if self.sda && !sda {
+                self.start();
+            } else if !self.sda && sda {
+                self.mode = Mode::Idle;
+                self.output = true;
+            }
+        } else if !self.scl && scl {
+            self.clock_rise(sda, memory);
+        } else if self.scl && !scl {
+            self.clock_fall(memory);
+        }
+
+        self.scl = scl;
+        self.sda = sda;
+    }
+
+    fn start(&mut self) {
+        self.mode = match self.chip {
+            Chip::X24C01 => Mode::Address,
+            Chip::C24C02 => Mode::DeviceAddress,
+        };
+        self.data = 0;
+        self.bit = 0;
+        self.acking = false;
+        self.output = true;
+    }
+
+    fn clock_rise(&mut self, sda: bool, memory: &mut Memory) {
+        match self.mode {
+            Mode::Idle => (),
+            Mode::Read if self.bit < 8 => self.bit += 1,
+            Mode::Read => {
+                // Reading carries on until the CPU doesn't acknowledge a byte.
+                if sda {
+                    self.mode = Mode::Idle;
+                } else {
+                    self.address = self.next_address(memory, false);
+                    self.bit = 0;
+                }
+            }
+            _ if self.bit < 8 => {
+                self.data = match self.chip {
+                    Chip::X24C01 => self.data | (sda as u8) << self.bit,
+                    Chip::C24C02 => (self.data << 1) | sda as u8,
+                };
+                self.bit += 1;
+                if self.bit == 8 {
+                    self.receive(memory);
+                }
+            }
+            _ => {
+                // We've acknowledged the byte.
+                self.mode = self.next_mode;
+                self.data = 0;
+                self.bit = 0;
+                self.acking = false;
+            }
+        }
+    }
+
+    fn clock_fall(&mut self, memory: &Memory) {
+        self.output = match self.mode {
+            Mode::Read if self.bit < 8 => {
+                let byte = self.read(memory);
+                let shift = match self.chip {
+                    Chip::X24C01 => self.bit,
+                    Chip::C24C02 => 7 - self.bit,
+                };
+                (byte >> shift) & 0x01 != 0
+            }
+            Mode::DeviceAddress | Mode::Address | Mode::Write => !(self.bit == 8 && self.acking),
+            _ => true,
+        };
+    }
+
+    fn receive(&mut self, memory: &mut Memory) {
+        let byte = self.data;
+        self.acking = true;
+        match (self.mode, self.chip) {
+            (Mode::DeviceAddress, _) => {
+                if byte & 0xF0 != 0xA0 {
+                    // Addressed to some other device.
+                    self.acking = false;
+                    self.next_mode = Mode::Idle;
+                } else if byte & 0x01 != 0 {
+                    self.next_mode = Mode::Read;
+                } else {
+                    self.next_mode = Mode::Address;
+                }
+            }
+            (Mode::Address, Chip::X24C01) => {
+                self.address = byte & 0x7F;
+                self.next_mode = if byte & 0x80 != 0 {
+                    Mode::Read
+                } else {
+                    Mode::Write
+                };
+            }
+            (Mode::Address, Chip::C24C02) => {
+                self.address = byte;
+                self.next_mode = Mode::Write;
+            }
+            _ => {
+                let size = self.size(memory);
+                if size > 0 {
+                    memory.put(self.address as usize % size, byte);
+                }
+                self.address = self.next_address(memory, true);
+                self.next_mode = Mode::Write;
+            }
+        }
+    }
+
+    fn read(&self, memory: &Memory) -> u8 {
+        match self.size(memory) {
+            0 => 0xFF,
+            size => memory.get(self.address as usize % size),
+        }
+    }
+
+    fn next_address(&self, memory: &Memory, within_page: bool) -> u8 {
+        let size = self.size(memory).max(1);
+        let next = ((self.address as usize + 1) % size) as u8;
+        if within_page {
+            let mask = self.chip.page_size() - 1;
+            (self.address & !mask) | (next & mask)
+        } else {
+            next
+        }
+    }
+
+    // The EEPROM lives at the start of PRG RAM, which may be smaller than the chip.
+    fn size(&self, memory: &Memory) -> usize {
+        self.chip.size().min(memory.len())
+    }
+}
+
+impl<'de> SaveState<'de, EepromState> for Eeprom {
+    fn freeze(&mut self) -> EepromState {
+        EepromState {
+            mode: self.mode,
+            next_mode: self.next_mode,
+            address: self.address,
+            data: self.data,
+            bit: self.bit,
+            acking: self.acking,
+            scl: self.scl,
+            sda: self.sda,
+            output: self.output,
+        }
+    }
+
+    fn hydrate(&mut self, state: EepromState) {
+        self.mode = state.mode;
+        self.next_mode = state.next_mode;
+        self.address = state.address;
+        self.data = state.data;
+        self.bit = state.bit;
+        self.acking = state.acking;
+        self.scl = state.scl;
+        self.sda = state.sda;
+        self.output = state.output;
+    }
+}
@@ -0,0 +1,786 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::emulator::fds::SIDE_SIZE;
+use crate::emulator::memory::{Mapper, Memory};
+use crate::emulator::ppu::MirrorMode;
+use crate::emulator::state::{FDSAudioState, FDSEnvelopeState, FDSState, MapperState, SaveState};
+
+// Famicom Disk System RAM adapter
+// 32kb PRG RAM at $6000-$DFFF, which is the console's SRAM, and the 8kb BIOS at $E000.
+// 8kb CHR RAM.
+// Mirroring is set by software.
+// A 16-bit timer IRQ, the disk drive, and a wavetable sound channel with frequency modulation, all
+// through registers at $4020-$4092.
+//
+// The drive reads or writes a byte every 150 CPU cycles as the disk goes past the head, firing an
+// IRQ for each one.  Disk images leave out the gaps between blocks, and the CRCs after them, so
+// those are put back in when the disk is inserted.  The CRCs are never checked.
+//
+// Writes to the disk last as long as the emulator is running, and are kept in save states, but
+// aren't written back to the disk image.
+pub struct FDS {
+    bios: Memory,
+    chr_mem: Memory,
+    prg_ram: Option<Rc<RefCell<Memory>>>,
+
+    // Each disk side as the drive sees it, including gaps and CRCs.
+    sides: Vec<Vec<u8>>,
+    disk_side: Option<usize>,
+
+    // $4023.
+    disk_registers_enabled: bool,
+    sound_registers_enabled: bool,
+
+    // $4020-$4022.
+    timer_reload: u16,
+    timer_counter: u16,
+    timer_enabled: bool,
+    timer_repeat: bool,
+    timer_irq: bool,
+
+    // $4025.
+    motor_on: bool,
+    reset_transfer: bool,
+    read_mode: bool,
+    mirror_mode: MirrorMode,
+    crc_control: bool,
+    transfer_enabled: bool,
+    disk_irq_enabled: bool,
+
+    // The drive.
+    position: usize,
+    delay: u32,
+    end_of_head: bool,
+    scanning: bool,
+    gap_ended: bool,
+    read_data: u8,
+    write_data: u8,
+    transfer_complete: bool,
+    disk_irq: bool,
+
+    audio: Audio,
+}
+
+// The drive takes this many CPU cycles to get going, and then to move on each byte.
+const SPIN_UP_CYCLES: u32 = 50000;
+const BYTE_CYCLES: u32 = 150;
+
+// Sides start with 28300 bits of gap, and each block is followed by 976 more.
+const LEAD_IN_GAP_SIZE: usize = 28300 / 8;
+const BLOCK_GAP_SIZE: usize = 976 / 8;
+const BLOCK_START_MARK: u8 = 0x80;
+
+// Turns a side from a disk image into what the drive sees.
+fn add_gaps(side: &[u8]) -> Vec<u8> {
+    let mut raw = vec![0; LEAD_IN_GAP_SIZE];
+    let mut pos = 0;
+    while pos < side.len() {
+        // File data blocks take their length from the file header block before them.
+        let length = match side[pos] {
+            1 => 56,
+            2 => 2,
+            3 => 16,
+            4 if pos >= 3 => 1 + side[pos - 3] as usize + ((side[pos - 2] as usize) << 8),
+            _ => break,
+        };
+        let end = (pos + length).min(side.len());
+
+        raw.push(BLOCK_START_MARK);
+        raw.extend_from_slice(&side[pos..end]);
+        // Stand-in CRC.
+        raw.extend_from_slice(&[0x4D, 0x62]);
+        raw.resize(raw.len() + BLOCK_GAP_SIZE, 0);
+        pos = end;
+    }
+
+    // Leave room for games to write more files.
+    raw.resize(raw.len().max(LEAD_IN_GAP_SIZE + SIDE_SIZE), 0);
+    raw
+}
+
+impl FDS {
+    pub fn new(bios: Vec<u8>, sides: Vec<Vec<u8>>) -> FDS {
+        FDS {
+            bios: Memory::new_rom(bios),
+            chr_mem: Memory::new_ram(0x2000),
+            prg_ram: None,
+            sides: sides.iter().map(|side| add_gaps(side)).collect(),
+            disk_side: Some(0),
+            disk_registers_enabled: false,
+            sound_registers_enabled: false,
+            timer_reload: 0,
+            timer_counter: 0,
+            timer_enabled: false,
+            timer_repeat: false,
+            timer_irq: false,
+            motor_on: false,
+            reset_transfer: false,
+            read_mode: true,
+            mirror_mode: MirrorMode::Horizontal,
+            crc_control: false,
+            transfer_enabled: false,
+            disk_irq_enabled: false,
+            position: 0,
+            delay: 0,
+            end_of_head: true,
+            scanning: false,
+            gap_ended: false,
+            read_data: 0,
+            write_data: 0,
+            transfer_complete: false,
+            disk_irq: false,
+            audio: Audio::new(),
+        }
+    }
+
+    fn read_prg_ram(&self, address: u16) -> u8 {
+        match self.prg_ram {
+            Some(ref prg_ram) => {
+                let prg_ram = prg_ram.borrow();
+                prg_ram.get((address - 0x6000) as usize % prg_ram.len())
+            }
+            None => 0,
+        }
+    }
+
+    fn write_prg_ram(&mut self, address: u16, byte: u8) {
+        if let Some(ref prg_ram) = self.prg_ram {
+            let mut prg_ram = prg_ram.borrow_mut();
+            let len = prg_ram.len();
+            prg_ram.put((address - 0x6000) as usize % len, byte);
+        }
+    }
+
+    fn clock_timer(&mut self) {
+        if !self.timer_enabled || !self.disk_registers_enabled {
+            return;
+        }
+
+        if self.timer_counter == 0 {
+            self.timer_irq = true;
+            self.timer_counter = self.timer_reload;
+            if !self.timer_repeat {
+                self.timer_enabled = false;
+            }
+        } else {
+            self.timer_counter -= 1;
+        }
+    }
+
+    fn clock_disk(&mut self) {
+        let side = match self.disk_side {
+            Some(side) if self.motor_on => side,
+            _ => {
+                self.end_of_head = true;
+                self.scanning = false;
+                return;
+            }
+        };
+
+        if self.reset_transfer && !self.scanning {
+            return;
+        }
+
+        // Back at the start of the disk.
+        if self.end_of_head {
+            self.delay = SPIN_UP_CYCLES;
+            self.end_of_head = false;
+            self.position = 0;
+            self.gap_ended = false;
+            return;
+        }
+
+        if self.delay > 0 {
+            self.delay -= 1;
+            return;
+        }
+
+        self.scanning = true;
+        let disk = &mut self.sides[side];
+        if self.read_mode {
+            // Data is only passed on after the start mark at the end of a gap, which doesn't
+            // cause an IRQ itself.
+            let byte = disk[self.position];
+            let mut irq = self.disk_irq_enabled;
+            if !self.transfer_enabled {
+                self.gap_ended = false;
+            } else if byte != 0 && !self.gap_ended {
+                self.gap_ended = true;
+                irq = false;
+            }
+
+            if self.gap_ended {
+                self.transfer_complete = true;
+                self.read_data = byte;
+                self.disk_irq |= irq;
+            }
+        } else {
+            if !self.crc_control {
+                self.transfer_complete = true;
+                self.disk_irq |= self.disk_irq_enabled;
+            }
+
+            disk[self.position] = if self.transfer_enabled && !self.crc_control {
+                self.write_data
+            } else {
+                0
+            };
+            self.gap_ended = false;
+        }
+
+        self.position += 1;
+        if self.position >= disk.len() {
+            self.motor_on = false;
+        } else {
+            self.delay = BYTE_CYCLES;
+        }
+    }
+
+    fn read_register(&mut self, address: u16) -> Option<u8> {
+        match address {
+            0x4030 if self.disk_registers_enabled => {
+                let mut status = 0;
+                if self.timer_irq {
+                    status |= 0x01;
+                }
+                if self.transfer_complete {
+                    status |= 0x02;
+                }
+                if self.end_of_head {
+                    status |= 0x40;
+                }
+                if self.disk_registers_enabled {
+                    status |= 0x80;
+                }
+                self.timer_irq = false;
+                self.transfer_complete = false;
+                self.disk_irq = false;
+                Some(status)
+            }
+            0x4031 if self.disk_registers_enabled => {
+                self.transfer_complete = false;
+                self.disk_irq = false;
+                Some(self.read_data)
+            }
+            0x4032 if self.disk_registers_enabled => {
+                let inserted = self.disk_side.is_some();
+                let mut status = 0;
+                if !inserted {
+                    // No disk, so not ready, and not writeable.
+                    status |= 0x05;
+                }
+                if !inserted || !self.scanning {
+                    status |= 0x02;
+                }
+                Some(status)
+            }
+            // The battery's fine.
+            0x4033 if self.disk_registers_enabled => Some(0x80),
+            0x4040..=0x407F | 0x4090 | 0x4092 if self.sound_registers_enabled => {
+                Some(self.audio.read(address))
+            }
+            _ => None,
+        }
+    }
+
+    fn write_register(&mut self, address: u16, byte: u8) {
+        match address {
+            0x4020 if self.disk_registers_enabled => {
+                self.timer_reload = (self.timer_reload & 0xFF00) | byte as u16;
+            }
+            0x4021 if self.disk_registers_enabled => {
+                self.timer_reload = (self.timer_reload & 0x00FF) | ((byte as u16) << 8);
+            }
+            0x4022 if self.disk_registers_enabled => {
+                self.timer_repeat = byte & 0x01 != 0;
+                self.timer_enabled = byte & 0x02 != 0;
+                if self.timer_enabled {
+                    self.timer_counter = self.timer_reload;
+                } else {
+                    self.timer_irq = false;
+                }
+            }
+            0x4023 => {
+                self.disk_registers_enabled = byte & 0x01 != 0;
+                self.sound_registers_enabled = byte & 0x02 != 0;
+                if !self.disk_registers_enabled {
+                    self.timer_enabled = false;
+                    self.timer_irq = false;
+                    self.disk_irq = false;
+                }
+            }
+            0x4024 if self.disk_registers_enabled => {
+                self.write_data = byte;
+                self.transfer_complete = false;
+                self.disk_irq = false;
+            }
+            0x4025 if self.disk_registers_enabled => {
+                self.motor_on = byte & 0x01 != 0;
+                self.reset_transfer = byte & 0x02 != 0;
+                self.read_mode = byte & 0x04 != 0;
+                self.mirror_mode = if byte & 0x08 != 0 {
+                    MirrorMode::Horizontal
+                } else {
+                    MirrorMode::Vertical
+                };
+                self.crc_control = byte & 0x10 != 0;
+                self.transfer_enabled = byte & 0x40 != 0;
+                self.disk_irq_enabled = byte & 0x80 != 0;
+                self.disk_irq = false;
+            }
+            0x4040..=0x408A if self.sound_registers_enabled => self.audio.write(address, byte),
+            _ => (),
+        }
+    }
+}
+
+impl Mapper for FDS {
+    fn read_chr(&mut self, address: u16) -> u8 {
+        self.chr_mem.get(address as usize)
+    }
+
+    fn write_chr(&mut self, address: u16, byte: u8) {
+        self.chr_mem.put(address as usize, byte);
+    }
+
+    fn read_prg(&mut self, address: u16) -> u8 {
+        match address {
+            0xE000..=0xFFFF => self.bios.get((address - 0xE000) as usize),
+            _ => self.read_prg_ram(address),
+        }
+    }
+
+    fn write_prg(&mut self, address: u16, byte: u8) {
+        if address < 0xE000 {
+            self.write_prg_ram(address, byte);
+        }
+    }
+
+    fn read_expansion(&mut self, address: u16) -> Option<u8> {
+        match address {
+            0x6000..=0x7FFF => Some(self.read_prg_ram(address)),
+            _ => self.read_register(address),
+        }
+    }
+
+    fn write_expansion(&mut self, address: u16, byte: u8) {
+        match address {
+            0x6000..=0x7FFF => self.write_prg_ram(address, byte),
+            _ => self.write_register(address, byte),
+        }
+    }
+
+    fn attach_prg_ram(&mut self, prg_ram: Rc<RefCell<Memory>>) -> bool {
+        self.prg_ram = Some(prg_ram);
+        true
+    }
+
+    fn mirror_mode(&self) -> MirrorMode {
+        self.mirror_mode
+    }
+
+    fn irq_triggered(&self) -> bool {
+        self.timer_irq || self.disk_irq
+    }
+
+    fn clock_cpu(&mut self, cycles: u32) {
+        for _ in 0..cycles {
+            self.clock_timer();
+            self.clock_disk();
+        }
+    }
+
+    fn clock_audio(&mut self) -> f32 {
+        // The channel is clocked by the CPU, twice per APU cycle.
+        self.audio.clock();
+        self.audio.clock();
+        self.audio.output()
+    }
+
+    fn disk_sides(&self) -> usize {
+        self.sides.len()
+    }
+
+    fn disk_side(&self) -> Option<usize> {
+        self.disk_side
+    }
+
+    fn insert_disk(&mut self, side: Option<usize>) {
+        self.disk_side = side.filter(|&side| side < self.sides.len());
+    }
+}
+
+// The volume and modulation envelopes.
+struct Envelope {
+    // $4080 and $4084.
+    disabled: bool,
+    increase: bool,
+    speed: u8,
+    gain: u8,
+    timer: u32,
+}
+
+impl Envelope {
+    fn new() -> Envelope {
+        Envelope {
+            disabled: true,
+            increase: false,
+            speed: 0,
+            gain: 0,
+            timer: 0,
+        }
+    }
+
+    fn write(&mut self, byte: u8, master_speed: u8) {
+        self.disabled = byte & 0x80 != 0;
+        self.increase = byte & 0x40 != 0;
+        self.speed = byte & 0x3F;
+        if self.disabled {
+            self.gain = self.speed;
+        }
+        self.reset_timer(master_speed);
+    }
+
+    fn reset_timer(&mut self, master_speed: u8) {
+        self.timer = 8 * (self.speed as u32 + 1) * master_speed as u32;
+    }
+
+    fn clock(&mut self, master_speed: u8) {
+        if self.disabled {
+            return;
+        }
+
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+
+        self.reset_timer(master_speed);
+        if self.increase && self.gain < 32 {
+            self.gain += 1;
+        } else if !self.increase && self.gain > 0 {
+            self.gain -= 1;
+        }
+    }
+}
+
+struct Audio {
+    // $4040-$407F.  Only writeable while bit 7 of $4089 is set, which also holds the output.
+    wave_table: [u8; 64],
+    wave_write_enabled: bool,
+    // Bits 0-1 of $4089.
+    master_volume: u8,
+    // $408A.
+    envelope_speed: u8,
+
+    volume_envelope: Envelope,
+    wave_frequency: u16,
+    wave_halted: bool,
+    envelopes_halted: bool,
+    wave_accumulator: u32,
+    wave_position: u8,
+
+    // The modulator steps through a table of adjustments to its counter, which bends the wave's
+    // frequency.
+    mod_envelope: Envelope,
+    mod_table: [u8; 64],
+    mod_frequency: u16,
+    mod_halted: bool,
+    mod_accumulator: u32,
+    mod_position: u8,
+    mod_counter: i8,
+
+    level: u8,
+}
+
+const MOD_ADJUSTMENTS: [i8; 8] = [0, 1, 2, 4, 0, -4, -2, -1];
+const MOD_RESET: u8 = 4;
+
+// Master volumes of 2/2, 2/3, 2/4 and 2/5.
+const MASTER_VOLUMES: [f32; 4] = [1.0, 2.0 / 3.0, 2.0 / 4.0, 2.0 / 5.0];
+
+// The channel outputs up to 63 at full volume, a little louder than an APU pulse channel.
+const AUDIO_SCALE: f32 = 0.0035;
+
+impl Audio {
+    fn new() -> Audio {
+        Audio {
+            wave_table: [0; 64],
+            wave_write_enabled: false,
+            master_volume: 0,
+            envelope_speed: 0xE8,
+            volume_envelope: Envelope::new(),
+            wave_frequency: 0,
+            wave_halted: true,
+            envelopes_halted: false,
+            wave_accumulator: 0,
+            wave_position: 0,
+            mod_envelope: Envelope::new(),
+            mod_table: [0; 64],
+            mod_frequency: 0,
+            mod_halted: true,
+            mod_accumulator: 0,
+            mod_position: 0,
+            mod_counter: 0,
+            level: 0,
+        }
+    }
+
+    fn read(&self, address: u16) -> u8 {
+        match address {
+            0x4040..=0x407F => self.wave_table[(address - 0x4040) as usize] | 0x40,
+            0x4090 => self.volume_envelope.gain | 0x40,
+            _ => self.mod_envelope.gain | 0x40,
+        }
+    }
+
+    fn write(&mut self, address: u16, byte: u8) {
+        match address {
+            0x4040..=0x407F if self.wave_write_enabled => {
+                self.wave_table[(address - 0x4040) as usize] = byte & 0x3F;
+            }
+            0x4080 => self.volume_envelope.write(byte, self.envelope_speed),
+            0x4082 => self.wave_frequency = (self.wave_frequency & 0x0F00) | byte as u16,
+            0x4083 => {
+                self.wave_frequency =
+                    (self.wave_frequency & 0x00FF) | (((byte & 0x0F) as u16) << 8);
+                self.wave_halted = byte & 0x80 != 0;
+                self.envelopes_halted = byte & 0x40 != 0;
+                if self.wave_halted {
+                    self.wave_accumulator = 0;
+                    self.wave_position = 0;
+                }
+                if self.envelopes_halted {
+                    self.volume_envelope.reset_timer(self.envelope_speed);
+                    self.mod_envelope.reset_timer(self.envelope_speed);
+                }
+            }
+            0x4084 => self.mod_envelope.write(byte, self.envelope_speed),
+            // A 7-bit signed value.
+            0x4085 => self.mod_counter = ((byte << 1) as i8) >> 1,
+            0x4086 => self.mod_frequency = (self.mod_frequency & 0x0F00) | byte as u16,
+            0x4087 => {
+                self.mod_frequency = (self.mod_frequency & 0x00FF) | (((byte & 0x0F) as u16) << 8);
+                self.mod_halted = byte & 0x80 != 0;
+                if self.mod_halted {
+                    self.mod_accumulator = 0;
+                }
+            }
+            // Each write fills 2 entries of the table, while the modulator is halted.
+            0x4088 if self.mod_halted => {
+                let ix = self.mod_position as usize;
+                self.mod_table[ix] = byte & 0x07;
+                self.mod_table[(ix + 1) & 0x3F] = byte & 0x07;
+                self.mod_position = (self.mod_position + 2) & 0x3F;
+            }
+            0x4089 => {
+                self.wave_write_enabled = byte & 0x80 != 0;
+                self.master_volume = byte & 0x03;
+            }
+            0x408A => self.envelope_speed = byte,
+            _ => (),
+        }
+    }
+
+    // The wave's frequency, bent by the modulator.
+    fn pitch(&self) -> u32 {
+        let mut temp = self.mod_counter as i32 * self.mod_envelope.gain as i32;
+        let remainder = temp & 0x0F;
+        temp >>= 4;
+        if remainder > 0 && temp & 0x80 == 0 {
+            temp += if self.mod_counter < 0 { -1 } else { 2 };
+        }
+        if temp >= 192 {
+            temp -= 256;
+        } else if temp < -64 {
+            temp += 256;
+        }
+
+        let frequency = self.wave_frequency as i32;
+        temp *= frequency;
+        let remainder = temp & 0x3F;
+        temp >>= 6;
+        if remainder >= 32 {
+            temp += 1;
+        }
+        (frequency + temp).max(0) as u32
+    }
+
+    // Called once per CPU cycle.
+    fn clock(&mut self) {
+        if !self.envelopes_halted && !self.wave_halted && self.envelope_speed != 0 {
+            self.volume_envelope.clock(self.envelope_speed);
+            self.mod_envelope.clock(self.envelope_speed);
+        }
+
+        if !self.mod_halted && self.mod_frequency != 0 {
+            self.mod_accumulator += self.mod_frequency as u32;
+            if self.mod_accumulator >= 0x10000 {
+                self.mod_accumulator &= 0xFFFF;
+                let adjustment = self.mod_table[self.mod_position as usize];
+                self.mod_position = (self.mod_position + 1) & 0x3F;
+                self.mod_counter = if adjustment == MOD_RESET {
+                    0
+                } else {
+                    // Wraps around within 7 bits.
+                    let counter = self.mod_counter + MOD_ADJUSTMENTS[adjustment as usize];
+                    (counter << 1) >> 1
+                };
+            }
+        }
+
+        if !self.wave_halted && !self.wave_write_enabled {
+            self.wave_accumulator += self.pitch();
+            if self.wave_accumulator >= 0x10000 {
+                self.wave_accumulator &= 0xFFFF;
+                self.wave_position = (self.wave_position + 1) & 0x3F;
+            }
+        }
+
+        // The output holds while the wave table is being written.
+        if !self.wave_write_enabled {
+            self.level = self.wave_table[self.wave_position as usize];
+        }
+    }
+
+    fn output(&self) -> f32 {
+        let gain = self.volume_envelope.gain.min(32) as f32 / 32.0;
+        self.level as f32 * gain * MASTER_VOLUMES[self.master_volume as usize] * AUDIO_SCALE
+    }
+}
+
+impl<'de> SaveState<'de, FDSEnvelopeState> for Envelope {
+    fn freeze(&mut self) -> FDSEnvelopeState {
+        FDSEnvelopeState {
+            disabled: self.disabled,
+            increase: self.increase,
+            speed: self.speed,
+            gain: self.gain,
+            timer: self.timer,
+        }
+    }
+
+    fn hydrate(&mut self, state: FDSEnvelopeState) {
+        self.disabled = state.disabled;
+        self.increase = state.increase;
+        self.speed = state.speed;
+        self.gain = state.gain;
+        self.timer = state.timer;
+    }
+}
+
+impl<'de> SaveState<'de, FDSAudioState> for Audio {
+    fn freeze(&mut self) -> FDSAudioState {
+        FDSAudioState {
+            wave_table: self.wave_table.to_vec(),
+            wave_write_enabled: self.wave_write_enabled,
+            master_volume: self.master_volume,
+            envelope_speed: self.envelope_speed,
+            volume_envelope: self.volume_envelope.freeze(),
+            wave_frequency: self.wave_frequency,
+            wave_halted: self.wave_halted,
+            envelopes_halted: self.envelopes_halted,
+            wave_accumulator: self.wave_accumulator,
+            wave_position: self.wave_position,
+            mod_envelope: self.mod_envelope.freeze(),
+            mod_table: self.mod_table.to_vec(),
+            mod_frequency: self.mod_frequency,
+            mod_halted: self.mod_halted,
+            mod_accumulator: self.mod_accumulator,
+            mod_position: self.mod_position,
+            mod_counter: self.mod_counter,
+            level: self.level,
+        }
+    }
+
+    fn hydrate(&mut self, state: FDSAudioState) {
+        self.wave_table.copy_from_slice(&state.wave_table);
+        self.wave_write_enabled = state.wave_write_enabled;
+        self.master_volume = state.master_volume;
+        self.envelope_speed = state.envelope_speed;
+        self.volume_envelope.hydrate(state.volume_envelope);
+        self.wave_frequency = state.wave_frequency;
+        self.wave_halted = state.wave_halted;
+        self.envelopes_halted = state.envelopes_halted;
+        self.wave_accumulator = state.wave_accumulator;
+        self.wave_position = state.wave_position;
+        self.mod_envelope.hydrate(state.mod_envelope);
+        self.mod_table.copy_from_slice(&state.mod_table);
+        self.mod_frequency = state.mod_frequency;
+        self.mod_halted = state.mod_halted;
+        self.mod_accumulator = state.mod_accumulator;
+        self.mod_position = state.mod_position;
+        self.mod_counter = state.mod_counter;
+        self.level = state.level;
+    }
+}
+
+impl<'de> SaveState<'de, MapperState> for FDS {
+    fn freeze(&mut self) -> MapperState {
+        MapperState::FDS(FDSState {
+            sides: self.sides.clone(),
+            disk_side: self.disk_side,
+            disk_registers_enabled: self.disk_registers_enabled,
+            sound_registers_enabled: self.sound_registers_enabled,
+            timer_reload: self.timer_reload,
+            timer_counter: self.timer_counter,
+            timer_enabled: self.timer_enabled,
+            timer_repeat: self.timer_repeat,
+            timer_irq: self.timer_irq,
+            motor_on: self.motor_on,
+            reset_transfer: self.reset_transfer,
+            read_mode: self.read_mode,
+            mirror_mode: self.mirror_mode,
+            crc_control: self.crc_control,
+            transfer_enabled: self.transfer_enabled,
+            disk_irq_enabled: self.disk_irq_enabled,
+            position: self.position,
+            delay: self.delay,
+            end_of_head: self.end_of_head,
+            scanning: self.scanning,
+            gap_ended: self.gap_ended,
+            read_data: self.read_data,
+            write_data: self.write_data,
+            transfer_complete: self.transfer_complete,
+            disk_irq: self.disk_irq,
+            audio: self.audio.freeze(),
+            chr_mem: self.chr_mem.freeze(),
+        })
+    }
+
+    fn hydrate(&mut self, state: MapperState) {
+        match state {
+            MapperState::FDS(s) => {
+                self.sides = s.sides;
+                self.disk_side = s.disk_side;
+                self.disk_registers_enabled = s.disk_registers_enabled;
+                self.sound_registers_enabled = s.sound_registers_enabled;
+                self.timer_reload = s.timer_reload;
+                self.timer_counter = s.timer_counter;
+                self.timer_enabled = s.timer_enabled;
+                self.timer_repeat = s.timer_repeat;
+                self.timer_irq = s.timer_irq;
+                self.motor_on = s.motor_on;
+                self.reset_transfer = s.reset_transfer;
+                self.read_mode = s.read_mode;
+                self.mirror_mode = s.mirror_mode;
+                self.crc_control = s.crc_control;
+                self.transfer_enabled = s.transfer_enabled;
+                self.disk_irq_enabled = s.disk_irq_enabled;
+                self.position = s.position;
+                self.delay = s.delay;
+                self.end_of_head = s.end_of_head;
+                self.scanning = s.scanning;
+                self.gap_ended = s.gap_ended;
+                self.read_data = s.read_data;
+                self.write_data = s.write_data;
+                self.transfer_complete = s.transfer_complete;
+                self.disk_irq = s.disk_irq;
+                self.audio.hydrate(s.audio);
+                self.chr_mem.hydrate(s.chr_mem);
+            }
+            _ => panic!("Incompatible mapper state for FDS mapper: {:?}", state),
+        }
+    }
+}
@@ -0,0 +1,405 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::emulator::memory::{Mapper, Memory};
+use crate::emulator::ppu::MirrorMode;
+use crate::emulator::state::{FME7State, MapperState, SaveState, Sunsoft5BState};
+
+// iNES Mapper 69: Sunsoft FME-7 and 5B
+// 4x 8kb switchable PRG banks, the first at $6000 which can be PRG RAM instead of ROM, and the last
+// 8kb fixed at $E000.
+// 8x 1kb switchable CHR banks.
+// A 16-bit IRQ counter, clocked every CPU cycle.
+//
+// The 5B is the same mapper with 3 channels of AY-3-8910 style audio, used by Gimmick!.  Nothing
+// else writes to its registers, so there's no harm in having it on every FME-7.
+pub struct FME7 {
+    prg_rom: Memory,
+    chr_mem: Memory,
+    prg_ram: Option<Rc<RefCell<Memory>>>,
+
+    command: u8,
+    chr_banks: [u8; 8],
+    // Bit 6 of the $6000 bank selects RAM, and bit 7 enables it.
+    prg_banks: [u8; 4],
+    mirror_mode: MirrorMode,
+
+    irq_enabled: bool,
+    irq_counter_enabled: bool,
+    irq_counter: u16,
+    irq_pending: bool,
+
+    audio: Sunsoft5B,
+}
+
+// The channels' counters tick every 16 CPU cycles.
+const AUDIO_PRESCALER_PERIOD: u8 = 8;
+
+// Louder than the APU's pulse channels, as it is on the real cartridge.
+const AUDIO_CHANNEL_SCALE: f32 = 0.15;
+
+struct Sunsoft5B {
+    register: u8,
+    registers: [u8; 16],
+
+    prescaler: u8,
+    tone_counters: [u16; 3],
+    tone_outputs: [bool; 3],
+    noise_counter: u8,
+    noise_lfsr: u32,
+    envelope_counter: u16,
+    // The envelope steps through 32 levels, rising or falling.
+    envelope_step: u8,
+    envelope_rising: bool,
+    envelope_holding: bool,
+
+    // Each step of the volume is 1.5dB.
+    levels: [f32; 32],
+}
+
+impl Sunsoft5B {
+    fn new() -> Sunsoft5B {
+        let mut levels = [0.0; 32];
+        for (level, amplitude) in levels.iter_mut().enumerate().skip(1) {
+            *amplitude = 10f32.powf((level as f32 - 31.0) * 1.5 / 20.0);
+        }
+
+        Sunsoft5B {
+            register: 0,
+            registers: [0; 16],
+            prescaler: AUDIO_PRESCALER_PERIOD,
+            tone_counters: [0; 3],
+            tone_outputs: [false; 3],
+            noise_counter: 0,
+            noise_lfsr: 1,
+            envelope_counter: 0,
+            envelope_step: 0,
+            envelope_rising: false,
+            envelope_holding: false,
+            levels,
+        }
+    }
+
+    fn write(&mut self, byte: u8) {
+        self.registers[self.register as usize] = byte;
+        if self.register == 0x0D {
+            self.envelope_step = 0;
+            self.envelope_rising = byte & 0x04 != 0;
+            self.envelope_holding = false;
+        }
+    }
+
+    fn tone_period(&self, channel: usize) -> u16 {
+        (((self.registers[channel * 2 + 1] & 0x0F) as u16) << 8)
+            | self.registers[channel * 2] as u16
+    }
+
+    fn envelope_level(&self) -> u8 {
+        if self.envelope_rising {
+            self.envelope_step
+        } else {
+            31 - self.envelope_step
+        }
+    }
+
+    fn step_envelope(&mut self) {
+        if self.envelope_holding {
+            return;
+        }
+
+        self.envelope_step += 1;
+        if self.envelope_step < 32 {
+            return;
+        }
+
+        let shape = self.registers[0x0D];
+        let (cont, alternate, hold) = (shape & 0x08 != 0, shape & 0x02 != 0, shape & 0x01 != 0);
+        if !cont {
+            // Drops to silence and stays there.
+            self.envelope_holding = true;
+            self.envelope_rising = false;
+            self.envelope_step = 31;
+        } else if hold {
+            self.envelope_holding = true;
+            self.envelope_step = 31;
+            if alternate {
+                self.envelope_rising = !self.envelope_rising;
+            }
+        } else {
+            self.envelope_step = 0;
+            if alternate {
+                self.envelope_rising = !self.envelope_rising;
+            }
+        }
+    }
+
+    // Called once per APU cycle.
+    fn clock(&mut self) -> f32 {
+        self.prescaler -= 1;
+        if self.prescaler == 0 {
+            self.prescaler = AUDIO_PRESCALER_PERIOD;
+
+            for channel in 0..3 {
+                self.tone_counters[channel] += 1;
+                if self.tone_counters[channel] >= self.tone_period(channel) {
+                    self.tone_counters[channel] = 0;
+                    self.tone_outputs[channel] = !self.tone_outputs[channel];
+                }
+            }
+
+            self.noise_counter += 1;
+            if self.noise_counter >= self.registers[0x06] & 0x1F {
+                self.noise_counter = 0;
+                let feedback = (self.noise_lfsr ^ (self.noise_lfsr >> 3)) & 0x1;
+                self.noise_lfsr = (self.noise_lfsr >> 1) | (feedback << 16);
+            }
+
+            let envelope_period =
+                ((self.registers[0x0C] as u16) << 8) | self.registers[0x0B] as u16;
+            self.envelope_counter += 1;
+            if self.envelope_counter >= envelope_period {
+                self.envelope_counter = 0;
+                self.step_envelope();
+            }
+        }
+
+        // Disabling a channel's tone or noise holds that input high.
+        let mixer = self.registers[0x07];
+        let noise = self.noise_lfsr & 0x1 != 0;
+        let mut output = 0.0;
+        for channel in 0..3 {
+            let tone_on = self.tone_outputs[channel] || mixer & (0x01 << channel) != 0;
+            let noise_on = noise || mixer & (0x08 << channel) != 0;
+            if !(tone_on && noise_on) {
+                continue;
+            }
+
+            let volume = self.registers[0x08 + channel];
+            let level = if volume & 0x10 != 0 {
+                self.envelope_level()
+            } else if volume & 0x0F == 0 {
+                0
+            } else {
+                (volume & 0x0F) * 2 + 1
+            };
+            output += self.levels[level as usize];
+        }
+        output * AUDIO_CHANNEL_SCALE
+    }
+}
+
+impl FME7 {
+    pub fn new(prg_rom: Memory, chr_mem: Memory) -> FME7 {
+        FME7 {
+            prg_rom,
+            chr_mem,
+            prg_ram: None,
+            command: 0,
+            chr_banks: [0; 8],
+            prg_banks: [0; 4],
+            mirror_mode: MirrorMode::Vertical,
+            irq_enabled: false,
+            irq_counter_enabled: false,
+            irq_counter: 0,
+            irq_pending: false,
+            audio: Sunsoft5B::new(),
+        }
+    }
+
+    fn chr_address(&self, address: u16) -> usize {
+        let bank = self.chr_banks[(address >> 10) as usize] as usize;
+        ((bank << 10) | (address & 0x03FF) as usize) % self.chr_mem.len()
+    }
+
+    fn read_rom(&self, bank: u8, address: u16) -> u8 {
+        let offset = (bank & 0x3F) as usize * 0x2000 + (address & 0x1FFF) as usize;
+        self.prg_rom.get(offset % self.prg_rom.len())
+    }
+
+    fn prg_ram_address(&self, address: u16) -> Option<usize> {
+        let bank = self.prg_banks[0];
+        let prg_ram = self.prg_ram.as_ref()?.borrow();
+        if bank & 0xC0 != 0xC0 || prg_ram.len() == 0 {
+            return None;
+        }
+        Some(((bank & 0x3F) as usize * 0x2000 + (address & 0x1FFF) as usize) % prg_ram.len())
+    }
+
+    fn write_parameter(&mut self, byte: u8) {
+        match self.command {
+            0x0..=0x7 => self.chr_banks[self.command as usize] = byte,
+            0x8..=0xB => self.prg_banks[(self.command - 0x8) as usize] = byte,
+            0xC => {
+                self.mirror_mode = match byte & 0x3 {
+                    0 => MirrorMode::Vertical,
+                    1 => MirrorMode::Horizontal,
+                    2 => MirrorMode::SingleLower,
+                    _ => MirrorMode::SingleUpper,
+                }
+            }
+            0xD => {
+                self.irq_enabled = byte & 0x01 != 0;
+                self.irq_counter_enabled = byte & 0x80 != 0;
+                self.irq_pending = false;
+            }
+            0xE => self.irq_counter = (self.irq_counter & 0xFF00) | byte as u16,
+            _ => self.irq_counter = (self.irq_counter & 0x00FF) | ((byte as u16) << 8),
+        }
+    }
+}
+
+impl Mapper for FME7 {
+    fn read_chr(&mut self, address: u16) -> u8 {
+        let chr_address = self.chr_address(address);
+        self.chr_mem.get(chr_address)
+    }
+
+    fn write_chr(&mut self, address: u16, byte: u8) {
+        let chr_address = self.chr_address(address);
+        self.chr_mem.put(chr_address, byte);
+    }
+
+    fn read_prg(&mut self, address: u16) -> u8 {
+        match address {
+            0x8000..=0x9FFF => self.read_rom(self.prg_banks[1], address),
+            0xA000..=0xBFFF => self.read_rom(self.prg_banks[2], address),
+            0xC000..=0xDFFF => self.read_rom(self.prg_banks[3], address),
+            _ => {
+                let offset = self.prg_rom.len() - 0x2000 + (address & 0x1FFF) as usize;
+                self.prg_rom.get(offset)
+            }
+        }
+    }
+
+    fn write_prg(&mut self, address: u16, byte: u8) {
+        match address {
+            0x8000..=0x9FFF => self.command = byte & 0x0F,
+            0xA000..=0xBFFF => self.write_parameter(byte),
+            0xC000..=0xDFFF => self.audio.register = byte & 0x0F,
+            _ => self.audio.write(byte),
+        }
+    }
+
+    fn read_expansion(&mut self, address: u16) -> Option<u8> {
+        match address {
+            0x6000..=0x7FFF => match self.prg_ram_address(address) {
+                Some(ram_address) => self.prg_ram.as_ref().map(|r| r.borrow().get(ram_address)),
+                // With ROM selected, the RAM enable bit doesn't matter.
+                None if self.prg_banks[0] & 0x40 == 0 => {
+                    Some(self.read_rom(self.prg_banks[0], address))
+                }
+                None => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn write_expansion(&mut self, address: u16, byte: u8) {
+        if address < 0x6000 {
+            return;
+        }
+
+        if let Some(ram_address) = self.prg_ram_address(address)
+            && let Some(ref prg_ram) = self.prg_ram
+        {
+            prg_ram.borrow_mut().put(ram_address, byte);
+        }
+    }
+
+    fn attach_prg_ram(&mut self, prg_ram: Rc<RefCell<Memory>>) -> bool {
+        self.prg_ram = Some(prg_ram);
+        true
+    }
+
+    fn mirror_mode(&self) -> MirrorMode {
+        self.mirror_mode
+    }
+
+    fn irq_triggered(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn clock_cpu(&mut self, cycles: u32) {
+        if !self.irq_counter_enabled {
+            return;
+        }
+
+        for _ in 0..cycles {
+            self.irq_counter = self.irq_counter.wrapping_sub(1);
+            if self.irq_counter == 0xFFFF && self.irq_enabled {
+                self.irq_pending = true;
+            }
+        }
+    }
+
+    fn clock_audio(&mut self) -> f32 {
+        self.audio.clock()
+    }
+}
+
+impl<'de> SaveState<'de, Sunsoft5BState> for Sunsoft5B {
+    fn freeze(&mut self) -> Sunsoft5BState {
+        Sunsoft5BState {
+            register: self.register,
+            registers: self.registers.to_vec(),
+            prescaler: self.prescaler,
+            tone_counters: self.tone_counters.to_vec(),
+            tone_outputs: self.tone_outputs.to_vec(),
+            noise_counter: self.noise_counter,
+            noise_lfsr: self.noise_lfsr,
+            envelope_counter: self.envelope_counter,
+            envelope_step: self.envelope_step,
+            envelope_rising: self.envelope_rising,
+            envelope_holding: self.envelope_holding,
+        }
+    }
+
+    fn hydrate(&mut self, state: Sunsoft5BState) {
+        self.register = state.register;
+        self.registers.copy_from_slice(&state.registers);
+        self.prescaler = state.prescaler;
+        self.tone_counters.copy_from_slice(&state.tone_counters);
+        self.tone_outputs.copy_from_slice(&state.tone_outputs);
+        self.noise_counter = state.noise_counter;
+        self.noise_lfsr = state.noise_lfsr;
+        self.envelope_counter = state.envelope_counter;
+        self.envelope_step = state.envelope_step;
+        self.envelope_rising = state.envelope_rising;
+        self.envelope_holding = state.envelope_holding;
+    }
+}
+
+impl<'de> SaveState<'de, MapperState> for FME7 {
+    fn freeze(&mut self) -> MapperState {
+        MapperState::FME7(FME7State {
+            command: self.command,
+            chr_banks: self.chr_banks.to_vec(),
+            prg_banks: self.prg_banks.to_vec(),
+            mirror_mode: self.mirror_mode,
+            irq_enabled: self.irq_enabled,
+            irq_counter_enabled: self.irq_counter_enabled,
+            irq_counter: self.irq_counter,
+            irq_pending: self.irq_pending,
+            audio: self.audio.freeze(),
+            chr_mem: self.chr_mem.freeze(),
+        })
+    }
+
+    fn hydrate(&mut self, state: MapperState) {
+        match state {
+            MapperState::FME7(s) => {
+                self.command = s.command;
+                self.chr_banks.copy_from_slice(&s.chr_banks);
+                self.prg_banks.copy_from_slice(&s.prg_banks);
+                self.mirror_mode = s.mirror_mode;
+                self.irq_enabled = s.irq_enabled;
+                self.irq_counter_enabled = s.irq_counter_enabled;
+                self.irq_counter = s.irq_counter;
+                self.irq_pending = s.irq_pending;
+                self.audio.hydrate(s.audio);
+                self.chr_mem.hydrate(s.chr_mem);
+            }
+            _ => panic!("Incompatible mapper state for FME7 mapper: {:?}", state),
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use crate::emulator::memory::{Mapper, Memory};
+use crate::emulator::ppu::MirrorMode;
+use crate::emulator::state::{GXROMState, MapperState, SaveState};
+
+// iNES Mapper 66: GxROM
+// Up to 4 switchable 32kb PRG ROM banks.
+// Up to 4 switchable 8kb CHR ROM banks.
+pub struct GXROM {
+    prg_rom: Memory,
+    chr_mem: Memory,
+    mirror_mode: MirrorMode,
+    prg_bank: u8,
+    chr_bank: u8,
+}
+
+impl GXROM {
+    pub fn new(prg_rom: Memory, chr_mem: Memory, mirror_mode: MirrorMode) -> GXROM {
+        GXROM {
+            prg_rom,
+            chr_mem,
+            mirror_mode,
+            prg_bank: 0,
+            chr_bank: 0,
+        }
+    }
+
+    fn chr_address(&self, address: u16) -> usize {
+        ((self.chr_bank as usize) << 13 | address as usize) % self.chr_mem.len()
+    }
+}
+
+impl Mapper for GXROM {
+    fn read_chr(&mut self, address: u16) -> u8 {
+        self.chr_mem.get(self.chr_address(address))
+    }
+
+    fn write_chr(&mut self, address: u16, byte: u8) {
+        let chr_address = self.chr_address(address);
+        self.chr_mem.put(chr_address, byte);
+    }
+
+    fn read_prg(&mut self, address: u16) -> u8 {
+        let base = (self.prg_bank as usize) << 15;
+        let rel = (address & 0x7FFF) as usize;
+        self.prg_rom.get((base | rel) % self.prg_rom.len())
+    }
+
+    fn write_prg(&mut self, _address: u16, byte: u8) {
+        self.prg_bank = (byte >> 4) & 0x03;
+        self.chr_bank = byte & 0x03;
+    }
+
+    fn mirror_mode(&self) -> MirrorMode {
+        self.mirror_mode
+    }
+}
+
+impl<'de> SaveState<'de, MapperState> for GXROM {
+    fn freeze(&mut self) -> MapperState {
+        MapperState::GXROM(GXROMState {
+            prg_bank: self.prg_bank,
+            chr_bank: self.chr_bank,
+            chr_mem: self.chr_mem.freeze(),
+        })
+    }
+
+    fn hydrate(&mut self, state: MapperState) {
+        match state {
+            MapperState::GXROM(s) => {
+                self.prg_bank = s.prg_bank;
+                self.chr_bank = s.chr_bank;
+                self.chr_mem.hydrate(s.chr_mem);
+            }
+            _ => panic!("Incompatible mapper state for GXROM mapper: {:?}", state),
+        }
+    }
+}
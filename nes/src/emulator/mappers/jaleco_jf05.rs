@@ -0,0 +1,86 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::emulator::memory::{Mapper, Memory};
+use crate::emulator::ppu::MirrorMode;
+use crate::emulator::state::{JalecoJF05State, MapperState, SaveState};
+
+// iNES Mapper 87: Jaleco JF-05 to JF-10, and similar boards from Konami and Taito
+// Non-switchable PRG ROM, mirrorred to fill the space.
+// Up to 4 switchable 8kb CHR ROM banks, with the 2 bits of the bank number swapped.
+// The register is at $6000-$7FFF, where there's no SRAM.
+pub struct JalecoJF05 {
+    prg_rom: Memory,
+    chr_mem: Memory,
+    mirror_mode: MirrorMode,
+    chr_bank: u8,
+}
+
+impl JalecoJF05 {
+    pub fn new(prg_rom: Memory, chr_mem: Memory, mirror_mode: MirrorMode) -> JalecoJF05 {
+        JalecoJF05 {
+            prg_rom,
+            chr_mem,
+            mirror_mode,
+            chr_bank: 0,
+        }
+    }
+
+    fn chr_address(&self, address: u16) -> usize {
+        ((self.chr_bank as usize) << 13 | address as usize) % self.chr_mem.len()
+    }
+}
+
+impl Mapper for JalecoJF05 {
+    fn read_chr(&mut self, address: u16) -> u8 {
+        self.chr_mem.get(self.chr_address(address))
+    }
+
+    fn write_chr(&mut self, address: u16, byte: u8) {
+        let chr_address = self.chr_address(address);
+        self.chr_mem.put(chr_address, byte);
+    }
+
+    fn read_prg(&mut self, address: u16) -> u8 {
+        self.prg_rom
+            .get((address & 0x7FFF) as usize % self.prg_rom.len())
+    }
+
+    fn write_prg(&mut self, _address: u16, _byte: u8) {}
+
+    fn write_expansion(&mut self, address: u16, byte: u8) {
+        if address >= 0x6000 {
+            self.chr_bank = ((byte & 0x01) << 1) | ((byte & 0x02) >> 1);
+        }
+    }
+
+    fn attach_prg_ram(&mut self, _prg_ram: Rc<RefCell<Memory>>) -> bool {
+        true
+    }
+
+    fn mirror_mode(&self) -> MirrorMode {
+        self.mirror_mode
+    }
+}
+
+impl<'de> SaveState<'de, MapperState> for JalecoJF05 {
+    fn freeze(&mut self) -> MapperState {
+        MapperState::JalecoJF05(JalecoJF05State {
+            chr_bank: self.chr_bank,
+            chr_mem: self.chr_mem.freeze(),
+        })
+    }
+
+    fn hydrate(&mut self, state: MapperState) {
+        match state {
+            MapperState::JalecoJF05(s) => {
+                self.chr_bank = s.chr_bank;
+                self.chr_mem.hydrate(s.chr_mem);
+            }
+            _ => panic!(
+                "Incompatible mapper state for JalecoJF05 mapper: {:?}",
+                state
+            ),
+        }
+    }
+}
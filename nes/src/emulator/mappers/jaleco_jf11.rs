@@ -0,0 +1,92 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::emulator::memory::{Mapper, Memory};
+use crate::emulator::ppu::MirrorMode;
+use crate::emulator::state::{JalecoJF11State, MapperState, SaveState};
+
+// iNES Mapper 140: Jaleco JF-11 and JF-14
+// Up to 4 switchable 32kb PRG ROM banks.
+// Up to 16 switchable 8kb CHR ROM banks.
+// The register is at $6000-$7FFF, where there's no SRAM.
+pub struct JalecoJF11 {
+    prg_rom: Memory,
+    chr_mem: Memory,
+    mirror_mode: MirrorMode,
+    prg_bank: u8,
+    chr_bank: u8,
+}
+
+impl JalecoJF11 {
+    pub fn new(prg_rom: Memory, chr_mem: Memory, mirror_mode: MirrorMode) -> JalecoJF11 {
+        JalecoJF11 {
+            prg_rom,
+            chr_mem,
+            mirror_mode,
+            prg_bank: 0,
+            chr_bank: 0,
+        }
+    }
+
+    fn chr_address(&self, address: u16) -> usize {
+        ((self.chr_bank as usize) << 13 | address as usize) % self.chr_mem.len()
+    }
+}
+
+impl Mapper for JalecoJF11 {
+    fn read_chr(&mut self, address: u16) -> u8 {
+        self.chr_mem.get(self.chr_address(address))
+    }
+
+    fn write_chr(&mut self, address: u16, byte: u8) {
+        let chr_address = self.chr_address(address);
+        self.chr_mem.put(chr_address, byte);
+    }
+
+    fn read_prg(&mut self, address: u16) -> u8 {
+        let base = (self.prg_bank as usize) << 15;
+        let rel = (address & 0x7FFF) as usize;
+        self.prg_rom.get((base | rel) % self.prg_rom.len())
+    }
+
+    fn write_prg(&mut self, _address: u16, _byte: u8) {}
+
+    fn write_expansion(&mut self, address: u16, byte: u8) {
+        if address >= 0x6000 {
+            self.prg_bank = (byte >> 4) & 0x03;
+            self.chr_bank = byte & 0x0F;
+        }
+    }
+
+    fn attach_prg_ram(&mut self, _prg_ram: Rc<RefCell<Memory>>) -> bool {
+        true
+    }
+
+    fn mirror_mode(&self) -> MirrorMode {
+        self.mirror_mode
+    }
+}
+
+impl<'de> SaveState<'de, MapperState> for JalecoJF11 {
+    fn freeze(&mut self) -> MapperState {
+        MapperState::JalecoJF11(JalecoJF11State {
+            prg_bank: self.prg_bank,
+            chr_bank: self.chr_bank,
+            chr_mem: self.chr_mem.freeze(),
+        })
+    }
+
+    fn hydrate(&mut self, state: MapperState) {
+        match state {
+            MapperState::JalecoJF11(s) => {
+                self.prg_bank = s.prg_bank;
+                self.chr_bank = s.chr_bank;
+                self.chr_mem.hydrate(s.chr_mem);
+            }
+            _ => panic!(
+                "Incompatible mapper state for JalecoJF11 mapper: {:?}",
+                state
+            ),
+        }
+    }
+}
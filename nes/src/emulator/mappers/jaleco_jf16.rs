@@ -0,0 +1,102 @@
+use crate::emulator::memory::{Mapper, Memory};
+use crate::emulator::ppu::MirrorMode;
+use crate::emulator::state::{JalecoJF16State, MapperState, SaveState};
+
+// iNES Mapper 78: Jaleco JF-16 and Irem IF-12
+// 16k switchable + 16k fixed PRG ROM.
+// Up to 16 switchable 8kb CHR ROM banks.
+//
+// Bit 3 of the register picks the mirroring: single-screen on JF-16 (Uchuusen), or horizontal and
+// vertical on IF-12 (Holy Diver), which is submapper 3.
+pub struct JalecoJF16 {
+    prg_rom: Memory,
+    chr_mem: Memory,
+    mirror_mode: MirrorMode,
+    hv_mirroring: bool,
+    prg_bank: u8,
+    chr_bank: u8,
+}
+
+impl JalecoJF16 {
+    pub fn new(prg_rom: Memory, chr_mem: Memory, submapper: u8) -> JalecoJF16 {
+        let hv_mirroring = submapper == 3;
+        JalecoJF16 {
+            prg_rom,
+            chr_mem,
+            mirror_mode: if hv_mirroring {
+                MirrorMode::Horizontal
+            } else {
+                MirrorMode::SingleLower
+            },
+            hv_mirroring,
+            prg_bank: 0,
+            chr_bank: 0,
+        }
+    }
+
+    fn chr_address(&self, address: u16) -> usize {
+        ((self.chr_bank as usize) << 13 | address as usize) % self.chr_mem.len()
+    }
+}
+
+impl Mapper for JalecoJF16 {
+    fn read_chr(&mut self, address: u16) -> u8 {
+        self.chr_mem.get(self.chr_address(address))
+    }
+
+    fn write_chr(&mut self, address: u16, byte: u8) {
+        let chr_address = self.chr_address(address);
+        self.chr_mem.put(chr_address, byte);
+    }
+
+    fn read_prg(&mut self, address: u16) -> u8 {
+        let bank = if address < 0xC000 {
+            self.prg_bank as usize
+        } else {
+            self.prg_rom.len() / 0x4000 - 1
+        };
+        let rel = (address & 0x3FFF) as usize;
+        self.prg_rom.get(((bank << 14) | rel) % self.prg_rom.len())
+    }
+
+    fn write_prg(&mut self, _address: u16, byte: u8) {
+        self.prg_bank = byte & 0x07;
+        self.chr_bank = byte >> 4;
+        self.mirror_mode = match (self.hv_mirroring, byte & 0x08 != 0) {
+            (true, false) => MirrorMode::Horizontal,
+            (true, true) => MirrorMode::Vertical,
+            (false, false) => MirrorMode::SingleLower,
+            (false, true) => MirrorMode::SingleUpper,
+        };
+    }
+
+    fn mirror_mode(&self) -> MirrorMode {
+        self.mirror_mode
+    }
+}
+
+impl<'de> SaveState<'de, MapperState> for JalecoJF16 {
+    fn freeze(&mut self) -> MapperState {
+        MapperState::JalecoJF16(JalecoJF16State {
+            mirror_mode: self.mirror_mode,
+            prg_bank: self.prg_bank,
+            chr_bank: self.chr_bank,
+            chr_mem: self.chr_mem.freeze(),
+        })
+    }
+
+    fn hydrate(&mut self, state: MapperState) {
+        match state {
+            MapperState::JalecoJF16(s) => {
+                self.mirror_mode = s.mirror_mode;
+                self.prg_bank = s.prg_bank;
+                self.chr_bank = s.chr_bank;
+                self.chr_mem.hydrate(s.chr_mem);
+            }
+            _ => panic!(
+                "Incompatible mapper state for JalecoJF16 mapper: {:?}",
+                state
+            ),
+        }
+    }
+}
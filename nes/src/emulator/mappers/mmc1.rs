@@ -1,3 +1,6 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
 use crate::emulator::memory::{Mapper, Memory};
 use crate::emulator::ppu::MirrorMode;
 use crate::emulator::state::{MMC1State, MapperState, SaveState};
@@ -6,13 +9,25 @@ use crate::emulator::state::{MMC1State, MapperState, SaveState};
 // 2 switchable 16k PRG ROM banks.
 // 2 switchable 4k CHR ROM banks.
 // Non-switchable CHR ROM.
+//
+// The larger SxROM boards use the upper bits of the CHR bank registers to bank PRG ROM and PRG RAM
+// instead, since they only have 8kb of CHR RAM.  See Variant.
 pub struct MMC1 {
     prg_rom: Memory,
     chr_mem: Memory,
+    prg_ram: Option<Rc<RefCell<Memory>>>,
+    // SOROM's PRG RAM bank which isn't battery-backed.
+    work_ram: Memory,
+    variant: Variant,
 
     load_register: u8,
     write_index: u8,
     control: u8,
+    // The MMC1 ignores the second of two writes on consecutive cycles, as done by read-modify-write
+    // instructions.
+    written: bool,
+    // Which CHR bank register is in use, in 4kb CHR mode.
+    chr_a12: bool,
 
     prg_bank: u8,
     chr_bank_1: u8,
@@ -22,14 +37,51 @@ pub struct MMC1 {
     chr_offsets: [u32; 2],
 }
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Variant {
+    // SNROM, SKROM etc. with up to 256kb of PRG ROM and 8kb of PRG RAM.
+    Standard,
+    // SEROM, SHROM and SH1ROM: 32kb of PRG ROM, which isn't banked.
+    SEROM,
+    // 16kb of PRG RAM, with bit 3 of the CHR bank choosing the 8kb bank.  Only the second bank is
+    // battery-backed.
+    SOROM,
+    // 512kb of PRG ROM, with bit 4 of the CHR bank choosing the 256kb half.
+    SUROM,
+    // SUROM with 32kb of PRG RAM, with bits 2-3 of the CHR bank choosing the 8kb bank.
+    SXROM,
+}
+
+impl Variant {
+    // The boards are told apart by their memory sizes, except for SEROM.
+    pub fn from_ines(submapper: u8, prg_rom_size: usize, prg_ram_size: usize) -> Variant {
+        if submapper == 5 {
+            Variant::SEROM
+        } else if prg_ram_size >= 0x8000 {
+            Variant::SXROM
+        } else if prg_rom_size > 0x40000 {
+            Variant::SUROM
+        } else if prg_ram_size == 0x4000 {
+            Variant::SOROM
+        } else {
+            Variant::Standard
+        }
+    }
+}
+
 impl MMC1 {
-    pub fn new(prg_rom: Memory, chr_mem: Memory) -> MMC1 {
+    pub fn new(prg_rom: Memory, chr_mem: Memory, variant: Variant) -> MMC1 {
         let mut mapper = MMC1 {
             prg_rom,
             chr_mem,
+            prg_ram: None,
+            work_ram: Memory::new_ram(if variant == Variant::SOROM { 0x2000 } else { 0 }),
+            variant,
 
             load_register: 0x10,
             write_index: 0,
+            written: false,
+            chr_a12: false,
 
             // 4bit0
             // -----
@@ -66,7 +118,7 @@ impl MMC1 {
             }
             3 => {
                 self.prg_offsets[0] = self.prg_offset((self.prg_bank as u32) & 0x0F);
-                self.prg_offsets[1] = self.prg_offset((self.prg_rom.len() as u32) / 0x4000 - 1);
+                self.prg_offsets[1] = self.prg_offset(self.prg_banks() - 1);
             }
             _ => panic!("Invalid prg control value: {:b}", self.control),
         }
@@ -84,8 +136,45 @@ impl MMC1 {
         }
     }
 
+    // Banks within the 256kb which the PRG bank register can reach.
+    fn prg_banks(&self) -> u32 {
+        ((self.prg_rom.len() as u32) / 0x4000).min(16)
+    }
+
     fn prg_offset(&self, index: u32) -> u32 {
-        (index % ((self.prg_rom.len() as u32) / 0x4000)) * 0x4000
+        (index % self.prg_banks()) * 0x4000
+    }
+
+    // The CHR bank register which SxROM boards take their extra bank bits from.  In 4kb CHR mode
+    // it's whichever one the PPU is using.
+    fn chr_bank(&self) -> u8 {
+        if self.control & 0x10 != 0 && self.chr_a12 {
+            self.chr_bank_2
+        } else {
+            self.chr_bank_1
+        }
+    }
+
+    fn prg_outer_offset(&self) -> u32 {
+        match self.variant {
+            Variant::SUROM | Variant::SXROM => (self.chr_bank() as u32 & 0x10) << 14,
+            _ => 0,
+        }
+    }
+
+    // Returns the PRG RAM bank, and whether it's the battery-backed RAM rather than SOROM's work
+    // RAM.
+    fn prg_ram_bank(&self) -> (usize, bool) {
+        match self.variant {
+            Variant::SOROM => (0, self.chr_bank() & 0x08 != 0),
+            Variant::SXROM => (((self.chr_bank() >> 2) & 0x03) as usize, true),
+            _ => (0, true),
+        }
+    }
+
+    // Bit 4 of the PRG bank register disables PRG RAM.
+    fn prg_ram_enabled(&self) -> bool {
+        self.prg_bank & 0x10 == 0
     }
 
     fn chr_offset(&self, index: u32) -> u32 {
@@ -95,6 +184,7 @@ impl MMC1 {
 
 impl Mapper for MMC1 {
     fn read_chr(&mut self, address: u16) -> u8 {
+        self.chr_a12 = address & 0x1000 != 0;
         let rel = address;
         let bank = rel / 0x1000;
         let offset = rel % 0x1000;
@@ -114,13 +204,23 @@ impl Mapper for MMC1 {
 
     fn read_prg(&mut self, address: u16) -> u8 {
         let rel = address - 0x8000;
+        if self.variant == Variant::SEROM {
+            return self.prg_rom.get(rel as usize % self.prg_rom.len());
+        }
+
         let bank = rel / 0x4000;
         let offset = rel % 0x4000;
-        let final_addr = self.prg_offsets[bank as usize] + (offset as u32);
-        self.prg_rom.get(final_addr as usize)
+        let final_addr =
+            self.prg_outer_offset() + self.prg_offsets[bank as usize] + (offset as u32);
+        self.prg_rom.get(final_addr as usize % self.prg_rom.len())
     }
 
     fn write_prg(&mut self, address: u16, byte: u8) {
+        if self.written {
+            return;
+        }
+        self.written = true;
+
         // If bit 7 is set, clear the register.
         if byte & 0x80 != 0 {
             self.load_register = 0;
@@ -155,6 +255,54 @@ impl Mapper for MMC1 {
         }
     }
 
+    fn read_expansion(&mut self, address: u16) -> Option<u8> {
+        if address < 0x6000 || !self.prg_ram_enabled() {
+            return None;
+        }
+
+        let offset = (address & 0x1FFF) as usize;
+        match self.prg_ram_bank() {
+            (_, false) => Some(self.work_ram.get(offset)),
+            (bank, true) => {
+                let prg_ram = self.prg_ram.as_ref()?.borrow();
+                match prg_ram.len() {
+                    0 => None,
+                    len => Some(prg_ram.get((bank * 0x2000 + offset) % len)),
+                }
+            }
+        }
+    }
+
+    fn write_expansion(&mut self, address: u16, byte: u8) {
+        if address < 0x6000 || !self.prg_ram_enabled() {
+            return;
+        }
+
+        let offset = (address & 0x1FFF) as usize;
+        match self.prg_ram_bank() {
+            (_, false) => self.work_ram.put(offset, byte),
+            (bank, true) => {
+                if let Some(ref prg_ram) = self.prg_ram {
+                    let mut prg_ram = prg_ram.borrow_mut();
+                    let len = prg_ram.len();
+                    if len > 0 {
+                        prg_ram.put((bank * 0x2000 + offset) % len, byte);
+                    }
+                }
+            }
+        }
+    }
+
+    // Every board takes PRG RAM, as bit 4 of the PRG bank register can disable it.
+    fn attach_prg_ram(&mut self, prg_ram: Rc<RefCell<Memory>>) -> bool {
+        self.prg_ram = Some(prg_ram);
+        true
+    }
+
+    fn clock_cpu(&mut self, _cycles: u32) {
+        self.written = false;
+    }
+
     fn mirror_mode(&self) -> MirrorMode {
         match self.control & 0x3 {
             0 => MirrorMode::SingleLower,
@@ -177,7 +325,9 @@ impl<'de> SaveState<'de, MapperState> for MMC1 {
             chr_bank_2: self.chr_bank_2,
             prg_offsets: self.prg_offsets.to_vec(),
             chr_offsets: self.chr_offsets.to_vec(),
+            chr_a12: self.chr_a12,
             chr_mem: self.chr_mem.freeze(),
+            work_ram: self.work_ram.freeze(),
         })
     }
 
@@ -192,7 +342,9 @@ impl<'de> SaveState<'de, MapperState> for MMC1 {
                 self.chr_bank_2 = s.chr_bank_2;
                 self.prg_offsets.copy_from_slice(s.prg_offsets.as_slice());
                 self.chr_offsets.copy_from_slice(s.chr_offsets.as_slice());
+                self.chr_a12 = s.chr_a12;
                 self.chr_mem.hydrate(s.chr_mem);
+                self.work_ram.hydrate(s.work_ram);
             }
             _ => panic!("Incompatible mapper state for MMC1 mapper: {:?}", state),
         }
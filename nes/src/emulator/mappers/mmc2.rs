@@ -0,0 +1,163 @@
+use crate::emulator::memory::{Mapper, Memory};
+use crate::emulator::ppu::MirrorMode;
+use crate::emulator::state::{MMC2State, MapperState, SaveState};
+
+// iNES Mapper 9: MMC2
+// 1x 8kb switchable PRG ROM bank at $8000, with the last 3 8kb banks fixed at $A000-$FFFF.
+// 2x 4kb switchable CHR ROM windows, each with two banks which are selected by a latch.
+// The latches flip when the PPU fetches tile $FD or $FE, which is how Punch-Out!! switches
+// graphics mid-frame.
+pub struct MMC2 {
+    prg_rom: Memory,
+    prg_bank: u8,
+    chr: ChrLatches,
+    mirror_mode: MirrorMode,
+}
+
+impl MMC2 {
+    pub fn new(prg_rom: Memory, chr_mem: Memory) -> MMC2 {
+        MMC2 {
+            prg_rom,
+            prg_bank: 0,
+            chr: ChrLatches::new(chr_mem, false),
+            mirror_mode: MirrorMode::Vertical,
+        }
+    }
+}
+
+impl Mapper for MMC2 {
+    fn read_chr(&mut self, address: u16) -> u8 {
+        self.chr.read(address)
+    }
+
+    fn write_chr(&mut self, address: u16, byte: u8) {
+        self.chr.write(address, byte);
+    }
+
+    fn read_prg(&mut self, address: u16) -> u8 {
+        let num_banks = self.prg_rom.len() / 0x2000;
+        let bank = match address {
+            0x8000..=0x9FFF => self.prg_bank as usize,
+            0xA000..=0xBFFF => num_banks - 3,
+            0xC000..=0xDFFF => num_banks - 2,
+            0xE000..=0xFFFF => num_banks - 1,
+            _ => panic!("Unexpected PRG address: ${:X}", address),
+        };
+        let offset = (address & 0x1FFF) as usize;
+        self.prg_rom
+            .get(((bank << 13) | offset) % self.prg_rom.len())
+    }
+
+    fn write_prg(&mut self, address: u16, byte: u8) {
+        match address {
+            0xA000..=0xAFFF => self.prg_bank = byte & 0x0F,
+            0xF000..=0xFFFF => self.mirror_mode = ChrLatches::decode_mirror_mode(byte),
+            _ => self.chr.write_register(address, byte),
+        }
+    }
+
+    fn mirror_mode(&self) -> MirrorMode {
+        self.mirror_mode
+    }
+}
+
+impl<'de> SaveState<'de, MapperState> for MMC2 {
+    fn freeze(&mut self) -> MapperState {
+        MapperState::MMC2(MMC2State {
+            prg_bank: self.prg_bank,
+            chr_banks: self.chr.banks.to_vec(),
+            chr_latches: self.chr.latches.to_vec(),
+            mirror_mode: self.mirror_mode,
+            chr_mem: self.chr.chr_mem.freeze(),
+        })
+    }
+
+    fn hydrate(&mut self, state: MapperState) {
+        match state {
+            MapperState::MMC2(s) => {
+                self.prg_bank = s.prg_bank;
+                self.chr.banks.copy_from_slice(&s.chr_banks);
+                self.chr.latches.copy_from_slice(&s.chr_latches);
+                self.mirror_mode = s.mirror_mode;
+                self.chr.chr_mem.hydrate(s.chr_mem);
+            }
+            _ => panic!("Incompatible mapper state for MMC2 mapper: {:?}", state),
+        }
+    }
+}
+
+// CHR banking shared by MMC2 and MMC4.
+pub struct ChrLatches {
+    pub chr_mem: Memory,
+
+    // 4kb banks for $0000 when latch 0 is $FD, $0000 when it's $FE, and the same for $1000 and
+    // latch 1.
+    pub banks: [u8; 4],
+
+    // false for $FD, true for $FE.
+    pub latches: [bool; 2],
+
+    // MMC2 only flips latch 0 on fetches from exactly $0FD8 or $0FE8, whereas MMC4 watches the
+    // whole tile, the same as latch 1.
+    latch_0_whole_tile: bool,
+}
+
+impl ChrLatches {
+    pub fn new(chr_mem: Memory, latch_0_whole_tile: bool) -> ChrLatches {
+        ChrLatches {
+            chr_mem,
+            banks: [0; 4],
+            latches: [true, true],
+            latch_0_whole_tile,
+        }
+    }
+
+    pub fn read(&mut self, address: u16) -> u8 {
+        let window = ((address >> 12) & 1) as usize;
+        let bank = self.banks[window * 2 + self.latches[window] as usize] as usize;
+        let offset = (address & 0x0FFF) as usize;
+        let byte = self
+            .chr_mem
+            .get(((bank << 12) | offset) % self.chr_mem.len());
+
+        // The latch only switches after the fetch, so this one still comes from the old bank.
+        let whole_tile = window == 1 || self.latch_0_whole_tile;
+        let tile_address = if whole_tile {
+            address & 0x0FF8
+        } else {
+            address & 0x0FFF
+        };
+        match tile_address {
+            0x0FD8 => self.latches[window] = false,
+            0x0FE8 => self.latches[window] = true,
+            _ => (),
+        }
+
+        byte
+    }
+
+    pub fn write(&mut self, address: u16, byte: u8) {
+        self.chr_mem.put(address as usize, byte);
+    }
+
+    // $B000-$EFFF.
+    pub fn write_register(&mut self, address: u16, byte: u8) {
+        let ix = match address {
+            0xB000..=0xBFFF => 0,
+            0xC000..=0xCFFF => 1,
+            0xD000..=0xDFFF => 2,
+            0xE000..=0xEFFF => 3,
+            _ => return,
+        };
+        self.banks[ix] = byte & 0x1F;
+    }
+
+    // $F000-$FFFF.
+    pub fn decode_mirror_mode(byte: u8) -> MirrorMode {
+        if byte & 1 == 0 {
+            MirrorMode::Vertical
+        } else {
+            MirrorMode::Horizontal
+        }
+    }
+}
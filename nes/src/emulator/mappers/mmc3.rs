@@ -1,19 +1,29 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
 use crate::emulator::memory::{Mapper, Memory};
 use crate::emulator::ppu::MirrorMode;
 use crate::emulator::state::{MMC3State, MapperState, SaveState};
 
-// 1x 8kb PRG RAM - right now we have this sram outside the mappers, so ignored here.
+// 1x 8kb PRG RAM, which is left to the CPU at $6000-$7FFF.  The MMC6 has 1kb of its own instead,
+// with separate enable bits for each half.
 // 4x 8kb switchable PRG ROM
 // 2x 2kb switchable CHR ROM (we will treat this as 4x 1kb)
 // 4x 1kb switchable CHR ROM
 // Capable of generating IRQs.
+//
+// The variants are other boards and chips built around the same bank registers.  See Variant.
 pub struct MMC3 {
     prg_rom: Memory,
     chr_mem: Memory,
+    // TQROM has CHR RAM as well as CHR ROM.
+    chr_ram: Memory,
+    // The MMC6's 1kb of PRG RAM, which is internal to the chip.
+    prg_ram: Option<Rc<RefCell<Memory>>>,
+    variant: Variant,
 
-    // 8 registers for banks R0-R7, plus 2 slots which always point to the 2nd last and last PRG
-    // banks.
-    bank_registers: [usize; 10],
+    // Bank registers R0-R7, as written.
+    bank_registers: [u8; 8],
     bank_select: usize,
     prg_inversion: bool,
     chr_inversion: bool,
@@ -28,14 +38,80 @@ pub struct MMC3 {
     ppu_a12_low_counter: u8,
 
     mirror_mode: MirrorMode,
+
+    // MMC6 only.
+    prg_ram_enabled: bool,
+    prg_ram_protect: u8,
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Variant {
+    // TxROM with the MMC3B or MMC3C, whose IRQ fires whenever the counter is 0 after a clock.
+    MMC3,
+    // The MMC3A only fires when the counter reaches 0 by counting down, or by a requested
+    // reload, so a latch of 0 gives a single IRQ rather than one every scanline.
+    MMC3A,
+    // Adds 1kb of PRG RAM at $7000-$7FFF, with separate protection for each 512 byte half.  Has
+    // the MMC3A's IRQ.
+    MMC6,
+    // Mapper 118: bit 7 of the CHR banks for $0000-$0FFF chooses the CIRAM page for each
+    // nametable.
+    TxSROM,
+    // Mapper 119: bit 6 of the CHR banks chooses 8kb of CHR RAM instead of ROM.
+    TQROM,
+    // Mapper 206: Namco 108, the MMC3's predecessor.  Only the bank registers, with no
+    // inversion, IRQ or mirroring control.
+    Namco108,
+    // Mapper 76: R2-R5 are 2kb CHR banks, to reach 128kb of CHR ROM.
+    NAMCOT3446,
+    // Mapper 88: R0-R1 use the first 64kb of CHR ROM, R2-R5 the second.
+    NAMCOT3443,
+    // Mapper 95: bit 5 of R0 and R1 choose the CIRAM page for each half of the nametables.
+    NAMCOT3425,
+}
+
+impl Variant {
+    pub fn from_ines(mapper_number: u16, submapper: u8) -> Variant {
+        match (mapper_number, submapper) {
+            (76, _) => Variant::NAMCOT3446,
+            (88, _) => Variant::NAMCOT3443,
+            (95, _) => Variant::NAMCOT3425,
+            (118, _) => Variant::TxSROM,
+            (119, _) => Variant::TQROM,
+            (206, _) => Variant::Namco108,
+            (_, 1) => Variant::MMC6,
+            (_, 4) => Variant::MMC3A,
+            (_, _) => Variant::MMC3,
+        }
+    }
+
+    fn is_namco108(self) -> bool {
+        matches!(
+            self,
+            Variant::Namco108 | Variant::NAMCOT3446 | Variant::NAMCOT3443 | Variant::NAMCOT3425
+        )
+    }
+
+    fn has_old_irq(self) -> bool {
+        matches!(self, Variant::MMC3A | Variant::MMC6)
+    }
 }
 
 impl MMC3 {
-    pub fn new(prg_rom: Memory, chr_mem: Memory) -> MMC3 {
-        let mut m = MMC3 {
+    pub fn new(
+        prg_rom: Memory,
+        chr_mem: Memory,
+        variant: Variant,
+        mirror_mode: MirrorMode,
+    ) -> MMC3 {
+        MMC3 {
             prg_rom,
             chr_mem,
-            bank_registers: [0; 10],
+            chr_ram: Memory::new_ram(if variant == Variant::TQROM { 0x2000 } else { 0 }),
+            prg_ram: None,
+            variant,
+            bank_registers: [0; 8],
             bank_select: 0,
             prg_inversion: false,
             chr_inversion: false,
@@ -46,15 +122,20 @@ impl MMC3 {
             irq_enabled: false,
             ppu_a12: false,
             ppu_a12_low_counter: 0,
-            mirror_mode: MirrorMode::Horizontal,
-        };
-        let num_banks = m.prg_rom.len() / 0x2000;
-        m.bank_registers[8] = ((num_banks - 2) * 0x2000) as usize;
-        m.bank_registers[9] = ((num_banks - 1) * 0x2000) as usize;
-        m
+            // The Namco 108 boards have their mirroring wired up, like the header says.
+            mirror_mode: if variant.is_namco108() {
+                mirror_mode
+            } else {
+                MirrorMode::Horizontal
+            },
+            prg_ram_enabled: false,
+            prg_ram_protect: 0,
+        }
     }
 
     fn clock_irq(&mut self) {
+        let count = self.irq_counter;
+        let reloaded = self.irq_reload_flag;
         if self.irq_counter == 0 || self.irq_reload_flag {
             self.irq_counter = self.irq_counter_reload;
             self.irq_reload_flag = false;
@@ -62,78 +143,74 @@ impl MMC3 {
             self.irq_counter = self.irq_counter.saturating_sub(1);
         }
 
-        if self.irq_counter == 0 {
+        if self.irq_counter == 0 && (!self.variant.has_old_irq() || count > 0 || reloaded) {
             self.irq_flag = self.irq_enabled;
         }
     }
-}
 
-impl Mapper for MMC3 {
-    fn read_chr(&mut self, address: u16) -> u8 {
-        let (bank_ix, bank_size) = match address {
-            // CHR banks.
-            0x0000..=0x03FF => {
-                if self.chr_inversion {
-                    (2, 0x400)
-                } else {
-                    (0, 0x800)
-                }
-            }
-            0x0400..=0x07FF => {
-                if self.chr_inversion {
-                    (3, 0x400)
-                } else {
-                    (0, 0x800)
-                }
-            }
-            0x0800..=0x0BFF => {
-                if self.chr_inversion {
-                    (4, 0x400)
-                } else {
-                    (1, 0x800)
-                }
-            }
-            0x0C00..=0x0FFF => {
-                if self.chr_inversion {
-                    (5, 0x400)
-                } else {
-                    (1, 0x800)
-                }
-            }
-            0x1000..=0x13FF => {
-                if self.chr_inversion {
-                    (0, 0x800)
-                } else {
-                    (2, 0x400)
-                }
-            }
-            0x1400..=0x17FF => {
-                if self.chr_inversion {
-                    (0, 0x800)
-                } else {
-                    (3, 0x400)
-                }
-            }
-            0x1800..=0x1BFF => {
-                if self.chr_inversion {
-                    (1, 0x800)
-                } else {
-                    (4, 0x400)
-                }
-            }
-            0x1C00..=0x1FFF => {
-                if self.chr_inversion {
-                    (1, 0x800)
-                } else {
-                    (5, 0x400)
-                }
-            }
-            _ => panic!("Unexpected address: ${:X}", address),
+    // The bank register and bank size for a CHR address.
+    fn chr_bank(&self, address: u16) -> (usize, usize) {
+        if self.variant == Variant::NAMCOT3446 {
+            return (2 + (address >> 11) as usize, 0x800);
+        }
+
+        // CHR inversion swaps the 2kb banks over to $1000-$1FFF, and the 1kb banks to $0000.
+        let slot = ((address >> 10) & 0x7) as usize ^ if self.chr_inversion { 4 } else { 0 };
+        match slot {
+            0 | 1 => (0, 0x800),
+            2 | 3 => (1, 0x800),
+            n => (n - 2, 0x400),
+        }
+    }
+
+    // Returns the address in CHR ROM, or in CHR RAM if the flag is set.
+    fn chr_address(&self, address: u16) -> (usize, bool) {
+        let (bank_ix, bank_size) = self.chr_bank(address);
+        let register = self.bank_registers[bank_ix] as usize;
+        let (bank, ram) = match self.variant {
+            Variant::TxSROM => (register & 0x7F, false),
+            Variant::TQROM => (register & 0x3F, register & 0x40 != 0),
+            Variant::NAMCOT3446 => ((register & 0x3F) << 1, false),
+            Variant::NAMCOT3443 if bank_ix < 2 => (register & 0x3F, false),
+            Variant::NAMCOT3443 => (register | 0x40, false),
+            Variant::Namco108 | Variant::NAMCOT3425 => (register & 0x3F, false),
+            _ => (register, false),
+        };
+
+        // 2kb CHR banks can only select even banks.
+        let bank = if bank_size == 0x800 {
+            bank & !0x1
+        } else {
+            bank
+        };
+        let offset = (bank << 10) + (address as usize % bank_size);
+        if ram {
+            (offset % self.chr_ram.len(), true)
+        } else {
+            (offset % self.chr_mem.len(), false)
+        }
+    }
+
+    fn prg_bank(&self, register: usize) -> usize {
+        let mask = if self.variant.is_namco108() {
+            0x0F
+        } else {
+            0x3F
         };
+        (self.bank_registers[register] & mask) as usize
+    }
 
-        let base = self.bank_registers[bank_ix];
-        let offset = (address % bank_size) as usize;
+    // The MMC6's RAM is mirrored through $7000-$7FFF.  Each half has its own read and write
+    // enable bits in $A001.
+    fn prg_ram_enables(&self, address: u16) -> (bool, bool) {
+        let shift = if address & 0x0200 == 0 { 4 } else { 6 };
+        let bits = self.prg_ram_protect >> shift;
+        (bits & 0x2 != 0, bits & 0x1 != 0)
+    }
+}
 
+impl Mapper for MMC3 {
+    fn read_chr(&mut self, address: u16) -> u8 {
         // Update A12 and clock IRQ.
         let a12 = address & 0x1000 == 0x1000;
         if a12 && !self.ppu_a12 && self.ppu_a12_low_counter > 12 {
@@ -145,54 +222,46 @@ impl Mapper for MMC3 {
         }
         self.ppu_a12 = a12;
 
-        self.chr_mem.get(base + offset)
+        match self.chr_address(address) {
+            (chr_address, true) => self.chr_ram.get(chr_address),
+            (chr_address, false) => self.chr_mem.get(chr_address),
+        }
     }
 
     fn write_chr(&mut self, address: u16, byte: u8) {
-        self.chr_mem.put(address as usize, byte);
+        match self.chr_address(address) {
+            (chr_address, true) => self.chr_ram.put(chr_address, byte),
+            (chr_address, false) => self.chr_mem.put(chr_address, byte),
+        }
     }
 
     fn read_prg(&mut self, address: u16) -> u8 {
-        let (bank_ix, bank_size) = match address {
-            // PRG banks.
-            0x8000..=0x9FFF => {
-                if self.prg_inversion {
-                    (8, 0x2000)
-                } else {
-                    (6, 0x2000)
-                }
-            }
-            0xA000..=0xBFFF => {
-                if self.prg_inversion {
-                    (7, 0x2000)
-                } else {
-                    (7, 0x2000)
-                }
-            }
-            0xC000..=0xDFFF => {
-                if self.prg_inversion {
-                    (6, 0x2000)
-                } else {
-                    (8, 0x2000)
-                }
-            }
-            0xE000..=0xFFFF => {
-                if self.prg_inversion {
-                    (9, 0x2000)
-                } else {
-                    (9, 0x2000)
-                }
-            }
+        let num_banks = self.prg_rom.len() / 0x2000;
+        let bank = match address {
+            0x8000..=0x9FFF if self.prg_inversion => num_banks - 2,
+            0x8000..=0x9FFF => self.prg_bank(6),
+            0xA000..=0xBFFF => self.prg_bank(7),
+            0xC000..=0xDFFF if self.prg_inversion => self.prg_bank(6),
+            0xC000..=0xDFFF => num_banks - 2,
+            0xE000..=0xFFFF => num_banks - 1,
             _ => panic!("Unexpected address: ${:X}", address),
         };
 
-        let base = self.bank_registers[bank_ix];
-        let offset = (address % bank_size) as usize;
-
-        self.prg_rom.get(base + offset)
+        let offset = (bank << 13) | (address & 0x1FFF) as usize;
+        self.prg_rom.get(offset % self.prg_rom.len())
     }
 
     fn write_prg(&mut self, address: u16, byte: u8) {
+        // The Namco 108 only has the bank registers, with no inversion.
+        if self.variant.is_namco108() {
+            if address < 0xA000 && address & 0x1 == 0 {
+                self.bank_select = (byte & 0x07) as usize;
+            } else if address < 0xA000 {
+                self.bank_registers[self.bank_select] = byte;
+            }
+            return;
+        }
+
         // The MMC3 has 4 pairs of registers at $8000-$9FFF, $A000-$BFFF, $C000-$DFFF, and $E000-$FFFF
         //   - even addresses ($8000, $8002, etc.) select the low register
         //   - odd addresses ($8001, $8003, etc.) select the high register in each pair.
@@ -206,21 +275,15 @@ impl Mapper for MMC3 {
                     self.bank_select = (byte & 0x07) as usize;
                     self.prg_inversion = byte & 0x40 == 0x40;
                     self.chr_inversion = byte & 0x80 == 0x80;
+                    if self.variant == Variant::MMC6 {
+                        self.prg_ram_enabled = byte & 0x20 == 0x20;
+                        if !self.prg_ram_enabled {
+                            self.prg_ram_protect = 0;
+                        }
+                    }
                 } else {
                     // 0x8000, odd => Bank data
-                    // Handle PRG and CHR separately.
-                    if self.bank_select >= 6 {
-                        // PRG, 8kb banks, ignores top 2 bits.
-                        self.bank_registers[self.bank_select] =
-                            (((byte & 0x3F) as usize) << 13) % self.prg_rom.len();
-                    } else if self.bank_select <= 1 {
-                        // 2kb CHR banks can only select even banks.
-                        self.bank_registers[self.bank_select] =
-                            (((byte & 0xFE) as usize) << 10) % self.chr_mem.len();
-                    } else {
-                        self.bank_registers[self.bank_select] =
-                            ((byte as usize) << 10) % self.chr_mem.len();
-                    }
+                    self.bank_registers[self.bank_select] = byte;
                 }
             }
             0xA000 => {
@@ -230,9 +293,11 @@ impl Mapper for MMC3 {
                         true => MirrorMode::Vertical,
                         false => MirrorMode::Horizontal,
                     };
-                } else {
+                } else if self.variant == Variant::MMC6 && self.prg_ram_enabled {
                     // 0xA000, odd => PRG RAM protect
-                    // Unimplemented for compatibility with MMC6.
+                    // Only the MMC6's internal RAM is protected.  The MMC3's SRAM is left
+                    // writeable, as some games forget to enable it.
+                    self.prg_ram_protect = byte & 0xF0;
                 }
             }
             0xC000 => {
@@ -259,8 +324,71 @@ impl Mapper for MMC3 {
         }
     }
 
+    fn read_expansion(&mut self, address: u16) -> Option<u8> {
+        if address < 0x7000 || !self.prg_ram_enabled {
+            return None;
+        }
+        let prg_ram = self.prg_ram.as_ref()?.borrow();
+        if prg_ram.len() == 0 {
+            return None;
+        }
+
+        // With neither half readable, nothing drives the bus.  Otherwise the unreadable half
+        // reads as 0.
+        let (read_enabled, _) = self.prg_ram_enables(address);
+        if read_enabled {
+            Some(prg_ram.get((address & 0x03FF) as usize % prg_ram.len()))
+        } else if self.prg_ram_protect & 0xA0 != 0 {
+            Some(0)
+        } else {
+            None
+        }
+    }
+
+    fn write_expansion(&mut self, address: u16, byte: u8) {
+        if address < 0x7000 || !self.prg_ram_enabled {
+            return;
+        }
+
+        let (read_enabled, write_enabled) = self.prg_ram_enables(address);
+        if read_enabled
+            && write_enabled
+            && let Some(ref prg_ram) = self.prg_ram
+        {
+            let mut prg_ram = prg_ram.borrow_mut();
+            let len = prg_ram.len();
+            if len > 0 {
+                prg_ram.put((address & 0x03FF) as usize % len, byte);
+            }
+        }
+    }
+
+    // The MMC6 keeps its RAM in the first 1kb of SRAM, so it's saved in the same way.
+    fn attach_prg_ram(&mut self, prg_ram: Rc<RefCell<Memory>>) -> bool {
+        if self.variant != Variant::MMC6 {
+            return false;
+        }
+        self.prg_ram = Some(prg_ram);
+        true
+    }
+
     fn mirror_mode(&self) -> MirrorMode {
-        self.mirror_mode
+        match self.variant {
+            Variant::TxSROM => {
+                let mut pages = [0; 4];
+                for (nametable, page) in pages.iter_mut().enumerate() {
+                    let (bank_ix, _) = self.chr_bank((nametable as u16) << 10);
+                    *page = self.bank_registers[bank_ix] >> 7;
+                }
+                MirrorMode::Custom(pages)
+            }
+            Variant::NAMCOT3425 => {
+                let lower = (self.bank_registers[0] >> 5) & 0x1;
+                let upper = (self.bank_registers[1] >> 5) & 0x1;
+                MirrorMode::Custom([lower, lower, upper, upper])
+            }
+            _ => self.mirror_mode,
+        }
     }
 
     fn irq_triggered(&self) -> bool {
@@ -283,7 +411,10 @@ impl<'de> SaveState<'de, MapperState> for MMC3 {
             ppu_a12: self.ppu_a12,
             ppu_a12_low_counter: self.ppu_a12_low_counter,
             mirror_mode: self.mirror_mode,
+            prg_ram_enabled: self.prg_ram_enabled,
+            prg_ram_protect: self.prg_ram_protect,
             chr_mem: self.chr_mem.freeze(),
+            chr_ram: self.chr_ram.freeze(),
         })
     }
 
@@ -298,11 +429,15 @@ impl<'de> SaveState<'de, MapperState> for MMC3 {
                 self.irq_flag = s.irq_flag;
                 self.irq_counter = s.irq_counter;
                 self.irq_reload_flag = s.irq_reload_flag;
+                self.irq_counter_reload = s.irq_counter_reload;
                 self.irq_enabled = s.irq_enabled;
                 self.ppu_a12 = s.ppu_a12;
                 self.ppu_a12_low_counter = s.ppu_a12_low_counter;
                 self.mirror_mode = s.mirror_mode;
+                self.prg_ram_enabled = s.prg_ram_enabled;
+                self.prg_ram_protect = s.prg_ram_protect;
                 self.chr_mem.hydrate(s.chr_mem);
+                self.chr_ram.hydrate(s.chr_ram);
             }
             _ => panic!("Incompatible mapper state for MMC3 mapper: {:?}", state),
         }
@@ -0,0 +1,83 @@
+use crate::emulator::mappers::mmc2::ChrLatches;
+use crate::emulator::memory::{Mapper, Memory};
+use crate::emulator::ppu::MirrorMode;
+use crate::emulator::state::{MMC4State, MapperState, SaveState};
+
+// iNES Mapper 10: MMC4
+// 1x 16kb switchable PRG ROM bank at $8000, with the last bank fixed at $C000.
+// 8kb PRG RAM, which is left to the CPU at $6000-$7FFF.
+// CHR latches as MMC2, except both latches are triggered by any fetch from tiles $FD and $FE.
+pub struct MMC4 {
+    prg_rom: Memory,
+    prg_bank: u8,
+    chr: ChrLatches,
+    mirror_mode: MirrorMode,
+}
+
+impl MMC4 {
+    pub fn new(prg_rom: Memory, chr_mem: Memory) -> MMC4 {
+        MMC4 {
+            prg_rom,
+            prg_bank: 0,
+            chr: ChrLatches::new(chr_mem, true),
+            mirror_mode: MirrorMode::Vertical,
+        }
+    }
+}
+
+impl Mapper for MMC4 {
+    fn read_chr(&mut self, address: u16) -> u8 {
+        self.chr.read(address)
+    }
+
+    fn write_chr(&mut self, address: u16, byte: u8) {
+        self.chr.write(address, byte);
+    }
+
+    fn read_prg(&mut self, address: u16) -> u8 {
+        let bank = match address {
+            0x8000..=0xBFFF => self.prg_bank as usize,
+            _ => self.prg_rom.len() / 0x4000 - 1,
+        };
+        let offset = (address & 0x3FFF) as usize;
+        self.prg_rom
+            .get(((bank << 14) | offset) % self.prg_rom.len())
+    }
+
+    fn write_prg(&mut self, address: u16, byte: u8) {
+        match address {
+            0xA000..=0xAFFF => self.prg_bank = byte & 0x0F,
+            0xF000..=0xFFFF => self.mirror_mode = ChrLatches::decode_mirror_mode(byte),
+            _ => self.chr.write_register(address, byte),
+        }
+    }
+
+    fn mirror_mode(&self) -> MirrorMode {
+        self.mirror_mode
+    }
+}
+
+impl<'de> SaveState<'de, MapperState> for MMC4 {
+    fn freeze(&mut self) -> MapperState {
+        MapperState::MMC4(MMC4State {
+            prg_bank: self.prg_bank,
+            chr_banks: self.chr.banks.to_vec(),
+            chr_latches: self.chr.latches.to_vec(),
+            mirror_mode: self.mirror_mode,
+            chr_mem: self.chr.chr_mem.freeze(),
+        })
+    }
+
+    fn hydrate(&mut self, state: MapperState) {
+        match state {
+            MapperState::MMC4(s) => {
+                self.prg_bank = s.prg_bank;
+                self.chr.banks.copy_from_slice(&s.chr_banks);
+                self.chr.latches.copy_from_slice(&s.chr_latches);
+                self.mirror_mode = s.mirror_mode;
+                self.chr.chr_mem.hydrate(s.chr_mem);
+            }
+            _ => panic!("Incompatible mapper state for MMC4 mapper: {:?}", state),
+        }
+    }
+}
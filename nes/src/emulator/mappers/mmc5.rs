@@ -0,0 +1,597 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::emulator::apu::{
+    Pulse, write_first_pulse_register, write_second_pulse_register, write_third_pulse_register,
+};
+use crate::emulator::memory::{Mapper, Memory};
+use crate::emulator::ppu::{MirrorMode, RenderEvent};
+use crate::emulator::state::{MMC5State, MapperState, SaveState};
+
+// iNES Mapper 5: MMC5
+// PRG ROM in 1x 32kb, 2x 16kb, 1x 16kb + 2x 8kb or 4x 8kb banks, any but the last of which can be
+// PRG RAM instead.  Up to 64kb PRG RAM, banked in 8kb units at $6000 too.
+// CHR in 8kb, 4kb, 2kb or 1kb banks, with separate sets for sprites and background in 8x16
+// sprite mode.
+// 1kb ExRAM, usable as a nametable, extended attributes, or plain RAM.
+// Per-nametable choice of CIRAM, ExRAM or a fill tile, a vertical split screen, a scanline IRQ,
+// an 8x8 multiplier, and 2 pulse channels plus 8-bit PCM.
+//
+// The real chip works out what the PPU is doing by watching its bus.  Here the PPU tells us with
+// RenderEvents instead.
+//
+// Not implemented: PCM read mode and its IRQ.
+pub struct MMC5 {
+    prg_rom: Memory,
+    chr_mem: Memory,
+    prg_ram: Option<Rc<RefCell<Memory>>>,
+    exram: Memory,
+
+    // $5100 and $5113-$5117.  Bit 7 of $5114-$5116 selects ROM rather than RAM.
+    prg_mode: u8,
+    prg_banks: [u8; 5],
+    // $5102 and $5103.  PRG RAM is only writeable while these are 2 and 1.
+    prg_ram_protect: [u8; 2],
+
+    // $5101 and $5120-$512B, with the $5130 upper bits applied when each was written.
+    // $5120-$5127 are set A, used for sprites.  $5128-$512B are set B, used for the background.
+    chr_mode: u8,
+    chr_banks: [u16; 12],
+    chr_upper: u8,
+    // Outside of 8x16 sprite rendering, whichever set was written last is used for everything.
+    last_chr_set_b: bool,
+
+    // $5104-$5107.
+    exram_mode: u8,
+    nametable_mapping: u8,
+    fill_tile: u8,
+    fill_attribute: u8,
+
+    // $5200-$5202.
+    split_control: u8,
+    split_scroll: u8,
+    split_bank: u8,
+
+    // $5203 and $5204.
+    irq_target: u8,
+    irq_enabled: bool,
+    irq_pending: bool,
+    in_frame: bool,
+    scanline_counter: u8,
+
+    // $5205 and $5206.
+    multiplicand: u8,
+    multiplier: u8,
+
+    // What the PPU is fetching.
+    fetching_background: bool,
+    fetching_sprites: bool,
+    tall_sprites: bool,
+    // The background tile being fetched, counting the 2 prefetched tiles as columns 0 and 1.
+    tile_column: u8,
+    expect_attribute: bool,
+    split_y: u8,
+    in_split: bool,
+    // The ExRAM byte for the tile, in extended attribute mode.
+    ext_attribute: u8,
+
+    // $5000-$5015.
+    pulse_1: Pulse,
+    pulse_2: Pulse,
+    pcm: u8,
+    pcm_control: u8,
+    audio_frame_counter: u16,
+}
+
+enum NametableSource {
+    Ciram,
+    ExRAM,
+    Fill,
+}
+
+const EXRAM_SIZE: usize = 0x400;
+
+// The pulse channels' envelopes and length counters are clocked at a fixed 240Hz, rather than by
+// a frame sequencer.  This is in APU cycles.
+const AUDIO_FRAME_PERIOD: u16 = 3728;
+
+impl MMC5 {
+    pub fn new(prg_rom: Memory, chr_mem: Memory) -> MMC5 {
+        MMC5 {
+            prg_rom,
+            chr_mem,
+            prg_ram: None,
+            exram: Memory::new_ram(EXRAM_SIZE),
+            prg_mode: 3,
+            prg_banks: [0, 0, 0, 0, 0xFF],
+            prg_ram_protect: [0; 2],
+            chr_mode: 0,
+            chr_banks: [0; 12],
+            chr_upper: 0,
+            last_chr_set_b: false,
+            exram_mode: 0,
+            nametable_mapping: 0,
+            fill_tile: 0,
+            fill_attribute: 0,
+            split_control: 0,
+            split_scroll: 0,
+            split_bank: 0,
+            irq_target: 0,
+            irq_enabled: false,
+            irq_pending: false,
+            in_frame: false,
+            scanline_counter: 0,
+            multiplicand: 0xFF,
+            multiplier: 0xFF,
+            fetching_background: false,
+            fetching_sprites: false,
+            tall_sprites: false,
+            tile_column: 0,
+            expect_attribute: false,
+            split_y: 0,
+            in_split: false,
+            ext_attribute: 0,
+            pulse_1: Pulse::without_sweep(),
+            pulse_2: Pulse::without_sweep(),
+            pcm: 0,
+            pcm_control: 0,
+            audio_frame_counter: 0,
+        }
+    }
+
+    // Returns the 8kb bank number, aligned to the bank size, and the bank size for an address in
+    // $8000-$FFFF.  Bit 7 is set for ROM.  $5117 always selects ROM.
+    fn prg_bank(&self, address: u16) -> (u8, usize) {
+        match (self.prg_mode, address) {
+            (0, _) => ((self.prg_banks[4] & 0xFC) | 0x80, 0x8000),
+            (1, 0x8000..=0xBFFF) | (2, 0x8000..=0xBFFF) => (self.prg_banks[2] & 0xFE, 0x4000),
+            (1, _) => ((self.prg_banks[4] & 0xFE) | 0x80, 0x4000),
+            (2, 0xC000..=0xDFFF) => (self.prg_banks[3], 0x2000),
+            (2, _) => (self.prg_banks[4] | 0x80, 0x2000),
+            (_, 0xE000..=0xFFFF) => (self.prg_banks[4] | 0x80, 0x2000),
+            (_, _) => (
+                self.prg_banks[1 + ((address - 0x8000) >> 13) as usize],
+                0x2000,
+            ),
+        }
+    }
+
+    fn prg_ram_writeable(&self) -> bool {
+        self.prg_ram_protect == [0x02, 0x01]
+    }
+
+    fn read_prg_ram(&self, bank: u8, offset: usize) -> Option<u8> {
+        let prg_ram = self.prg_ram.as_ref()?.borrow();
+        if prg_ram.len() == 0 {
+            return None;
+        }
+        Some(prg_ram.get(((bank & 0x07) as usize * 0x2000 + offset) % prg_ram.len()))
+    }
+
+    fn write_prg_ram(&mut self, bank: u8, offset: usize, byte: u8) {
+        if !self.prg_ram_writeable() {
+            return;
+        }
+        if let Some(ref prg_ram) = self.prg_ram {
+            let mut prg_ram = prg_ram.borrow_mut();
+            let len = prg_ram.len();
+            if len > 0 {
+                prg_ram.put(((bank & 0x07) as usize * 0x2000 + offset) % len, byte);
+            }
+        }
+    }
+
+    fn use_chr_set_b(&self) -> bool {
+        if self.tall_sprites && (self.fetching_background || self.fetching_sprites) {
+            self.fetching_background
+        } else {
+            self.last_chr_set_b
+        }
+    }
+
+    fn chr_address(&self, address: u16) -> usize {
+        let (bank, bank_size) = if self.use_chr_set_b() {
+            // Set B only covers 4kb, which is mirrored into both pattern tables.
+            match self.chr_mode {
+                0 => (self.chr_banks[11], 0x2000),
+                1 => (self.chr_banks[11], 0x1000),
+                2 => (
+                    self.chr_banks[9 + ((address >> 11) & 1) as usize * 2],
+                    0x800,
+                ),
+                _ => (self.chr_banks[8 + ((address >> 10) & 3) as usize], 0x400),
+            }
+        } else {
+            match self.chr_mode {
+                0 => (self.chr_banks[7], 0x2000),
+                1 => (
+                    self.chr_banks[3 + ((address >> 12) & 1) as usize * 4],
+                    0x1000,
+                ),
+                2 => (
+                    self.chr_banks[1 + ((address >> 11) & 3) as usize * 2],
+                    0x800,
+                ),
+                _ => (self.chr_banks[((address >> 10) & 7) as usize], 0x400),
+            }
+        };
+        bank as usize * bank_size + (address as usize % bank_size)
+    }
+
+    fn nametable_source(&self, address: u16) -> NametableSource {
+        let slot = (address >> 10) & 3;
+        match (self.nametable_mapping >> (slot * 2)) & 3 {
+            0 | 1 => NametableSource::Ciram,
+            2 => NametableSource::ExRAM,
+            _ => NametableSource::Fill,
+        }
+    }
+
+    fn split_enabled(&self) -> bool {
+        self.split_control & 0x80 != 0 && self.exram_mode <= 1
+    }
+
+    fn column_in_split(&self, column: u8) -> bool {
+        let split_tile = self.split_control & 0x1F;
+        if self.split_control & 0x40 != 0 {
+            column >= split_tile
+        } else {
+            column < split_tile
+        }
+    }
+
+    fn clock_audio_frame(&mut self) {
+        for pulse in [&mut self.pulse_1, &mut self.pulse_2] {
+            pulse.envelope.clock();
+            pulse.clock_length();
+        }
+    }
+}
+
+impl Mapper for MMC5 {
+    fn read_chr(&mut self, address: u16) -> u8 {
+        let chr_address = if self.fetching_background && self.in_split {
+            // The split uses its own 4kb bank, and its own fine Y scroll.
+            self.split_bank as usize * 0x1000
+                + ((address & 0x0FF8) | (self.split_y & 0x07) as u16) as usize
+        } else if self.fetching_background && self.exram_mode == 1 {
+            let bank = (self.ext_attribute & 0x3F) as usize | ((self.chr_upper as usize) << 6);
+            bank * 0x1000 + (address & 0x0FFF) as usize
+        } else {
+            self.chr_address(address)
+        };
+        self.chr_mem.get(chr_address % self.chr_mem.len())
+    }
+
+    fn write_chr(&mut self, address: u16, byte: u8) {
+        let chr_address = self.chr_address(address) % self.chr_mem.len();
+        self.chr_mem.put(chr_address, byte);
+    }
+
+    fn read_prg(&mut self, address: u16) -> u8 {
+        let (bank, bank_size) = self.prg_bank(address);
+        let offset = address as usize % bank_size;
+        if bank & 0x80 == 0 {
+            return self.read_prg_ram(bank, offset).unwrap_or(0);
+        }
+
+        let base = (bank & 0x7F) as usize * 0x2000;
+        self.prg_rom.get((base + offset) % self.prg_rom.len())
+    }
+
+    fn write_prg(&mut self, address: u16, byte: u8) {
+        let (bank, bank_size) = self.prg_bank(address);
+        if bank & 0x80 == 0 {
+            let offset = address as usize % bank_size;
+            self.write_prg_ram(bank, offset, byte);
+        }
+    }
+
+    fn mirror_mode(&self) -> MirrorMode {
+        let nt = self.nametable_mapping;
+        MirrorMode::Custom([nt & 1, (nt >> 2) & 1, (nt >> 4) & 1, (nt >> 6) & 1])
+    }
+
+    fn irq_triggered(&self) -> bool {
+        self.irq_pending && self.irq_enabled
+    }
+
+    fn read_expansion(&mut self, address: u16) -> Option<u8> {
+        match address {
+            0x5010 => Some(0),
+            0x5015 => {
+                let mut status = 0;
+                if self.pulse_1.length != 0 {
+                    status |= 1;
+                }
+                if self.pulse_2.length != 0 {
+                    status |= 1 << 1;
+                }
+                Some(status)
+            }
+            0x5204 => {
+                let mut status = 0;
+                if self.irq_pending {
+                    status |= 0x80;
+                }
+                if self.in_frame {
+                    status |= 0x40;
+                }
+                self.irq_pending = false;
+                Some(status)
+            }
+            0x5205 => Some((self.multiplicand as u16 * self.multiplier as u16) as u8),
+            0x5206 => Some(((self.multiplicand as u16 * self.multiplier as u16) >> 8) as u8),
+            0x5C00..=0x5FFF if self.exram_mode >= 2 => {
+                Some(self.exram.get((address - 0x5C00) as usize))
+            }
+            0x6000..=0x7FFF => self.read_prg_ram(self.prg_banks[0], (address - 0x6000) as usize),
+            _ => None,
+        }
+    }
+
+    fn write_expansion(&mut self, address: u16, byte: u8) {
+        match address {
+            0x5000 => write_first_pulse_register(&mut self.pulse_1, byte),
+            0x5002 => write_second_pulse_register(&mut self.pulse_1, byte),
+            0x5003 => write_third_pulse_register(&mut self.pulse_1, byte),
+            0x5004 => write_first_pulse_register(&mut self.pulse_2, byte),
+            0x5006 => write_second_pulse_register(&mut self.pulse_2, byte),
+            0x5007 => write_third_pulse_register(&mut self.pulse_2, byte),
+            0x5010 => self.pcm_control = byte,
+            // Writing 0 has no effect in write mode.
+            0x5011 if self.pcm_control & 0x01 == 0 && byte != 0 => self.pcm = byte,
+            0x5015 => {
+                for (ix, pulse) in [&mut self.pulse_1, &mut self.pulse_2]
+                    .into_iter()
+                    .enumerate()
+                {
+                    pulse.enabled = byte & (1 << ix) != 0;
+                    if !pulse.enabled {
+                        pulse.length = 0;
+                    }
+                }
+            }
+            0x5100 => self.prg_mode = byte & 0x03,
+            0x5101 => self.chr_mode = byte & 0x03,
+            0x5102 => self.prg_ram_protect[0] = byte & 0x03,
+            0x5103 => self.prg_ram_protect[1] = byte & 0x03,
+            0x5104 => self.exram_mode = byte & 0x03,
+            0x5105 => self.nametable_mapping = byte,
+            0x5106 => self.fill_tile = byte,
+            0x5107 => self.fill_attribute = byte & 0x03,
+            0x5113..=0x5117 => self.prg_banks[(address - 0x5113) as usize] = byte,
+            0x5120..=0x512B => {
+                let ix = (address - 0x5120) as usize;
+                self.chr_banks[ix] = ((self.chr_upper as u16) << 8) | byte as u16;
+                self.last_chr_set_b = ix >= 8;
+            }
+            0x5130 => self.chr_upper = byte & 0x03,
+            0x5200 => self.split_control = byte,
+            0x5201 => self.split_scroll = byte,
+            0x5202 => self.split_bank = byte,
+            0x5203 => self.irq_target = byte,
+            0x5204 => self.irq_enabled = byte & 0x80 != 0,
+            0x5205 => self.multiplicand = byte,
+            0x5206 => self.multiplier = byte,
+            0x5C00..=0x5FFF => {
+                // In the nametable modes, the CPU can only write while the PPU is rendering.
+                let byte = match self.exram_mode {
+                    0 | 1 if !self.in_frame => 0,
+                    3 => return,
+                    _ => byte,
+                };
+                self.exram.put((address - 0x5C00) as usize, byte);
+            }
+            0x6000..=0x7FFF => {
+                self.write_prg_ram(self.prg_banks[0], (address - 0x6000) as usize, byte)
+            }
+            _ => (),
+        }
+    }
+
+    fn attach_prg_ram(&mut self, prg_ram: Rc<RefCell<Memory>>) -> bool {
+        self.prg_ram = Some(prg_ram);
+        true
+    }
+
+    fn read_nametable(&mut self, address: u16) -> Option<u8> {
+        let offset = (address & 0x03FF) as usize;
+
+        // Background fetches alternate between nametable and attribute bytes.
+        if self.fetching_background {
+            let column = self.tile_column;
+            if !self.expect_attribute {
+                self.expect_attribute = true;
+                self.in_split = self.split_enabled() && self.column_in_split(column);
+                if self.in_split {
+                    let split_offset =
+                        ((self.split_y as usize & 0xF8) << 2) | (column & 0x1F) as usize;
+                    return Some(self.exram.get(split_offset));
+                }
+                if self.exram_mode == 1 {
+                    self.ext_attribute = self.exram.get(offset);
+                }
+            } else {
+                self.expect_attribute = false;
+                self.tile_column = column.wrapping_add(1);
+                // The PPU picks out the quadrant itself, so give it the same palette in all 4.
+                if self.in_split {
+                    let y = self.split_y as usize;
+                    let byte = self
+                        .exram
+                        .get(0x3C0 | ((y >> 5) << 3) | ((column & 0x1F) >> 2) as usize);
+                    let shift = ((y >> 2) & 0x04) | (column & 0x02) as usize;
+                    return Some(((byte >> shift) & 0x03) * 0x55);
+                }
+                if self.exram_mode == 1 {
+                    return Some((self.ext_attribute >> 6) * 0x55);
+                }
+            }
+        }
+
+        match self.nametable_source(address) {
+            NametableSource::Ciram => None,
+            NametableSource::ExRAM if self.exram_mode <= 1 => Some(self.exram.get(offset)),
+            NametableSource::ExRAM => Some(0),
+            NametableSource::Fill if offset < 0x3C0 => Some(self.fill_tile),
+            NametableSource::Fill => Some(self.fill_attribute * 0x55),
+        }
+    }
+
+    fn write_nametable(&mut self, address: u16, byte: u8) -> bool {
+        match self.nametable_source(address) {
+            NametableSource::Ciram => false,
+            NametableSource::ExRAM => {
+                if self.exram_mode <= 1 {
+                    self.exram.put((address & 0x03FF) as usize, byte);
+                }
+                true
+            }
+            NametableSource::Fill => true,
+        }
+    }
+
+    fn render_event(&mut self, event: RenderEvent) {
+        match event {
+            RenderEvent::ScanlineStart(_) => {
+                self.fetching_background = true;
+                self.fetching_sprites = false;
+                if self.in_frame {
+                    self.scanline_counter = self.scanline_counter.wrapping_add(1);
+                    if self.scanline_counter == self.irq_target {
+                        self.irq_pending = true;
+                    }
+                } else {
+                    self.in_frame = true;
+                    self.scanline_counter = 0;
+                    self.irq_pending = false;
+                }
+            }
+            RenderEvent::SpriteFetch { tall_sprites } => {
+                self.tall_sprites = tall_sprites;
+                self.fetching_background = false;
+                self.fetching_sprites = true;
+            }
+            RenderEvent::BackgroundPrefetch => {
+                self.fetching_background = true;
+                self.fetching_sprites = false;
+                self.tile_column = 0;
+                self.expect_attribute = false;
+                self.split_y = match (self.in_frame, self.split_y) {
+                    (false, _) => self.split_scroll,
+                    (true, 239) => 0,
+                    (true, y) => y.wrapping_add(1),
+                };
+            }
+            RenderEvent::FetchEnd => self.fetching_background = false,
+            RenderEvent::FrameEnd => {
+                self.in_frame = false;
+                self.fetching_background = false;
+                self.fetching_sprites = false;
+            }
+        }
+    }
+
+    fn clock_audio(&mut self) -> f32 {
+        self.audio_frame_counter += 1;
+        if self.audio_frame_counter >= AUDIO_FRAME_PERIOD {
+            self.audio_frame_counter = 0;
+            self.clock_audio_frame();
+        }
+
+        self.pulse_1.clock();
+        self.pulse_2.clock();
+
+        let p1 = self.pulse_1.volume() as f32;
+        let p2 = self.pulse_2.volume() as f32;
+        (0.00752 * (p1 + p2)) + (0.0017 * self.pcm as f32)
+    }
+}
+
+impl<'de> SaveState<'de, MapperState> for MMC5 {
+    fn freeze(&mut self) -> MapperState {
+        MapperState::MMC5(MMC5State {
+            prg_mode: self.prg_mode,
+            prg_banks: self.prg_banks.to_vec(),
+            prg_ram_protect: self.prg_ram_protect.to_vec(),
+            chr_mode: self.chr_mode,
+            chr_banks: self.chr_banks.to_vec(),
+            chr_upper: self.chr_upper,
+            last_chr_set_b: self.last_chr_set_b,
+            exram_mode: self.exram_mode,
+            nametable_mapping: self.nametable_mapping,
+            fill_tile: self.fill_tile,
+            fill_attribute: self.fill_attribute,
+            split_control: self.split_control,
+            split_scroll: self.split_scroll,
+            split_bank: self.split_bank,
+            irq_target: self.irq_target,
+            irq_enabled: self.irq_enabled,
+            irq_pending: self.irq_pending,
+            in_frame: self.in_frame,
+            scanline_counter: self.scanline_counter,
+            multiplicand: self.multiplicand,
+            multiplier: self.multiplier,
+            fetching_background: self.fetching_background,
+            fetching_sprites: self.fetching_sprites,
+            tall_sprites: self.tall_sprites,
+            tile_column: self.tile_column,
+            expect_attribute: self.expect_attribute,
+            split_y: self.split_y,
+            in_split: self.in_split,
+            ext_attribute: self.ext_attribute,
+            pulse_1: self.pulse_1.freeze(),
+            pulse_2: self.pulse_2.freeze(),
+            pcm: self.pcm,
+            pcm_control: self.pcm_control,
+            audio_frame_counter: self.audio_frame_counter,
+            exram: self.exram.freeze(),
+            chr_mem: self.chr_mem.freeze(),
+        })
+    }
+
+    fn hydrate(&mut self, state: MapperState) {
+        match state {
+            MapperState::MMC5(s) => {
+                self.prg_mode = s.prg_mode;
+                self.prg_banks.copy_from_slice(&s.prg_banks);
+                self.prg_ram_protect.copy_from_slice(&s.prg_ram_protect);
+                self.chr_mode = s.chr_mode;
+                self.chr_banks.copy_from_slice(&s.chr_banks);
+                self.chr_upper = s.chr_upper;
+                self.last_chr_set_b = s.last_chr_set_b;
+                self.exram_mode = s.exram_mode;
+                self.nametable_mapping = s.nametable_mapping;
+                self.fill_tile = s.fill_tile;
+                self.fill_attribute = s.fill_attribute;
+                self.split_control = s.split_control;
+                self.split_scroll = s.split_scroll;
+                self.split_bank = s.split_bank;
+                self.irq_target = s.irq_target;
+                self.irq_enabled = s.irq_enabled;
+                self.irq_pending = s.irq_pending;
+                self.in_frame = s.in_frame;
+                self.scanline_counter = s.scanline_counter;
+                self.multiplicand = s.multiplicand;
+                self.multiplier = s.multiplier;
+                self.fetching_background = s.fetching_background;
+                self.fetching_sprites = s.fetching_sprites;
+                self.tall_sprites = s.tall_sprites;
+                self.tile_column = s.tile_column;
+                self.expect_attribute = s.expect_attribute;
+                self.split_y = s.split_y;
+                self.in_split = s.in_split;
+                self.ext_attribute = s.ext_attribute;
+                self.pulse_1.hydrate(s.pulse_1);
+                self.pulse_2.hydrate(s.pulse_2);
+                self.pcm = s.pcm;
+                self.pcm_control = s.pcm_control;
+                self.audio_frame_counter = s.audio_frame_counter;
+                self.exram.hydrate(s.exram);
+                self.chr_mem.hydrate(s.chr_mem);
+            }
+            _ => panic!("Incompatible mapper state for MMC5 mapper: {:?}", state),
+        }
+    }
+}
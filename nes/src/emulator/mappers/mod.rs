@@ -1,3 +1,7 @@
+// Builds the mappers below from a ROM's mapper number.
+mod registry;
+pub use self::registry::{MapperFactory, MapperRegistry};
+
 // In iNES mapper number order.
 
 // #0 NROM
@@ -7,6 +11,7 @@ pub use self::nrom::NROM;
 // #1 MMC1
 mod mmc1;
 pub use self::mmc1::MMC1;
+pub use self::mmc1::Variant as MMC1Variant;
 
 // #2 UxROM
 mod uxrom;
@@ -16,14 +21,108 @@ pub use self::uxrom::UXROM;
 mod cnrom;
 pub use self::cnrom::CNROM;
 
-// #4 MMC3
+// #4, #76, #88, #95, #118, #119, #206 MMC3, MMC6 and Namco 108
 mod mmc3;
 pub use self::mmc3::MMC3;
+pub use self::mmc3::Variant as MMC3Variant;
+
+// #5 MMC5
+mod mmc5;
+pub use self::mmc5::MMC5;
 
 // #7 AxROM
 mod axrom;
 pub use self::axrom::AXROM;
 
+// #9 MMC2
+mod mmc2;
+pub use self::mmc2::MMC2;
+
+// #10 MMC4
+mod mmc4;
+pub use self::mmc4::MMC4;
+
 // #11 ColorDreams
 mod color_dreams;
 pub use self::color_dreams::ColorDreams;
+
+// #16, #153, #159 Bandai FCG and LZ93D50
+mod bandai_fcg;
+pub use self::bandai_fcg::BandaiFCG;
+pub use self::bandai_fcg::Variant as BandaiFCGVariant;
+
+// #19 Namco 129 and 163
+mod namco163;
+pub use self::namco163::Namco163;
+
+// #21, #22, #23, #25 VRC2 and VRC4
+mod vrc4;
+pub use self::vrc4::VRC4;
+pub use self::vrc4::Variant as VRC4Variant;
+
+// #24, #26 VRC6
+mod vrc6;
+pub use self::vrc6::VRC6;
+
+// #34 BNROM and NINA-001
+mod bnrom;
+pub use self::bnrom::BNROM;
+
+// #66 GxROM
+mod gxrom;
+pub use self::gxrom::GXROM;
+
+// #69 Sunsoft FME-7 and 5B
+mod fme7;
+pub use self::fme7::FME7;
+
+// #70, #152 Bandai 74161/7432
+mod bandai_74161;
+pub use self::bandai_74161::Bandai74161;
+
+// #71 Camerica / Codemasters
+mod camerica;
+pub use self::camerica::Camerica;
+
+// #78 Jaleco JF-16 and Irem IF-12
+mod jaleco_jf16;
+pub use self::jaleco_jf16::JalecoJF16;
+
+// #85 VRC7
+mod vrc7;
+pub use self::vrc7::VRC7;
+
+// #87 Jaleco JF-05 to JF-10
+mod jaleco_jf05;
+pub use self::jaleco_jf05::JalecoJF05;
+
+// #140 Jaleco JF-11 and JF-14
+mod jaleco_jf11;
+pub use self::jaleco_jf11::JalecoJF11;
+
+// #180 UNROM with the first bank fixed
+mod unrom_180;
+pub use self::unrom_180::UNROM180;
+
+// #184 Sunsoft-1
+mod sunsoft1;
+pub use self::sunsoft1::Sunsoft1;
+
+// #232 Camerica Quattro
+mod quattro;
+pub use self::quattro::Quattro;
+
+// Famicom Disk System RAM adapter
+mod fds;
+pub use self::fds::FDS;
+
+// NSF music player
+mod nsf;
+pub use self::nsf::NSFPlayer;
+
+// IRQ counter shared by the VRC mappers.
+mod vrc_irq;
+
+// Serial EEPROMs used by the Bandai mappers.
+mod eeprom;
+pub use self::eeprom::Mode as EepromMode;
@@ -0,0 +1,347 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::emulator::memory::{Mapper, Memory};
+use crate::emulator::ppu::MirrorMode;
+use crate::emulator::state::{MapperState, Namco163State, SaveState};
+
+// iNES Mapper 19: Namco 129 and 163
+// 3x 8kb switchable PRG ROM banks, and the last 8kb fixed at $E000.
+// 8x 1kb switchable CHR ROM banks.
+// Each nametable can be either page of CIRAM, or a 1kb bank of CHR ROM.  So can each pattern table
+// bank, unless CIRAM is disabled for that pattern table.
+// A 15-bit IRQ counter, clocked every CPU cycle.
+// 8kb PRG RAM, write protected in 2kb windows by $F800.
+//
+// The 163 also has 128 bytes of internal RAM at $4800, which holds both the waveforms and the
+// registers for up to 8 wavetable channels.
+pub struct Namco163 {
+    prg_rom: Memory,
+    chr_mem: Memory,
+    prg_ram: Option<Rc<RefCell<Memory>>>,
+
+    prg_banks: [u8; 3],
+    chr_banks: [u8; 8],
+    // $E800 bits 6-7.  Whether CHR banks $E0-$FF read CHR ROM, rather than CIRAM, in each pattern
+    // table.
+    ciram_disabled: [bool; 2],
+    // $C000-$DFFF.  Banks $E0-$FF are CIRAM, whose page is the lowest bit.
+    nametable_banks: [u8; 4],
+    sound_disabled: bool,
+
+    irq_counter: u16,
+    irq_enabled: bool,
+    irq_pending: bool,
+
+    // $F800.  The address increments after each access through $4800 if bit 7 was set.  The same
+    // bits write protect PRG RAM.
+    ram: Memory,
+    ram_address: u8,
+    auto_increment: bool,
+
+    // The chip updates one channel every 15 CPU cycles, counting down from channel 7.
+    audio_cycles: u8,
+    current_channel: u8,
+    channel_outputs: [i16; 8],
+}
+
+const INTERNAL_RAM_SIZE: usize = 0x80;
+const CIRAM_BANKS: u8 = 0xE0;
+const CPU_CYCLES_PER_CHANNEL_UPDATE: u8 = 15;
+const IRQ_COUNTER_MAX: u16 = 0x7FFF;
+
+// The most a single channel can output is 7.5 * 15.
+const AUDIO_SCALE: f32 = 0.0015;
+
+impl Namco163 {
+    pub fn new(prg_rom: Memory, chr_mem: Memory) -> Namco163 {
+        Namco163 {
+            prg_rom,
+            chr_mem,
+            prg_ram: None,
+            prg_banks: [0; 3],
+            chr_banks: [0; 8],
+            ciram_disabled: [false; 2],
+            nametable_banks: [CIRAM_BANKS; 4],
+            sound_disabled: false,
+            irq_counter: 0,
+            irq_enabled: false,
+            irq_pending: false,
+            ram: Memory::new_ram(INTERNAL_RAM_SIZE),
+            ram_address: 0,
+            auto_increment: false,
+            audio_cycles: 0,
+            current_channel: 7,
+            channel_outputs: [0; 8],
+        }
+    }
+
+    fn chr_rom_address(&self, bank: u8, address: u16) -> usize {
+        ((bank as usize) << 10 | (address & 0x03FF) as usize) % self.chr_mem.len()
+    }
+
+    fn access_ram(&mut self) -> usize {
+        let address = self.ram_address as usize;
+        if self.auto_increment {
+            self.ram_address = (self.ram_address + 1) & 0x7F;
+        }
+        address
+    }
+
+    // Writes need $F800 bits 4-7 to be 0100, and the window's bit in bits 0-3 to be clear.
+    fn prg_ram_writable(&self, address: u16) -> bool {
+        let window = (address - 0x6000) >> 11;
+        !self.auto_increment
+            && self.ram_address & 0x70 == 0x40
+            && self.ram_address & (1 << window) == 0
+    }
+
+    fn enabled_channels(&self) -> u8 {
+        ((self.ram.get(0x7F) >> 4) & 0x07) + 1
+    }
+
+    // Advances a channel's phase by its frequency, and works out its new output.
+    fn update_channel(&mut self, channel: u8) {
+        let base = 0x40 + channel as usize * 8;
+        let register = |offset: usize| self.ram.get(base + offset) as u32;
+
+        let frequency = register(0) | (register(2) << 8) | ((register(4) & 0x03) << 16);
+        let phase = register(1) | (register(3) << 8) | (register(5) << 16);
+        let length = 256 - (register(4) & 0xFC);
+        let wave_address = register(6);
+        let volume = (register(7) & 0x0F) as i16;
+
+        let phase = (phase + frequency) % (length << 16);
+        self.ram.put(base + 1, phase as u8);
+        self.ram.put(base + 3, (phase >> 8) as u8);
+        self.ram.put(base + 5, (phase >> 16) as u8);
+
+        let nibble_address = ((phase >> 16) + wave_address) & 0xFF;
+        let byte = self.ram.get((nibble_address >> 1) as usize);
+        let sample = if nibble_address & 0x1 == 0 {
+            byte & 0x0F
+        } else {
+            byte >> 4
+        };
+        self.channel_outputs[channel as usize] = (sample as i16 - 8) * volume;
+    }
+}
+
+impl Mapper for Namco163 {
+    fn read_chr(&mut self, address: u16) -> u8 {
+        let bank = self.chr_banks[(address >> 10) as usize];
+        let chr_address = self.chr_rom_address(bank, address);
+        self.chr_mem.get(chr_address)
+    }
+
+    fn write_chr(&mut self, address: u16, byte: u8) {
+        let bank = self.chr_banks[(address >> 10) as usize];
+        let chr_address = self.chr_rom_address(bank, address);
+        self.chr_mem.put(chr_address, byte);
+    }
+
+    fn read_prg(&mut self, address: u16) -> u8 {
+        let offset = (address & 0x1FFF) as usize;
+        let base = match address {
+            0x8000..=0x9FFF => self.prg_banks[0] as usize * 0x2000,
+            0xA000..=0xBFFF => self.prg_banks[1] as usize * 0x2000,
+            0xC000..=0xDFFF => self.prg_banks[2] as usize * 0x2000,
+            _ => self.prg_rom.len() - 0x2000,
+        };
+        self.prg_rom.get((base + offset) % self.prg_rom.len())
+    }
+
+    fn write_prg(&mut self, address: u16, byte: u8) {
+        match address {
+            0x8000..=0xBFFF => self.chr_banks[((address - 0x8000) >> 11) as usize] = byte,
+            0xC000..=0xDFFF => self.nametable_banks[((address - 0xC000) >> 11) as usize] = byte,
+            0xE000..=0xE7FF => {
+                self.prg_banks[0] = byte & 0x3F;
+                self.sound_disabled = byte & 0x40 != 0;
+            }
+            0xE800..=0xEFFF => {
+                self.prg_banks[1] = byte & 0x3F;
+                self.ciram_disabled = [byte & 0x40 != 0, byte & 0x80 != 0];
+            }
+            0xF000..=0xF7FF => self.prg_banks[2] = byte & 0x3F,
+            _ => {
+                self.ram_address = byte & 0x7F;
+                self.auto_increment = byte & 0x80 != 0;
+            }
+        }
+    }
+
+    fn read_expansion(&mut self, address: u16) -> Option<u8> {
+        match address {
+            0x4800..=0x4FFF => {
+                let ram_address = self.access_ram();
+                Some(self.ram.get(ram_address))
+            }
+            0x5000..=0x57FF => Some(self.irq_counter as u8),
+            0x5800..=0x5FFF => {
+                Some(((self.irq_counter >> 8) as u8) | if self.irq_enabled { 0x80 } else { 0 })
+            }
+            0x6000..=0x7FFF => {
+                let prg_ram = self.prg_ram.as_ref()?.borrow();
+                match prg_ram.len() {
+                    0 => None,
+                    len => Some(prg_ram.get((address & 0x1FFF) as usize % len)),
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn write_expansion(&mut self, address: u16, byte: u8) {
+        match address {
+            0x4800..=0x4FFF => {
+                let ram_address = self.access_ram();
+                self.ram.put(ram_address, byte);
+            }
+            0x5000..=0x57FF => {
+                self.irq_counter = (self.irq_counter & 0x7F00) | byte as u16;
+                self.irq_pending = false;
+            }
+            0x5800..=0x5FFF => {
+                self.irq_counter = (self.irq_counter & 0x00FF) | (((byte & 0x7F) as u16) << 8);
+                self.irq_enabled = byte & 0x80 != 0;
+                self.irq_pending = false;
+            }
+            0x6000..=0x7FFF if self.prg_ram_writable(address) => {
+                if let Some(ref prg_ram) = self.prg_ram {
+                    let mut prg_ram = prg_ram.borrow_mut();
+                    let len = prg_ram.len();
+                    if len > 0 {
+                        prg_ram.put((address & 0x1FFF) as usize % len, byte);
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn attach_prg_ram(&mut self, prg_ram: Rc<RefCell<Memory>>) -> bool {
+        self.prg_ram = Some(prg_ram);
+        true
+    }
+
+    fn mirror_mode(&self) -> MirrorMode {
+        MirrorMode::Custom(self.nametable_banks.map(|bank| bank & 0x1))
+    }
+
+    fn read_nametable(&mut self, address: u16) -> Option<u8> {
+        let bank = self.nametable_banks[((address >> 10) & 0x3) as usize];
+        if bank >= CIRAM_BANKS {
+            return None;
+        }
+        let chr_address = self.chr_rom_address(bank, address);
+        Some(self.chr_mem.get(chr_address))
+    }
+
+    fn write_nametable(&mut self, address: u16, byte: u8) -> bool {
+        let bank = self.nametable_banks[((address >> 10) & 0x3) as usize];
+        if bank >= CIRAM_BANKS {
+            return false;
+        }
+        let chr_address = self.chr_rom_address(bank, address);
+        self.chr_mem.put(chr_address, byte);
+        true
+    }
+
+    fn chr_ciram_page(&self, address: u16) -> Option<u8> {
+        let bank = self.chr_banks[(address >> 10) as usize];
+        if bank >= CIRAM_BANKS && !self.ciram_disabled[(address >> 12) as usize] {
+            Some(bank & 0x1)
+        } else {
+            None
+        }
+    }
+
+    fn irq_triggered(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn clock_cpu(&mut self, cycles: u32) {
+        if !self.irq_enabled || self.irq_counter == IRQ_COUNTER_MAX {
+            return;
+        }
+
+        self.irq_counter = (self.irq_counter as u32 + cycles).min(IRQ_COUNTER_MAX as u32) as u16;
+        if self.irq_counter == IRQ_COUNTER_MAX {
+            self.irq_pending = true;
+        }
+    }
+
+    fn clock_audio(&mut self) -> f32 {
+        if self.sound_disabled {
+            return 0.0;
+        }
+
+        self.audio_cycles += 2;
+        if self.audio_cycles >= CPU_CYCLES_PER_CHANNEL_UPDATE {
+            self.audio_cycles -= CPU_CYCLES_PER_CHANNEL_UPDATE;
+            let channel = self.current_channel;
+            self.update_channel(channel);
+
+            let lowest_channel = 8 - self.enabled_channels();
+            self.current_channel = if channel <= lowest_channel {
+                7
+            } else {
+                channel - 1
+            };
+        }
+
+        // The real chip outputs each channel in turn, which is too fast to hear as anything but
+        // their average.
+        let enabled_channels = self.enabled_channels();
+        let total: i16 = self.channel_outputs[(8 - enabled_channels) as usize..]
+            .iter()
+            .sum();
+        total as f32 / enabled_channels as f32 * AUDIO_SCALE
+    }
+}
+
+impl<'de> SaveState<'de, MapperState> for Namco163 {
+    fn freeze(&mut self) -> MapperState {
+        MapperState::Namco163(Namco163State {
+            prg_banks: self.prg_banks.to_vec(),
+            chr_banks: self.chr_banks.to_vec(),
+            ciram_disabled: self.ciram_disabled.to_vec(),
+            nametable_banks: self.nametable_banks.to_vec(),
+            sound_disabled: self.sound_disabled,
+            irq_counter: self.irq_counter,
+            irq_enabled: self.irq_enabled,
+            irq_pending: self.irq_pending,
+            ram: self.ram.freeze(),
+            ram_address: self.ram_address,
+            auto_increment: self.auto_increment,
+            audio_cycles: self.audio_cycles,
+            current_channel: self.current_channel,
+            channel_outputs: self.channel_outputs.to_vec(),
+            chr_mem: self.chr_mem.freeze(),
+        })
+    }
+
+    fn hydrate(&mut self, state: MapperState) {
+        match state {
+            MapperState::Namco163(s) => {
+                self.prg_banks.copy_from_slice(&s.prg_banks);
+                self.chr_banks.copy_from_slice(&s.chr_banks);
+                self.ciram_disabled.copy_from_slice(&s.ciram_disabled);
+                self.nametable_banks.copy_from_slice(&s.nametable_banks);
+                self.sound_disabled = s.sound_disabled;
+                self.irq_counter = s.irq_counter;
+                self.irq_enabled = s.irq_enabled;
+                self.irq_pending = s.irq_pending;
+                self.ram.hydrate(s.ram);
+                self.ram_address = s.ram_address;
+                self.auto_increment = s.auto_increment;
+                self.audio_cycles = s.audio_cycles;
+                self.current_channel = s.current_channel;
+                self.channel_outputs.copy_from_slice(&s.channel_outputs);
+                self.chr_mem.hydrate(s.chr_mem);
+            }
+            _ => panic!("Incompatible mapper state for Namco163 mapper: {:?}", state),
+        }
+    }
+}
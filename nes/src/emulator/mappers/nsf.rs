@@ -0,0 +1,489 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::emulator::mappers::{FDS, FME7, MMC5, Namco163, VRC6, VRC7};
+use crate::emulator::memory::{Mapper, Memory};
+use crate::emulator::nsf::{
+    EXPANSION_FDS, EXPANSION_MMC5, EXPANSION_NAMCO163, EXPANSION_SUNSOFT5B, EXPANSION_VRC6,
+    EXPANSION_VRC7, NSF,
+};
+use crate::emulator::ppu::MirrorMode;
+use crate::emulator::region::Region;
+use crate::emulator::state::{MapperState, NSFPlayerState, SaveState};
+
+// NSF music player
+// The music data in 4kb banks at $8000-$FFFF, switched through $5FF8-$5FFF.
+// 8kb PRG RAM at $6000, which is the console's SRAM.  FDS music gets 32kb of RAM at $6000-$DFFF
+// instead, and switching a bank there with $5FF6-$5FFD copies it into RAM.
+// 8kb CHR RAM, which nothing uses.
+//
+// There's no cartridge program, so we provide a small driver at $4100 along with the vectors.  On
+// reset it clears RAM and the APU, and calls the init routine with the selected song.  Then it
+// waits for our timer IRQ, which fires at the play rate from the file, to call the play routine.
+//
+// Music for expansion chips is played through the chips from the matching mappers.
+pub struct NSFPlayer {
+    data: Memory,
+    chr_mem: Memory,
+    prg_ram: Option<Rc<RefCell<Memory>>>,
+    fds: bool,
+    initial_banks: [u8; 10],
+    init_address: u16,
+    play_address: u16,
+    songs: usize,
+    region: Region,
+    // Master clocks per play routine call, times a million.  See clock_cpu.
+    play_period: u64,
+
+    song: usize,
+    // Banks for each 4kb window from $6000.
+    banks: [u8; 10],
+    playing: bool,
+    play_timer: u64,
+    play_pending: bool,
+    expansion: u8,
+    chips: Chips,
+}
+
+const DRIVER_ADDRESS: u16 = 0x4100;
+const NMI_ADDRESS: u16 = 0x415B;
+const IRQ_ADDRESS: u16 = 0x414A;
+
+// The driver's registers.
+const SONG_REGISTER: u16 = 0x41F0;
+const REGION_REGISTER: u16 = 0x41F1;
+const RESTART_REGISTER: u16 = 0x41F2;
+const START_PLAYING_REGISTER: u16 = 0x41F3;
+const ACKNOWLEDGE_REGISTER: u16 = 0x41F4;
+const INIT_VECTOR: u16 = 0x41F6;
+const PLAY_VECTOR: u16 = 0x41F8;
+
+#[rustfmt::skip]
+const DRIVER: [u8; 0x62] = [
+    // reset:
+    0x78,             // SEI
+    0xD8,             // CLD
+    0xA2, 0xFF,       // LDX #$FF
+    0x9A,             // TXS
+    0x8D, 0xF2, 0x41, // STA RESTART_REGISTER
+    0xA9, 0x00,       // LDA #$00
+    0xAA,             // TAX
+    // Clear RAM.
+    0x95, 0x00,       // STA $00,X
+    0x9D, 0x00, 0x01, // STA $0100,X
+    0x9D, 0x00, 0x02, // STA $0200,X
+    0x9D, 0x00, 0x03, // STA $0300,X
+    0x9D, 0x00, 0x04, // STA $0400,X
+    0x9D, 0x00, 0x05, // STA $0500,X
+    0x9D, 0x00, 0x06, // STA $0600,X
+    0x9D, 0x00, 0x07, // STA $0700,X
+    0xE8,             // INX
+    0xD0, 0xE6,       // BNE clear RAM
+    // Clear the APU.
+    0x9D, 0x00, 0x40, // STA $4000,X
+    0xE8,             // INX
+    0xE0, 0x14,       // CPX #$14
+    0xD0, 0xF8,       // BNE clear the APU
+    0x8D, 0x15, 0x40, // STA $4015
+    0xA9, 0x0F,       // LDA #$0F
+    0x8D, 0x15, 0x40, // STA $4015
+    0xA9, 0x40,       // LDA #$40
+    0x8D, 0x17, 0x40, // STA $4017
+    // Call init.
+    0xAD, 0xF0, 0x41, // LDA SONG_REGISTER
+    0xAE, 0xF1, 0x41, // LDX REGION_REGISTER
+    0x20, 0x5C, 0x41, // JSR init
+    0x8D, 0xF3, 0x41, // STA START_PLAYING_REGISTER
+    0x58,             // CLI
+    // idle:
+    0x4C, 0x47, 0x41, // JMP idle
+    // irq:
+    0x48,             // PHA
+    0x8A,             // TXA
+    0x48,             // PHA
+    0x98,             // TYA
+    0x48,             // PHA
+    0xAD, 0xF4, 0x41, // LDA ACKNOWLEDGE_REGISTER
+    0x20, 0x5F, 0x41, // JSR play
+    0x68,             // PLA
+    0xA8,             // TAY
+    0x68,             // PLA
+    0xAA,             // TAX
+    0x68,             // PLA
+    0x40,             // RTI
+    // nmi:
+    0x40,             // RTI
+    // init:
+    0x6C, 0xF6, 0x41, // JMP (INIT_VECTOR)
+    // play:
+    0x6C, 0xF8, 0x41, // JMP (PLAY_VECTOR)
+];
+
+const BANK_SIZE: usize = 0x1000;
+
+impl NSFPlayer {
+    pub fn new(nsf: NSF) -> NSFPlayer {
+        let fds = nsf.expansion() & EXPANSION_FDS != 0;
+        let base = if fds { 0x6000 } else { 0x8000 };
+
+        // Music which isn't bankswitched is loaded straight into the address space, as if the
+        // banks were in order.  FDS music starts with the banks for $6000 and $7000 the same as
+        // for $E000 and $F000.
+        let mut initial_banks = [0; 10];
+        let padding = if nsf.is_bankswitched() {
+            initial_banks[2..].copy_from_slice(&nsf.initial_banks());
+            initial_banks[0] = initial_banks[8];
+            initial_banks[1] = initial_banks[9];
+            nsf.load_address() as usize & 0x0FFF
+        } else {
+            let first = (base - 0x6000) / BANK_SIZE;
+            for (window, bank) in initial_banks.iter_mut().enumerate() {
+                *bank = window.saturating_sub(first) as u8;
+            }
+            (nsf.load_address() as usize).saturating_sub(base)
+        };
+        let mut data = vec![0; padding];
+        data.extend_from_slice(nsf.data());
+        data.resize(data.len().div_ceil(BANK_SIZE) * BANK_SIZE, 0);
+
+        let region = Region::from(nsf.timing());
+        let play_speed_us = nsf.play_speed_us(nsf.timing()) as u64;
+
+        let mut player = NSFPlayer {
+            data: Memory::new_rom(data),
+            chr_mem: Memory::new_ram(0x2000),
+            prg_ram: None,
+            fds,
+            initial_banks,
+            init_address: nsf.init_address(),
+            play_address: nsf.play_address(),
+            songs: nsf.songs(),
+            region,
+            play_period: play_speed_us * region.master_clock_hz(),
+            song: nsf.starting_song(),
+            banks: [0; 10],
+            playing: false,
+            play_timer: 0,
+            play_pending: false,
+            expansion: nsf.expansion(),
+            chips: Chips::new(nsf.expansion()),
+        };
+        player.restart();
+        player
+    }
+
+    // Called by the driver before each song starts.
+    fn restart(&mut self) {
+        self.playing = false;
+        self.play_timer = 0;
+        self.play_pending = false;
+        self.chips = Chips::new(self.expansion);
+
+        if let Some(ref prg_ram) = self.prg_ram {
+            let mut prg_ram = prg_ram.borrow_mut();
+            for ix in 0..prg_ram.len() {
+                prg_ram.put(ix, 0);
+            }
+        }
+
+        let initial_banks = self.initial_banks;
+        for (window, &bank) in initial_banks.iter().enumerate() {
+            self.switch_bank(window, bank);
+        }
+    }
+
+    fn switch_bank(&mut self, window: usize, bank: u8) {
+        self.banks[window] = bank;
+
+        // FDS RAM is loaded from the bank.
+        if self.fds
+            && window < 8
+            && let Some(ref prg_ram) = self.prg_ram
+        {
+            let mut prg_ram = prg_ram.borrow_mut();
+            let base = bank as usize * BANK_SIZE;
+            for ix in 0..BANK_SIZE {
+                let byte = self.data.get((base + ix) % self.data.len());
+                prg_ram.put(window * BANK_SIZE + ix, byte);
+            }
+        }
+    }
+
+    fn read_bank(&self, address: u16) -> u8 {
+        let bank = self.banks[((address - 0x6000) >> 12) as usize] as usize;
+        let offset = bank * BANK_SIZE + (address & 0x0FFF) as usize;
+        self.data.get(offset % self.data.len())
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        match self.prg_ram {
+            Some(ref prg_ram) => {
+                let prg_ram = prg_ram.borrow();
+                prg_ram.get((address - 0x6000) as usize % prg_ram.len())
+            }
+            None => 0,
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, byte: u8) {
+        if let Some(ref prg_ram) = self.prg_ram {
+            let mut prg_ram = prg_ram.borrow_mut();
+            let len = prg_ram.len();
+            prg_ram.put((address - 0x6000) as usize % len, byte);
+        }
+    }
+}
+
+impl Mapper for NSFPlayer {
+    fn read_chr(&mut self, address: u16) -> u8 {
+        self.chr_mem.get(address as usize)
+    }
+
+    fn write_chr(&mut self, address: u16, byte: u8) {
+        self.chr_mem.put(address as usize, byte);
+    }
+
+    fn read_prg(&mut self, address: u16) -> u8 {
+        match address {
+            0xFFFA => NMI_ADDRESS as u8,
+            0xFFFB => (NMI_ADDRESS >> 8) as u8,
+            0xFFFC => DRIVER_ADDRESS as u8,
+            0xFFFD => (DRIVER_ADDRESS >> 8) as u8,
+            0xFFFE => IRQ_ADDRESS as u8,
+            0xFFFF => (IRQ_ADDRESS >> 8) as u8,
+            0x8000..=0xDFFF if self.fds => self.read_ram(address),
+            _ => self.read_bank(address),
+        }
+    }
+
+    fn write_prg(&mut self, address: u16, byte: u8) {
+        if self.fds && address < 0xE000 {
+            self.write_ram(address, byte);
+        }
+        self.chips.write(address, byte);
+    }
+
+    fn read_expansion(&mut self, address: u16) -> Option<u8> {
+        match address {
+            SONG_REGISTER => Some(self.song as u8),
+            REGION_REGISTER => Some(if self.region == Region::NTSC { 0 } else { 1 }),
+            ACKNOWLEDGE_REGISTER => {
+                self.play_pending = false;
+                Some(0)
+            }
+            INIT_VECTOR => Some(self.init_address as u8),
+            0x41F7 => Some((self.init_address >> 8) as u8),
+            PLAY_VECTOR => Some(self.play_address as u8),
+            0x41F9 => Some((self.play_address >> 8) as u8),
+            0x4100..=0x41FF => Some(
+                DRIVER
+                    .get((address - DRIVER_ADDRESS) as usize)
+                    .copied()
+                    .unwrap_or(0),
+            ),
+            0x6000..=0x7FFF => Some(self.read_ram(address)),
+            _ => self.chips.read(address),
+        }
+    }
+
+    fn write_expansion(&mut self, address: u16, byte: u8) {
+        match address {
+            RESTART_REGISTER => self.restart(),
+            START_PLAYING_REGISTER => self.playing = true,
+            0x5FF6..=0x5FF7 if self.fds => self.switch_bank((address - 0x5FF6) as usize, byte),
+            0x5FF8..=0x5FFF => self.switch_bank((address - 0x5FF6) as usize, byte),
+            0x6000..=0x7FFF => self.write_ram(address, byte),
+            _ => self.chips.write(address, byte),
+        }
+    }
+
+    fn attach_prg_ram(&mut self, prg_ram: Rc<RefCell<Memory>>) -> bool {
+        self.prg_ram = Some(prg_ram);
+        self.restart();
+        true
+    }
+
+    fn mirror_mode(&self) -> MirrorMode {
+        MirrorMode::Horizontal
+    }
+
+    fn irq_triggered(&self) -> bool {
+        self.play_pending
+    }
+
+    fn clock_cpu(&mut self, cycles: u32) {
+        if !self.playing {
+            return;
+        }
+
+        // Counting in millionths of a master clock keeps the play rate exact.
+        self.play_timer += cycles as u64 * self.region.cpu_clock_factor() as u64 * 1_000_000;
+        if self.play_timer >= self.play_period {
+            self.play_timer -= self.play_period;
+            self.play_pending = true;
+        }
+    }
+
+    fn clock_audio(&mut self) -> f32 {
+        self.chips.clock()
+    }
+
+    fn songs(&self) -> usize {
+        self.songs
+    }
+
+    fn song(&self) -> Option<usize> {
+        Some(self.song)
+    }
+
+    fn select_song(&mut self, song: usize) {
+        self.song = song % self.songs;
+    }
+}
+
+// The expansion chips, borrowed from their mappers.  Only their sound registers are passed on.
+struct Chips {
+    vrc6: Option<VRC6>,
+    vrc7: Option<VRC7>,
+    fds: Option<FDS>,
+    mmc5: Option<MMC5>,
+    namco163: Option<Namco163>,
+    sunsoft5b: Option<FME7>,
+}
+
+fn empty_rom() -> Memory {
+    Memory::new_rom(vec![0; 0x2000])
+}
+
+fn empty_chr() -> Memory {
+    Memory::new_ram(0x2000)
+}
+
+impl Chips {
+    fn new(expansion: u8) -> Chips {
+        let has = |chip: u8| expansion & chip != 0;
+        Chips {
+            vrc6: has(EXPANSION_VRC6).then(|| VRC6::new(empty_rom(), empty_chr(), false)),
+            vrc7: has(EXPANSION_VRC7).then(|| VRC7::new(empty_rom(), empty_chr(), 0)),
+            fds: has(EXPANSION_FDS).then(|| {
+                let mut fds = FDS::new(vec![0; 0x2000], vec![]);
+                fds.write_expansion(0x4023, 0x02);
+                fds
+            }),
+            mmc5: has(EXPANSION_MMC5).then(|| {
+                // ExRAM is plain RAM.
+                let mut mmc5 = MMC5::new(empty_rom(), empty_chr());
+                mmc5.write_expansion(0x5104, 0x02);
+                mmc5
+            }),
+            namco163: has(EXPANSION_NAMCO163).then(|| Namco163::new(empty_rom(), empty_chr())),
+            sunsoft5b: has(EXPANSION_SUNSOFT5B).then(|| FME7::new(empty_rom(), empty_chr())),
+        }
+    }
+
+    fn read(&mut self, address: u16) -> Option<u8> {
+        match address {
+            0x4040..=0x407F | 0x4090 | 0x4092 => self.fds.as_mut()?.read_expansion(address),
+            0x4800..=0x4FFF => self.namco163.as_mut()?.read_expansion(address),
+            0x5000..=0x5015 | 0x5205..=0x5206 | 0x5C00..=0x5FF5 => {
+                self.mmc5.as_mut()?.read_expansion(address)
+            }
+            _ => None,
+        }
+    }
+
+    fn write(&mut self, address: u16, byte: u8) {
+        match address {
+            0x4040..=0x408A => {
+                if let Some(ref mut fds) = self.fds {
+                    fds.write_expansion(address, byte);
+                }
+            }
+            0x4800..=0x4FFF => {
+                if let Some(ref mut namco163) = self.namco163 {
+                    namco163.write_expansion(address, byte);
+                }
+            }
+            0x5000..=0x5015 | 0x5205..=0x5206 | 0x5C00..=0x5FF5 => {
+                if let Some(ref mut mmc5) = self.mmc5 {
+                    mmc5.write_expansion(address, byte);
+                }
+            }
+            0x9000..=0x9003 | 0xA000..=0xA002 | 0xB000..=0xB002 => {
+                if let Some(ref mut vrc6) = self.vrc6 {
+                    vrc6.write_prg(address, byte);
+                }
+            }
+            _ => (),
+        }
+
+        if let 0x9010 | 0x9030 = address
+            && let Some(ref mut vrc7) = self.vrc7
+        {
+            vrc7.write_prg(address, byte);
+        }
+
+        if let 0xF800..=0xFFFF = address
+            && let Some(ref mut namco163) = self.namco163
+        {
+            namco163.write_prg(address, byte);
+        }
+
+        if let 0xC000..=0xFFFF = address
+            && let Some(ref mut sunsoft5b) = self.sunsoft5b
+        {
+            sunsoft5b.write_prg(address, byte);
+        }
+    }
+
+    fn clock(&mut self) -> f32 {
+        let mut output = 0.0;
+        for chip in self.iter_mut() {
+            output += chip.clock_audio();
+        }
+        output
+    }
+
+    fn iter_mut(&mut self) -> impl Iterator<Item = &mut dyn Mapper> {
+        let chips: [Option<&mut dyn Mapper>; 6] = [
+            self.vrc6.as_mut().map(|chip| chip as &mut dyn Mapper),
+            self.vrc7.as_mut().map(|chip| chip as &mut dyn Mapper),
+            self.fds.as_mut().map(|chip| chip as &mut dyn Mapper),
+            self.mmc5.as_mut().map(|chip| chip as &mut dyn Mapper),
+            self.namco163.as_mut().map(|chip| chip as &mut dyn Mapper),
+            self.sunsoft5b.as_mut().map(|chip| chip as &mut dyn Mapper),
+        ];
+        chips.into_iter().flatten()
+    }
+}
+
+impl<'de> SaveState<'de, MapperState> for NSFPlayer {
+    fn freeze(&mut self) -> MapperState {
+        MapperState::NSFPlayer(NSFPlayerState {
+            song: self.song,
+            banks: self.banks.to_vec(),
+            playing: self.playing,
+            play_timer: self.play_timer,
+            play_pending: self.play_pending,
+            chips: self.chips.iter_mut().map(|chip| chip.freeze()).collect(),
+            chr_mem: self.chr_mem.freeze(),
+        })
+    }
+
+    fn hydrate(&mut self, state: MapperState) {
+        match state {
+            MapperState::NSFPlayer(s) => {
+                self.song = s.song;
+                self.banks.copy_from_slice(&s.banks);
+                self.playing = s.playing;
+                self.play_timer = s.play_timer;
+                self.play_pending = s.play_pending;
+                for (chip, state) in self.chips.iter_mut().zip(s.chips) {
+                    chip.hydrate(state);
+                }
+                self.chr_mem.hydrate(s.chr_mem);
+            }
+            _ => panic!("Incompatible mapper state for NSF player: {:?}", state),
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use crate::emulator::memory::{Mapper, Memory};
+use crate::emulator::ppu::MirrorMode;
+use crate::emulator::state::{MapperState, QuattroState, SaveState};
+
+// iNES Mapper 232: Camerica Quattro (BF9096)
+// PRG ROM is split into 4 blocks of 64kb.  Within the selected block, 16k switchable + 16k fixed
+// to the block's last bank.
+// 8kb CHR RAM.
+//
+// The Aladdin Deck Enhancer (submapper 1) has the 2 block bits swapped.
+pub struct Quattro {
+    prg_rom: Memory,
+    chr_mem: Memory,
+    mirror_mode: MirrorMode,
+    aladdin: bool,
+    prg_block: u8,
+    prg_bank: u8,
+}
+
+impl Quattro {
+    pub fn new(
+        prg_rom: Memory,
+        chr_mem: Memory,
+        mirror_mode: MirrorMode,
+        submapper: u8,
+    ) -> Quattro {
+        Quattro {
+            prg_rom,
+            chr_mem,
+            mirror_mode,
+            aladdin: submapper == 1,
+            prg_block: 0,
+            prg_bank: 0,
+        }
+    }
+}
+
+impl Mapper for Quattro {
+    fn read_chr(&mut self, address: u16) -> u8 {
+        self.chr_mem.get(address as usize % self.chr_mem.len())
+    }
+
+    fn write_chr(&mut self, address: u16, byte: u8) {
+        let len = self.chr_mem.len();
+        self.chr_mem.put(address as usize % len, byte);
+    }
+
+    fn read_prg(&mut self, address: u16) -> u8 {
+        let bank = if address < 0xC000 { self.prg_bank } else { 3 };
+        let base = ((self.prg_block as usize) << 16) | ((bank as usize) << 14);
+        let rel = (address & 0x3FFF) as usize;
+        self.prg_rom.get((base | rel) % self.prg_rom.len())
+    }
+
+    fn write_prg(&mut self, address: u16, byte: u8) {
+        if address < 0xC000 {
+            let block = (byte >> 3) & 0x03;
+            self.prg_block = if self.aladdin {
+                ((block & 0x01) << 1) | (block >> 1)
+            } else {
+                block
+            };
+        } else {
+            self.prg_bank = byte & 0x03;
+        }
+    }
+
+    fn mirror_mode(&self) -> MirrorMode {
+        self.mirror_mode
+    }
+}
+
+impl<'de> SaveState<'de, MapperState> for Quattro {
+    fn freeze(&mut self) -> MapperState {
+        MapperState::Quattro(QuattroState {
+            prg_block: self.prg_block,
+            prg_bank: self.prg_bank,
+            chr_mem: self.chr_mem.freeze(),
+        })
+    }
+
+    fn hydrate(&mut self, state: MapperState) {
+        match state {
+            MapperState::Quattro(s) => {
+                self.prg_block = s.prg_block;
+                self.prg_bank = s.prg_bank;
+                self.chr_mem.hydrate(s.chr_mem);
+            }
+            _ => panic!("Incompatible mapper state for Quattro mapper: {:?}", state),
+        }
+    }
+}
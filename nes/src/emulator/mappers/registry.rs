@@ -0,0 +1,269 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use crate::emulator::ines::{ROM, RomError};
+use crate::emulator::mappers;
+use crate::emulator::memory::Mapper;
+
+// Builds a mapper for a cartridge.  The ROM gives the header fields as well as the PRG and CHR
+// data, so factories can pick a variant from the submapper or memory sizes.
+pub type MapperFactory = dyn Fn(&ROM) -> Result<Rc<RefCell<dyn Mapper>>, RomError> + Send + Sync;
+
+// Which mapper to build for each mapper number, and optionally submapper.
+//
+// The default registry has all of the mappers in this crate.  Other crates can add their own
+// boards to it, or replace built-in ones, and pass it to ROM::with_mapper_registry.  Mappers which
+// aren't built in should save their state as MapperState::Custom.
+#[derive(Clone)]
+pub struct MapperRegistry {
+    // A submapper of None matches any submapper.
+    factories: HashMap<(u16, Option<u8>), Arc<MapperFactory>>,
+}
+
+impl MapperRegistry {
+    // A registry with no mappers at all.
+    pub fn empty() -> MapperRegistry {
+        MapperRegistry {
+            factories: HashMap::new(),
+        }
+    }
+
+    // A registry with the built-in mappers.
+    pub fn new() -> MapperRegistry {
+        let mut registry = MapperRegistry::empty();
+        registry.register_builtin();
+        registry
+    }
+
+    // Use the factory for a mapper number, whatever the submapper.
+    pub fn register<F>(&mut self, mapper_number: u16, factory: F)
+    where
+        F: Fn(&ROM) -> Result<Rc<RefCell<dyn Mapper>>, RomError> + Send + Sync + 'static,
+    {
+        self.factories
+            .insert((mapper_number, None), Arc::new(factory));
+    }
+
+    // Use the factory for one submapper, in preference to any registered for the whole mapper.
+    pub fn register_submapper<F>(&mut self, mapper_number: u16, submapper: u8, factory: F)
+    where
+        F: Fn(&ROM) -> Result<Rc<RefCell<dyn Mapper>>, RomError> + Send + Sync + 'static,
+    {
+        self.factories
+            .insert((mapper_number, Some(submapper)), Arc::new(factory));
+    }
+
+    pub fn supports(&self, mapper_number: u16, submapper: u8) -> bool {
+        self.factory(mapper_number, submapper).is_some()
+    }
+
+    pub fn create(&self, rom: &ROM) -> Result<Rc<RefCell<dyn Mapper>>, RomError> {
+        let mapper_number = rom.mapper_number();
+        match self.factory(mapper_number, rom.submapper()) {
+            Some(factory) => factory(rom),
+            None => Err(RomError::UnsupportedMapper(mapper_number)),
+        }
+    }
+
+    fn factory(&self, mapper_number: u16, submapper: u8) -> Option<&Arc<MapperFactory>> {
+        self.factories
+            .get(&(mapper_number, Some(submapper)))
+            .or_else(|| self.factories.get(&(mapper_number, None)))
+    }
+
+    fn register_builtin(&mut self) {
+        self.register(0, |rom| {
+            builtin(mappers::NROM::new(
+                rom.prg_rom(),
+                rom.chr_mem(),
+                rom.mirror_mode(),
+            ))
+        });
+        self.register(1, |rom| {
+            builtin(mappers::MMC1::new(
+                rom.prg_rom(),
+                rom.chr_mem(),
+                mappers::MMC1Variant::from_ines(
+                    rom.submapper(),
+                    rom.prg_rom_size_bytes(),
+                    rom.prg_ram_size_bytes(),
+                ),
+            ))
+        });
+        self.register(2, |rom| {
+            builtin(mappers::UXROM::new(
+                rom.prg_rom(),
+                rom.chr_mem(),
+                rom.mirror_mode(),
+            ))
+        });
+        self.register(3, |rom| {
+            builtin(mappers::CNROM::new(
+                rom.prg_rom(),
+                rom.chr_mem(),
+                rom.mirror_mode(),
+            ))
+        });
+        for mapper_number in [4, 76, 88, 95, 118, 119, 206] {
+            self.register(mapper_number, |rom| {
+                builtin(mappers::MMC3::new(
+                    rom.prg_rom(),
+                    rom.chr_mem(),
+                    mappers::MMC3Variant::from_ines(rom.mapper_number(), rom.submapper()),
+                    rom.mirror_mode(),
+                ))
+            });
+        }
+        self.register(5, |rom| {
+            builtin(mappers::MMC5::new(rom.prg_rom(), rom.chr_mem()))
+        });
+        self.register(7, |rom| {
+            builtin(mappers::AXROM::new(rom.prg_rom(), rom.chr_mem()))
+        });
+        self.register(9, |rom| {
+            builtin(mappers::MMC2::new(rom.prg_rom(), rom.chr_mem()))
+        });
+        self.register(10, |rom| {
+            builtin(mappers::MMC4::new(rom.prg_rom(), rom.chr_mem()))
+        });
+        self.register(11, |rom| {
+            builtin(mappers::ColorDreams::new(
+                rom.prg_rom(),
+                rom.chr_mem(),
+                rom.mirror_mode(),
+            ))
+        });
+        for mapper_number in [16, 153, 159] {
+            self.register(mapper_number, |rom| {
+                builtin(mappers::BandaiFCG::new(
+                    rom.prg_rom(),
+                    rom.chr_mem(),
+                    mappers::BandaiFCGVariant::from_ines(rom.mapper_number(), rom.submapper()),
+                ))
+            });
+        }
+        self.register(19, |rom| {
+            builtin(mappers::Namco163::new(rom.prg_rom(), rom.chr_mem()))
+        });
+        for mapper_number in [21, 22, 23, 25] {
+            self.register(mapper_number, |rom| {
+                builtin(mappers::VRC4::new(
+                    rom.prg_rom(),
+                    rom.chr_mem(),
+                    mappers::VRC4Variant::from_ines(rom.mapper_number(), rom.submapper()),
+                ))
+            });
+        }
+        for mapper_number in [24, 26] {
+            self.register(mapper_number, |rom| {
+                builtin(mappers::VRC6::new(
+                    rom.prg_rom(),
+                    rom.chr_mem(),
+                    rom.mapper_number() == 26,
+                ))
+            });
+        }
+        self.register(34, |rom| {
+            builtin(mappers::BNROM::new(
+                rom.prg_rom(),
+                rom.chr_mem(),
+                rom.mirror_mode(),
+                // NINA-001 is the one with CHR ROM to bank.
+                match rom.submapper() {
+                    1 => true,
+                    2 => false,
+                    _ => rom.chr_rom_size_bytes() > 0x2000,
+                },
+            ))
+        });
+        self.register(66, |rom| {
+            builtin(mappers::GXROM::new(
+                rom.prg_rom(),
+                rom.chr_mem(),
+                rom.mirror_mode(),
+            ))
+        });
+        self.register(69, |rom| {
+            builtin(mappers::FME7::new(rom.prg_rom(), rom.chr_mem()))
+        });
+        for mapper_number in [70, 152] {
+            self.register(mapper_number, |rom| {
+                builtin(mappers::Bandai74161::new(
+                    rom.prg_rom(),
+                    rom.chr_mem(),
+                    rom.mirror_mode(),
+                    rom.mapper_number() == 152,
+                ))
+            });
+        }
+        self.register(71, |rom| {
+            builtin(mappers::Camerica::new(
+                rom.prg_rom(),
+                rom.chr_mem(),
+                rom.mirror_mode(),
+            ))
+        });
+        self.register(78, |rom| {
+            builtin(mappers::JalecoJF16::new(
+                rom.prg_rom(),
+                rom.chr_mem(),
+                rom.submapper(),
+            ))
+        });
+        self.register(85, |rom| {
+            builtin(mappers::VRC7::new(
+                rom.prg_rom(),
+                rom.chr_mem(),
+                rom.submapper(),
+            ))
+        });
+        self.register(87, |rom| {
+            builtin(mappers::JalecoJF05::new(
+                rom.prg_rom(),
+                rom.chr_mem(),
+                rom.mirror_mode(),
+            ))
+        });
+        self.register(140, |rom| {
+            builtin(mappers::JalecoJF11::new(
+                rom.prg_rom(),
+                rom.chr_mem(),
+                rom.mirror_mode(),
+            ))
+        });
+        self.register(180, |rom| {
+            builtin(mappers::UNROM180::new(
+                rom.prg_rom(),
+                rom.chr_mem(),
+                rom.mirror_mode(),
+            ))
+        });
+        self.register(184, |rom| {
+            builtin(mappers::Sunsoft1::new(
+                rom.prg_rom(),
+                rom.chr_mem(),
+                rom.mirror_mode(),
+            ))
+        });
+        self.register(232, |rom| {
+            builtin(mappers::Quattro::new(
+                rom.prg_rom(),
+                rom.chr_mem(),
+                rom.mirror_mode(),
+                rom.submapper(),
+            ))
+        });
+    }
+}
+
+impl Default for MapperRegistry {
+    fn default() -> MapperRegistry {
+        MapperRegistry::new()
+    }
+}
+
+fn builtin<M: Mapper + 'static>(mapper: M) -> Result<Rc<RefCell<dyn Mapper>>, RomError> {
+    Ok(Rc::new(RefCell::new(mapper)))
+}
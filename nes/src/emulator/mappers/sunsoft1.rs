@@ -0,0 +1,85 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::emulator::memory::{Mapper, Memory};
+use crate::emulator::ppu::MirrorMode;
+use crate::emulator::state::{MapperState, SaveState, Sunsoft1State};
+
+// iNES Mapper 184: Sunsoft-1
+// Non-switchable PRG ROM, mirrorred to fill the space.
+// 2 switchable 4kb CHR ROM banks.  The upper one always comes from the second half of CHR ROM.
+// The register is at $6000-$7FFF, where there's no SRAM.
+pub struct Sunsoft1 {
+    prg_rom: Memory,
+    chr_mem: Memory,
+    mirror_mode: MirrorMode,
+    chr_banks: [u8; 2],
+}
+
+impl Sunsoft1 {
+    pub fn new(prg_rom: Memory, chr_mem: Memory, mirror_mode: MirrorMode) -> Sunsoft1 {
+        Sunsoft1 {
+            prg_rom,
+            chr_mem,
+            mirror_mode,
+            chr_banks: [0, 4],
+        }
+    }
+
+    fn chr_address(&self, address: u16) -> usize {
+        let bank = self.chr_banks[(address >> 12) as usize & 0x01] as usize;
+        ((bank << 12) | (address & 0x0FFF) as usize) % self.chr_mem.len()
+    }
+}
+
+impl Mapper for Sunsoft1 {
+    fn read_chr(&mut self, address: u16) -> u8 {
+        self.chr_mem.get(self.chr_address(address))
+    }
+
+    fn write_chr(&mut self, address: u16, byte: u8) {
+        let chr_address = self.chr_address(address);
+        self.chr_mem.put(chr_address, byte);
+    }
+
+    fn read_prg(&mut self, address: u16) -> u8 {
+        self.prg_rom
+            .get((address & 0x7FFF) as usize % self.prg_rom.len())
+    }
+
+    fn write_prg(&mut self, _address: u16, _byte: u8) {}
+
+    fn write_expansion(&mut self, address: u16, byte: u8) {
+        if address >= 0x6000 {
+            self.chr_banks[0] = byte & 0x07;
+            self.chr_banks[1] = ((byte >> 4) & 0x07) | 0x04;
+        }
+    }
+
+    fn attach_prg_ram(&mut self, _prg_ram: Rc<RefCell<Memory>>) -> bool {
+        true
+    }
+
+    fn mirror_mode(&self) -> MirrorMode {
+        self.mirror_mode
+    }
+}
+
+impl<'de> SaveState<'de, MapperState> for Sunsoft1 {
+    fn freeze(&mut self) -> MapperState {
+        MapperState::Sunsoft1(Sunsoft1State {
+            chr_banks: self.chr_banks.to_vec(),
+            chr_mem: self.chr_mem.freeze(),
+        })
+    }
+
+    fn hydrate(&mut self, state: MapperState) {
+        match state {
+            MapperState::Sunsoft1(s) => {
+                self.chr_banks.copy_from_slice(&s.chr_banks);
+                self.chr_mem.hydrate(s.chr_mem);
+            }
+            _ => panic!("Incompatible mapper state for Sunsoft1 mapper: {:?}", state),
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use crate::emulator::memory::{Mapper, Memory};
+use crate::emulator::ppu::MirrorMode;
+use crate::emulator::state::{MapperState, SaveState, UNROM180State};
+
+// iNES Mapper 180: UNROM with a 74HC08, used by Crazy Climber
+// 16k fixed + 16k switchable PRG ROM.  The other way round to UxROM, so the first bank is fixed.
+// 8kb CHR RAM.
+pub struct UNROM180 {
+    prg_rom: Memory,
+    chr_mem: Memory,
+    mirror_mode: MirrorMode,
+    prg_bank: u8,
+}
+
+impl UNROM180 {
+    pub fn new(prg_rom: Memory, chr_mem: Memory, mirror_mode: MirrorMode) -> UNROM180 {
+        UNROM180 {
+            prg_rom,
+            chr_mem,
+            mirror_mode,
+            prg_bank: 0,
+        }
+    }
+}
+
+impl Mapper for UNROM180 {
+    fn read_chr(&mut self, address: u16) -> u8 {
+        self.chr_mem.get(address as usize % self.chr_mem.len())
+    }
+
+    fn write_chr(&mut self, address: u16, byte: u8) {
+        let len = self.chr_mem.len();
+        self.chr_mem.put(address as usize % len, byte);
+    }
+
+    fn read_prg(&mut self, address: u16) -> u8 {
+        let base = if address & 0x4000 == 0 {
+            0
+        } else {
+            (self.prg_bank as usize) << 14
+        };
+        let rel = (address & 0x3FFF) as usize;
+        self.prg_rom.get((base | rel) % self.prg_rom.len())
+    }
+
+    fn write_prg(&mut self, _address: u16, byte: u8) {
+        self.prg_bank = byte & 0x07;
+    }
+
+    fn mirror_mode(&self) -> MirrorMode {
+        self.mirror_mode
+    }
+}
+
+impl<'de> SaveState<'de, MapperState> for UNROM180 {
+    fn freeze(&mut self) -> MapperState {
+        MapperState::UNROM180(UNROM180State {
+            prg_bank: self.prg_bank,
+            chr_mem: self.chr_mem.freeze(),
+        })
+    }
+
+    fn hydrate(&mut self, state: MapperState) {
+        match state {
+            MapperState::UNROM180(s) => {
+                self.prg_bank = s.prg_bank;
+                self.chr_mem.hydrate(s.chr_mem);
+            }
+            _ => panic!("Incompatible mapper state for UNROM180 mapper: {:?}", state),
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use crate::emulator::mappers::vrc_irq::VrcIrq;
+use crate::emulator::memory::{Mapper, Memory};
+use crate::emulator::ppu::MirrorMode;
+use crate::emulator::state::{MapperState, SaveState, VRC4State};
+
+// iNES Mappers 21, 22, 23 and 25: Konami VRC2 and VRC4
+// 2x 8kb switchable PRG ROM banks, plus 2 fixed to the second last and last banks.  VRC4 can swap
+// the first switchable bank with the second last bank.
+// 8x 1kb switchable CHR ROM banks.
+// 8kb PRG RAM, which is left to the CPU at $6000-$7FFF.
+// VRC4 adds single-screen mirroring and the VRC IRQ counter.
+//
+// The boards wire different CPU address lines to the chip's 2 register select lines, which is
+// what the variants are about.  VRC2a also drops the lowest bit of the CHR banks.
+pub struct VRC4 {
+    prg_rom: Memory,
+    chr_mem: Memory,
+    variant: Variant,
+
+    prg_banks: [u8; 2],
+    prg_swap: bool,
+    chr_banks: [u16; 8],
+    mirror_mode: MirrorMode,
+    irq: VrcIrq,
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Variant {
+    VRC2a,
+    VRC2b,
+    VRC2c,
+    VRC4a,
+    VRC4b,
+    VRC4c,
+    VRC4d,
+    VRC4e,
+    VRC4f,
+    // iNES 1.0 headers don't say which board is used, so the possible wirings for each mapper
+    // number are combined.  No game relies on the lines that aren't wired up.
+    VRC4_21,
+    VRC4_23,
+    VRC4_25,
+}
+
+impl Variant {
+    pub fn from_ines(mapper_number: u16, submapper: u8) -> Variant {
+        match (mapper_number, submapper) {
+            (21, 1) => Variant::VRC4a,
+            (21, 2) => Variant::VRC4c,
+            (21, _) => Variant::VRC4_21,
+            (22, _) => Variant::VRC2a,
+            (23, 1) => Variant::VRC4f,
+            (23, 2) => Variant::VRC4e,
+            (23, 3) => Variant::VRC2b,
+            (23, _) => Variant::VRC4_23,
+            (25, 1) => Variant::VRC4b,
+            (25, 2) => Variant::VRC4d,
+            (25, 3) => Variant::VRC2c,
+            (_, _) => Variant::VRC4_25,
+        }
+    }
+
+    // The CPU address lines connected to the chip's A0 and A1.
+    fn register_lines(self) -> (u16, u16) {
+        match self {
+            Variant::VRC2a => (0x02, 0x01),
+            Variant::VRC2b => (0x01, 0x02),
+            Variant::VRC2c => (0x02, 0x01),
+            Variant::VRC4a => (0x02, 0x04),
+            Variant::VRC4b => (0x02, 0x01),
+            Variant::VRC4c => (0x40, 0x80),
+            Variant::VRC4d => (0x08, 0x04),
+            Variant::VRC4e => (0x04, 0x08),
+            Variant::VRC4f => (0x01, 0x02),
+            Variant::VRC4_21 => (0x42, 0x84),
+            Variant::VRC4_23 => (0x05, 0x0A),
+            Variant::VRC4_25 => (0x0A, 0x05),
+        }
+    }
+
+    fn is_vrc2(self) -> bool {
+        matches!(self, Variant::VRC2a | Variant::VRC2b | Variant::VRC2c)
+    }
+}
+
+impl VRC4 {
+    pub fn new(prg_rom: Memory, chr_mem: Memory, variant: Variant) -> VRC4 {
+        VRC4 {
+            prg_rom,
+            chr_mem,
+            variant,
+            prg_banks: [0; 2],
+            prg_swap: false,
+            chr_banks: [0; 8],
+            mirror_mode: MirrorMode::Vertical,
+            irq: VrcIrq::new(),
+        }
+    }
+
+    // Translates the address into $X000-$X003, according to the board's wiring.
+    fn register(&self, address: u16) -> u16 {
+        let (a0, a1) = self.variant.register_lines();
+        let mut register = address & 0xF000;
+        if address & a0 != 0 {
+            register |= 0x1;
+        }
+        if address & a1 != 0 {
+            register |= 0x2;
+        }
+        register
+    }
+
+    fn chr_address(&self, address: u16) -> usize {
+        let mut bank = self.chr_banks[(address >> 10) as usize] as usize;
+        if self.variant == Variant::VRC2a {
+            bank >>= 1;
+        }
+        ((bank << 10) | (address & 0x03FF) as usize) % self.chr_mem.len()
+    }
+
+    fn write_chr_bank(&mut self, register: u16, byte: u8) {
+        let ix = (((register - 0xB000) >> 12) * 2 + ((register & 0x2) >> 1)) as usize;
+        self.chr_banks[ix] = if register & 0x1 == 0 {
+            (self.chr_banks[ix] & 0x1F0) | (byte & 0x0F) as u16
+        } else {
+            (self.chr_banks[ix] & 0x00F) | (((byte & 0x1F) as u16) << 4)
+        };
+    }
+}
+
+impl Mapper for VRC4 {
+    fn read_chr(&mut self, address: u16) -> u8 {
+        let chr_address = self.chr_address(address);
+        self.chr_mem.get(chr_address)
+    }
+
+    fn write_chr(&mut self, address: u16, byte: u8) {
+        let chr_address = self.chr_address(address);
+        self.chr_mem.put(chr_address, byte);
+    }
+
+    fn read_prg(&mut self, address: u16) -> u8 {
+        let num_banks = self.prg_rom.len() / 0x2000;
+        let bank = match (address, self.prg_swap) {
+            (0x8000..=0x9FFF, false) | (0xC000..=0xDFFF, true) => self.prg_banks[0] as usize,
+            (0x8000..=0x9FFF, true) | (0xC000..=0xDFFF, false) => num_banks - 2,
+            (0xA000..=0xBFFF, _) => self.prg_banks[1] as usize,
+            _ => num_banks - 1,
+        };
+        let offset = (address & 0x1FFF) as usize;
+        self.prg_rom
+            .get((bank * 0x2000 + offset) % self.prg_rom.len())
+    }
+
+    fn write_prg(&mut self, address: u16, byte: u8) {
+        let vrc2 = self.variant.is_vrc2();
+        match self.register(address) {
+            0x8000..=0x8003 => self.prg_banks[0] = byte & 0x1F,
+            0x9000..=0x9003 if vrc2 => {
+                self.mirror_mode = match byte & 0x1 {
+                    0 => MirrorMode::Vertical,
+                    _ => MirrorMode::Horizontal,
+                }
+            }
+            0x9000..=0x9001 => {
+                self.mirror_mode = match byte & 0x3 {
+                    0 => MirrorMode::Vertical,
+                    1 => MirrorMode::Horizontal,
+                    2 => MirrorMode::SingleLower,
+                    _ => MirrorMode::SingleUpper,
+                }
+            }
+            0x9002..=0x9003 => self.prg_swap = byte & 0x2 != 0,
+            0xA000..=0xA003 => self.prg_banks[1] = byte & 0x1F,
+            register @ 0xB000..=0xEFFF => self.write_chr_bank(register, byte),
+            0xF000 if !vrc2 => self.irq.write_latch_low(byte),
+            0xF001 if !vrc2 => self.irq.write_latch_high(byte),
+            0xF002 if !vrc2 => self.irq.write_control(byte),
+            0xF003 if !vrc2 => self.irq.acknowledge(),
+            _ => (),
+        }
+    }
+
+    fn mirror_mode(&self) -> MirrorMode {
+        self.mirror_mode
+    }
+
+    fn irq_triggered(&self) -> bool {
+        self.irq.pending()
+    }
+
+    fn clock_cpu(&mut self, cycles: u32) {
+        self.irq.clock(cycles);
+    }
+}
+
+impl<'de> SaveState<'de, MapperState> for VRC4 {
+    fn freeze(&mut self) -> MapperState {
+        MapperState::VRC4(VRC4State {
+            prg_banks: self.prg_banks.to_vec(),
+            prg_swap: self.prg_swap,
+            chr_banks: self.chr_banks.to_vec(),
+            mirror_mode: self.mirror_mode,
+            irq: self.irq.freeze(),
+            chr_mem: self.chr_mem.freeze(),
+        })
+    }
+
+    fn hydrate(&mut self, state: MapperState) {
+        match state {
+            MapperState::VRC4(s) => {
+                self.prg_banks.copy_from_slice(&s.prg_banks);
+                self.prg_swap = s.prg_swap;
+                self.chr_banks.copy_from_slice(&s.chr_banks);
+                self.mirror_mode = s.mirror_mode;
+                self.irq.hydrate(s.irq);
+                self.chr_mem.hydrate(s.chr_mem);
+            }
+            _ => panic!("Incompatible mapper state for VRC4 mapper: {:?}", state),
+        }
+    }
+}
@@ -0,0 +1,393 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::emulator::mappers::vrc_irq::VrcIrq;
+use crate::emulator::memory::{Mapper, Memory};
+use crate::emulator::ppu::MirrorMode;
+use crate::emulator::state::{MapperState, SaveState, VRC6PulseState, VRC6SawState, VRC6State};
+
+// iNES Mappers 24 and 26: Konami VRC6
+// 1x 16kb switchable PRG ROM bank at $8000, 1x 8kb at $C000, and the last 8kb fixed at $E000.
+// 8x 1kb switchable CHR ROM banks.
+// 8kb PRG RAM, enabled by bit 7 of $B003.
+// The VRC IRQ counter, and 2 pulse channels plus a sawtooth channel of expansion audio.
+//
+// Mapper 26 swaps the A0 and A1 register select lines.
+//
+// Only the CHR banking mode used by the commercial games is implemented.
+pub struct VRC6 {
+    prg_rom: Memory,
+    chr_mem: Memory,
+    prg_ram: Option<Rc<RefCell<Memory>>>,
+    swap_register_lines: bool,
+
+    prg_16k_bank: u8,
+    prg_8k_bank: u8,
+    chr_banks: [u8; 8],
+    mirror_mode: MirrorMode,
+    prg_ram_enabled: bool,
+    irq: VrcIrq,
+
+    pulse_1: Pulse,
+    pulse_2: Pulse,
+    saw: Saw,
+    // $9003.
+    halt_audio: bool,
+    frequency_shift: u8,
+}
+
+// The channels' timers all count down 12-bit periods, clocked every CPU cycle.
+struct Pulse {
+    volume: u8,
+    duty: u8,
+    ignore_duty: bool,
+    period: u16,
+    enabled: bool,
+    counter: u16,
+    step: u8,
+}
+
+struct Saw {
+    rate: u8,
+    period: u16,
+    enabled: bool,
+    counter: u16,
+    step: u8,
+    accumulator: u8,
+}
+
+impl Pulse {
+    fn new() -> Pulse {
+        Pulse {
+            volume: 0,
+            duty: 0,
+            ignore_duty: false,
+            period: 0,
+            enabled: false,
+            counter: 0,
+            step: 15,
+        }
+    }
+
+    fn write(&mut self, register: u16, byte: u8) {
+        match register {
+            0 => {
+                self.ignore_duty = byte & 0x80 != 0;
+                self.duty = (byte >> 4) & 0x07;
+                self.volume = byte & 0x0F;
+            }
+            1 => self.period = (self.period & 0x0F00) | byte as u16,
+            _ => {
+                self.period = (self.period & 0x00FF) | (((byte & 0x0F) as u16) << 8);
+                self.enabled = byte & 0x80 != 0;
+                if !self.enabled {
+                    self.step = 15;
+                }
+            }
+        }
+    }
+
+    fn clock(&mut self, frequency_shift: u8) {
+        if !self.enabled {
+            return;
+        }
+
+        if self.counter == 0 {
+            self.counter = self.period >> frequency_shift;
+            self.step = if self.step == 0 { 15 } else { self.step - 1 };
+        } else {
+            self.counter -= 1;
+        }
+    }
+
+    fn volume(&self) -> u8 {
+        if self.enabled && (self.ignore_duty || self.step <= self.duty) {
+            self.volume
+        } else {
+            0
+        }
+    }
+}
+
+impl Saw {
+    fn new() -> Saw {
+        Saw {
+            rate: 0,
+            period: 0,
+            enabled: false,
+            counter: 0,
+            step: 0,
+            accumulator: 0,
+        }
+    }
+
+    fn write(&mut self, register: u16, byte: u8) {
+        match register {
+            0 => self.rate = byte & 0x3F,
+            1 => self.period = (self.period & 0x0F00) | byte as u16,
+            _ => {
+                self.period = (self.period & 0x00FF) | (((byte & 0x0F) as u16) << 8);
+                self.enabled = byte & 0x80 != 0;
+                if !self.enabled {
+                    self.step = 0;
+                    self.accumulator = 0;
+                }
+            }
+        }
+    }
+
+    // The accumulator is added to on every other clock, and reset on every 14th.
+    fn clock(&mut self, frequency_shift: u8) {
+        if !self.enabled {
+            return;
+        }
+
+        if self.counter != 0 {
+            self.counter -= 1;
+            return;
+        }
+
+        self.counter = self.period >> frequency_shift;
+        self.step += 1;
+        if self.step == 14 {
+            self.step = 0;
+            self.accumulator = 0;
+        } else if self.step.is_multiple_of(2) {
+            self.accumulator = self.accumulator.wrapping_add(self.rate);
+        }
+    }
+
+    fn volume(&self) -> u8 {
+        self.accumulator >> 3
+    }
+}
+
+impl VRC6 {
+    pub fn new(prg_rom: Memory, chr_mem: Memory, swap_register_lines: bool) -> VRC6 {
+        VRC6 {
+            prg_rom,
+            chr_mem,
+            prg_ram: None,
+            swap_register_lines,
+            prg_16k_bank: 0,
+            prg_8k_bank: 0,
+            chr_banks: [0; 8],
+            mirror_mode: MirrorMode::Vertical,
+            prg_ram_enabled: false,
+            irq: VrcIrq::new(),
+            pulse_1: Pulse::new(),
+            pulse_2: Pulse::new(),
+            saw: Saw::new(),
+            halt_audio: false,
+            frequency_shift: 0,
+        }
+    }
+
+    fn chr_address(&self, address: u16) -> usize {
+        let bank = self.chr_banks[(address >> 10) as usize] as usize;
+        ((bank << 10) | (address & 0x03FF) as usize) % self.chr_mem.len()
+    }
+}
+
+impl Mapper for VRC6 {
+    fn read_chr(&mut self, address: u16) -> u8 {
+        let chr_address = self.chr_address(address);
+        self.chr_mem.get(chr_address)
+    }
+
+    fn write_chr(&mut self, address: u16, byte: u8) {
+        let chr_address = self.chr_address(address);
+        self.chr_mem.put(chr_address, byte);
+    }
+
+    fn read_prg(&mut self, address: u16) -> u8 {
+        let offset = match address {
+            0x8000..=0xBFFF => self.prg_16k_bank as usize * 0x4000 + (address & 0x3FFF) as usize,
+            0xC000..=0xDFFF => self.prg_8k_bank as usize * 0x2000 + (address & 0x1FFF) as usize,
+            _ => self.prg_rom.len() - 0x2000 + (address & 0x1FFF) as usize,
+        };
+        self.prg_rom.get(offset % self.prg_rom.len())
+    }
+
+    fn write_prg(&mut self, address: u16, byte: u8) {
+        let register = if self.swap_register_lines {
+            ((address & 0x1) << 1) | ((address & 0x2) >> 1)
+        } else {
+            address & 0x3
+        };
+
+        match (address & 0xF000, register) {
+            (0x8000, _) => self.prg_16k_bank = byte & 0x0F,
+            (0x9000, 3) => {
+                self.halt_audio = byte & 0x01 != 0;
+                self.frequency_shift = if byte & 0x04 != 0 {
+                    8
+                } else if byte & 0x02 != 0 {
+                    4
+                } else {
+                    0
+                };
+            }
+            (0x9000, _) => self.pulse_1.write(register, byte),
+            (0xA000, 3) => (),
+            (0xA000, _) => self.pulse_2.write(register, byte),
+            (0xB000, 3) => {
+                self.mirror_mode = match (byte >> 2) & 0x3 {
+                    0 => MirrorMode::Vertical,
+                    1 => MirrorMode::Horizontal,
+                    2 => MirrorMode::SingleLower,
+                    _ => MirrorMode::SingleUpper,
+                };
+                self.prg_ram_enabled = byte & 0x80 != 0;
+            }
+            (0xB000, _) => self.saw.write(register, byte),
+            (0xC000, _) => self.prg_8k_bank = byte & 0x1F,
+            (0xD000, _) => self.chr_banks[register as usize] = byte,
+            (0xE000, _) => self.chr_banks[4 + register as usize] = byte,
+            (0xF000, 0) => self.irq.write_latch(byte),
+            (0xF000, 1) => self.irq.write_control(byte),
+            (0xF000, 2) => self.irq.acknowledge(),
+            _ => (),
+        }
+    }
+
+    fn read_expansion(&mut self, address: u16) -> Option<u8> {
+        if address < 0x6000 || !self.prg_ram_enabled {
+            return None;
+        }
+        let prg_ram = self.prg_ram.as_ref()?.borrow();
+        match prg_ram.len() {
+            0 => None,
+            len => Some(prg_ram.get((address & 0x1FFF) as usize % len)),
+        }
+    }
+
+    fn write_expansion(&mut self, address: u16, byte: u8) {
+        if address < 0x6000 || !self.prg_ram_enabled {
+            return;
+        }
+        if let Some(ref prg_ram) = self.prg_ram {
+            let mut prg_ram = prg_ram.borrow_mut();
+            let len = prg_ram.len();
+            if len > 0 {
+                prg_ram.put((address & 0x1FFF) as usize % len, byte);
+            }
+        }
+    }
+
+    fn attach_prg_ram(&mut self, prg_ram: Rc<RefCell<Memory>>) -> bool {
+        self.prg_ram = Some(prg_ram);
+        true
+    }
+
+    fn mirror_mode(&self) -> MirrorMode {
+        self.mirror_mode
+    }
+
+    fn irq_triggered(&self) -> bool {
+        self.irq.pending()
+    }
+
+    fn clock_cpu(&mut self, cycles: u32) {
+        self.irq.clock(cycles);
+    }
+
+    fn clock_audio(&mut self) -> f32 {
+        // The channels are clocked by the CPU, twice per APU cycle.
+        if !self.halt_audio {
+            for _ in 0..2 {
+                self.pulse_1.clock(self.frequency_shift);
+                self.pulse_2.clock(self.frequency_shift);
+                self.saw.clock(self.frequency_shift);
+            }
+        }
+
+        let output = self.pulse_1.volume() + self.pulse_2.volume() + self.saw.volume();
+        0.00752 * output as f32
+    }
+}
+
+impl<'de> SaveState<'de, VRC6PulseState> for Pulse {
+    fn freeze(&mut self) -> VRC6PulseState {
+        VRC6PulseState {
+            volume: self.volume,
+            duty: self.duty,
+            ignore_duty: self.ignore_duty,
+            period: self.period,
+            enabled: self.enabled,
+            counter: self.counter,
+            step: self.step,
+        }
+    }
+
+    fn hydrate(&mut self, state: VRC6PulseState) {
+        self.volume = state.volume;
+        self.duty = state.duty;
+        self.ignore_duty = state.ignore_duty;
+        self.period = state.period;
+        self.enabled = state.enabled;
+        self.counter = state.counter;
+        self.step = state.step;
+    }
+}
+
+impl<'de> SaveState<'de, VRC6SawState> for Saw {
+    fn freeze(&mut self) -> VRC6SawState {
+        VRC6SawState {
+            rate: self.rate,
+            period: self.period,
+            enabled: self.enabled,
+            counter: self.counter,
+            step: self.step,
+            accumulator: self.accumulator,
+        }
+    }
+
+    fn hydrate(&mut self, state: VRC6SawState) {
+        self.rate = state.rate;
+        self.period = state.period;
+        self.enabled = state.enabled;
+        self.counter = state.counter;
+        self.step = state.step;
+        self.accumulator = state.accumulator;
+    }
+}
+
+impl<'de> SaveState<'de, MapperState> for VRC6 {
+    fn freeze(&mut self) -> MapperState {
+        MapperState::VRC6(VRC6State {
+            prg_16k_bank: self.prg_16k_bank,
+            prg_8k_bank: self.prg_8k_bank,
+            chr_banks: self.chr_banks.to_vec(),
+            mirror_mode: self.mirror_mode,
+            prg_ram_enabled: self.prg_ram_enabled,
+            irq: self.irq.freeze(),
+            pulse_1: self.pulse_1.freeze(),
+            pulse_2: self.pulse_2.freeze(),
+            saw: self.saw.freeze(),
+            halt_audio: self.halt_audio,
+            frequency_shift: self.frequency_shift,
+            chr_mem: self.chr_mem.freeze(),
+        })
+    }
+
+    fn hydrate(&mut self, state: MapperState) {
+        match state {
+            MapperState::VRC6(s) => {
+                self.prg_16k_bank = s.prg_16k_bank;
+                self.prg_8k_bank = s.prg_8k_bank;
+                self.chr_banks.copy_from_slice(&s.chr_banks);
+                self.mirror_mode = s.mirror_mode;
+                self.prg_ram_enabled = s.prg_ram_enabled;
+                self.irq.hydrate(s.irq);
+                self.pulse_1.hydrate(s.pulse_1);
+                self.pulse_2.hydrate(s.pulse_2);
+                self.saw.hydrate(s.saw);
+                self.halt_audio = s.halt_audio;
+                self.frequency_shift = s.frequency_shift;
+                self.chr_mem.hydrate(s.chr_mem);
+            }
+            _ => panic!("Incompatible mapper state for VRC6 mapper: {:?}", state),
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::emulator::apu::{Opll, VRC7_PATCHES};
+use crate::emulator::mappers::vrc_irq::VrcIrq;
+use crate::emulator::memory::{Mapper, Memory};
+use crate::emulator::ppu::MirrorMode;
+use crate::emulator::state::{MapperState, SaveState, VRC7State};
+
+// iNES Mapper 85: Konami VRC7
+// 3x 8kb switchable PRG ROM banks, and the last 8kb fixed at $E000.
+// 8x 1kb switchable CHR banks.
+// 8kb PRG RAM, enabled by bit 7 of $E000.
+// The VRC IRQ counter, and a 6 channel FM synthesizer derived from the YM2413.
+//
+// VRC7a selects the second register at each address with A4, and VRC7b with A3.  Submapper 0
+// doesn't say which, so accepts both.
+pub struct VRC7 {
+    prg_rom: Memory,
+    chr_mem: Memory,
+    prg_ram: Option<Rc<RefCell<Memory>>>,
+    register_line: u16,
+
+    prg_banks: [u8; 3],
+    chr_banks: [u8; 8],
+    mirror_mode: MirrorMode,
+    prg_ram_enabled: bool,
+    irq: VrcIrq,
+
+    // Bit 6 of $E000 holds the synthesizer in reset.
+    audio_silenced: bool,
+    audio_register: u8,
+    opll: Opll,
+    // The synthesizer makes a sample every 36 CPU cycles.  In between, we interpolate from the
+    // previous one to the latest.
+    audio_cycles: u8,
+    previous_sample: f32,
+    latest_sample: f32,
+}
+
+const APU_CYCLES_PER_SAMPLE: u8 = 18;
+const NUM_CHANNELS: usize = 6;
+
+// Each channel at full volume is around as loud as an APU pulse channel.
+const AUDIO_SCALE: f32 = 0.1;
+
+impl VRC7 {
+    pub fn new(prg_rom: Memory, chr_mem: Memory, submapper: u8) -> VRC7 {
+        VRC7 {
+            prg_rom,
+            chr_mem,
+            prg_ram: None,
+            register_line: match submapper {
+                1 => 0x08,
+                2 => 0x10,
+                _ => 0x18,
+            },
+            prg_banks: [0; 3],
+            chr_banks: [0; 8],
+            mirror_mode: MirrorMode::Vertical,
+            prg_ram_enabled: false,
+            irq: VrcIrq::new(),
+            audio_silenced: false,
+            audio_register: 0,
+            opll: Opll::new(&VRC7_PATCHES, NUM_CHANNELS),
+            audio_cycles: 0,
+            previous_sample: 0.0,
+            latest_sample: 0.0,
+        }
+    }
+
+    fn chr_address(&self, address: u16) -> usize {
+        let bank = self.chr_banks[(address >> 10) as usize] as usize;
+        ((bank << 10) | (address & 0x03FF) as usize) % self.chr_mem.len()
+    }
+}
+
+impl Mapper for VRC7 {
+    fn read_chr(&mut self, address: u16) -> u8 {
+        let chr_address = self.chr_address(address);
+        self.chr_mem.get(chr_address)
+    }
+
+    fn write_chr(&mut self, address: u16, byte: u8) {
+        let chr_address = self.chr_address(address);
+        self.chr_mem.put(chr_address, byte);
+    }
+
+    fn read_prg(&mut self, address: u16) -> u8 {
+        let offset = (address & 0x1FFF) as usize;
+        let base = match address {
+            0x8000..=0x9FFF => self.prg_banks[0] as usize * 0x2000,
+            0xA000..=0xBFFF => self.prg_banks[1] as usize * 0x2000,
+            0xC000..=0xDFFF => self.prg_banks[2] as usize * 0x2000,
+            _ => self.prg_rom.len() - 0x2000,
+        };
+        self.prg_rom.get((base + offset) % self.prg_rom.len())
+    }
+
+    fn write_prg(&mut self, address: u16, byte: u8) {
+        // The audio ports are at the same addresses on both boards.
+        match address & 0xF030 {
+            0x9010 => {
+                self.audio_register = byte;
+                return;
+            }
+            0x9030 => {
+                self.opll.write(self.audio_register, byte);
+                return;
+            }
+            _ => (),
+        }
+
+        let second = address & self.register_line != 0;
+        match (address & 0xF000, second) {
+            (0x8000, false) => self.prg_banks[0] = byte & 0x3F,
+            (0x8000, true) => self.prg_banks[1] = byte & 0x3F,
+            (0x9000, false) => self.prg_banks[2] = byte & 0x3F,
+            (bank @ 0xA000..=0xD000, second) => {
+                let ix = ((bank - 0xA000) >> 11) as usize + second as usize;
+                self.chr_banks[ix] = byte;
+            }
+            (0xE000, false) => {
+                self.mirror_mode = match byte & 0x3 {
+                    0 => MirrorMode::Vertical,
+                    1 => MirrorMode::Horizontal,
+                    2 => MirrorMode::SingleLower,
+                    _ => MirrorMode::SingleUpper,
+                };
+                self.audio_silenced = byte & 0x40 != 0;
+                self.prg_ram_enabled = byte & 0x80 != 0;
+            }
+            (0xE000, true) => self.irq.write_latch(byte),
+            (0xF000, false) => self.irq.write_control(byte),
+            (0xF000, true) => self.irq.acknowledge(),
+            _ => (),
+        }
+    }
+
+    fn read_expansion(&mut self, address: u16) -> Option<u8> {
+        if address < 0x6000 || !self.prg_ram_enabled {
+            return None;
+        }
+        let prg_ram = self.prg_ram.as_ref()?.borrow();
+        match prg_ram.len() {
+            0 => None,
+            len => Some(prg_ram.get((address & 0x1FFF) as usize % len)),
+        }
+    }
+
+    fn write_expansion(&mut self, address: u16, byte: u8) {
+        if address < 0x6000 || !self.prg_ram_enabled {
+            return;
+        }
+        if let Some(ref prg_ram) = self.prg_ram {
+            let mut prg_ram = prg_ram.borrow_mut();
+            let len = prg_ram.len();
+            if len > 0 {
+                prg_ram.put((address & 0x1FFF) as usize % len, byte);
+            }
+        }
+    }
+
+    fn attach_prg_ram(&mut self, prg_ram: Rc<RefCell<Memory>>) -> bool {
+        self.prg_ram = Some(prg_ram);
+        true
+    }
+
+    fn mirror_mode(&self) -> MirrorMode {
+        self.mirror_mode
+    }
+
+    fn irq_triggered(&self) -> bool {
+        self.irq.pending()
+    }
+
+    fn clock_cpu(&mut self, cycles: u32) {
+        self.irq.clock(cycles);
+    }
+
+    fn clock_audio(&mut self) -> f32 {
+        if self.audio_silenced {
+            return 0.0;
+        }
+
+        self.audio_cycles += 1;
+        if self.audio_cycles == APU_CYCLES_PER_SAMPLE {
+            self.audio_cycles = 0;
+            self.previous_sample = self.latest_sample;
+            self.latest_sample = self.opll.sample();
+        }
+
+        let progress = self.audio_cycles as f32 / APU_CYCLES_PER_SAMPLE as f32;
+        let sample = self.previous_sample + (self.latest_sample - self.previous_sample) * progress;
+        sample * AUDIO_SCALE
+    }
+}
+
+impl<'de> SaveState<'de, MapperState> for VRC7 {
+    fn freeze(&mut self) -> MapperState {
+        MapperState::VRC7(VRC7State {
+            prg_banks: self.prg_banks.to_vec(),
+            chr_banks: self.chr_banks.to_vec(),
+            mirror_mode: self.mirror_mode,
+            prg_ram_enabled: self.prg_ram_enabled,
+            irq: self.irq.freeze(),
+            audio_silenced: self.audio_silenced,
+            audio_register: self.audio_register,
+            opll: self.opll.freeze(),
+            audio_cycles: self.audio_cycles,
+            previous_sample: self.previous_sample,
+            latest_sample: self.latest_sample,
+            chr_mem: self.chr_mem.freeze(),
+        })
+    }
+
+    fn hydrate(&mut self, state: MapperState) {
+        match state {
+            MapperState::VRC7(s) => {
+                self.prg_banks.copy_from_slice(&s.prg_banks);
+                self.chr_banks.copy_from_slice(&s.chr_banks);
+                self.mirror_mode = s.mirror_mode;
+                self.prg_ram_enabled = s.prg_ram_enabled;
+                self.irq.hydrate(s.irq);
+                self.audio_silenced = s.audio_silenced;
+                self.audio_register = s.audio_register;
+                self.opll.hydrate(s.opll);
+                self.audio_cycles = s.audio_cycles;
+                self.previous_sample = s.previous_sample;
+                self.latest_sample = s.latest_sample;
+                self.chr_mem.hydrate(s.chr_mem);
+            }
+            _ => panic!("Incompatible mapper state for VRC7 mapper: {:?}", state),
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use crate::emulator::state::{SaveState, VrcIrqState};
+
+// The IRQ counter shared by Konami's VRC4, VRC6 and VRC7.
+//
+// An 8-bit counter counts up from the latch, and fires when it overflows.  In cycle mode it's
+// clocked every CPU cycle.  In scanline mode a prescaler clocks it every 341/3 CPU cycles, i.e.
+// once per scanline, without having to watch the PPU.
+pub struct VrcIrq {
+    latch: u8,
+    counter: u8,
+    prescaler: i16,
+    enabled: bool,
+    enable_after_ack: bool,
+    cycle_mode: bool,
+    pending: bool,
+}
+
+const PRESCALER_PERIOD: i16 = 341;
+
+impl VrcIrq {
+    pub fn new() -> VrcIrq {
+        VrcIrq {
+            latch: 0,
+            counter: 0,
+            prescaler: PRESCALER_PERIOD,
+            enabled: false,
+            enable_after_ack: false,
+            cycle_mode: false,
+            pending: false,
+        }
+    }
+
+    pub fn pending(&self) -> bool {
+        self.pending
+    }
+
+    pub fn write_latch(&mut self, byte: u8) {
+        self.latch = byte;
+    }
+
+    // VRC4 takes the latch a nibble at a time.
+    pub fn write_latch_low(&mut self, byte: u8) {
+        self.latch = (self.latch & 0xF0) | (byte & 0x0F);
+    }
+
+    pub fn write_latch_high(&mut self, byte: u8) {
+        self.latch = (self.latch & 0x0F) | (byte << 4);
+    }
+
+    pub fn write_control(&mut self, byte: u8) {
+        self.enable_after_ack = byte & 0x01 != 0;
+        self.enabled = byte & 0x02 != 0;
+        self.cycle_mode = byte & 0x04 != 0;
+        self.pending = false;
+        if self.enabled {
+            self.counter = self.latch;
+            self.prescaler = PRESCALER_PERIOD;
+        }
+    }
+
+    pub fn acknowledge(&mut self) {
+        self.pending = false;
+        self.enabled = self.enable_after_ack;
+    }
+
+    pub fn clock(&mut self, cpu_cycles: u32) {
+        if !self.enabled {
+            return;
+        }
+
+        for _ in 0..cpu_cycles {
+            if self.cycle_mode {
+                self.clock_counter();
+            } else {
+                self.prescaler -= 3;
+                if self.prescaler <= 0 {
+                    self.prescaler += PRESCALER_PERIOD;
+                    self.clock_counter();
+                }
+            }
+        }
+    }
+
+    fn clock_counter(&mut self) {
+        if self.counter == 0xFF {
+            self.counter = self.latch;
+            self.pending = true;
+        } else {
+            self.counter += 1;
+        }
+    }
+}
+
+impl<'de> SaveState<'de, VrcIrqState> for VrcIrq {
+    fn freeze(&mut self) -> VrcIrqState {
+        VrcIrqState {
+            latch: self.latch,
+            counter: self.counter,
+            prescaler: self.prescaler,
+            enabled: self.enabled,
+            enable_after_ack: self.enable_after_ack,
+            cycle_mode: self.cycle_mode,
+            pending: self.pending,
+        }
+    }
+
+    fn hydrate(&mut self, state: VrcIrqState) {
+        self.latch = state.latch;
+        self.counter = state.counter;
+        self.prescaler = state.prescaler;
+        self.enabled = state.enabled;
+        self.enable_after_ack = state.enable_after_ack;
+        self.cycle_mode = state.cycle_mode;
+        self.pending = state.pending;
+    }
+}
@@ -0,0 +1,193 @@
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::emulator::fds::Disk;
+use crate::emulator::ines::{ROM, RomError, Timing};
+use crate::emulator::mappers;
+use crate::emulator::memory::Mapper;
+use crate::emulator::nsf::{EXPANSION_FDS, NSF};
+use crate::emulator::ppu::MirrorMode;
+
+// Whatever is plugged into the console: an iNES cartridge, a disk in the Famicom Disk System, or
+// NSF music.
+// NES::new builds the rest of the machine around it.
+pub trait Media {
+    fn get_mapper(&self) -> Result<Rc<RefCell<dyn Mapper>>, RomError>;
+
+    fn timing(&self) -> Timing;
+
+    // Size of the RAM the console gives the mapper for $6000-$7FFF, and maybe more.  See
+    // Mapper::attach_prg_ram.
+    fn prg_ram_size_bytes(&self) -> usize;
+
+    fn nametable_ram_size_bytes(&self) -> usize;
+
+    fn mirror_mode(&self) -> MirrorMode;
+
+    fn has_battery(&self) -> bool;
+
+    // Loaded into $7000-$71FF at power on.
+    fn trainer(&self) -> Option<&[u8]>;
+}
+
+// Loads an iNES ROM, a disk image or NSF music, going by the contents of the file.  Disk images need
+// the Disk System's BIOS too.
+pub fn load<P: AsRef<Path>>(
+    path: P,
+    fds_bios_path: &Path,
+) -> Result<Box<dyn Media + Send>, RomError> {
+    let mut file = File::open(path)?;
+    let mut contents = vec![];
+    file.read_to_end(&mut contents)?;
+
+    if Disk::is_disk_image(&contents) {
+        let mut bios = vec![];
+        File::open(fds_bios_path)
+            .and_then(|mut file| file.read_to_end(&mut bios))
+            .map_err(RomError::MissingBios)?;
+        Ok(Box::new(Disk::from_bytes(contents, bios)?))
+    } else if NSF::is_nsf(&contents) {
+        Ok(Box::new(NSF::from_bytes(contents)?))
+    } else {
+        Ok(Box::new(ROM::from_bytes(contents)?))
+    }
+}
+
+impl Media for ROM {
+    fn get_mapper(&self) -> Result<Rc<RefCell<dyn Mapper>>, RomError> {
+        ROM::get_mapper(self)
+    }
+
+    fn timing(&self) -> Timing {
+        ROM::timing(self)
+    }
+
+    fn prg_ram_size_bytes(&self) -> usize {
+        ROM::prg_ram_size_bytes(self)
+    }
+
+    fn nametable_ram_size_bytes(&self) -> usize {
+        ROM::nametable_ram_size_bytes(self)
+    }
+
+    fn mirror_mode(&self) -> MirrorMode {
+        ROM::mirror_mode(self)
+    }
+
+    fn has_battery(&self) -> bool {
+        ROM::has_battery(self) || ROM::has_eeprom(self)
+    }
+
+    fn trainer(&self) -> Option<&[u8]> {
+        ROM::trainer(self)
+    }
+}
+
+// The RAM adapter's 32KB of RAM covers $6000-$DFFF.
+const FDS_PRG_RAM_SIZE: usize = 0x8000;
+const NAMETABLE_RAM_SIZE: usize = 0x800;
+
+impl Media for Disk {
+    fn get_mapper(&self) -> Result<Rc<RefCell<dyn Mapper>>, RomError> {
+        Ok(Rc::new(RefCell::new(mappers::FDS::new(
+            self.bios().to_vec(),
+            self.sides().to_vec(),
+        ))))
+    }
+
+    fn timing(&self) -> Timing {
+        Timing::NTSC
+    }
+
+    fn prg_ram_size_bytes(&self) -> usize {
+        FDS_PRG_RAM_SIZE
+    }
+
+    fn nametable_ram_size_bytes(&self) -> usize {
+        NAMETABLE_RAM_SIZE
+    }
+
+    // The RAM adapter controls mirroring.
+    fn mirror_mode(&self) -> MirrorMode {
+        MirrorMode::Horizontal
+    }
+
+    // Games save to the disk instead.
+    fn has_battery(&self) -> bool {
+        false
+    }
+
+    fn trainer(&self) -> Option<&[u8]> {
+        None
+    }
+}
+
+// Plain RAM at $6000, or 32KB at $6000-$DFFF for FDS music.
+const NSF_PRG_RAM_SIZE: usize = 0x2000;
+
+impl Media for NSF {
+    fn get_mapper(&self) -> Result<Rc<RefCell<dyn Mapper>>, RomError> {
+        Ok(Rc::new(RefCell::new(mappers::NSFPlayer::new(self.clone()))))
+    }
+
+    fn timing(&self) -> Timing {
+        NSF::timing(self)
+    }
+
+    fn prg_ram_size_bytes(&self) -> usize {
+        if self.expansion() & EXPANSION_FDS != 0 {
+            FDS_PRG_RAM_SIZE
+        } else {
+            NSF_PRG_RAM_SIZE
+        }
+    }
+
+    fn nametable_ram_size_bytes(&self) -> usize {
+        NAMETABLE_RAM_SIZE
+    }
+
+    fn mirror_mode(&self) -> MirrorMode {
+        MirrorMode::Horizontal
+    }
+
+    fn has_battery(&self) -> bool {
+        false
+    }
+
+    fn trainer(&self) -> Option<&[u8]> {
+        None
+    }
+}
+
+impl<M: Media + ?Sized> Media for Box<M> {
+    fn get_mapper(&self) -> Result<Rc<RefCell<dyn Mapper>>, RomError> {
+        (**self).get_mapper()
+    }
+
+    fn timing(&self) -> Timing {
+        (**self).timing()
+    }
+
+    fn prg_ram_size_bytes(&self) -> usize {
+        (**self).prg_ram_size_bytes()
+    }
+
+    fn nametable_ram_size_bytes(&self) -> usize {
+        (**self).nametable_ram_size_bytes()
+    }
+
+    fn mirror_mode(&self) -> MirrorMode {
+        (**self).mirror_mode()
+    }
+
+    fn has_battery(&self) -> bool {
+        (**self).has_battery()
+    }
+
+    fn trainer(&self) -> Option<&[u8]> {
+        (**self).trainer()
+    }
+}
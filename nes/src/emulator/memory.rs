@@ -1,13 +1,34 @@
 use std::cell::RefCell;
 use std::rc::Rc;
 
-use crate::emulator::ppu::{MirrorMode, Mirrorer};
+use crate::emulator::apu::ExpansionAudio;
+use crate::emulator::debugger::{Access, Debugger};
+use crate::emulator::ppu::{MirrorMode, Mirrorer, RenderEvent};
 use crate::emulator::state::{MapperState, MemoryState, SaveState};
 
 const ADDRESS_SPACE: usize = 65536;
 
+// VRAM holds the palettes, followed by the nametables.
+pub const PALETTE_RAM_SIZE: usize = 0x20;
+
+// What the CPU is reading a byte for.  Only data reads trigger read watchpoints.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BusAccess {
+    // An opcode, or the debugger's own reads.
+    Fetch,
+    // The bytes following an opcode, including immediate values.
+    Operand,
+    // Everything else.
+    Data,
+}
+
 pub trait Reader {
     fn read(&mut self, address: u16) -> u8;
+
+    // As read, but with the last value on the CPU's data bus, for any bits which nothing drives.
+    fn read_bus(&mut self, address: u16, _open_bus: u8, _access: BusAccess) -> u8 {
+        self.read(address)
+    }
 }
 
 pub trait Writer {
@@ -21,6 +42,10 @@ impl<M: Reader> Reader for Rc<RefCell<M>> {
     fn read(&mut self, address: u16) -> u8 {
         self.borrow_mut().read(address)
     }
+
+    fn read_bus(&mut self, address: u16, open_bus: u8, access: BusAccess) -> u8 {
+        self.borrow_mut().read_bus(address, open_bus, access)
+    }
 }
 
 impl<M: Writer> Writer for Rc<RefCell<M>> {
@@ -59,12 +84,18 @@ impl IORegisters {
 
 impl Reader for IORegisters {
     fn read(&mut self, address: u16) -> u8 {
+        self.read_bus(address, 0, BusAccess::Data)
+    }
+
+    fn read_bus(&mut self, address: u16, open_bus: u8, _access: BusAccess) -> u8 {
         match address {
-            0x4000..=0x4013 | 0x4015 => self.apu.read(address),
-            0x4014 => self.oamdma.unwrap_or(0),
-            0x4016 => self.joy1.read(address),
-            0x4017 => self.joy2.read(address),
-            _ => 0,
+            // Bit 5 of the APU status isn't driven.
+            0x4015 => (self.apu.read(address) & !0x20) | (open_bus & 0x20),
+            // The controller ports only drive the low 5 bits.
+            0x4016 => (self.joy1.read(address) & 0x1F) | (open_bus & 0xE0),
+            0x4017 => (self.joy2.read(address) & 0x1F) | (open_bus & 0xE0),
+            // Everything else is write only, or unused.
+            _ => open_bus,
         }
     }
 }
@@ -90,7 +121,11 @@ pub struct CPUMemory {
     ppu_registers: Box<dyn ReadWriter>,
     io_registers: Box<dyn ReadWriter>,
     sram: Box<dyn ReadWriter>,
+    sram_size: usize,
     prg_rom: Box<dyn ReadWriter>,
+
+    // For checking watchpoints.
+    debugger: Rc<RefCell<Debugger>>,
 }
 
 impl CPUMemory {
@@ -99,14 +134,18 @@ impl CPUMemory {
         ppu_registers: Box<dyn ReadWriter>,
         io_registers: Box<dyn ReadWriter>,
         sram: Box<dyn ReadWriter>,
+        sram_size: usize,
         prg_rom: Box<dyn ReadWriter>,
+        debugger: Rc<RefCell<Debugger>>,
     ) -> CPUMemory {
         CPUMemory {
             ram,
             ppu_registers,
             io_registers,
             sram,
+            sram_size,
             prg_rom,
+            debugger,
         }
     }
 
@@ -115,23 +154,42 @@ impl CPUMemory {
             0x0000..=0x1FFF => Some((&mut self.ram, address & 0x7FF)),
             0x2000..=0x3FFF => Some((&mut self.ppu_registers, address & 0x7)),
             0x4000..=0x401F => Some((&mut self.io_registers, address)),
-            0x6000..=0x7FFF => Some((&mut self.sram, address - 0x6000)),
-            0x8000..=0xFFFF => Some((&mut self.prg_rom, address)),
-            _ => None,
+            // SRAM smaller than the 8KB window is mirrored.  Mappers which bank SRAM handle this
+            // range themselves, in which case sram_size is 0.
+            0x6000..=0x7FFF if self.sram_size > 0 => Some((
+                &mut self.sram,
+                ((address - 0x6000) as usize % self.sram_size) as u16,
+            )),
+            // Everything else belongs to the cartridge.
+            0x4020..=0xFFFF => Some((&mut self.prg_rom, address)),
         }
     }
 }
 
 impl Reader for CPUMemory {
     fn read(&mut self, address: u16) -> u8 {
-        self.map(address)
-            .map(|(mem, addr)| mem.read(addr))
-            .unwrap_or(0)
+        self.read_bus(address, 0, BusAccess::Data)
+    }
+
+    fn read_bus(&mut self, address: u16, open_bus: u8, access: BusAccess) -> u8 {
+        let byte = self
+            .map(address)
+            .map(|(mem, addr)| mem.read_bus(addr, open_bus, access))
+            .unwrap_or(open_bus);
+        if access == BusAccess::Data {
+            self.debugger
+                .borrow_mut()
+                .check_access(address, Access::Read, byte);
+        }
+        byte
     }
 }
 
 impl Writer for CPUMemory {
     fn write(&mut self, address: u16, byte: u8) {
+        self.debugger
+            .borrow_mut()
+            .check_access(address, Access::Write, byte);
         self.map(address).map(|(mem, addr)| mem.write(addr, byte));
     }
 }
@@ -155,30 +213,44 @@ impl PPUMemory {
         }
     }
 
+    pub fn render_event(&mut self, event: RenderEvent) {
+        self.mirrorer.render_event(event);
+    }
+
     fn map(&mut self, address: u16) -> Option<(&mut Box<dyn ReadWriter>, u16)> {
         // Whole thing is mirrored above $4000.
         match address & 0x3FFF {
-            0x0000..=0x1FFF => Some((&mut self.chr_mem, address & 0x3FFF)),
+            0x0000..=0x1FFF => match self.mirrorer.chr_ciram_page(address & 0x1FFF) {
+                // Some mappers can put CIRAM in the pattern tables too.
+                Some(page) => {
+                    let ciram_addr = ((page as u16) << 10) | (address & 0x03FF);
+                    Some((&mut self.vram, PALETTE_RAM_SIZE as u16 + ciram_addr))
+                }
+                None => Some((&mut self.chr_mem, address & 0x3FFF)),
+            },
             0x2000..=0x3EFF => {
                 // Nametable and nametable mirrors.
                 // Note that we don't just literally mirror the address horizontally/vertically.
-                // We need to make sure we always read from one of just 2 banks of memory.
+                // We need to make sure we always read from one of just 2 banks of memory, or 4
+                // for four-screen cartridges which bring their own extra RAM.
                 let nt_bank = match self.mirrorer.mirror_mode() {
                     MirrorMode::SingleLower => 0,
                     MirrorMode::SingleUpper => 1,
                     MirrorMode::Vertical => (address & 0x0400) >> 10,
                     MirrorMode::Horizontal => (address & 0x0800) >> 11,
+                    MirrorMode::FourScreen => (address & 0x0C00) >> 10,
+                    MirrorMode::Custom(pages) => pages[((address & 0x0C00) >> 10) as usize] as u16,
                 };
                 let mirrored_addr = (nt_bank << 10) | (address & 0x03FF);
-                Some((&mut self.vram, mirrored_addr & 0x3FFF))
+                Some((&mut self.vram, PALETTE_RAM_SIZE as u16 + mirrored_addr))
             }
             0x3F00..=0x3FFF => {
                 // Palettes and palette mirrors.
                 let mirrored_addr = if address % 4 == 0 {
                     // Colour 0 in sprite palettes is mirrored to the BG palettes.
-                    address & 0x1F0F
+                    address & 0x000F
                 } else {
-                    address & 0x1F1F
+                    address & 0x001F
                 };
                 Some((&mut self.vram, mirrored_addr))
             }
@@ -189,6 +261,12 @@ impl PPUMemory {
 
 impl Reader for PPUMemory {
     fn read(&mut self, address: u16) -> u8 {
+        if let 0x2000..=0x3EFF = address & 0x3FFF
+            && let Some(byte) = self.mirrorer.read_nametable(0x2000 | (address & 0x0FFF))
+        {
+            return byte;
+        }
+
         self.map(address)
             .map(|(mem, addr)| mem.read(addr))
             .unwrap_or(0)
@@ -197,6 +275,14 @@ impl Reader for PPUMemory {
 
 impl Writer for PPUMemory {
     fn write(&mut self, address: u16, byte: u8) {
+        if let 0x2000..=0x3EFF = address & 0x3FFF
+            && self
+                .mirrorer
+                .write_nametable(0x2000 | (address & 0x0FFF), byte)
+        {
+            return;
+        }
+
         self.map(address).map(|(mem, addr)| mem.write(addr, byte));
     }
 }
@@ -210,6 +296,69 @@ pub trait Mapper: SaveState<'static, MapperState> {
     fn irq_triggered(&self) -> bool {
         false
     }
+
+    // The cartridge's part of $4020-$7FFF, which is everything but the SRAM.  Returning None
+    // leaves the data bus undriven.
+    fn read_expansion(&mut self, _address: u16) -> Option<u8> {
+        None
+    }
+
+    fn write_expansion(&mut self, _address: u16, _byte: u8) {}
+
+    // Mappers which bank SRAM take it over here, and return true.  It then appears at
+    // $6000-$7FFF only through read_expansion and write_expansion.
+    fn attach_prg_ram(&mut self, _prg_ram: Rc<RefCell<Memory>>) -> bool {
+        false
+    }
+
+    // Nametables supplied by the mapper, CIRAM in the pattern tables, and rendering progress.  See
+    // Mirrorer.
+    fn read_nametable(&mut self, _address: u16) -> Option<u8> {
+        None
+    }
+
+    fn write_nametable(&mut self, _address: u16, _byte: u8) -> bool {
+        false
+    }
+
+    fn chr_ciram_page(&self, _address: u16) -> Option<u8> {
+        None
+    }
+
+    fn render_event(&mut self, _event: RenderEvent) {}
+
+    // Called after each CPU instruction or DMA transfer, with how many CPU cycles it took.
+    fn clock_cpu(&mut self, _cycles: u32) {}
+
+    // Expansion audio.  Called once per APU cycle to clock the mapper's sound channels, and
+    // returns their output, on the same scale as the APU's own mixer output.
+    fn clock_audio(&mut self) -> f32 {
+        0.0
+    }
+
+    // The Famicom Disk System's drive.  How many disk sides there are, which is inserted, and
+    // swapping them, or ejecting with None.
+    fn disk_sides(&self) -> usize {
+        0
+    }
+
+    fn disk_side(&self) -> Option<usize> {
+        None
+    }
+
+    fn insert_disk(&mut self, _side: Option<usize>) {}
+
+    // The NSF player.  How many songs there are, which is selected, and choosing another, which
+    // starts at the next reset.
+    fn songs(&self) -> usize {
+        0
+    }
+
+    fn song(&self) -> Option<usize> {
+        None
+    }
+
+    fn select_song(&mut self, _song: usize) {}
 }
 
 pub type MapperRef = Rc<RefCell<dyn Mapper>>;
@@ -234,6 +383,70 @@ impl Mapper for MapperRef {
     fn mirror_mode(&self) -> MirrorMode {
         self.borrow().mirror_mode()
     }
+
+    fn irq_triggered(&self) -> bool {
+        self.borrow().irq_triggered()
+    }
+
+    fn read_expansion(&mut self, address: u16) -> Option<u8> {
+        self.borrow_mut().read_expansion(address)
+    }
+
+    fn write_expansion(&mut self, address: u16, byte: u8) {
+        self.borrow_mut().write_expansion(address, byte)
+    }
+
+    fn attach_prg_ram(&mut self, prg_ram: Rc<RefCell<Memory>>) -> bool {
+        self.borrow_mut().attach_prg_ram(prg_ram)
+    }
+
+    fn read_nametable(&mut self, address: u16) -> Option<u8> {
+        self.borrow_mut().read_nametable(address)
+    }
+
+    fn write_nametable(&mut self, address: u16, byte: u8) -> bool {
+        self.borrow_mut().write_nametable(address, byte)
+    }
+
+    fn chr_ciram_page(&self, address: u16) -> Option<u8> {
+        self.borrow().chr_ciram_page(address)
+    }
+
+    fn render_event(&mut self, event: RenderEvent) {
+        self.borrow_mut().render_event(event)
+    }
+
+    fn clock_cpu(&mut self, cycles: u32) {
+        self.borrow_mut().clock_cpu(cycles)
+    }
+
+    fn clock_audio(&mut self) -> f32 {
+        self.borrow_mut().clock_audio()
+    }
+
+    fn disk_sides(&self) -> usize {
+        self.borrow().disk_sides()
+    }
+
+    fn disk_side(&self) -> Option<usize> {
+        self.borrow().disk_side()
+    }
+
+    fn insert_disk(&mut self, side: Option<usize>) {
+        self.borrow_mut().insert_disk(side)
+    }
+
+    fn songs(&self) -> usize {
+        self.borrow().songs()
+    }
+
+    fn song(&self) -> Option<usize> {
+        self.borrow().song()
+    }
+
+    fn select_song(&mut self, song: usize) {
+        self.borrow_mut().select_song(song)
+    }
 }
 
 impl SaveState<'static, MapperState> for MapperRef {
@@ -250,6 +463,28 @@ impl Mirrorer for MapperRef {
     fn mirror_mode(&self) -> MirrorMode {
         self.borrow().mirror_mode()
     }
+
+    fn read_nametable(&mut self, address: u16) -> Option<u8> {
+        self.borrow_mut().read_nametable(address)
+    }
+
+    fn write_nametable(&mut self, address: u16, byte: u8) -> bool {
+        self.borrow_mut().write_nametable(address, byte)
+    }
+
+    fn chr_ciram_page(&self, address: u16) -> Option<u8> {
+        self.borrow().chr_ciram_page(address)
+    }
+
+    fn render_event(&mut self, event: RenderEvent) {
+        self.borrow_mut().render_event(event)
+    }
+}
+
+impl ExpansionAudio for MapperRef {
+    fn clock(&mut self) -> f32 {
+        self.borrow_mut().clock_audio()
+    }
 }
 
 pub struct PrgMapper<M: Mapper> {
@@ -264,13 +499,23 @@ impl<M: Mapper> PrgMapper<M> {
 
 impl<M: Mapper> Reader for PrgMapper<M> {
     fn read(&mut self, address: u16) -> u8 {
-        self.mapper.read_prg(address)
+        self.read_bus(address, 0, BusAccess::Data)
+    }
+
+    fn read_bus(&mut self, address: u16, open_bus: u8, _access: BusAccess) -> u8 {
+        match address {
+            0x8000..=0xFFFF => self.mapper.read_prg(address),
+            _ => self.mapper.read_expansion(address).unwrap_or(open_bus),
+        }
     }
 }
 
 impl<M: Mapper> Writer for PrgMapper<M> {
     fn write(&mut self, address: u16, byte: u8) {
-        self.mapper.write_prg(address, byte)
+        match address {
+            0x8000..=0xFFFF => self.mapper.write_prg(address, byte),
+            _ => self.mapper.write_expansion(address, byte),
+        }
     }
 }
 
@@ -332,6 +577,10 @@ impl Memory {
         self.data.len()
     }
 
+    pub fn as_slice(&self) -> &[u8] {
+        &self.data
+    }
+
     pub fn debug_print(&self, start_addr: u16, num_bytes: u16) {
         let end_addr = start_addr - 1 + num_bytes;
         println!(
@@ -379,3 +628,19 @@ fn test_get_and_set() {
     ram.write(1234, 23);
     assert_eq!(ram.read(1234), 23);
 }
+
+#[test]
+fn test_four_screen_nametables() {
+    let mut memory = PPUMemory::new(
+        Box::new(Memory::new_ram(0x2000)),
+        Box::new(MirrorMode::FourScreen),
+        Box::new(Memory::new_ram(PALETTE_RAM_SIZE + 0x1000)),
+    );
+    for nt in 0..4 {
+        memory.write(0x2000 + nt * 0x400, nt as u8 + 1);
+    }
+    for nt in 0..4 {
+        assert_eq!(memory.read(0x2000 + nt * 0x400), nt as u8 + 1);
+        assert_eq!(memory.read(0x3000 + nt * 0x400), nt as u8 + 1);
+    }
+}
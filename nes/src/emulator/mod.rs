@@ -4,11 +4,17 @@ pub mod clock;
 pub mod components;
 pub mod controller;
 pub mod cpu;
+pub mod debugger;
+pub mod fds;
 pub mod ines;
 pub mod io;
 pub mod mappers;
+pub mod media;
 pub mod memory;
+pub mod nsf;
 pub mod ppu;
+pub mod region;
+pub mod rewind;
 pub mod state;
 pub mod util;
 
@@ -20,12 +26,15 @@ use std::rc::Rc;
 
 use crate::emulator::apu::AudioOut;
 use crate::emulator::controller::Button;
+use crate::emulator::debugger::{BreakReason, Debugger, StepMode, Watchpoint};
 use crate::emulator::io::Screen;
 use crate::emulator::io::event::{EventBus, Key};
+use crate::emulator::media::Media;
 use crate::emulator::memory::{IORegisters, Writer};
+use crate::emulator::region::Region;
 use crate::emulator::state::{NESState, SaveState};
 
-// Timings (NTSC).
+// Timings (NTSC).  See region.rs for the other regions.
 // Master clock = 21.477272 MHz ~= 46.5ns per clock.
 // CPU clock = 12 master clocks.
 // PPU clock = 4 master clocks.
@@ -36,6 +45,7 @@ pub const NES_PPU_CLOCK_FACTOR: u32 = 4;
 
 pub struct NES {
     clock: clock::Clock,
+    region: Region,
     pub cpu: Rc<RefCell<cpu::CPU>>,
     pub ppu: Rc<RefCell<ppu::PPU>>,
     pub apu: Rc<RefCell<apu::APU>>,
@@ -47,45 +57,103 @@ pub struct NES {
     pub joy1: Rc<RefCell<controller::Controller>>,
     pub joy2: Rc<RefCell<controller::Controller>>,
     nmi_pin: bool,
+
+    // Battery-backed SRAM, and its contents as of the last time it was flushed.
+    battery_backed: bool,
+    sram_snapshot: Vec<u8>,
+
+    // Debugger state, and what it last saw so it can tell when to check breakpoints.
+    debugger: Rc<RefCell<Debugger>>,
+    debugging: bool,
+    last_instruction_count: u64,
+    last_scanline: u16,
 }
 
 impl NES {
-    pub fn new<A>(
+    pub fn new<A, M>(
         event_bus: Rc<RefCell<EventBus>>,
         screen: Rc<RefCell<Screen>>,
         audio: A,
-        rom: ines::ROM,
-    ) -> NES
+        rom: M,
+    ) -> Result<NES, ines::RomError>
     where
         A: AudioOut + 'static,
+        M: Media,
+    {
+        let region = Region::from(rom.timing());
+        NES::new_with_region(event_bus, screen, audio, rom, region)
+    }
+
+    // As `new`, but ignores the timing in the ROM header.
+    pub fn new_with_region<A, M>(
+        event_bus: Rc<RefCell<EventBus>>,
+        screen: Rc<RefCell<Screen>>,
+        audio: A,
+        rom: M,
+        region: Region,
+    ) -> Result<NES, ines::RomError>
+    where
+        A: AudioOut + 'static,
+        M: Media,
     {
         // Create master clock.
         let mut clock = clock::Clock::new();
 
         // Load ROM into memory.
-        let mapper = rom.get_mapper();
+        let mapper = rom.get_mapper()?;
+        let battery_backed = rom.has_battery();
 
         // Create RAM modules.
         let ram = Rc::new(RefCell::new(memory::Memory::new_ram(0x800)));
-        let sram = Rc::new(RefCell::new(memory::Memory::new_ram(0x2000)));
-        let vram = Rc::new(RefCell::new(memory::Memory::new_ram(0x2000)));
+        let sram_size = rom.prg_ram_size_bytes();
+        let sram = Rc::new(RefCell::new(memory::Memory::new_ram(sram_size)));
+        let vram = Rc::new(RefCell::new(memory::Memory::new_ram(
+            memory::PALETTE_RAM_SIZE + rom.nametable_ram_size_bytes(),
+        )));
+
+        // The trainer is loaded into $7000-$71FF.
+        if let Some(trainer) = rom.trainer()
+            && sram_size > 0
+        {
+            let mut sram = sram.borrow_mut();
+            for (ix, byte) in trainer.iter().enumerate() {
+                sram.put((0x1000 + ix) % sram_size, *byte);
+            }
+        }
+        let sram_snapshot = sram.borrow().as_slice().to_vec();
+
+        // Mappers which bank SRAM handle $6000-$7FFF themselves.
+        let cpu_sram_size = if mapper.borrow_mut().attach_prg_ram(sram.clone()) {
+            0
+        } else {
+            sram_size
+        };
 
         // Create graphics output module and PPU.
+        // Four-screen cartridges have their own nametable RAM, so the mapper doesn't control
+        // mirroring.
+        let mirrorer: Box<dyn ppu::Mirrorer> = match rom.mirror_mode() {
+            ppu::MirrorMode::FourScreen => Box::new(ppu::MirrorMode::FourScreen),
+            _ => Box::new(mapper.clone()),
+        };
         let ppu_memory = memory::PPUMemory::new(
             Box::new(memory::ChrMapper::new(mapper.clone())),
-            Box::new(mapper.clone()),
+            mirrorer,
             Box::new(vram.clone()),
         );
 
         let ppu = Rc::new(RefCell::new(ppu::PPU::new(
             ppu_memory,
             Box::new(screen.clone()),
+            region,
         )));
 
         // Create APU.
         let apu = Rc::new(RefCell::new(apu::APU::new(
             Box::new(audio),
             Box::new(memory::PrgMapper::new(mapper.clone())),
+            Box::new(mapper.clone()),
+            region,
         )));
 
         // Create controllers.
@@ -119,30 +187,35 @@ impl NES {
             Box::new(joy2.clone()),
         )));
 
+        let debugger = Rc::new(RefCell::new(Debugger::new()));
         let cpu_memory = memory::CPUMemory::new(
             Box::new(ram.clone()),
             Box::new(ppu.clone()),
             Box::new(io_registers.clone()),
             Box::new(sram.clone()),
+            cpu_sram_size,
             Box::new(memory::PrgMapper::new(mapper.clone())),
+            debugger.clone(),
         );
 
         let cpu = Rc::new(RefCell::new(cpu::new(Box::new(cpu_memory))));
         cpu.borrow_mut().disable_bcd();
         cpu.borrow_mut().startup_sequence();
 
-        let dma_controller = DMAController::new(io_registers.clone(), cpu.clone());
+        let dma_controller = DMAController::new(io_registers.clone(), cpu.clone(), mapper.clone());
 
         // Wire up the clock timings.
-        let cpu_ticker = clock::ScaledTicker::new(Box::new(dma_controller), NES_CPU_CLOCK_FACTOR);
-        let ppu_ticker = clock::ScaledTicker::new(Box::new(ppu.clone()), NES_PPU_CLOCK_FACTOR);
-        let apu_ticker = clock::ScaledTicker::new(Box::new(apu.clone()), NES_APU_CLOCK_FACTOR);
+        let cpu_ticker =
+            clock::ScaledTicker::new(Box::new(dma_controller), region.cpu_clock_factor());
+        let ppu_ticker = clock::ScaledTicker::new(Box::new(ppu.clone()), region.ppu_clock_factor());
+        let apu_ticker = clock::ScaledTicker::new(Box::new(apu.clone()), region.apu_clock_factor());
         clock.manage(cpu_ticker);
         clock.manage(apu_ticker);
         clock.manage(ppu_ticker);
 
-        NES {
+        Ok(NES {
             clock,
+            region,
             cpu,
             ppu,
             apu,
@@ -154,7 +227,13 @@ impl NES {
             joy1,
             joy2,
             nmi_pin: false,
-        }
+            battery_backed,
+            sram_snapshot,
+            debugger,
+            debugging: false,
+            last_instruction_count: 0,
+            last_scanline: 0,
+        })
     }
 
     #[inline]
@@ -177,13 +256,21 @@ impl NES {
             self.cpu.borrow_mut().trigger_irq();
         }
 
+        if self.debugging {
+            self.check_debugger();
+        }
+
         cycles
     }
 
+    // Stops early if the debugger breaks.
     pub fn tick_multi(&mut self, ticks: u32) -> u64 {
         let mut cycles = 0u64;
         for _ in 0..ticks {
-            cycles += self.tick()
+            cycles += self.tick();
+            if self.debugging && self.debugger.borrow().is_breaking() {
+                break;
+            }
         }
         cycles
     }
@@ -195,6 +282,213 @@ impl NES {
         // Restart CPU.
         self.cpu.borrow_mut().startup_sequence();
     }
+
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    pub fn has_battery(&self) -> bool {
+        self.battery_backed
+    }
+
+    // Restore the contents of battery-backed SRAM, e.g. from a .sav file.
+    pub fn load_sram(&mut self, data: &[u8]) {
+        let mut sram = self.sram.borrow_mut();
+        for (ix, byte) in data.iter().take(sram.len()).enumerate() {
+            sram.put(ix, *byte);
+        }
+        self.sram_snapshot = sram.as_slice().to_vec();
+    }
+
+    // Returns the contents of battery-backed SRAM if they have changed since the last flush.
+    // Always returns None for cartridges without a battery.
+    pub fn flush_sram(&mut self) -> Option<Vec<u8>> {
+        if !self.battery_backed {
+            return None;
+        }
+
+        let sram = self.sram.borrow();
+        if sram.as_slice() == self.sram_snapshot.as_slice() {
+            return None;
+        }
+
+        self.sram_snapshot = sram.as_slice().to_vec();
+        Some(self.sram_snapshot.clone())
+    }
+
+    // Disk System only.  The number of disk sides, zero for cartridges.
+    pub fn disk_sides(&self) -> usize {
+        self.mapper.borrow().disk_sides()
+    }
+
+    // Which disk side is in the drive, if any.
+    pub fn disk_side(&self) -> Option<usize> {
+        self.mapper.borrow().disk_side()
+    }
+
+    // Swap to the given disk side, or eject the disk with None.
+    pub fn insert_disk(&mut self, side: Option<usize>) {
+        self.mapper.borrow_mut().insert_disk(side);
+    }
+
+    // NSF music only.  The number of songs, zero for anything else.
+    pub fn songs(&self) -> usize {
+        self.mapper.borrow().songs()
+    }
+
+    pub fn song(&self) -> Option<usize> {
+        self.mapper.borrow().song()
+    }
+
+    // Starts playing the given song from the beginning.
+    pub fn select_song(&mut self, song: usize) {
+        self.mapper.borrow_mut().select_song(song);
+        self.reset();
+    }
+}
+
+// Debugging.
+impl NES {
+    pub fn add_breakpoint(&mut self, pc: u16) {
+        self.debugger.borrow_mut().add_breakpoint(pc);
+        self.update_debugging();
+    }
+
+    pub fn remove_breakpoint(&mut self, pc: u16) -> bool {
+        let removed = self.debugger.borrow_mut().remove_breakpoint(pc);
+        self.update_debugging();
+        removed
+    }
+
+    pub fn breakpoints(&self) -> Vec<u16> {
+        self.debugger.borrow().breakpoints().collect()
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.debugger.borrow_mut().add_watchpoint(watchpoint);
+        self.update_debugging();
+    }
+
+    pub fn remove_watchpoints(&mut self, address: u16) -> bool {
+        let removed = self.debugger.borrow_mut().remove_watchpoints(address);
+        self.update_debugging();
+        removed
+    }
+
+    pub fn watchpoints(&self) -> Vec<Watchpoint> {
+        self.debugger.borrow().watchpoints().to_vec()
+    }
+
+    // The following arm the debugger to break at some point in the future.
+    // Keep ticking, or use `run_until_break`, to get there.
+
+    pub fn step(&mut self) {
+        self.set_step(Some(StepMode::Instruction));
+    }
+
+    // As `step`, but runs subroutine calls through to completion.
+    pub fn step_over(&mut self) {
+        let mut cpu = self.cpu.borrow_mut();
+        let registers = cpu.registers();
+        self.debugger.borrow_mut().set_suspended(true);
+        let is_call = cpu.next_instruction_is_call();
+        self.debugger.borrow_mut().set_suspended(false);
+        drop(cpu);
+
+        if is_call {
+            self.set_step(Some(StepMode::Until {
+                pc: registers.pc.wrapping_add(3),
+                sp: registers.sp,
+            }));
+        } else {
+            self.step();
+        }
+    }
+
+    // Runs until the current subroutine returns.
+    pub fn step_out(&mut self) {
+        let sp = self.cpu.borrow().registers().sp;
+        self.set_step(Some(StepMode::Out { sp }));
+    }
+
+    pub fn run_to_scanline(&mut self, scanline: u16) {
+        self.set_step(Some(StepMode::Scanline(scanline)));
+    }
+
+    pub fn cancel_step(&mut self) {
+        self.set_step(None);
+    }
+
+    // Returns why the debugger stopped, if it has, and lets emulation continue.
+    pub fn take_break(&mut self) -> Option<BreakReason> {
+        self.debugger.borrow_mut().take_break()
+    }
+
+    // Returns the number of cycles run and the reason for breaking, if the debugger broke before
+    // max_cycles elapsed.
+    pub fn run_until_break(&mut self, max_cycles: u64) -> (u64, Option<BreakReason>) {
+        let mut cycles = 0;
+        while cycles < max_cycles {
+            cycles += self.tick();
+            if let Some(reason) = self.take_break() {
+                return (cycles, Some(reason));
+            }
+        }
+        (cycles, None)
+    }
+
+    // Reads memory without triggering watchpoints.
+    // Note that reading some registers, e.g. PPUSTATUS, still has side effects.
+    pub fn debug_read(&mut self, address: u16) -> u8 {
+        self.cpu.borrow_mut().peek_memory(address)
+    }
+
+    pub fn debug_write(&mut self, address: u16, byte: u8) {
+        self.debugger.borrow_mut().set_suspended(true);
+        self.cpu.borrow_mut().store_memory(address, byte);
+        self.debugger.borrow_mut().set_suspended(false);
+    }
+
+    // The next instruction and current registers, formatted like the CPU trace.
+    pub fn debug_format_instruction(&mut self) -> String {
+        self.debugger.borrow_mut().set_suspended(true);
+        let line = self.cpu.borrow_mut().format_next_instruction();
+        self.debugger.borrow_mut().set_suspended(false);
+        line
+    }
+
+    fn set_step(&mut self, step: Option<StepMode>) {
+        self.debugger.borrow_mut().set_step(step);
+        self.update_debugging();
+    }
+
+    // Called whenever the debugger is reconfigured.
+    // Resyncs what we last saw, so that we don't immediately break at the current instruction.
+    fn update_debugging(&mut self) {
+        self.debugging = self.debugger.borrow().is_active();
+        self.last_instruction_count = self.cpu.borrow().instruction_count();
+        self.last_scanline = self.ppu.borrow().scanline;
+    }
+
+    fn check_debugger(&mut self) {
+        let mut debugger = self.debugger.borrow_mut();
+
+        let instruction_count = self.cpu.borrow().instruction_count();
+        if instruction_count != self.last_instruction_count {
+            self.last_instruction_count = instruction_count;
+            let registers = self.cpu.borrow().registers();
+            debugger.check_instruction(registers.pc, registers.sp);
+        }
+
+        let scanline = self.ppu.borrow().scanline;
+        if scanline != self.last_scanline {
+            self.last_scanline = scanline;
+            debugger.check_scanline(scanline);
+        }
+
+        // Keep checking while there's a break to report, even if it cancelled the last step.
+        self.debugging = debugger.is_active() || debugger.is_breaking();
+    }
 }
 
 pub struct DMAController {
@@ -202,18 +496,23 @@ pub struct DMAController {
     base_address: u16,
     io_registers: Rc<RefCell<IORegisters>>,
     cpu: Rc<RefCell<cpu::CPU>>,
+
+    // Mappers with cycle-counting IRQs are clocked along with the CPU.
+    mapper: memory::MapperRef,
 }
 
 impl DMAController {
     pub fn new(
         io_registers: Rc<RefCell<IORegisters>>,
         cpu: Rc<RefCell<cpu::CPU>>,
+        mapper: memory::MapperRef,
     ) -> DMAController {
         DMAController {
             copies_remaining: 0,
             base_address: 0,
             io_registers,
             cpu,
+            mapper,
         }
     }
 }
@@ -229,7 +528,7 @@ impl clock::Ticker for DMAController {
             }
         }
 
-        if self.copies_remaining > 0 {
+        let cycles = if self.copies_remaining > 0 {
             // CPU is suspended during copy.
             let byte = self
                 .cpu
@@ -240,15 +539,20 @@ impl clock::Ticker for DMAController {
             2
         } else {
             self.cpu.borrow_mut().tick()
-        }
+        };
+
+        self.mapper.borrow_mut().clock_cpu(cycles);
+        cycles
     }
 }
 
 impl<'de> SaveState<'de, NESState> for NES {
     fn freeze(&mut self) -> NESState {
         NESState {
+            clock: self.clock.freeze(),
             cpu: self.cpu.borrow_mut().freeze(),
             ppu: self.ppu.borrow_mut().freeze(),
+            apu: self.apu.borrow_mut().freeze(),
             mapper: self.mapper.borrow_mut().freeze(),
             ram: self.ram.borrow_mut().freeze(),
             sram: self.sram.borrow_mut().freeze(),
@@ -260,11 +564,14 @@ impl<'de> SaveState<'de, NESState> for NES {
     }
 
     fn hydrate(&mut self, state: NESState) {
+        self.clock.hydrate(state.clock);
         self.cpu.borrow_mut().hydrate(state.cpu);
         self.ppu.borrow_mut().hydrate(state.ppu);
+        self.apu.borrow_mut().hydrate(state.apu);
         self.mapper.borrow_mut().hydrate(state.mapper);
         self.ram.borrow_mut().hydrate(state.ram);
         self.sram.borrow_mut().hydrate(state.sram);
+        self.sram_snapshot = self.sram.borrow().as_slice().to_vec();
         self.vram.borrow_mut().hydrate(state.vram);
         self.screen.borrow_mut().hydrate(state.screen);
         self.joy1.borrow_mut().hydrate(state.joy1);
@@ -0,0 +1,301 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use crate::emulator::ines::{RomError, Timing};
+
+// NSF and NSFe music files.
+//
+// An NSF is a 128 byte header followed by the music data, which is loaded at the load address, or
+// bankswitched in 4kb banks through $5FF8-$5FFF if any of the initial banks are set.  The music is
+// played by calling the init routine once with the song number, then the play routine at the rate
+// given in the header.  See mappers/nsf.rs for how we do that.
+//
+// NSFe files hold the same information in chunks, and can also name each track.
+
+const HEADER_SIZE: usize = 0x80;
+const MAGIC: [u8; 5] = [b'N', b'E', b'S', b'M', 0x1A];
+const NSFE_MAGIC: [u8; 4] = [b'N', b'S', b'F', b'E'];
+
+// Play rates in microseconds, used when the file doesn't give one.
+const DEFAULT_NTSC_SPEED: u16 = 16639;
+const DEFAULT_PAL_SPEED: u16 = 19997;
+
+// Expansion chip flags.
+pub const EXPANSION_VRC6: u8 = 0x01;
+pub const EXPANSION_VRC7: u8 = 0x02;
+pub const EXPANSION_FDS: u8 = 0x04;
+pub const EXPANSION_MMC5: u8 = 0x08;
+pub const EXPANSION_NAMCO163: u8 = 0x10;
+pub const EXPANSION_SUNSOFT5B: u8 = 0x20;
+
+#[derive(Clone, Debug)]
+pub struct NSF {
+    songs: u8,
+    starting_song: u8,
+    load_address: u16,
+    init_address: u16,
+    play_address: u16,
+    title: String,
+    artist: String,
+    copyright: String,
+    ntsc_speed: u16,
+    pal_speed: u16,
+    initial_banks: [u8; 8],
+    timing: Timing,
+    expansion: u8,
+    data: Vec<u8>,
+    track_labels: Vec<String>,
+}
+
+impl NSF {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<NSF, RomError> {
+        let mut file = File::open(path)?;
+        let mut contents = vec![];
+        file.read_to_end(&mut contents)?;
+        NSF::from_bytes(contents)
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> Result<NSF, RomError> {
+        if data.starts_with(&MAGIC) {
+            NSF::from_nsf(data)
+        } else if data.starts_with(&NSFE_MAGIC) {
+            NSF::from_nsfe(&data)
+        } else {
+            Err(RomError::BadNsf(String::from("bad magic number")))
+        }
+    }
+
+    // Whether the data looks like an NSF or NSFe file.
+    pub fn is_nsf(data: &[u8]) -> bool {
+        data.starts_with(&MAGIC) || data.starts_with(&NSFE_MAGIC)
+    }
+
+    fn from_nsf(data: Vec<u8>) -> Result<NSF, RomError> {
+        if data.len() < HEADER_SIZE {
+            return Err(RomError::TruncatedHeader);
+        }
+
+        let word = |ix: usize| u16::from_le_bytes([data[ix], data[ix + 1]]);
+        let mut initial_banks = [0; 8];
+        initial_banks.copy_from_slice(&data[0x70..0x78]);
+
+        // NSF2 files give the data length, since metadata may follow it.
+        let data_length =
+            data[0x7D] as usize | (data[0x7E] as usize) << 8 | (data[0x7F] as usize) << 16;
+        let end = if data[0x05] >= 2 && data_length > 0 {
+            (HEADER_SIZE + data_length).min(data.len())
+        } else {
+            data.len()
+        };
+
+        NSF::new(NSF {
+            songs: data[0x06],
+            starting_song: data[0x07].saturating_sub(1),
+            load_address: word(0x08),
+            init_address: word(0x0A),
+            play_address: word(0x0C),
+            title: read_string(&data[0x0E..0x2E]),
+            artist: read_string(&data[0x2E..0x4E]),
+            copyright: read_string(&data[0x4E..0x6E]),
+            ntsc_speed: word(0x6E),
+            pal_speed: word(0x78),
+            initial_banks,
+            timing: read_timing(data[0x7A]),
+            expansion: data[0x7B],
+            data: data[HEADER_SIZE..end].to_vec(),
+            track_labels: vec![],
+        })
+    }
+
+    fn from_nsfe(data: &[u8]) -> Result<NSF, RomError> {
+        let mut nsf = NSF {
+            songs: 1,
+            starting_song: 0,
+            load_address: 0,
+            init_address: 0,
+            play_address: 0,
+            title: String::new(),
+            artist: String::new(),
+            copyright: String::new(),
+            ntsc_speed: 0,
+            pal_speed: 0,
+            initial_banks: [0; 8],
+            timing: Timing::NTSC,
+            expansion: 0,
+            data: vec![],
+            track_labels: vec![],
+        };
+        let mut has_info = false;
+        let mut has_data = false;
+
+        let mut pos = NSFE_MAGIC.len();
+        while pos + 8 <= data.len() {
+            let length =
+                u32::from_le_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]])
+                    as usize;
+            let id = &data[pos + 4..pos + 8];
+            pos += 8;
+            let chunk = match data.get(pos..pos + length) {
+                Some(chunk) => chunk,
+                None => return Err(RomError::BadNsf(String::from("truncated NSFe chunk"))),
+            };
+            pos += length;
+
+            let word = |ix: usize| u16::from_le_bytes([chunk[ix], chunk[ix + 1]]);
+            match id {
+                b"INFO" => {
+                    if chunk.len() < 10 {
+                        return Err(RomError::BadNsf(String::from("truncated INFO chunk")));
+                    }
+                    nsf.load_address = word(0);
+                    nsf.init_address = word(2);
+                    nsf.play_address = word(4);
+                    nsf.timing = read_timing(chunk[6]);
+                    nsf.expansion = chunk[7];
+                    nsf.songs = chunk[8];
+                    nsf.starting_song = chunk[9];
+                    has_info = true;
+                }
+                b"DATA" => {
+                    nsf.data = chunk.to_vec();
+                    has_data = true;
+                }
+                b"BANK" => {
+                    let banks = chunk.len().min(8);
+                    nsf.initial_banks[..banks].copy_from_slice(&chunk[..banks]);
+                }
+                b"RATE" => {
+                    if chunk.len() >= 2 {
+                        nsf.ntsc_speed = word(0);
+                    }
+                    if chunk.len() >= 4 {
+                        nsf.pal_speed = word(2);
+                    }
+                }
+                b"auth" => {
+                    let mut strings = chunk.split(|&b| b == 0).map(read_string);
+                    nsf.title = strings.next().unwrap_or_default();
+                    nsf.artist = strings.next().unwrap_or_default();
+                    nsf.copyright = strings.next().unwrap_or_default();
+                }
+                b"tlbl" => {
+                    nsf.track_labels = chunk.split(|&b| b == 0).map(read_string).collect();
+                }
+                b"NEND" => break,
+                // Chunks starting with a capital letter can't be skipped.
+                _ if id[0].is_ascii_uppercase() => {
+                    return Err(RomError::BadNsf(format!(
+                        "unsupported NSFe chunk {}",
+                        String::from_utf8_lossy(id)
+                    )));
+                }
+                _ => (),
+            }
+        }
+
+        if !has_info || !has_data {
+            return Err(RomError::BadNsf(String::from("missing INFO or DATA chunk")));
+        }
+        NSF::new(nsf)
+    }
+
+    fn new(mut nsf: NSF) -> Result<NSF, RomError> {
+        if nsf.data.is_empty() {
+            return Err(RomError::BadNsf(String::from("no music data")));
+        }
+        if nsf.songs == 0 {
+            return Err(RomError::BadNsf(String::from("no songs")));
+        }
+        if nsf.ntsc_speed == 0 {
+            nsf.ntsc_speed = DEFAULT_NTSC_SPEED;
+        }
+        if nsf.pal_speed == 0 {
+            nsf.pal_speed = DEFAULT_PAL_SPEED;
+        }
+        nsf.starting_song = nsf.starting_song.min(nsf.songs - 1);
+        Ok(nsf)
+    }
+
+    pub fn songs(&self) -> usize {
+        self.songs as usize
+    }
+
+    // Numbered from 0, unlike in NSF headers.
+    pub fn starting_song(&self) -> usize {
+        self.starting_song as usize
+    }
+
+    pub fn load_address(&self) -> u16 {
+        self.load_address
+    }
+
+    pub fn init_address(&self) -> u16 {
+        self.init_address
+    }
+
+    pub fn play_address(&self) -> u16 {
+        self.play_address
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn artist(&self) -> &str {
+        &self.artist
+    }
+
+    pub fn copyright(&self) -> &str {
+        &self.copyright
+    }
+
+    pub fn track_label(&self, song: usize) -> Option<&str> {
+        self.track_labels
+            .get(song)
+            .map(|label| label.as_str())
+            .filter(|label| !label.is_empty())
+    }
+
+    // How often to call the play routine, in microseconds.
+    pub fn play_speed_us(&self, timing: Timing) -> u16 {
+        match timing {
+            Timing::PAL | Timing::Dendy => self.pal_speed,
+            _ => self.ntsc_speed,
+        }
+    }
+
+    pub fn initial_banks(&self) -> [u8; 8] {
+        self.initial_banks
+    }
+
+    pub fn is_bankswitched(&self) -> bool {
+        self.initial_banks.iter().any(|&bank| bank != 0)
+    }
+
+    pub fn timing(&self) -> Timing {
+        self.timing
+    }
+
+    pub fn expansion(&self) -> u8 {
+        self.expansion
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+// Bit 1 means the music works on either, and bit 0 which one it prefers otherwise.
+fn read_timing(flags: u8) -> Timing {
+    match flags & 0x03 {
+        0 => Timing::NTSC,
+        1 => Timing::PAL,
+        _ => Timing::MultiRegion,
+    }
+}
+
+fn read_string(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
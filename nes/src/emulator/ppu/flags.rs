@@ -43,6 +43,7 @@ impl Into<u8> for PPUCTRL {
 // ||+------- Emphasize red*
 // |+-------- Emphasize green*
 // +--------- Emphasize blue
+// * Red and green are swapped on PAL and Dendy.
 pub enum PPUMASK {
     B = 1 << 7,
     G = 1 << 6,
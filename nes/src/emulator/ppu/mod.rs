@@ -15,6 +15,7 @@ use crate::emulator::clock;
 use crate::emulator::components::bitfield::BitField;
 use crate::emulator::components::latch;
 use crate::emulator::memory::{PPUMemory, Reader};
+use crate::emulator::region::Region;
 use crate::emulator::util;
 
 // Colours represented as a single byte:
@@ -56,22 +57,70 @@ impl<V: VideoOut> VideoOut for Rc<RefCell<V>> {
     }
 }
 
-#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum MirrorMode {
     SingleLower,
     SingleUpper,
     Vertical,
     Horizontal,
+    FourScreen,
+    // The CIRAM page used for each of the 4 nametables.
+    Custom([u8; 4]),
 }
 
+// What the PPU is about to fetch, for mappers which need to know more than the addresses alone
+// tell them.  Only sent while rendering is enabled, except for FrameEnd.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RenderEvent {
+    // Dot 1 of a visible scanline.  The rest of the scanline's background tiles come next.
+    ScanlineStart(u16),
+    // Dot 257.  Sprite patterns for the next scanline come next.
+    SpriteFetch { tall_sprites: bool },
+    // Dot 321.  The first 2 background tiles of the next scanline come next.
+    BackgroundPrefetch,
+    // Dot 337.  Only dummy nametable reads until the next scanline.
+    FetchEnd,
+    // The PPU has reached the post-render scanline.
+    FrameEnd,
+}
+
+// The nametable side of the PPU's memory.  Usually this just means choosing the mirroring, but
+// some mappers supply nametables of their own or need to follow along with rendering.
 pub trait Mirrorer {
     fn mirror_mode(&self) -> MirrorMode;
+
+    // Return Some to override a nametable read, which would otherwise go to CIRAM.
+    fn read_nametable(&mut self, _address: u16) -> Option<u8> {
+        None
+    }
+
+    // Return true if the write was handled, and shouldn't go to CIRAM.
+    fn write_nametable(&mut self, _address: u16, _byte: u8) -> bool {
+        false
+    }
+
+    // Return the page of CIRAM to use for a pattern table address, instead of CHR memory.
+    fn chr_ciram_page(&self, _address: u16) -> Option<u8> {
+        None
+    }
+
+    fn render_event(&mut self, _event: RenderEvent) {}
+}
+
+// Fixed mirroring, e.g. for four-screen cartridges which ignore the mapper.
+impl Mirrorer for MirrorMode {
+    fn mirror_mode(&self) -> MirrorMode {
+        *self
+    }
 }
 
 pub struct PPU {
     // Device to output rendered pixels to.
     output: Box<dyn VideoOut>,
 
+    // Determines the frame timing and the order of the emphasis bits.
+    region: Region,
+
     // --- Registers.
 
     // PPUCTRL
@@ -157,6 +206,8 @@ pub struct PPU {
 
     // There are 262 scanlines in total. 0-239 are visible, 240-260 occur durng vblank, and 261 is
     // idle.
+    // PAL and Dendy have 312 scanlines, with the extra 50 spent in vblank or, for Dendy, before
+    // vblank starts.  See region.rs.
     pub scanline: u16,
 
     // Each scanline takes 341 cycles to render.
@@ -199,9 +250,10 @@ impl clock::Ticker for PPU {
 }
 
 impl PPU {
-    pub fn new(memory: PPUMemory, output: Box<dyn VideoOut>) -> PPU {
+    pub fn new(memory: PPUMemory, output: Box<dyn VideoOut>, region: Region) -> PPU {
         PPU {
             output: output,
+            region,
             ppuctrl: BitField::new(),
             ppumask: BitField::new(),
             ppustatus: BitField::new(),
@@ -225,7 +277,7 @@ impl PPU {
             sprites_tile_low: [0; 8],
             sprites_attribute: [0; 8],
             sprites_x: [0; 8],
-            scanline: 261,
+            scanline: region.scanlines_per_frame() - 1,
             cycle: 0,
             tmp_pattern_coords: 0,
             tmp_attribute_byte: 0,
@@ -249,14 +301,16 @@ impl PPU {
 
     // Returns how many PPU cycles the tick took.
     fn tick_internal(&mut self) -> u16 {
+        let pre_render_scanline = self.pre_render_scanline();
         let cycles = match self.scanline {
-            0..=239 | 261 => self.tick_render_scanline(),
-            240 => self.tick_idle_scanline(),
-            241..=260 => self.tick_vblank_scanline(),
-            _ => panic!(
-                "Scanline index should never exceed 261.  Got {}.",
-                self.scanline
+            0..=239 => self.tick_render_scanline(),
+            s if s == pre_render_scanline => self.tick_render_scanline(),
+            s if s > pre_render_scanline => panic!(
+                "Scanline index should never exceed {}.  Got {}.",
+                pre_render_scanline, self.scanline
             ),
+            s if s < self.region.vblank_scanline() => self.tick_idle_scanline(),
+            _ => self.tick_vblank_scanline(),
         };
 
         self.cycle = self.cycle + cycles;
@@ -267,7 +321,7 @@ impl PPU {
 
         if self.cycle == 341 {
             self.cycle = 0;
-            self.scanline = (self.scanline + 1) % 262;
+            self.scanline = (self.scanline + 1) % self.region.scanlines_per_frame();
         }
 
         cycles
@@ -275,6 +329,20 @@ impl PPU {
 
     fn tick_render_scanline(&mut self) -> u16 {
         // Rendering stages.
+        if self.rendering_is_enabled() {
+            match self.cycle {
+                1 if self.scanline <= 239 => self
+                    .memory
+                    .render_event(RenderEvent::ScanlineStart(self.scanline)),
+                257 => self.memory.render_event(RenderEvent::SpriteFetch {
+                    tall_sprites: self.ppuctrl.is_set(flags::PPUCTRL::H),
+                }),
+                321 => self.memory.render_event(RenderEvent::BackgroundPrefetch),
+                337 => self.memory.render_event(RenderEvent::FetchEnd),
+                _ => (),
+            }
+        }
+
         let cycles = match self.cycle {
             // Cycle 0 is an idle cycle.
             0 => self.tick_idle_cycle(),
@@ -301,7 +369,7 @@ impl PPU {
 
         // Sprite evaluation.
         // Does not occur on the pre-render scanline or if rendering totally disabled.
-        if self.scanline != 261 && self.rendering_is_enabled() {
+        if self.scanline != self.pre_render_scanline() && self.rendering_is_enabled() {
             self.sprite_evaluation();
         }
 
@@ -309,7 +377,7 @@ impl PPU {
         self.handle_scrolling();
 
         // On dot 1 of the pre-render scanline, clear vblank flag and sprite overflow flag.
-        if self.scanline == 261 && self.cycle == 1 {
+        if self.scanline == self.pre_render_scanline() && self.cycle == 1 {
             self.ppustatus.clear(flags::PPUSTATUS::V);
             self.ppustatus.clear(flags::PPUSTATUS::O);
             self.ppustatus.clear(flags::PPUSTATUS::S);
@@ -319,13 +387,17 @@ impl PPU {
     }
 
     fn tick_idle_scanline(&mut self) -> u16 {
+        if self.scanline == 240 {
+            self.memory.render_event(RenderEvent::FrameEnd);
+        }
+
         // PPU does nothing on the idle scanline.
         // Just idle for 341 cycles.
         341
     }
 
     fn tick_vblank_scanline(&mut self) -> u16 {
-        if self.scanline == 241 && self.cycle == 1 {
+        if self.scanline == self.region.vblank_scanline() && self.cycle == 1 {
             // Set VBlank flag.
             self.ppustatus.set(flags::PPUSTATUS::V);
         }
@@ -347,8 +419,8 @@ impl PPU {
         self.fetch_tile_data();
 
         // Actually render and emit one pixel.
-        // Unless this is scanline 261, which is just a dummy scanline.
-        if self.scanline != 261 {
+        // Unless this is the pre-render scanline, which is just a dummy scanline.
+        if self.scanline != self.pre_render_scanline() {
             let pixel = self.render_pixel();
             self.output.emit(pixel);
         }
@@ -513,10 +585,16 @@ impl PPU {
             colour_byte &= 0x30;
         }
 
+        let mut em_r = self.ppumask.is_set(flags::PPUMASK::R);
+        let mut em_g = self.ppumask.is_set(flags::PPUMASK::G);
+        if self.region.swaps_emphasis() {
+            std::mem::swap(&mut em_r, &mut em_g);
+        }
+
         Colour {
             byte: colour_byte,
-            em_r: self.ppumask.is_set(flags::PPUMASK::R),
-            em_g: self.ppumask.is_set(flags::PPUMASK::G),
+            em_r,
+            em_g,
             em_b: self.ppumask.is_set(flags::PPUMASK::B),
         }
     }
@@ -527,12 +605,12 @@ impl PPU {
             0 => self.sprite_reset_state(),
             // These 2 phases do not occur on the pre-render scanline.
             1..=64 => {
-                if self.scanline != 261 {
+                if self.scanline != self.pre_render_scanline() {
                     self.sprite_init_cycle()
                 }
             }
             65..=256 => {
-                if self.scanline != 261 {
+                if self.scanline != self.pre_render_scanline() {
                     self.sprite_evaluation_cycle()
                 }
             }
@@ -751,7 +829,7 @@ impl PPU {
 
         // If rendering is enabled, between dots 280 to 304 of the pre-render scanline, the PPU repeatedly copies the
         // vertical bits from t to v.
-        if self.scanline == 261 && self.cycle >= 280 && self.cycle <= 304 {
+        if self.scanline == self.pre_render_scanline() && self.cycle >= 280 && self.cycle <= 304 {
             let vertical_bitmask = 0b1111011_11100000;
             self.v = self.v & !vertical_bitmask;
             self.v = self.v | (self.t & vertical_bitmask);
@@ -893,6 +971,10 @@ impl PPU {
     }
 
     // Utility methods to query internal state.
+    fn pre_render_scanline(&self) -> u16 {
+        self.region.scanlines_per_frame() - 1
+    }
+
     fn rendering_is_enabled(&self) -> bool {
         self.ppumask.is_set(flags::PPUMASK::S) || self.ppumask.is_set(flags::PPUMASK::BG)
     }
@@ -1,17 +1,23 @@
 mod background;
 mod data;
+mod timing;
 
 use crate::emulator::memory;
 use crate::emulator::memory::Writer;
 use crate::emulator::ppu::{Colour, MirrorMode, Mirrorer, PPU, VideoOut};
+use crate::emulator::region::Region;
 
 fn new_ppu(output: Box<dyn VideoOut>) -> PPU {
+    new_ppu_with_region(output, Region::NTSC)
+}
+
+fn new_ppu_with_region(output: Box<dyn VideoOut>, region: Region) -> PPU {
     let ppu_memory = memory::PPUMemory::new(
         Box::new(memory::Memory::new_ram(0x2000)),
         Box::new(DummyMirrorer {}),
         Box::new(memory::Memory::new_ram(0x2000)),
     );
-    PPU::new(ppu_memory, output)
+    PPU::new(ppu_memory, output, region)
 }
 
 fn load_data_into_vram(ppu: &mut PPU, addr: u16, bytes: &[u8]) {
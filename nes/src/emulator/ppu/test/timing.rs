@@ -0,0 +1,83 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::emulator::clock::Ticker;
+use crate::emulator::memory::Writer;
+use crate::emulator::ppu::test::new_ppu_with_region;
+use crate::emulator::ppu::{Colour, VideoOut, flags};
+use crate::emulator::region::Region;
+
+struct EmphasisCapture {
+    em_r: bool,
+    em_g: bool,
+}
+
+impl VideoOut for EmphasisCapture {
+    fn emit(&mut self, c: Colour) {
+        self.em_r = c.em_r;
+        self.em_g = c.em_g;
+    }
+}
+
+fn new_capture() -> Rc<RefCell<EmphasisCapture>> {
+    Rc::new(RefCell::new(EmphasisCapture {
+        em_r: false,
+        em_g: false,
+    }))
+}
+
+fn frame_cycles(region: Region) -> u32 {
+    let mut ppu = new_ppu_with_region(Box::new(new_capture()), region);
+    let start = ppu.scanline;
+
+    let mut cycles = ppu.tick();
+    while ppu.scanline != start || ppu.cycle != 0 {
+        cycles += ppu.tick();
+    }
+    cycles
+}
+
+fn vblank_scanline(region: Region) -> u16 {
+    let mut ppu = new_ppu_with_region(Box::new(new_capture()), region);
+    loop {
+        // The rest of the scanline is skipped in the same tick that sets the flag.
+        let scanline = ppu.scanline;
+        ppu.tick();
+        if ppu.ppustatus.is_set(flags::PPUSTATUS::V) {
+            return scanline;
+        }
+    }
+}
+
+#[test]
+fn test_frame_length() {
+    assert_eq!(frame_cycles(Region::NTSC), 262 * 341);
+    assert_eq!(frame_cycles(Region::PAL), 312 * 341);
+    assert_eq!(frame_cycles(Region::Dendy), 312 * 341);
+}
+
+#[test]
+fn test_vblank_start() {
+    assert_eq!(vblank_scanline(Region::NTSC), 241);
+    assert_eq!(vblank_scanline(Region::PAL), 241);
+    assert_eq!(vblank_scanline(Region::Dendy), 291);
+}
+
+#[test]
+fn test_emphasis_swap() {
+    for (region, swapped) in [(Region::NTSC, false), (Region::PAL, true)] {
+        let capture = new_capture();
+        let mut ppu = new_ppu_with_region(Box::new(capture.clone()), region);
+
+        // PPUMASK.  Enable background and set the "red" emphasis bit.
+        ppu.write(0x2001, 0b0010_1010);
+
+        // Render through to the first visible pixel.
+        while ppu.scanline != 0 || ppu.cycle < 2 {
+            ppu.tick();
+        }
+
+        assert_eq!(capture.borrow().em_r, !swapped);
+        assert_eq!(capture.borrow().em_g, swapped);
+    }
+}
@@ -0,0 +1,117 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::emulator::ines::Timing;
+use crate::emulator::{
+    NES_APU_CLOCK_FACTOR, NES_CPU_CLOCK_FACTOR, NES_MASTER_CLOCK_HZ, NES_PPU_CLOCK_FACTOR,
+};
+
+// The TV system the console was built for.
+// This determines the master clock speed, how it is divided between the CPU, PPU and APU, and the
+// number of scanlines in each frame.
+//
+//          Master clock    CPU   PPU   APU   Scanlines   VBlank starts
+// NTSC     21.477272 MHz   /12   /4    /24   262         241
+// PAL      26.601712 MHz   /16   /5    /32   312         241
+// Dendy    26.601712 MHz   /15   /5    /30   312         291
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub enum Region {
+    NTSC,
+    PAL,
+    Dendy,
+}
+
+impl Region {
+    pub fn master_clock_hz(&self) -> u64 {
+        match self {
+            Region::NTSC => NES_MASTER_CLOCK_HZ,
+            Region::PAL | Region::Dendy => 26_601_712,
+        }
+    }
+
+    pub fn cpu_clock_factor(&self) -> u32 {
+        match self {
+            Region::NTSC => NES_CPU_CLOCK_FACTOR,
+            Region::PAL => 16,
+            Region::Dendy => 15,
+        }
+    }
+
+    pub fn ppu_clock_factor(&self) -> u32 {
+        match self {
+            Region::NTSC => NES_PPU_CLOCK_FACTOR,
+            Region::PAL | Region::Dendy => 5,
+        }
+    }
+
+    // The APU always runs at half the CPU clock.
+    pub fn apu_clock_factor(&self) -> u32 {
+        match self {
+            Region::NTSC => NES_APU_CLOCK_FACTOR,
+            _ => self.cpu_clock_factor() * 2,
+        }
+    }
+
+    pub fn scanlines_per_frame(&self) -> u16 {
+        match self {
+            Region::NTSC => 262,
+            Region::PAL | Region::Dendy => 312,
+        }
+    }
+
+    // Dendy keeps the NTSC vblank length, and pads out the extra scanlines before vblank instead so
+    // that NTSC games written around NMI timing still work.
+    pub fn vblank_scanline(&self) -> u16 {
+        match self {
+            Region::NTSC | Region::PAL => 241,
+            Region::Dendy => 291,
+        }
+    }
+
+    // PAL-derived PPUs swap the meaning of the red and green emphasis bits in PPUMASK.
+    pub fn swaps_emphasis(&self) -> bool {
+        match self {
+            Region::NTSC => false,
+            Region::PAL | Region::Dendy => true,
+        }
+    }
+}
+
+// Multi-region games run fine on an NTSC console.
+impl From<Timing> for Region {
+    fn from(timing: Timing) -> Region {
+        match timing {
+            Timing::NTSC | Timing::MultiRegion => Region::NTSC,
+            Timing::PAL => Region::PAL,
+            Timing::Dendy => Region::Dendy,
+        }
+    }
+}
+
+impl FromStr for Region {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Region, String> {
+        match s.to_ascii_lowercase().as_str() {
+            "ntsc" => Ok(Region::NTSC),
+            "pal" => Ok(Region::PAL),
+            "dendy" => Ok(Region::Dendy),
+            _ => Err(format!(
+                "unknown region \"{}\" (expected ntsc, pal or dendy)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Region::NTSC => write!(f, "NTSC"),
+            Region::PAL => write!(f, "PAL"),
+            Region::Dendy => write!(f, "Dendy"),
+        }
+    }
+}
@@ -0,0 +1,233 @@
+use std::collections::VecDeque;
+
+use crate::emulator::NES;
+use crate::emulator::state::{NESState, SaveState};
+
+// Rewind support.
+//
+// Snapshots are taken with `SaveState::freeze` and serialized with bincode, so successive
+// snapshots have the same layout.  Every `keyframe_interval` snapshots one is stored whole as a
+// keyframe, and the rest are stored as a run-length encoded XOR against it.  Relatively little
+// changes from frame to frame, so these deltas are much smaller.
+//
+// When the memory budget is exceeded, the oldest keyframe is discarded along with its deltas.
+
+pub struct Rewinder {
+    groups: VecDeque<SnapshotGroup>,
+    budget_bytes: usize,
+    used_bytes: usize,
+
+    // Take a snapshot every this many frames.
+    capture_interval: u32,
+    frames_since_capture: u32,
+
+    // Store a whole snapshot every this many snapshots.  1 disables delta compression.
+    keyframe_interval: usize,
+}
+
+// A keyframe and the deltas which are based on it, oldest first.
+struct SnapshotGroup {
+    keyframe: Vec<u8>,
+    deltas: Vec<Vec<u8>>,
+}
+
+impl SnapshotGroup {
+    fn size(&self) -> usize {
+        self.keyframe.len() + self.deltas.iter().map(|d| d.len()).sum::<usize>()
+    }
+}
+
+impl Rewinder {
+    pub fn new(budget_bytes: usize, capture_interval: u32, keyframe_interval: usize) -> Rewinder {
+        Rewinder {
+            groups: VecDeque::new(),
+            budget_bytes,
+            used_bytes: 0,
+            capture_interval: capture_interval.max(1),
+            frames_since_capture: 0,
+            keyframe_interval: keyframe_interval.max(1),
+        }
+    }
+
+    // Call at the start of each frame while running normally.
+    // Takes a snapshot if one is due.
+    pub fn frame(&mut self, nes: &mut NES) {
+        if self.frames_since_capture == 0 {
+            self.capture(nes);
+        }
+        self.frames_since_capture = (self.frames_since_capture + 1) % self.capture_interval;
+    }
+
+    pub fn capture(&mut self, nes: &mut NES) {
+        let state = nes.freeze();
+        let snapshot = bincode::serialize(&state).expect("Failed to serialize rewind snapshot");
+        self.push(snapshot);
+    }
+
+    // Restores the most recent snapshot, and forgets it, so that calling this repeatedly steps
+    // further back.  Returns false if there's nothing left to rewind to.
+    //
+    // Since snapshots are taken at the start of a frame, running a frame after this redraws the
+    // screen as it was.
+    pub fn rewind(&mut self, nes: &mut NES) -> bool {
+        match self.pop() {
+            None => false,
+            Some(snapshot) => {
+                let state: NESState =
+                    bincode::deserialize(&snapshot).expect("Corrupt rewind snapshot");
+                nes.hydrate(state);
+                self.frames_since_capture = 0;
+                true
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.groups.clear();
+        self.used_bytes = 0;
+        self.frames_since_capture = 0;
+    }
+
+    // Number of snapshots held.
+    pub fn len(&self) -> usize {
+        self.groups.iter().map(|g| g.deltas.len() + 1).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    pub fn used_bytes(&self) -> usize {
+        self.used_bytes
+    }
+
+    fn push(&mut self, snapshot: Vec<u8>) {
+        let keyframe_interval = self.keyframe_interval;
+        match self.groups.back_mut() {
+            Some(group)
+                if group.deltas.len() + 1 < keyframe_interval
+                    && group.keyframe.len() == snapshot.len() =>
+            {
+                let delta = encode_delta(&group.keyframe, &snapshot);
+                self.used_bytes += delta.len();
+                group.deltas.push(delta);
+            }
+            _ => {
+                self.used_bytes += snapshot.len();
+                self.groups.push_back(SnapshotGroup {
+                    keyframe: snapshot,
+                    deltas: vec![],
+                });
+            }
+        }
+
+        // Always keep the newest group, even if it's over budget on its own.
+        while self.used_bytes > self.budget_bytes && self.groups.len() > 1 {
+            if let Some(group) = self.groups.pop_front() {
+                self.used_bytes -= group.size();
+            }
+        }
+    }
+
+    fn pop(&mut self) -> Option<Vec<u8>> {
+        let group = self.groups.back_mut()?;
+        match group.deltas.pop() {
+            Some(delta) => {
+                self.used_bytes -= delta.len();
+                Some(decode_delta(&group.keyframe, &delta))
+            }
+            None => {
+                let group = self.groups.pop_back()?;
+                self.used_bytes -= group.keyframe.len();
+                Some(group.keyframe)
+            }
+        }
+    }
+}
+
+// Deltas are a sequence of:
+//   - number of unchanged bytes (varint)
+//   - number of changed bytes (varint)
+//   - the changed bytes, XORed with the base.
+fn encode_delta(base: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut delta = vec![];
+    let mut ix = 0;
+    while ix < data.len() {
+        let unchanged_start = ix;
+        while ix < data.len() && data[ix] == base[ix] {
+            ix += 1;
+        }
+        let changed_start = ix;
+        while ix < data.len() && data[ix] != base[ix] {
+            ix += 1;
+        }
+
+        write_varint(&mut delta, changed_start - unchanged_start);
+        write_varint(&mut delta, ix - changed_start);
+        for (byte, base_byte) in data[changed_start..ix].iter().zip(&base[changed_start..ix]) {
+            delta.push(byte ^ base_byte);
+        }
+    }
+    delta
+}
+
+fn decode_delta(base: &[u8], delta: &[u8]) -> Vec<u8> {
+    let mut data = base.to_vec();
+    let mut delta_ix = 0;
+    let mut ix = 0;
+    while delta_ix < delta.len() {
+        ix += read_varint(delta, &mut delta_ix);
+        let changed = read_varint(delta, &mut delta_ix);
+        for _ in 0..changed {
+            data[ix] ^= delta[delta_ix];
+            ix += 1;
+            delta_ix += 1;
+        }
+    }
+    data
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(data: &[u8], ix: &mut usize) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = data[*ix];
+        *ix += 1;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return value;
+        }
+        shift += 7;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::emulator::rewind::{decode_delta, encode_delta};
+
+    #[test]
+    fn test_delta_round_trip() {
+        let base: Vec<u8> = (0..1000).map(|ix| (ix % 251) as u8).collect();
+        let mut data = base.clone();
+        data[0] = 0xFF;
+        data[300..500]
+            .iter_mut()
+            .for_each(|b| *b = b.wrapping_add(1));
+        data[999] = 0x00;
+
+        let delta = encode_delta(&base, &data);
+        assert!(delta.len() < 250);
+        assert_eq!(decode_delta(&base, &delta), data);
+
+        assert_eq!(encode_delta(&base, &base), vec![0xE8, 0x07, 0x00]);
+        assert_eq!(decode_delta(&base, &encode_delta(&base, &base)), base);
+    }
+}
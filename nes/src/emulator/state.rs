@@ -3,6 +3,8 @@
 
 use serde::{Deserialize, Serialize};
 
+use crate::emulator::apu::{OpllEnvelopePhase, SequenceMode};
+use crate::emulator::mappers::EepromMode;
 use crate::emulator::ppu::MirrorMode;
 
 pub trait SaveState<'de, T: Serialize + Deserialize<'de>> {
@@ -12,8 +14,10 @@ pub trait SaveState<'de, T: Serialize + Deserialize<'de>> {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct NESState {
+    pub clock: ClockState,
     pub cpu: CPUState,
     pub ppu: PPUState,
+    pub apu: APUState,
     pub mapper: MapperState,
     pub ram: MemoryState,
     pub sram: MemoryState,
@@ -23,6 +27,12 @@ pub struct NESState {
     pub joy2: ControllerState,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ClockState {
+    pub elapsed_cycles: u64,
+    pub next_tick_cycles: Vec<u64>,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct MemoryState {
     #[serde(with = "serde_bytes")]
@@ -40,6 +50,7 @@ pub struct CPUState {
     pub dec_arith_on: bool,
     pub irq_flip_flop: bool,
     pub nmi_flip_flop: bool,
+    pub data_bus: u8,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -96,6 +107,99 @@ pub struct PPUState {
     pub bus_latch: u8,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct APUState {
+    pub sequence_mode: SequenceMode,
+    pub cycle_counter: u64,
+    pub irq_flag: bool,
+    pub irq_enabled: bool,
+    pub pulse_1: PulseState,
+    pub pulse_2: PulseState,
+    pub triangle: TriangleState,
+    pub noise: NoiseState,
+    pub dmc: DMCState,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DividerState {
+    pub period: u16,
+    pub counter: u16,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EnvelopeState {
+    pub start_flag: bool,
+    pub decay_level: u8,
+    pub divider: DividerState,
+    pub loop_flag: bool,
+    pub constant_volume: bool,
+    pub volume: u8,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SweepState {
+    pub enabled: bool,
+    pub divider: DividerState,
+    pub negate_flag: bool,
+    pub shift_count: u8,
+    pub reload_flag: bool,
+    pub target_period: u16,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PulseState {
+    pub enabled: bool,
+    pub timer: DividerState,
+    pub length: u8,
+    pub halt_length: bool,
+    pub sequence: u8,
+    pub sequence_ix: u8,
+    pub envelope: EnvelopeState,
+    pub sweep: SweepState,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TriangleState {
+    pub enabled: bool,
+    pub timer: DividerState,
+    pub linear: u8,
+    pub length: u8,
+    pub halt_length: bool,
+    pub linear_reload_flag: bool,
+    pub linear_reload_value: u8,
+    pub control_flag: bool,
+    pub sequence_ix: u8,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NoiseState {
+    pub enabled: bool,
+    pub envelope: EnvelopeState,
+    pub shift_register: u16,
+    pub length: u8,
+    pub halt_length: bool,
+    pub mode: bool,
+    pub timer: DividerState,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DMCState {
+    pub enabled: bool,
+    pub irq_enabled: bool,
+    pub loop_flag: bool,
+    pub silence_flag: bool,
+    pub timer: DividerState,
+    pub volume: u8,
+    pub sample_addr: u16,
+    pub sample_len: u16,
+    pub sample_buffer: Option<u8>,
+    pub current_addr: u16,
+    pub bytes_remaining: u16,
+    pub irq_flag: bool,
+    pub shift_register: u8,
+    pub bits_remaining: u8,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ScreenState {
     pub scanline: u32,
@@ -115,8 +219,39 @@ pub enum MapperState {
     UXROM(UXROMState),
     CNROM(CNROMState),
     MMC3(MMC3State),
+    MMC5(MMC5State),
     AXROM(AXROMState),
+    MMC2(MMC2State),
+    MMC4(MMC4State),
     ColorDreams(ColorDreamsState),
+    VRC4(VRC4State),
+    VRC6(VRC6State),
+    FME7(FME7State),
+    Namco163(Namco163State),
+    VRC7(VRC7State),
+    FDS(FDSState),
+    NSFPlayer(NSFPlayerState),
+    BandaiFCG(BandaiFCGState),
+    GXROM(GXROMState),
+    BNROM(BNROMState),
+    Camerica(CamericaState),
+    Bandai74161(Bandai74161State),
+    JalecoJF16(JalecoJF16State),
+    JalecoJF05(JalecoJF05State),
+    JalecoJF11(JalecoJF11State),
+    UNROM180(UNROM180State),
+    Sunsoft1(Sunsoft1State),
+    Quattro(QuattroState),
+    Custom(CustomMapperState),
+}
+
+// For mappers from outside this crate, which serialise their own state.  The id is up to the mapper,
+// e.g. its mapper number, so that it can check the state is its own.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CustomMapperState {
+    pub id: u16,
+    #[serde(with = "serde_bytes")]
+    pub data: Vec<u8>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -129,7 +264,9 @@ pub struct MMC1State {
     pub chr_bank_2: u8,
     pub prg_offsets: Vec<u32>,
     pub chr_offsets: Vec<u32>,
+    pub chr_a12: bool,
     pub chr_mem: MemoryState,
+    pub work_ram: MemoryState,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -146,7 +283,7 @@ pub struct CNROMState {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct MMC3State {
-    pub bank_registers: Vec<usize>,
+    pub bank_registers: Vec<u8>,
     pub bank_select: usize,
     pub prg_inversion: bool,
     pub chr_inversion: bool,
@@ -158,6 +295,49 @@ pub struct MMC3State {
     pub ppu_a12: bool,
     pub ppu_a12_low_counter: u8,
     pub mirror_mode: MirrorMode,
+    pub prg_ram_enabled: bool,
+    pub prg_ram_protect: u8,
+    pub chr_mem: MemoryState,
+    pub chr_ram: MemoryState,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MMC5State {
+    pub prg_mode: u8,
+    pub prg_banks: Vec<u8>,
+    pub prg_ram_protect: Vec<u8>,
+    pub chr_mode: u8,
+    pub chr_banks: Vec<u16>,
+    pub chr_upper: u8,
+    pub last_chr_set_b: bool,
+    pub exram_mode: u8,
+    pub nametable_mapping: u8,
+    pub fill_tile: u8,
+    pub fill_attribute: u8,
+    pub split_control: u8,
+    pub split_scroll: u8,
+    pub split_bank: u8,
+    pub irq_target: u8,
+    pub irq_enabled: bool,
+    pub irq_pending: bool,
+    pub in_frame: bool,
+    pub scanline_counter: u8,
+    pub multiplicand: u8,
+    pub multiplier: u8,
+    pub fetching_background: bool,
+    pub fetching_sprites: bool,
+    pub tall_sprites: bool,
+    pub tile_column: u8,
+    pub expect_attribute: bool,
+    pub split_y: u8,
+    pub in_split: bool,
+    pub ext_attribute: u8,
+    pub pulse_1: PulseState,
+    pub pulse_2: PulseState,
+    pub pcm: u8,
+    pub pcm_control: u8,
+    pub audio_frame_counter: u16,
+    pub exram: MemoryState,
     pub chr_mem: MemoryState,
 }
 
@@ -168,9 +348,347 @@ pub struct AXROMState {
     pub chr_mem: MemoryState,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MMC2State {
+    pub prg_bank: u8,
+    pub chr_banks: Vec<u8>,
+    pub chr_latches: Vec<bool>,
+    pub mirror_mode: MirrorMode,
+    pub chr_mem: MemoryState,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MMC4State {
+    pub prg_bank: u8,
+    pub chr_banks: Vec<u8>,
+    pub chr_latches: Vec<bool>,
+    pub mirror_mode: MirrorMode,
+    pub chr_mem: MemoryState,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ColorDreamsState {
     pub prg_bank: u8,
     pub chr_bank: u8,
     pub chr_mem: MemoryState,
 }
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VrcIrqState {
+    pub latch: u8,
+    pub counter: u8,
+    pub prescaler: i16,
+    pub enabled: bool,
+    pub enable_after_ack: bool,
+    pub cycle_mode: bool,
+    pub pending: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VRC4State {
+    pub prg_banks: Vec<u8>,
+    pub prg_swap: bool,
+    pub chr_banks: Vec<u16>,
+    pub mirror_mode: MirrorMode,
+    pub irq: VrcIrqState,
+    pub chr_mem: MemoryState,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VRC6PulseState {
+    pub volume: u8,
+    pub duty: u8,
+    pub ignore_duty: bool,
+    pub period: u16,
+    pub enabled: bool,
+    pub counter: u16,
+    pub step: u8,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VRC6SawState {
+    pub rate: u8,
+    pub period: u16,
+    pub enabled: bool,
+    pub counter: u16,
+    pub step: u8,
+    pub accumulator: u8,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VRC6State {
+    pub prg_16k_bank: u8,
+    pub prg_8k_bank: u8,
+    pub chr_banks: Vec<u8>,
+    pub mirror_mode: MirrorMode,
+    pub prg_ram_enabled: bool,
+    pub irq: VrcIrqState,
+    pub pulse_1: VRC6PulseState,
+    pub pulse_2: VRC6PulseState,
+    pub saw: VRC6SawState,
+    pub halt_audio: bool,
+    pub frequency_shift: u8,
+    pub chr_mem: MemoryState,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Sunsoft5BState {
+    pub register: u8,
+    pub registers: Vec<u8>,
+    pub prescaler: u8,
+    pub tone_counters: Vec<u16>,
+    pub tone_outputs: Vec<bool>,
+    pub noise_counter: u8,
+    pub noise_lfsr: u32,
+    pub envelope_counter: u16,
+    pub envelope_step: u8,
+    pub envelope_rising: bool,
+    pub envelope_holding: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FME7State {
+    pub command: u8,
+    pub chr_banks: Vec<u8>,
+    pub prg_banks: Vec<u8>,
+    pub mirror_mode: MirrorMode,
+    pub irq_enabled: bool,
+    pub irq_counter_enabled: bool,
+    pub irq_counter: u16,
+    pub irq_pending: bool,
+    pub audio: Sunsoft5BState,
+    pub chr_mem: MemoryState,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Namco163State {
+    pub prg_banks: Vec<u8>,
+    pub chr_banks: Vec<u8>,
+    pub ciram_disabled: Vec<bool>,
+    pub nametable_banks: Vec<u8>,
+    pub sound_disabled: bool,
+    pub irq_counter: u16,
+    pub irq_enabled: bool,
+    pub irq_pending: bool,
+    pub ram: MemoryState,
+    pub ram_address: u8,
+    pub auto_increment: bool,
+    pub audio_cycles: u8,
+    pub current_channel: u8,
+    pub channel_outputs: Vec<i16>,
+    pub chr_mem: MemoryState,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OpllOperatorState {
+    pub phase: u32,
+    pub envelope_phase: OpllEnvelopePhase,
+    pub envelope_level: f32,
+    pub output: f32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OpllChannelState {
+    pub fnum: u16,
+    pub block: u8,
+    pub key_on: bool,
+    pub sustain: bool,
+    pub instrument: u8,
+    pub volume: u8,
+    pub modulator: OpllOperatorState,
+    pub carrier: OpllOperatorState,
+    pub feedback: Vec<f32>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OpllState {
+    pub custom_patch: Vec<u8>,
+    pub channels: Vec<OpllChannelState>,
+    pub am_counter: u32,
+    pub pm_counter: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VRC7State {
+    pub prg_banks: Vec<u8>,
+    pub chr_banks: Vec<u8>,
+    pub mirror_mode: MirrorMode,
+    pub prg_ram_enabled: bool,
+    pub irq: VrcIrqState,
+    pub audio_silenced: bool,
+    pub audio_register: u8,
+    pub opll: OpllState,
+    pub audio_cycles: u8,
+    pub previous_sample: f32,
+    pub latest_sample: f32,
+    pub chr_mem: MemoryState,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FDSEnvelopeState {
+    pub disabled: bool,
+    pub increase: bool,
+    pub speed: u8,
+    pub gain: u8,
+    pub timer: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FDSAudioState {
+    pub wave_table: Vec<u8>,
+    pub wave_write_enabled: bool,
+    pub master_volume: u8,
+    pub envelope_speed: u8,
+    pub volume_envelope: FDSEnvelopeState,
+    pub wave_frequency: u16,
+    pub wave_halted: bool,
+    pub envelopes_halted: bool,
+    pub wave_accumulator: u32,
+    pub wave_position: u8,
+    pub mod_envelope: FDSEnvelopeState,
+    pub mod_table: Vec<u8>,
+    pub mod_frequency: u16,
+    pub mod_halted: bool,
+    pub mod_accumulator: u32,
+    pub mod_position: u8,
+    pub mod_counter: i8,
+    pub level: u8,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FDSState {
+    pub sides: Vec<Vec<u8>>,
+    pub disk_side: Option<usize>,
+    pub disk_registers_enabled: bool,
+    pub sound_registers_enabled: bool,
+    pub timer_reload: u16,
+    pub timer_counter: u16,
+    pub timer_enabled: bool,
+    pub timer_repeat: bool,
+    pub timer_irq: bool,
+    pub motor_on: bool,
+    pub reset_transfer: bool,
+    pub read_mode: bool,
+    pub mirror_mode: MirrorMode,
+    pub crc_control: bool,
+    pub transfer_enabled: bool,
+    pub disk_irq_enabled: bool,
+    pub position: usize,
+    pub delay: u32,
+    pub end_of_head: bool,
+    pub scanning: bool,
+    pub gap_ended: bool,
+    pub read_data: u8,
+    pub write_data: u8,
+    pub transfer_complete: bool,
+    pub disk_irq: bool,
+    pub audio: FDSAudioState,
+    pub chr_mem: MemoryState,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NSFPlayerState {
+    pub song: usize,
+    pub banks: Vec<u8>,
+    pub playing: bool,
+    pub play_timer: u64,
+    pub play_pending: bool,
+    pub chips: Vec<MapperState>,
+    pub chr_mem: MemoryState,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BandaiFCGState {
+    pub chr_banks: Vec<u8>,
+    pub prg_bank: u8,
+    pub mirror_mode: MirrorMode,
+    pub prg_ram_enabled: bool,
+    pub irq_enabled: bool,
+    pub irq_counter: u16,
+    pub irq_latch: u16,
+    pub irq_pending: bool,
+    pub eeprom: Option<EepromState>,
+    pub chr_mem: MemoryState,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EepromState {
+    pub mode: EepromMode,
+    pub next_mode: EepromMode,
+    pub address: u8,
+    pub data: u8,
+    pub bit: u8,
+    pub acking: bool,
+    pub scl: bool,
+    pub sda: bool,
+    pub output: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GXROMState {
+    pub prg_bank: u8,
+    pub chr_bank: u8,
+    pub chr_mem: MemoryState,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BNROMState {
+    pub prg_bank: u8,
+    pub chr_banks: Vec<u8>,
+    pub chr_mem: MemoryState,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CamericaState {
+    pub mirror_mode: MirrorMode,
+    pub prg_bank: u8,
+    pub chr_mem: MemoryState,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Bandai74161State {
+    pub mirror_mode: MirrorMode,
+    pub prg_bank: u8,
+    pub chr_bank: u8,
+    pub chr_mem: MemoryState,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct JalecoJF16State {
+    pub mirror_mode: MirrorMode,
+    pub prg_bank: u8,
+    pub chr_bank: u8,
+    pub chr_mem: MemoryState,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct JalecoJF05State {
+    pub chr_bank: u8,
+    pub chr_mem: MemoryState,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct JalecoJF11State {
+    pub prg_bank: u8,
+    pub chr_bank: u8,
+    pub chr_mem: MemoryState,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UNROM180State {
+    pub prg_bank: u8,
+    pub chr_mem: MemoryState,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Sunsoft1State {
+    pub chr_banks: Vec<u8>,
+    pub chr_mem: MemoryState,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct QuattroState {
+    pub prg_block: u8,
+    pub prg_bank: u8,
+    pub chr_mem: MemoryState,
+}
@@ -0,0 +1,53 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::emulator::NES;
+use crate::emulator::apu::AudioOut;
+use crate::emulator::ines;
+use crate::emulator::io;
+use crate::emulator::io::event::EventBus;
+use crate::emulator::state::SaveState;
+
+use crate::emulator::test::run_for;
+use crate::emulator::test::test_resource_path;
+
+struct AudioCapture {
+    samples: Vec<f32>,
+}
+
+impl AudioOut for AudioCapture {
+    fn emit(&mut self, sample: f32) {
+        self.samples.push(sample);
+    }
+}
+
+fn prepare_audio_test(name: &str) -> (NES, Rc<RefCell<AudioCapture>>) {
+    let rom = ines::ROM::load(test_resource_path(name)).unwrap();
+    let event_bus = Rc::new(RefCell::new(EventBus::new()));
+    let screen = Rc::new(RefCell::new(io::Screen::new()));
+    let audio = Rc::new(RefCell::new(AudioCapture { samples: vec![] }));
+    let nes = NES::new(event_bus, screen, audio.clone(), rom).unwrap();
+    (nes, audio)
+}
+
+// The blargg test roms beep when they finish.  Save state in the middle of the beep and check
+// the restored emulator produces exactly the same audio as the original.
+#[test]
+fn test_apu_savestate() {
+    let rom = "ppu_sprite_hit/rom_singles/01-basics.nes";
+    let (mut nes, audio) = prepare_audio_test(rom);
+    run_for(&mut nes, 15_500_000);
+    let state = nes.freeze();
+
+    let (mut nes_2, audio_2) = prepare_audio_test(rom);
+    nes_2.hydrate(state);
+
+    audio.borrow_mut().samples.clear();
+    run_for(&mut nes, 1_000_000);
+    run_for(&mut nes_2, 1_000_000);
+
+    let expected = &audio.borrow().samples;
+    let actual = &audio_2.borrow().samples;
+    assert!(expected.iter().any(|s| *s != 0.0));
+    assert_eq!(expected, actual);
+}
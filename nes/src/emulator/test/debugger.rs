@@ -0,0 +1,223 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::emulator::NES;
+use crate::emulator::cpu::Flag;
+use crate::emulator::debugger::{Access, BreakReason, Watchpoint};
+use crate::emulator::ines::ROM;
+use crate::emulator::io;
+use crate::emulator::io::event::EventBus;
+
+// A little program which repeatedly calls a subroutine.
+const PROGRAM: [(u16, &[u8]); 10] = [
+    (0x8000, &[0xA2, 0x00]),       // LDX #$00
+    (0x8002, &[0x20, 0x10, 0x80]), // JSR $8010
+    (0x8005, &[0x8D, 0x00, 0x02]), // STA $0200
+    (0x8008, &[0xE8]),             // INX
+    (0x8009, &[0x4C, 0x02, 0x80]), // JMP $8002
+    (0x8010, &[0xA9, 0x42]),       // LDA #$42
+    (0x8012, &[0x85, 0x10]),       // STA $10
+    (0x8014, &[0xA5, 0x11]),       // LDA $11
+    (0x8016, &[0x60]),             // RTS
+    (0x8020, &[0x40]),             // RTI
+];
+
+fn new_nes() -> NES {
+    let mut data = vec![b'N', b'E', b'S', 0x1A, 0x01, 0x01, 0x00, 0x00];
+    data.resize(16 + 0x4000 + 0x2000, 0);
+
+    for (address, bytes) in PROGRAM.iter() {
+        let offset = 16 + (*address as usize - 0x8000);
+        data[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    // NMI, reset and IRQ vectors.
+    data[16 + 0x3FFA..16 + 0x4000].copy_from_slice(&[0x20, 0x80, 0x00, 0x80, 0x20, 0x80]);
+
+    NES::new(
+        Rc::new(RefCell::new(EventBus::new())),
+        Rc::new(RefCell::new(io::Screen::new())),
+        io::nop::DummyAudio {},
+        ROM::from_bytes(data).unwrap(),
+    )
+    .unwrap()
+}
+
+fn run_to(nes: &mut NES, pc: u16) {
+    nes.add_breakpoint(pc);
+    assert_eq!(
+        nes.run_until_break(100_000).1,
+        Some(BreakReason::Breakpoint(pc))
+    );
+    nes.remove_breakpoint(pc);
+}
+
+fn pc(nes: &NES) -> u16 {
+    nes.cpu.borrow().registers().pc
+}
+
+#[test]
+fn test_breakpoint() {
+    let mut nes = new_nes();
+    nes.add_breakpoint(0x8010);
+
+    let (cycles, reason) = nes.run_until_break(100_000);
+    assert!(cycles > 0);
+    assert_eq!(reason, Some(BreakReason::Breakpoint(0x8010)));
+    assert_eq!(pc(&nes), 0x8010);
+    assert_eq!(nes.cpu.borrow().registers().x, 0);
+
+    // Continuing shouldn't break again until the next time round the loop.
+    assert_eq!(
+        nes.run_until_break(100_000).1,
+        Some(BreakReason::Breakpoint(0x8010))
+    );
+    assert_eq!(nes.cpu.borrow().registers().x, 1);
+
+    assert!(nes.remove_breakpoint(0x8010));
+    assert_eq!(nes.run_until_break(100_000).1, None);
+}
+
+#[test]
+fn test_tick_multi_stops_at_break() {
+    let mut nes = new_nes();
+    nes.add_breakpoint(0x8010);
+    while nes.take_break().is_none() {
+        nes.tick_multi(100);
+    }
+    assert_eq!(pc(&nes), 0x8010);
+}
+
+#[test]
+fn test_step() {
+    let mut nes = new_nes();
+    run_to(&mut nes, 0x8010);
+
+    nes.step();
+    assert_eq!(nes.run_until_break(100_000).1, Some(BreakReason::Step));
+    assert_eq!(pc(&nes), 0x8012);
+
+    nes.step();
+    assert_eq!(nes.run_until_break(100_000).1, Some(BreakReason::Step));
+    assert_eq!(pc(&nes), 0x8014);
+}
+
+#[test]
+fn test_step_over() {
+    let mut nes = new_nes();
+    run_to(&mut nes, 0x8002);
+
+    nes.step_over();
+    assert_eq!(nes.run_until_break(100_000).1, Some(BreakReason::Step));
+    assert_eq!(pc(&nes), 0x8005);
+    assert_eq!(nes.debug_read(0x0010), 0x42);
+
+    // Not a subroutine call, so just a normal step.
+    nes.step_over();
+    assert_eq!(nes.run_until_break(100_000).1, Some(BreakReason::Step));
+    assert_eq!(pc(&nes), 0x8008);
+}
+
+#[test]
+fn test_step_out() {
+    let mut nes = new_nes();
+    run_to(&mut nes, 0x8012);
+
+    nes.step_out();
+    assert_eq!(nes.run_until_break(100_000).1, Some(BreakReason::Step));
+    assert_eq!(pc(&nes), 0x8005);
+}
+
+#[test]
+fn test_run_to_scanline() {
+    let mut nes = new_nes();
+    nes.run_to_scanline(100);
+    assert_eq!(nes.run_until_break(1_000_000).1, Some(BreakReason::Step));
+    assert_eq!(nes.ppu.borrow().scanline, 100);
+}
+
+#[test]
+fn test_watchpoints() {
+    let mut nes = new_nes();
+
+    nes.add_watchpoint(Watchpoint::new(0x0010, 0x0010, Access::Write));
+    assert_eq!(
+        nes.run_until_break(100_000).1,
+        Some(BreakReason::Watchpoint {
+            address: 0x0010,
+            access: Access::Write,
+            value: 0x42,
+        })
+    );
+    assert_eq!(pc(&nes), 0x8014);
+    assert!(nes.remove_watchpoints(0x0010));
+
+    nes.add_watchpoint(Watchpoint::new(0x0011, 0x0011, Access::Read));
+    assert_eq!(
+        nes.run_until_break(100_000).1,
+        Some(BreakReason::Watchpoint {
+            address: 0x0011,
+            access: Access::Read,
+            value: 0x00,
+        })
+    );
+    assert_eq!(pc(&nes), 0x8016);
+
+    // The debugger's own reads don't count.
+    nes.debug_read(0x0011);
+    assert_eq!(nes.take_break(), None);
+    assert!(nes.remove_watchpoints(0x0011));
+
+    nes.add_watchpoint(Watchpoint::new(0x8005, 0x8009, Access::Execute));
+    assert_eq!(
+        nes.run_until_break(100_000).1,
+        Some(BreakReason::Watchpoint {
+            address: 0x8005,
+            access: Access::Execute,
+            value: 0x00,
+        })
+    );
+    assert_eq!(pc(&nes), 0x8005);
+}
+
+#[test]
+fn test_read_watchpoint_ignores_fetches() {
+    let mut nes = new_nes();
+
+    // The program only executes these addresses, it never reads them as data.
+    nes.add_watchpoint(Watchpoint::new(0x8000, 0x8016, Access::Read));
+    run_to(&mut nes, 0x8012);
+    nes.step_over();
+    assert_eq!(nes.run_until_break(100_000).1, Some(BreakReason::Step));
+    assert_eq!(nes.debug_read(0x8010), 0xA9);
+    assert_eq!(nes.run_until_break(100_000).1, None);
+}
+
+#[test]
+fn test_edit_registers() {
+    let mut nes = new_nes();
+    run_to(&mut nes, 0x8008);
+
+    let mut registers = nes.cpu.borrow().registers();
+    registers.x = 0x41;
+    nes.cpu.borrow_mut().set_registers(registers);
+
+    nes.step();
+    nes.run_until_break(100_000);
+    assert_eq!(nes.cpu.borrow().registers().x, 0x42);
+
+    nes.cpu.borrow_mut().set_flag(Flag::C, true);
+    assert!(nes.cpu.borrow().is_flag_set(Flag::C));
+    nes.cpu.borrow_mut().set_flag(Flag::C, false);
+    assert!(!nes.cpu.borrow().is_flag_set(Flag::C));
+}
+
+#[test]
+fn test_format_instruction() {
+    let mut nes = new_nes();
+    run_to(&mut nes, 0x8010);
+    assert!(
+        nes.debug_format_instruction()
+            .starts_with("8010  A9 42     LDA #$42")
+    );
+}
@@ -9,3 +9,12 @@ fn test_instr_test_v5_official_only() {
     assert_eq!(status, 0x00);
     assert_eq!(output, "All 16 tests passed\n\n\n");
 }
+
+#[test]
+fn test_instr_test_v5_all_instrs() {
+    let path = test_resource_path("instr_test-v5/all_instrs.nes");
+    let (status, output) = load_and_run_blargg_test_rom_with_cycles(path, 3_000_000_000);
+
+    assert_eq!(status, 0x00);
+    assert_eq!(output, "All 16 tests passed\n\n\n");
+}
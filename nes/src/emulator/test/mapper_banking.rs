@@ -0,0 +1,1862 @@
+// -- Bank switching tests for mappers which don't have test ROMs.
+// -- Each bank of the PRG and CHR data is filled with its own bank number.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::emulator::mappers;
+use crate::emulator::memory::{
+    ChrMapper, Mapper, MapperRef, Memory, PALETTE_RAM_SIZE, PPUMemory, Reader, Writer,
+};
+use crate::emulator::ppu::{MirrorMode, RenderEvent};
+use crate::emulator::state::SaveState;
+
+fn banked_rom(size: usize, bank_size: usize) -> Memory {
+    Memory::new_rom((0..size).map(|ix| (ix / bank_size) as u8).collect())
+}
+
+fn mmc1(prg_size: usize, variant: mappers::MMC1Variant) -> mappers::MMC1 {
+    mappers::MMC1::new(
+        banked_rom(prg_size, 0x4000),
+        Memory::new_ram(0x2000),
+        variant,
+    )
+}
+
+// Writes a register one bit at a time, as the MMC1 expects.
+fn mmc1_write(mapper: &mut mappers::MMC1, address: u16, byte: u8) {
+    for bit in 0..5 {
+        mapper.write_prg(address, byte >> bit);
+        mapper.clock_cpu(1);
+    }
+}
+
+#[test]
+fn test_mmc1_variants() {
+    let variant = mappers::MMC1Variant::from_ines;
+    assert_eq!(variant(0, 0x40000, 0x2000), mappers::MMC1Variant::Standard);
+    assert_eq!(variant(5, 0x8000, 0x2000), mappers::MMC1Variant::SEROM);
+    assert_eq!(variant(0, 0x40000, 0x4000), mappers::MMC1Variant::SOROM);
+    assert_eq!(variant(0, 0x80000, 0x2000), mappers::MMC1Variant::SUROM);
+    assert_eq!(variant(0, 0x80000, 0x8000), mappers::MMC1Variant::SXROM);
+}
+
+#[test]
+fn test_mmc1_consecutive_writes() {
+    let mut mapper = mmc1(0x20000, mappers::MMC1Variant::Standard);
+
+    // A read-modify-write instruction writes twice, and only the first is seen.
+    mapper.write_prg(0xE000, 0x80);
+    mapper.write_prg(0xE000, 0x01);
+    mapper.clock_cpu(6);
+    mmc1_write(&mut mapper, 0xE000, 3);
+    assert_eq!(mapper.read_prg(0x8000), 3);
+    assert_eq!(mapper.read_prg(0xC000), 7);
+}
+
+#[test]
+fn test_mmc1_surom() {
+    let mut mapper = mmc1(0x80000, mappers::MMC1Variant::SUROM);
+    mmc1_write(&mut mapper, 0xE000, 2);
+    assert_eq!(mapper.read_prg(0x8000), 2);
+    assert_eq!(mapper.read_prg(0xC000), 15);
+
+    // Bit 4 of the CHR bank chooses the 256kb half, including the fixed bank.
+    mmc1_write(&mut mapper, 0xA000, 0x10);
+    assert_eq!(mapper.read_prg(0x8000), 18);
+    assert_eq!(mapper.read_prg(0xC000), 31);
+
+    // In 4kb CHR mode, it comes from whichever bank the PPU last used.
+    mmc1_write(&mut mapper, 0x8000, 0x1C);
+    mapper.read_chr(0x1000);
+    assert_eq!(mapper.read_prg(0xC000), 15);
+    mapper.read_chr(0x0000);
+    assert_eq!(mapper.read_prg(0xC000), 31);
+
+    assert_state_restores(
+        &mut mapper,
+        mmc1(0x80000, mappers::MMC1Variant::SUROM),
+        &[0x8000, 0xC000],
+    );
+}
+
+#[test]
+fn test_mmc1_sxrom() {
+    let mut mapper = mmc1(0x80000, mappers::MMC1Variant::SXROM);
+    let prg_ram = Rc::new(RefCell::new(Memory::new_ram(0x8000)));
+    assert!(mapper.attach_prg_ram(prg_ram.clone()));
+
+    // Bits 2-3 of the CHR bank choose the PRG RAM bank.
+    mmc1_write(&mut mapper, 0xA000, 0x18);
+    mapper.write_expansion(0x6001, 0x42);
+    assert_eq!(prg_ram.borrow().get(0x4001), 0x42);
+    assert_eq!(mapper.read_expansion(0x6001), Some(0x42));
+    assert_eq!(mapper.read_prg(0xC000), 31);
+
+    // Bit 4 of the PRG bank disables it.
+    mmc1_write(&mut mapper, 0xE000, 0x10);
+    assert_eq!(mapper.read_expansion(0x6001), None);
+    mapper.write_expansion(0x6001, 0x43);
+    assert_eq!(prg_ram.borrow().get(0x4001), 0x42);
+}
+
+#[test]
+fn test_mmc1_sorom() {
+    let mut mapper = mmc1(0x20000, mappers::MMC1Variant::SOROM);
+    let prg_ram = Rc::new(RefCell::new(Memory::new_ram(0x4000)));
+    assert!(mapper.attach_prg_ram(prg_ram.clone()));
+
+    // The first bank isn't battery-backed, so isn't kept in PRG RAM.
+    mapper.write_expansion(0x6000, 1);
+    mmc1_write(&mut mapper, 0xA000, 0x08);
+    mapper.write_expansion(0x6000, 2);
+    assert_eq!(prg_ram.borrow().get(0x0000), 2);
+    assert_eq!(mapper.read_expansion(0x6000), Some(2));
+    mmc1_write(&mut mapper, 0xA000, 0x00);
+    assert_eq!(mapper.read_expansion(0x6000), Some(1));
+
+    // Other boards can disable PRG RAM too.
+    let mut mapper = mmc1(0x20000, mappers::MMC1Variant::Standard);
+    let prg_ram = Rc::new(RefCell::new(Memory::new_ram(0x2000)));
+    assert!(mapper.attach_prg_ram(prg_ram));
+    mapper.write_expansion(0x6000, 3);
+    assert_eq!(mapper.read_expansion(0x6000), Some(3));
+    mmc1_write(&mut mapper, 0xE000, 0x10);
+    assert_eq!(mapper.read_expansion(0x6000), None);
+    mapper.write_expansion(0x6000, 4);
+    mmc1_write(&mut mapper, 0xE000, 0x00);
+    assert_eq!(mapper.read_expansion(0x6000), Some(3));
+}
+
+#[test]
+fn test_mmc1_serom() {
+    let mut mapper = mmc1(0x8000, mappers::MMC1Variant::SEROM);
+    mmc1_write(&mut mapper, 0xE000, 1);
+    assert_eq!(mapper.read_prg(0x8000), 0);
+    assert_eq!(mapper.read_prg(0xC000), 1);
+}
+
+#[test]
+fn test_mmc2() {
+    let mut mapper = mappers::MMC2::new(banked_rom(0x20000, 0x2000), banked_rom(0x20000, 0x1000));
+
+    // Last 3 PRG banks are fixed.
+    mapper.write_prg(0xA000, 5);
+    assert_eq!(mapper.read_prg(0x8000), 5);
+    assert_eq!(mapper.read_prg(0xA000), 13);
+    assert_eq!(mapper.read_prg(0xC000), 14);
+    assert_eq!(mapper.read_prg(0xFFFF), 15);
+
+    mapper.write_prg(0xB000, 1);
+    mapper.write_prg(0xC000, 2);
+    mapper.write_prg(0xD000, 3);
+    mapper.write_prg(0xE000, 4);
+
+    // Latches start out at $FE.
+    assert_eq!(mapper.read_chr(0x0000), 2);
+    assert_eq!(mapper.read_chr(0x1000), 4);
+
+    // The fetch which flips the latch still sees the old bank.
+    assert_eq!(mapper.read_chr(0x0FD8), 2);
+    assert_eq!(mapper.read_chr(0x0000), 1);
+
+    // Latch 0 only flips on exactly $0FE8.
+    mapper.read_chr(0x0FE9);
+    assert_eq!(mapper.read_chr(0x0000), 1);
+    mapper.read_chr(0x0FE8);
+    assert_eq!(mapper.read_chr(0x0000), 2);
+
+    // Latch 1 flips on any row of the tile.
+    mapper.read_chr(0x1FDB);
+    assert_eq!(mapper.read_chr(0x1000), 3);
+    mapper.read_chr(0x1FEF);
+    assert_eq!(mapper.read_chr(0x1000), 4);
+
+    assert_eq!(mapper.mirror_mode(), MirrorMode::Vertical);
+    mapper.write_prg(0xF000, 1);
+    assert_eq!(mapper.mirror_mode(), MirrorMode::Horizontal);
+
+    mapper.read_chr(0x1FD8);
+    let state = mapper.freeze();
+    let mut mapper_2 = mappers::MMC2::new(banked_rom(0x20000, 0x2000), banked_rom(0x20000, 0x1000));
+    mapper_2.hydrate(state);
+    assert_eq!(mapper_2.read_prg(0x8000), 5);
+    assert_eq!(mapper_2.read_chr(0x0000), 2);
+    assert_eq!(mapper_2.read_chr(0x1000), 3);
+    assert_eq!(mapper_2.mirror_mode(), MirrorMode::Horizontal);
+}
+
+#[test]
+fn test_mmc4() {
+    let mut mapper = mappers::MMC4::new(banked_rom(0x20000, 0x4000), banked_rom(0x20000, 0x1000));
+
+    // Last PRG bank is fixed.
+    mapper.write_prg(0xA000, 3);
+    assert_eq!(mapper.read_prg(0x8000), 3);
+    assert_eq!(mapper.read_prg(0xBFFF), 3);
+    assert_eq!(mapper.read_prg(0xC000), 7);
+
+    mapper.write_prg(0xB000, 1);
+    mapper.write_prg(0xC000, 2);
+    mapper.write_prg(0xD000, 3);
+    mapper.write_prg(0xE000, 4);
+
+    // Unlike MMC2, latch 0 flips on any row of the tile.
+    assert_eq!(mapper.read_chr(0x0FDC), 2);
+    assert_eq!(mapper.read_chr(0x0000), 1);
+    mapper.read_chr(0x0FEA);
+    assert_eq!(mapper.read_chr(0x0000), 2);
+
+    mapper.read_chr(0x1FD8);
+    assert_eq!(mapper.read_chr(0x1000), 3);
+
+    let state = mapper.freeze();
+    let mut mapper_2 = mappers::MMC4::new(banked_rom(0x20000, 0x4000), banked_rom(0x20000, 0x1000));
+    mapper_2.hydrate(state);
+    assert_eq!(mapper_2.read_prg(0x8000), 3);
+    assert_eq!(mapper_2.read_chr(0x1000), 3);
+}
+
+fn mmc3(mapper_number: u16, submapper: u8, chr_mem: Memory) -> mappers::MMC3 {
+    mappers::MMC3::new(
+        banked_rom(0x20000, 0x2000),
+        chr_mem,
+        mappers::MMC3Variant::from_ines(mapper_number, submapper),
+        MirrorMode::Vertical,
+    )
+}
+
+// Clocks the scanline counter with a rising edge on PPU A12.
+fn mmc3_scanline(mapper: &mut mappers::MMC3) {
+    for _ in 0..16 {
+        mapper.read_chr(0x0000);
+    }
+    mapper.read_chr(0x1000);
+}
+
+#[test]
+fn test_mmc3_irq_revisions() {
+    for (submapper, repeats) in [(0, true), (4, false)] {
+        let mut mapper = mmc3(4, submapper, banked_rom(0x20000, 0x400));
+        mapper.write_prg(0xC000, 0);
+        mapper.write_prg(0xC001, 0);
+        mapper.write_prg(0xE001, 0);
+
+        // Both revisions fire when reloaded with 0.
+        mmc3_scanline(&mut mapper);
+        assert!(mapper.irq_triggered());
+        mapper.write_prg(0xE000, 0);
+        mapper.write_prg(0xE001, 0);
+
+        // But only the MMC3B/C keeps firing every scanline.
+        mmc3_scanline(&mut mapper);
+        assert_eq!(mapper.irq_triggered(), repeats);
+        mapper.write_prg(0xE000, 0);
+        mapper.write_prg(0xE001, 0);
+
+        // Both fire when counting down to 0.
+        mapper.write_prg(0xC000, 2);
+        mapper.write_prg(0xC001, 0);
+        mmc3_scanline(&mut mapper);
+        mmc3_scanline(&mut mapper);
+        assert!(!mapper.irq_triggered());
+        mmc3_scanline(&mut mapper);
+        assert!(mapper.irq_triggered());
+    }
+}
+
+#[test]
+fn test_mmc3_txsrom() {
+    let mut mapper = mmc3(118, 0, banked_rom(0x20000, 0x400));
+    mapper.write_prg(0x8000, 0);
+    mapper.write_prg(0x8001, 0x84);
+    mapper.write_prg(0x8000, 1);
+    mapper.write_prg(0x8001, 0x02);
+    assert_eq!(mapper.read_chr(0x0000), 4);
+    assert_eq!(mapper.read_chr(0x0800), 2);
+    assert_eq!(mapper.mirror_mode(), MirrorMode::Custom([1, 1, 0, 0]));
+
+    // $A000 does nothing, as the CHR banks decide the mirroring.
+    mapper.write_prg(0xA000, 1);
+    assert_eq!(mapper.mirror_mode(), MirrorMode::Custom([1, 1, 0, 0]));
+
+    // With CHR inversion, the 1kb banks are used instead.
+    for (register, byte) in [(2, 0x80), (3, 0x00), (4, 0x00), (5, 0x81)] {
+        mapper.write_prg(0x8000, 0x80 | register);
+        mapper.write_prg(0x8001, byte);
+    }
+    assert_eq!(mapper.read_chr(0x0C00), 1);
+    assert_eq!(mapper.mirror_mode(), MirrorMode::Custom([1, 0, 0, 1]));
+    assert_state_restores(
+        &mut mapper,
+        mmc3(118, 0, banked_rom(0x20000, 0x400)),
+        &[0x0000, 0x0C00, 0x1000],
+    );
+}
+
+#[test]
+fn test_mmc3_tqrom() {
+    let mut mapper = mmc3(119, 0, banked_rom(0x10000, 0x400));
+    mapper.write_prg(0x8000, 2);
+    mapper.write_prg(0x8001, 0x05);
+    mapper.write_prg(0x8000, 3);
+    mapper.write_prg(0x8001, 0x41);
+    assert_eq!(mapper.read_chr(0x1000), 5);
+
+    // CHR RAM is writeable, CHR ROM isn't.
+    mapper.write_chr(0x1000, 0xAA);
+    mapper.write_chr(0x1400, 0x55);
+    assert_eq!(mapper.read_chr(0x1000), 5);
+    assert_eq!(mapper.read_chr(0x1400), 0x55);
+
+    mapper.write_prg(0x8000, 2);
+    mapper.write_prg(0x8001, 0x41);
+    assert_eq!(mapper.read_chr(0x1000), 0x55);
+    assert_state_restores(
+        &mut mapper,
+        mmc3(119, 0, banked_rom(0x10000, 0x400)),
+        &[0x1000, 0x1400],
+    );
+}
+
+#[test]
+fn test_mmc6_prg_ram() {
+    let mut mapper = mmc3(4, 1, banked_rom(0x20000, 0x400));
+    assert!(mapper.attach_prg_ram(Rc::new(RefCell::new(Memory::new_ram(0x2000)))));
+    assert_eq!(mapper.read_expansion(0x7000), None);
+
+    // Protection can't be changed until the RAM is enabled.
+    mapper.write_prg(0xA001, 0x30);
+    mapper.write_prg(0x8000, 0x20);
+    assert_eq!(mapper.read_expansion(0x7000), None);
+
+    // Only the lower half is enabled, so the upper half reads as 0.
+    mapper.write_prg(0xA001, 0x30);
+    mapper.write_expansion(0x7000, 0x12);
+    mapper.write_expansion(0x7200, 0x34);
+    assert_eq!(mapper.read_expansion(0x7000), Some(0x12));
+    assert_eq!(mapper.read_expansion(0x7200), Some(0));
+    assert_eq!(mapper.read_expansion(0x6000), None);
+
+    // The 1kb of RAM is mirrored through $7000-$7FFF.
+    mapper.write_prg(0xA001, 0xF0);
+    mapper.write_expansion(0x7200, 0x34);
+    assert_eq!(mapper.read_expansion(0x7E00), Some(0x34));
+    assert_eq!(mapper.read_expansion(0x7C00), Some(0x12));
+
+    // Read only.
+    mapper.write_prg(0xA001, 0xA0);
+    mapper.write_expansion(0x7000, 0x56);
+    assert_eq!(mapper.read_expansion(0x7000), Some(0x12));
+
+    // Disabling the RAM also protects it.
+    mapper.write_prg(0x8000, 0x00);
+    mapper.write_prg(0x8000, 0x20);
+    assert_eq!(mapper.read_expansion(0x7000), None);
+
+    // Other MMC3 boards leave PRG RAM alone.
+    let mut mapper = mmc3(4, 0, banked_rom(0x20000, 0x400));
+    assert!(!mapper.attach_prg_ram(Rc::new(RefCell::new(Memory::new_ram(0x2000)))));
+}
+
+#[test]
+fn test_namco108() {
+    let mut mapper = mmc3(206, 0, banked_rom(0x10000, 0x400));
+    assert_eq!(mapper.mirror_mode(), MirrorMode::Vertical);
+
+    // No PRG or CHR inversion, and only 4 bits of PRG bank.
+    mapper.write_prg(0x8000, 0xC6);
+    mapper.write_prg(0x8001, 0x13);
+    mapper.write_prg(0x8000, 0xC0);
+    mapper.write_prg(0x8001, 0x05);
+    assert_eq!(mapper.read_prg(0x8000), 3);
+    assert_eq!(mapper.read_prg(0xC000), 14);
+    assert_eq!(mapper.read_prg(0xE000), 15);
+    assert_eq!(mapper.read_chr(0x0000), 4);
+    assert_eq!(mapper.read_chr(0x0400), 5);
+
+    // No mirroring control or IRQs.
+    mapper.write_prg(0xA000, 1);
+    mapper.write_prg(0xC000, 0);
+    mapper.write_prg(0xC001, 0);
+    mapper.write_prg(0xE001, 0);
+    mmc3_scanline(&mut mapper);
+    assert_eq!(mapper.mirror_mode(), MirrorMode::Vertical);
+    assert!(!mapper.irq_triggered());
+}
+
+#[test]
+fn test_namco108_variants() {
+    // NAMCOT-3446 has 2kb banks in R2-R5.
+    let mut mapper = mmc3(76, 0, banked_rom(0x20000, 0x800));
+    mapper.write_prg(0x8000, 2);
+    mapper.write_prg(0x8001, 3);
+    mapper.write_prg(0x8000, 5);
+    mapper.write_prg(0x8001, 0x3F);
+    assert_eq!(mapper.read_chr(0x0000), 3);
+    assert_eq!(mapper.read_chr(0x07FF), 3);
+    assert_eq!(mapper.read_chr(0x1800), 0x3F);
+
+    // NAMCOT-3443 puts R2-R5 in the upper 64kb of CHR.
+    let mut mapper = mmc3(88, 0, banked_rom(0x20000, 0x400));
+    mapper.write_prg(0x8000, 0);
+    mapper.write_prg(0x8001, 0x42);
+    mapper.write_prg(0x8000, 2);
+    mapper.write_prg(0x8001, 0x01);
+    assert_eq!(mapper.read_chr(0x0000), 2);
+    assert_eq!(mapper.read_chr(0x1000), 0x41);
+
+    // NAMCOT-3425 mirrors with bit 5 of R0 and R1, which is past the end of its 32kb of CHR.
+    let mut mapper = mmc3(95, 0, banked_rom(0x8000, 0x400));
+    mapper.write_prg(0x8000, 0);
+    mapper.write_prg(0x8001, 0x20);
+    mapper.write_prg(0x8000, 1);
+    mapper.write_prg(0x8001, 0x02);
+    assert_eq!(mapper.read_chr(0x0000), 0);
+    assert_eq!(mapper.read_chr(0x0800), 2);
+    assert_eq!(mapper.mirror_mode(), MirrorMode::Custom([1, 1, 0, 0]));
+}
+
+fn mmc5() -> mappers::MMC5 {
+    let mut mapper = mappers::MMC5::new(banked_rom(0x40000, 0x2000), banked_rom(0x40000, 0x400));
+    mapper.attach_prg_ram(Rc::new(RefCell::new(Memory::new_ram(0x10000))));
+    mapper
+}
+
+#[test]
+fn test_mmc5_prg_banking() {
+    let mut mapper = mmc5();
+
+    // Starts in 8kb mode, with the last bank at $E000.
+    assert_eq!(mapper.read_prg(0xFFFF), 31);
+    mapper.write_expansion(0x5114, 0x81);
+    mapper.write_expansion(0x5115, 0x82);
+    mapper.write_expansion(0x5116, 0x83);
+    mapper.write_expansion(0x5117, 0x04);
+    assert_eq!(mapper.read_prg(0x8000), 1);
+    assert_eq!(mapper.read_prg(0xA000), 2);
+    assert_eq!(mapper.read_prg(0xC000), 3);
+    assert_eq!(mapper.read_prg(0xE000), 4);
+
+    // 16kb + 8kb + 8kb.  The 16kb bank ignores the low bit.
+    mapper.write_expansion(0x5100, 2);
+    assert_eq!(mapper.read_prg(0x8000), 2);
+    assert_eq!(mapper.read_prg(0xA000), 3);
+    assert_eq!(mapper.read_prg(0xC000), 3);
+    assert_eq!(mapper.read_prg(0xE000), 4);
+
+    // 2x 16kb.
+    mapper.write_expansion(0x5100, 1);
+    assert_eq!(mapper.read_prg(0xC000), 4);
+    assert_eq!(mapper.read_prg(0xE000), 5);
+
+    // 32kb.
+    mapper.write_expansion(0x5100, 0);
+    mapper.write_expansion(0x5117, 0x07);
+    assert_eq!(mapper.read_prg(0x8000), 4);
+    assert_eq!(mapper.read_prg(0xFFFF), 7);
+
+    // PRG RAM is write protected until $5102 and $5103 are set.
+    mapper.write_expansion(0x5113, 2);
+    mapper.write_expansion(0x6000, 0x42);
+    assert_eq!(mapper.read_expansion(0x6000), Some(0));
+    mapper.write_expansion(0x5102, 2);
+    mapper.write_expansion(0x5103, 1);
+    mapper.write_expansion(0x6000, 0x42);
+    assert_eq!(mapper.read_expansion(0x6000), Some(0x42));
+
+    // RAM can be banked into $8000-$DFFF too.
+    mapper.write_expansion(0x5100, 3);
+    mapper.write_expansion(0x5115, 0x02);
+    assert_eq!(mapper.read_prg(0xA000), 0x42);
+    mapper.write_prg(0xA001, 0x43);
+    mapper.write_expansion(0x5113, 2);
+    assert_eq!(mapper.read_expansion(0x6001), Some(0x43));
+
+    let state = mapper.freeze();
+    let mut mapper_2 = mmc5();
+    mapper_2.hydrate(state);
+    assert_eq!(mapper_2.read_prg(0x8000), 1);
+    assert_eq!(mapper_2.read_prg(0xE000), 7);
+}
+
+#[test]
+fn test_mmc5_chr_banking() {
+    let mut mapper = mmc5();
+    mapper.write_expansion(0x5101, 3);
+    for ix in 0..8 {
+        mapper.write_expansion(0x5120 + ix, 0x10 + ix as u8);
+    }
+    for ix in 0..4 {
+        mapper.write_expansion(0x5128 + ix, 0x20 + ix as u8);
+    }
+
+    // Set B was written last, so it's used everywhere, mirrored into both pattern tables.
+    assert_eq!(mapper.read_chr(0x0400), 0x21);
+    assert_eq!(mapper.read_chr(0x1C00), 0x23);
+
+    // Set A was written last.
+    mapper.write_expansion(0x5127, 0x17);
+    assert_eq!(mapper.read_chr(0x0400), 0x11);
+    assert_eq!(mapper.read_chr(0x1C00), 0x17);
+
+    // With 8x16 sprites, sprites use set A and the background set B.
+    mapper.render_event(RenderEvent::SpriteFetch { tall_sprites: true });
+    assert_eq!(mapper.read_chr(0x1400), 0x15);
+    mapper.render_event(RenderEvent::BackgroundPrefetch);
+    assert_eq!(mapper.read_chr(0x1400), 0x21);
+    mapper.render_event(RenderEvent::FrameEnd);
+    assert_eq!(mapper.read_chr(0x1400), 0x15);
+
+    // 4kb banks.
+    mapper.write_expansion(0x5101, 1);
+    mapper.write_expansion(0x5123, 0x02);
+    assert_eq!(mapper.read_chr(0x0000), 2 * 4);
+    assert_eq!(mapper.read_chr(0x1000), 0x17 * 4);
+
+    // Upper bank bits from $5130, for CHR ROM over 256kb.
+    let mut mapper = mappers::MMC5::new(banked_rom(0x40000, 0x2000), banked_rom(0x100000, 0x1000));
+    mapper.write_expansion(0x5101, 3);
+    mapper.write_expansion(0x5130, 1);
+    mapper.write_expansion(0x5120, 0x04);
+    assert_eq!(mapper.read_chr(0x0000), 0x41);
+}
+
+#[test]
+fn test_mmc5_nametables() {
+    let mut mapper = mmc5();
+
+    // CIRAM page 0, CIRAM page 1, ExRAM, fill.
+    mapper.write_expansion(0x5105, 0b11_10_01_00);
+    assert_eq!(mapper.mirror_mode(), MirrorMode::Custom([0, 1, 0, 1]));
+    mapper.write_expansion(0x5106, 0x33);
+    mapper.write_expansion(0x5107, 0x02);
+
+    assert_eq!(mapper.read_nametable(0x2000), None);
+    assert!(!mapper.write_nametable(0x2400, 0x01));
+
+    assert!(mapper.write_nametable(0x2805, 0x44));
+    assert_eq!(mapper.read_nametable(0x2805), Some(0x44));
+
+    assert!(mapper.write_nametable(0x2C00, 0x01));
+    assert_eq!(mapper.read_nametable(0x2C00), Some(0x33));
+    assert_eq!(mapper.read_nametable(0x2FC0), Some(0xAA));
+
+    // ExRAM is only readable by the CPU in the RAM modes.
+    assert_eq!(mapper.read_expansion(0x5C05), None);
+    mapper.write_expansion(0x5104, 2);
+    assert_eq!(mapper.read_expansion(0x5C05), Some(0x44));
+    mapper.write_expansion(0x5C06, 0x45);
+    assert_eq!(mapper.read_expansion(0x5C06), Some(0x45));
+
+    // Extended attributes: each tile's ExRAM byte gives its palette and 4kb CHR bank.
+    mapper.write_expansion(0x5C10, 0xC5);
+    mapper.write_expansion(0x5104, 1);
+    mapper.render_event(RenderEvent::BackgroundPrefetch);
+    assert_eq!(mapper.read_nametable(0x2010), None);
+    assert_eq!(mapper.read_nametable(0x23C4), Some(0xFF));
+    assert_eq!(mapper.read_chr(0x0123), 5 * 4);
+}
+
+#[test]
+fn test_mmc5_irq_and_multiplier() {
+    let mut mapper = mmc5();
+
+    mapper.write_expansion(0x5205, 200);
+    mapper.write_expansion(0x5206, 100);
+    assert_eq!(mapper.read_expansion(0x5205), Some((20000 & 0xFF) as u8));
+    assert_eq!(mapper.read_expansion(0x5206), Some((20000 >> 8) as u8));
+
+    mapper.write_expansion(0x5203, 3);
+    mapper.write_expansion(0x5204, 0x80);
+    for scanline in 0..3 {
+        mapper.render_event(RenderEvent::ScanlineStart(scanline));
+        assert!(!mapper.irq_triggered());
+    }
+    assert_eq!(mapper.read_expansion(0x5204), Some(0x40));
+    mapper.render_event(RenderEvent::ScanlineStart(3));
+    assert!(mapper.irq_triggered());
+
+    // Reading the status acknowledges the IRQ.
+    assert_eq!(mapper.read_expansion(0x5204), Some(0xC0));
+    assert!(!mapper.irq_triggered());
+
+    mapper.render_event(RenderEvent::FrameEnd);
+    assert_eq!(mapper.read_expansion(0x5204), Some(0x00));
+}
+
+fn vrc4(variant: mappers::VRC4Variant) -> mappers::VRC4 {
+    mappers::VRC4::new(
+        banked_rom(0x20000, 0x2000),
+        banked_rom(0x40000, 0x400),
+        variant,
+    )
+}
+
+#[test]
+fn test_vrc4_wiring() {
+    // The address of register 1 (the high half of CHR bank 0) and register 2 (CHR bank 1) on
+    // each board.
+    let boards = [
+        (mappers::VRC4Variant::VRC2b, 0xB001, 0xB002),
+        (mappers::VRC4Variant::VRC2c, 0xB002, 0xB001),
+        (mappers::VRC4Variant::VRC4a, 0xB002, 0xB004),
+        (mappers::VRC4Variant::VRC4b, 0xB002, 0xB001),
+        (mappers::VRC4Variant::VRC4c, 0xB040, 0xB080),
+        (mappers::VRC4Variant::VRC4d, 0xB008, 0xB004),
+        (mappers::VRC4Variant::VRC4e, 0xB004, 0xB008),
+        (mappers::VRC4Variant::VRC4f, 0xB001, 0xB002),
+        (mappers::VRC4Variant::from_ines(21, 0), 0xB040, 0xB004),
+        (mappers::VRC4Variant::from_ines(23, 0), 0xB004, 0xB002),
+        (mappers::VRC4Variant::from_ines(25, 0), 0xB008, 0xB001),
+    ];
+
+    for (variant, register_1, register_2) in boards.iter() {
+        let mut mapper = vrc4(*variant);
+        mapper.write_prg(0xB000, 0x03);
+        mapper.write_prg(*register_1, 0x01);
+        mapper.write_prg(*register_2, 0x05);
+        assert_eq!(mapper.read_chr(0x0000), 0x13, "{:?}", variant);
+        assert_eq!(mapper.read_chr(0x0400), 0x05, "{:?}", variant);
+    }
+
+    // VRC2a has A0 and A1 swapped, and ignores the lowest bit of CHR banks.
+    let mut mapper = vrc4(mappers::VRC4Variant::from_ines(22, 0));
+    mapper.write_prg(0xB000, 0x07);
+    mapper.write_prg(0xB002, 0x01);
+    assert_eq!(mapper.read_chr(0x0000), 0x0B);
+}
+
+#[test]
+fn test_vrc4_prg_banking() {
+    let mut mapper = vrc4(mappers::VRC4Variant::VRC4f);
+
+    mapper.write_prg(0x8000, 3);
+    mapper.write_prg(0xA000, 4);
+    assert_eq!(mapper.read_prg(0x8000), 3);
+    assert_eq!(mapper.read_prg(0xA000), 4);
+    assert_eq!(mapper.read_prg(0xC000), 14);
+    assert_eq!(mapper.read_prg(0xE000), 15);
+
+    // Swap mode exchanges $8000 and $C000.
+    mapper.write_prg(0x9002, 0x02);
+    assert_eq!(mapper.read_prg(0x8000), 14);
+    assert_eq!(mapper.read_prg(0xC000), 3);
+
+    mapper.write_prg(0x9000, 3);
+    assert_eq!(mapper.mirror_mode(), MirrorMode::SingleUpper);
+
+    // VRC2 only has 2 mirroring modes, and no swap mode.
+    let mut mapper = vrc4(mappers::VRC4Variant::VRC2b);
+    mapper.write_prg(0x8000, 3);
+    mapper.write_prg(0x9002, 0x03);
+    assert_eq!(mapper.mirror_mode(), MirrorMode::Horizontal);
+    assert_eq!(mapper.read_prg(0x8000), 3);
+}
+
+#[test]
+fn test_vrc4_irq() {
+    let mut mapper = vrc4(mappers::VRC4Variant::VRC4f);
+
+    // Cycle mode: counts up from the latch, and fires on overflow.
+    mapper.write_prg(0xF000, 0x0C);
+    mapper.write_prg(0xF001, 0x0F);
+    mapper.write_prg(0xF002, 0x07);
+    mapper.clock_cpu(3);
+    assert!(!mapper.irq_triggered());
+    mapper.clock_cpu(1);
+    assert!(mapper.irq_triggered());
+
+    // Acknowledging re-enables the IRQ if bit 0 of the control was set.
+    mapper.write_prg(0xF003, 0);
+    assert!(!mapper.irq_triggered());
+    mapper.clock_cpu(4);
+    assert!(mapper.irq_triggered());
+
+    let state = mapper.freeze();
+    let mut mapper_2 = vrc4(mappers::VRC4Variant::VRC4f);
+    mapper_2.hydrate(state);
+    assert!(mapper_2.irq_triggered());
+
+    // Scanline mode: 2 scanlines from $FE is 2 * 341 / 3 CPU cycles.
+    mapper.write_prg(0xF000, 0x0E);
+    mapper.write_prg(0xF002, 0x02);
+    assert!(!mapper.irq_triggered());
+    mapper.clock_cpu(227);
+    assert!(!mapper.irq_triggered());
+    mapper.clock_cpu(1);
+    assert!(mapper.irq_triggered());
+
+    // Without bit 0, acknowledging disables the IRQ.
+    mapper.write_prg(0xF003, 0);
+    mapper.clock_cpu(100_000);
+    assert!(!mapper.irq_triggered());
+}
+
+#[test]
+fn test_vrc6_banking() {
+    for (mapper_26, chr_register) in [(false, 0xD001), (true, 0xD002)] {
+        let mut mapper = mappers::VRC6::new(
+            banked_rom(0x40000, 0x2000),
+            banked_rom(0x40000, 0x400),
+            mapper_26,
+        );
+
+        mapper.write_prg(0x8000, 3);
+        mapper.write_prg(0xC000, 5);
+        assert_eq!(mapper.read_prg(0x8000), 6);
+        assert_eq!(mapper.read_prg(0xA000), 7);
+        assert_eq!(mapper.read_prg(0xC000), 5);
+        assert_eq!(mapper.read_prg(0xE000), 31);
+
+        mapper.write_prg(chr_register, 9);
+        assert_eq!(mapper.read_chr(0x0400), 9);
+        mapper.write_prg(0xE003, 200);
+        assert_eq!(mapper.read_chr(0x1C00), 200);
+
+        mapper.write_prg(0xB003, 0xA4);
+        assert_eq!(mapper.mirror_mode(), MirrorMode::Horizontal);
+        mapper.write_prg(0xB003, 0xAC);
+        assert_eq!(mapper.mirror_mode(), MirrorMode::SingleUpper);
+    }
+}
+
+#[test]
+fn test_vrc6_prg_ram() {
+    let mut mapper = mappers::VRC6::new(
+        banked_rom(0x40000, 0x2000),
+        banked_rom(0x40000, 0x400),
+        false,
+    );
+    let prg_ram = Rc::new(RefCell::new(Memory::new_ram(0x2000)));
+    assert!(mapper.attach_prg_ram(prg_ram.clone()));
+
+    // Bit 7 of $B003 enables PRG RAM.
+    mapper.write_expansion(0x6000, 1);
+    assert_eq!(mapper.read_expansion(0x6000), None);
+    mapper.write_prg(0xB003, 0x80);
+    mapper.write_expansion(0x6000, 2);
+    assert_eq!(mapper.read_expansion(0x6000), Some(2));
+    assert_eq!(prg_ram.borrow().get(0x0000), 2);
+
+    let state = mapper.freeze();
+    mapper.write_prg(0xB003, 0x00);
+    assert_eq!(mapper.read_expansion(0x6000), None);
+    mapper.hydrate(state);
+    assert_eq!(mapper.read_expansion(0x6000), Some(2));
+}
+
+#[test]
+fn test_vrc6_audio() {
+    let mut mapper = mappers::VRC6::new(
+        banked_rom(0x40000, 0x2000),
+        banked_rom(0x40000, 0x400),
+        false,
+    );
+    let levels = |mapper: &mut mappers::VRC6, n| {
+        (0..n)
+            .map(|_| (mapper.clock_audio() / 0.00752).round() as u8)
+            .collect::<Vec<u8>>()
+    };
+
+    // Pulse 1 with duty 1 (2/16) at volume 15, stepping every 2 CPU cycles, i.e. every APU cycle.
+    mapper.write_prg(0x9000, 0x1F);
+    mapper.write_prg(0x9001, 0x01);
+    mapper.write_prg(0x9002, 0x80);
+    let output = levels(&mut mapper, 16);
+    assert_eq!(output.iter().filter(|&&l| l == 15).count(), 2);
+    assert_eq!(output.iter().filter(|&&l| l == 0).count(), 14);
+
+    // Mode bit outputs the volume constantly.
+    mapper.write_prg(0x9000, 0x8A);
+    assert_eq!(levels(&mut mapper, 4), vec![10, 10, 10, 10]);
+
+    // Halting stops the channels where they are.
+    mapper.write_prg(0x9000, 0x1F);
+    mapper.write_prg(0x9003, 0x01);
+    let output = levels(&mut mapper, 16);
+    assert!(output.iter().all(|&l| l == output[0]));
+    mapper.write_prg(0x9003, 0x00);
+    mapper.write_prg(0x9002, 0x00);
+    assert_eq!(levels(&mut mapper, 4), vec![0, 0, 0, 0]);
+
+    // Sawtooth: the accumulator gains the rate every other clock, and resets on every 14th.
+    mapper.write_prg(0xB000, 0x20);
+    mapper.write_prg(0xB001, 0x00);
+    mapper.write_prg(0xB002, 0x80);
+    assert_eq!(levels(&mut mapper, 7), vec![4, 8, 12, 16, 20, 24, 0]);
+
+    let state = mapper.freeze();
+    let expected = levels(&mut mapper, 7);
+    let mut mapper_2 = mappers::VRC6::new(
+        banked_rom(0x40000, 0x2000),
+        banked_rom(0x40000, 0x400),
+        false,
+    );
+    mapper_2.hydrate(state);
+    assert_eq!(levels(&mut mapper_2, 7), expected);
+
+    // The IRQ takes its latch in one write.
+    mapper.write_prg(0xF000, 0xFE);
+    mapper.write_prg(0xF001, 0x06);
+    mapper.clock_cpu(1);
+    assert!(!mapper.irq_triggered());
+    mapper.clock_cpu(1);
+    assert!(mapper.irq_triggered());
+    mapper.write_prg(0xF002, 0);
+    assert!(!mapper.irq_triggered());
+}
+
+fn fme7() -> mappers::FME7 {
+    let mut mapper = mappers::FME7::new(banked_rom(0x40000, 0x2000), banked_rom(0x40000, 0x400));
+    mapper.attach_prg_ram(Rc::new(RefCell::new(Memory::new_ram(0x2000))));
+    mapper
+}
+
+#[test]
+fn test_fme7_banking() {
+    let mut mapper = fme7();
+    let command = |mapper: &mut mappers::FME7, command, parameter| {
+        mapper.write_prg(0x8000, command);
+        mapper.write_prg(0xA000, parameter);
+    };
+
+    command(&mut mapper, 0x9, 4);
+    command(&mut mapper, 0xA, 5);
+    command(&mut mapper, 0xB, 6);
+    assert_eq!(mapper.read_prg(0x8000), 4);
+    assert_eq!(mapper.read_prg(0xA000), 5);
+    assert_eq!(mapper.read_prg(0xC000), 6);
+    assert_eq!(mapper.read_prg(0xE000), 31);
+
+    command(&mut mapper, 0x3, 77);
+    assert_eq!(mapper.read_chr(0x0C00), 77);
+
+    command(&mut mapper, 0xC, 1);
+    assert_eq!(mapper.mirror_mode(), MirrorMode::Horizontal);
+
+    // $6000 is ROM, disabled RAM, or RAM.
+    command(&mut mapper, 0x8, 0x03);
+    assert_eq!(mapper.read_expansion(0x6000), Some(3));
+    command(&mut mapper, 0x8, 0x40);
+    mapper.write_expansion(0x6000, 0x55);
+    assert_eq!(mapper.read_expansion(0x6000), None);
+    command(&mut mapper, 0x8, 0xC0);
+    mapper.write_expansion(0x6000, 0x55);
+    assert_eq!(mapper.read_expansion(0x6000), Some(0x55));
+    command(&mut mapper, 0x8, 0x03);
+    assert_eq!(mapper.read_expansion(0x6000), Some(3));
+}
+
+#[test]
+fn test_fme7_irq() {
+    let mut mapper = fme7();
+    mapper.write_prg(0x8000, 0xE);
+    mapper.write_prg(0xA000, 0x02);
+    mapper.write_prg(0x8000, 0xF);
+    mapper.write_prg(0xA000, 0x01);
+
+    // The counter only runs while enabled.
+    mapper.clock_cpu(1000);
+    mapper.write_prg(0x8000, 0xD);
+    mapper.write_prg(0xA000, 0x81);
+
+    // Fires when it wraps from $0000 to $FFFF.
+    mapper.clock_cpu(0x102);
+    assert!(!mapper.irq_triggered());
+    mapper.clock_cpu(1);
+    assert!(mapper.irq_triggered());
+
+    // Writing the control acknowledges.
+    let state = mapper.freeze();
+    mapper.write_prg(0xA000, 0x80);
+    assert!(!mapper.irq_triggered());
+    mapper.clock_cpu(0x10000);
+    assert!(!mapper.irq_triggered());
+
+    let mut mapper_2 = fme7();
+    mapper_2.hydrate(state);
+    assert!(mapper_2.irq_triggered());
+}
+
+#[test]
+fn test_fme7_audio() {
+    let mut mapper = fme7();
+    let write = |mapper: &mut mappers::FME7, register, byte| {
+        mapper.write_prg(0xC000, register);
+        mapper.write_prg(0xE000, byte);
+    };
+    let output =
+        |mapper: &mut mappers::FME7, n| (0..n).map(|_| mapper.clock_audio()).collect::<Vec<f32>>();
+
+    // Silent until a channel has some volume.
+    write(&mut mapper, 0x07, 0x3E);
+    write(&mut mapper, 0x00, 0x02);
+    assert!(output(&mut mapper, 100).iter().all(|&o| o == 0.0));
+
+    // A square wave, toggling every 2 * 16 CPU cycles.
+    write(&mut mapper, 0x08, 0x0F);
+    let samples = output(&mut mapper, 64);
+    let loud = samples[0].max(samples[16]);
+    assert!(loud > 0.1);
+    let toggles: Vec<usize> = (1..samples.len())
+        .filter(|&ix| samples[ix] != samples[ix - 1])
+        .collect();
+    assert!(toggles.len() >= 3);
+    assert!(toggles.windows(2).all(|w| w[1] - w[0] == 16));
+
+    // Lower volumes are quieter.
+    write(&mut mapper, 0x08, 0x07);
+    let quiet = output(&mut mapper, 64).into_iter().fold(0.0, f32::max);
+    assert!(quiet > 0.0 && quiet < loud / 4.0);
+
+    // A falling envelope which then holds at silence.
+    write(&mut mapper, 0x07, 0x3F);
+    write(&mut mapper, 0x08, 0x10);
+    write(&mut mapper, 0x0B, 0x01);
+    write(&mut mapper, 0x0D, 0x00);
+    let samples = output(&mut mapper, 8 * 32);
+    assert!(samples.windows(2).all(|w| w[1] <= w[0]));
+    assert!(samples[0] > 0.1);
+    assert_eq!(output(&mut mapper, 1000), vec![0.0; 1000]);
+
+    // Repeating sawtooth.
+    write(&mut mapper, 0x0D, 0x0C);
+    let first = output(&mut mapper, 8 * 32);
+    let state = mapper.freeze();
+    let second = output(&mut mapper, 8 * 32);
+    assert_eq!(first.windows(2).filter(|w| w[1] < w[0]).count(), 1);
+    assert_eq!(first, second);
+
+    let mut mapper_2 = fme7();
+    mapper_2.hydrate(state);
+    assert_eq!(output(&mut mapper_2, 8 * 32), second);
+}
+
+fn namco163() -> mappers::Namco163 {
+    mappers::Namco163::new(banked_rom(0x40000, 0x2000), banked_rom(0x40000, 0x400))
+}
+
+#[test]
+fn test_namco163_banking() {
+    let mut mapper = namco163();
+    mapper.write_prg(0xE000, 4);
+    mapper.write_prg(0xE800, 5);
+    mapper.write_prg(0xF000, 6);
+    assert_eq!(mapper.read_prg(0x8000), 4);
+    assert_eq!(mapper.read_prg(0xA000), 5);
+    assert_eq!(mapper.read_prg(0xC000), 6);
+    assert_eq!(mapper.read_prg(0xE000), 31);
+
+    mapper.write_prg(0x8800, 33);
+    mapper.write_prg(0xB800, 44);
+    assert_eq!(mapper.read_chr(0x0400), 33);
+    assert_eq!(mapper.read_chr(0x1C00), 44);
+
+    // Nametables can be either page of CIRAM, or CHR ROM.
+    mapper.write_prg(0xC000, 0xE0);
+    mapper.write_prg(0xC800, 0xE1);
+    mapper.write_prg(0xD000, 0xE1);
+    mapper.write_prg(0xD800, 0x12);
+    assert_eq!(mapper.mirror_mode(), MirrorMode::Custom([0, 1, 1, 0]));
+    assert_eq!(mapper.read_nametable(0x2400), None);
+    assert!(!mapper.write_nametable(0x2800, 0));
+    assert_eq!(mapper.read_nametable(0x2C00), Some(0x12));
+    assert!(mapper.write_nametable(0x2C00, 0));
+
+    let state = mapper.freeze();
+    let mut mapper_2 = namco163();
+    mapper_2.hydrate(state);
+    assert_eq!(mapper_2.read_nametable(0x2C00), Some(0x12));
+}
+
+#[test]
+fn test_namco163_chr_ciram() {
+    let mapper: MapperRef = Rc::new(RefCell::new(namco163()));
+    let mut memory = PPUMemory::new(
+        Box::new(ChrMapper::new(mapper.clone())),
+        Box::new(mapper.clone()),
+        Box::new(Memory::new_ram(PALETTE_RAM_SIZE + 0x800)),
+    );
+
+    // CHR banks $E0-$FF are CIRAM, so share the nametables' RAM.
+    mapper.borrow_mut().write_prg(0x8000, 0xE1);
+    mapper.borrow_mut().write_prg(0xA000, 0xE1);
+    mapper.borrow_mut().write_prg(0xC000, 0xE1);
+    mapper.borrow_mut().write_prg(0xE800, 0x00);
+    memory.write(0x2005, 0x42);
+    assert_eq!(memory.read(0x0005), 0x42);
+    assert_eq!(memory.read(0x1005), 0x42);
+    memory.write(0x1006, 0x43);
+    assert_eq!(memory.read(0x2006), 0x43);
+
+    // Bits 6 and 7 of $E800 switch each pattern table back to CHR ROM.
+    mapper.borrow_mut().write_prg(0xE800, 0x40);
+    assert_eq!(memory.read(0x0005), 0xE1);
+    assert_eq!(memory.read(0x1005), 0x42);
+    mapper.borrow_mut().write_prg(0xE800, 0x80);
+    assert_eq!(memory.read(0x0005), 0x42);
+    assert_eq!(memory.read(0x1005), 0xE1);
+
+    let state = mapper.borrow_mut().freeze();
+    let mut mapper_2 = namco163();
+    mapper_2.hydrate(state);
+    assert_eq!(mapper_2.chr_ciram_page(0x0000), Some(1));
+    assert_eq!(mapper_2.chr_ciram_page(0x1000), None);
+}
+
+#[test]
+fn test_namco163_prg_ram_protection() {
+    let mut mapper = namco163();
+    let prg_ram = Rc::new(RefCell::new(Memory::new_ram(0x2000)));
+    assert!(mapper.attach_prg_ram(prg_ram.clone()));
+
+    // Writes are protected until $F800 bits 4-7 are 0100.
+    mapper.write_expansion(0x6000, 1);
+    assert_eq!(mapper.read_expansion(0x6000), Some(0));
+    mapper.write_prg(0xF800, 0x40);
+    mapper.write_expansion(0x6000, 2);
+    assert_eq!(mapper.read_expansion(0x6000), Some(2));
+
+    // Bits 0-3 protect each 2kb window.
+    mapper.write_prg(0xF800, 0x42);
+    mapper.write_expansion(0x6800, 3);
+    mapper.write_expansion(0x7000, 4);
+    assert_eq!(prg_ram.borrow().get(0x0800), 0);
+    assert_eq!(prg_ram.borrow().get(0x1000), 4);
+
+    // So does setting the auto-increment bit.
+    mapper.write_prg(0xF800, 0xC0);
+    mapper.write_expansion(0x7800, 5);
+    assert_eq!(mapper.read_expansion(0x7800), Some(0));
+}
+
+#[test]
+fn test_namco163_irq() {
+    let mut mapper = namco163();
+    mapper.write_expansion(0x5000, 0xF0);
+    mapper.write_expansion(0x5800, 0xFF);
+    assert_eq!(mapper.read_expansion(0x5000), Some(0xF0));
+    assert_eq!(mapper.read_expansion(0x5800), Some(0xFF));
+
+    // Counts up to $7FFF, and then stops.
+    mapper.clock_cpu(0x0E);
+    assert!(!mapper.irq_triggered());
+    mapper.clock_cpu(1);
+    assert!(mapper.irq_triggered());
+    mapper.clock_cpu(100);
+    assert_eq!(mapper.read_expansion(0x5000), Some(0xFF));
+
+    // Writing the counter acknowledges.
+    mapper.write_expansion(0x5000, 0x00);
+    assert!(!mapper.irq_triggered());
+}
+
+#[test]
+fn test_namco163_audio() {
+    let mut mapper = namco163();
+
+    // The internal RAM, with auto-increment.
+    mapper.write_prg(0xF800, 0x80);
+    for byte in 0..0x10 {
+        mapper.write_expansion(0x4800, byte);
+    }
+    mapper.write_prg(0xF800, 0x8E);
+    assert_eq!(mapper.read_expansion(0x4800), Some(0x0E));
+    assert_eq!(mapper.read_expansion(0x4800), Some(0x0F));
+    assert_eq!(mapper.read_expansion(0x4800), Some(0x00));
+
+    // A square wave in the first 4 bytes: 8 samples of $F, then 8 of $0.
+    mapper.write_prg(0xF800, 0x80);
+    for byte in [0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0x00] {
+        mapper.write_expansion(0x4800, byte);
+    }
+
+    // Channel 7 alone, playing the 16 sample wave one sample per update.
+    mapper.write_prg(0xF800, 0xF8);
+    for byte in [0x00, 0x00, 0x00, 0x00, 0xF1, 0x00, 0x00, 0x0F] {
+        mapper.write_expansion(0x4800, byte);
+    }
+
+    // 16 updates, of 15 CPU cycles each.
+    let mut levels = vec![];
+    for _ in 0..(15 * 8) {
+        let level = (mapper.clock_audio() / 0.0015).round() as i16;
+        if levels.last() != Some(&level) {
+            levels.push(level);
+        }
+    }
+    assert_eq!(levels, vec![0, 105, -120, 105]);
+
+    // Disabling the sound silences it.
+    mapper.write_prg(0xE000, 0x40);
+    assert_eq!(mapper.clock_audio(), 0.0);
+}
+
+fn vrc7(submapper: u8) -> mappers::VRC7 {
+    mappers::VRC7::new(
+        banked_rom(0x40000, 0x2000),
+        banked_rom(0x40000, 0x400),
+        submapper,
+    )
+}
+
+#[test]
+fn test_vrc7_banking() {
+    for (submapper, line) in [(1, 0x08), (2, 0x10), (0, 0x08), (0, 0x10)] {
+        let mut mapper = vrc7(submapper);
+        mapper.write_prg(0x8000, 4);
+        mapper.write_prg(0x8000 | line, 5);
+        mapper.write_prg(0x9000, 6);
+        assert_eq!(mapper.read_prg(0x8000), 4);
+        assert_eq!(mapper.read_prg(0xA000), 5);
+        assert_eq!(mapper.read_prg(0xC000), 6);
+        assert_eq!(mapper.read_prg(0xE000), 31);
+
+        mapper.write_prg(0xA000, 10);
+        mapper.write_prg(0xD000 | line, 17);
+        assert_eq!(mapper.read_chr(0x0000), 10);
+        assert_eq!(mapper.read_chr(0x1C00), 17);
+
+        mapper.write_prg(0xE000, 0x01);
+        assert_eq!(mapper.mirror_mode(), MirrorMode::Horizontal);
+
+        mapper.write_prg(0xE000 | line, 0xFF);
+        mapper.write_prg(0xF000, 0x06);
+        mapper.clock_cpu(1);
+        assert!(mapper.irq_triggered());
+        mapper.write_prg(0xF000 | line, 0);
+        assert!(!mapper.irq_triggered());
+    }
+}
+
+#[test]
+fn test_vrc7_prg_ram() {
+    let mut mapper = vrc7(0);
+    let prg_ram = Rc::new(RefCell::new(Memory::new_ram(0x2000)));
+    assert!(mapper.attach_prg_ram(prg_ram.clone()));
+
+    // Bit 7 of $E000 enables PRG RAM.
+    mapper.write_expansion(0x7FFF, 1);
+    assert_eq!(mapper.read_expansion(0x7FFF), None);
+    mapper.write_prg(0xE000, 0x80);
+    mapper.write_expansion(0x7FFF, 2);
+    assert_eq!(mapper.read_expansion(0x7FFF), Some(2));
+    assert_eq!(prg_ram.borrow().get(0x1FFF), 2);
+
+    let state = mapper.freeze();
+    mapper.write_prg(0xE000, 0x00);
+    assert_eq!(mapper.read_expansion(0x7FFF), None);
+    mapper.hydrate(state);
+    assert_eq!(mapper.read_expansion(0x7FFF), Some(2));
+}
+
+#[test]
+fn test_vrc7_audio() {
+    let mut mapper = vrc7(0);
+    let write = |mapper: &mut mappers::VRC7, register, byte| {
+        mapper.write_prg(0x9010, register);
+        mapper.write_prg(0x9030, byte);
+    };
+    let output =
+        |mapper: &mut mappers::VRC7, n| (0..n).map(|_| mapper.clock_audio()).collect::<Vec<f32>>();
+
+    // A custom patch which is close to a pure sine wave: the modulator is turned right down, and
+    // the carrier starts instantly and holds until key off.
+    for (register, byte) in [0x21, 0x21, 0x3F, 0x00, 0xF0, 0xF0, 0x00, 0x0F]
+        .into_iter()
+        .enumerate()
+    {
+        write(&mut mapper, register as u8, byte);
+    }
+    assert!(output(&mut mapper, 1000).iter().all(|&o| o == 0.0));
+
+    // F-number $100 in octave 4 is a 128 sample period, of 18 APU cycles each.
+    write(&mut mapper, 0x30, 0x00);
+    write(&mut mapper, 0x10, 0x00);
+    write(&mut mapper, 0x20, 0x19);
+    let samples = output(&mut mapper, 128 * 18 * 10);
+    let peak = samples.iter().fold(0.0f32, |a, &b| a.max(b.abs()));
+    assert!(peak > 0.09 && peak <= 0.1);
+    let cycles = samples
+        .windows(2)
+        .filter(|w| w[0] < 0.0 && w[1] >= 0.0)
+        .count();
+    assert!((9..=10).contains(&cycles));
+
+    // The output is interpolated between samples.
+    assert!(
+        samples
+            .windows(2)
+            .all(|w| (w[1] - w[0]).abs() < peak * 0.01)
+    );
+
+    let state = mapper.freeze();
+    let expected = output(&mut mapper, 1000);
+    let mut mapper_2 = vrc7(0);
+    mapper_2.hydrate(state);
+    assert_eq!(output(&mut mapper_2, 1000), expected);
+
+    // Quieter at a lower volume, and silent soon after key off.
+    write(&mut mapper, 0x30, 0x04);
+    output(&mut mapper, 18);
+    let samples = output(&mut mapper, 128 * 18);
+    let quiet = samples.iter().fold(0.0f32, |a, &b| a.max(b.abs()));
+    assert!(quiet < peak / 3.0 && quiet > peak / 5.0);
+
+    write(&mut mapper, 0x20, 0x09);
+    output(&mut mapper, 100 * 18);
+    assert!(output(&mut mapper, 1000).iter().all(|&o| o == 0.0));
+
+    // Silencing the synthesizer.
+    write(&mut mapper, 0x20, 0x19);
+    mapper.write_prg(0xE000, 0x40);
+    assert!(output(&mut mapper, 1000).iter().all(|&o| o == 0.0));
+}
+
+#[test]
+fn test_vrc7_instruments() {
+    // Every instrument in the ROM makes some sound.
+    for instrument in 1..16u8 {
+        let mut mapper = vrc7(0);
+        let write = |mapper: &mut mappers::VRC7, register, byte| {
+            mapper.write_prg(0x9010, register);
+            mapper.write_prg(0x9030, byte);
+        };
+        write(&mut mapper, 0x30, instrument << 4);
+        write(&mut mapper, 0x10, 0x80);
+        write(&mut mapper, 0x20, 0x19);
+        let peak = (0..18 * 5000)
+            .map(|_| mapper.clock_audio().abs())
+            .fold(0.0f32, f32::max);
+        assert!(peak > 0.01, "instrument {} peaked at {}", instrument, peak);
+    }
+}
+
+// A disk side with the disk info block, and a file amount block saying there's one file.
+fn fds_disk() -> mappers::FDS {
+    let mut side = b"\x01*NINTENDO-HVC*".to_vec();
+    side.resize(56, 0);
+    side.extend_from_slice(&[0x02, 0x01]);
+    side.resize(65500, 0);
+    let bios = (0..0x2000).map(|ix| (ix >> 8) as u8).collect();
+    mappers::FDS::new(bios, vec![side.clone(), side])
+}
+
+#[test]
+fn test_fds_memory() {
+    let mut mapper = fds_disk();
+    let prg_ram = Rc::new(RefCell::new(Memory::new_ram(0x8000)));
+    assert!(mapper.attach_prg_ram(prg_ram.clone()));
+
+    // RAM from $6000 to $DFFF, then the BIOS.
+    mapper.write_expansion(0x6000, 1);
+    mapper.write_prg(0x8000, 2);
+    mapper.write_prg(0xDFFF, 3);
+    mapper.write_prg(0xE100, 4);
+    assert_eq!(mapper.read_expansion(0x6000), Some(1));
+    assert_eq!(mapper.read_prg(0x8000), 2);
+    assert_eq!(mapper.read_prg(0xDFFF), 3);
+    assert_eq!(mapper.read_prg(0xE100), 1);
+    assert_eq!(prg_ram.borrow().get(0x2000), 2);
+    assert_eq!(prg_ram.borrow().get(0x7FFF), 3);
+
+    mapper.write_chr(0x1FFF, 5);
+    assert_eq!(mapper.read_chr(0x1FFF), 5);
+
+    // The registers only respond once enabled.
+    assert_eq!(mapper.read_expansion(0x4030), None);
+    mapper.write_expansion(0x4023, 0x01);
+    assert_eq!(mapper.read_expansion(0x4030), Some(0xC0));
+    assert_eq!(mapper.read_expansion(0x4033), Some(0x80));
+
+    mapper.write_expansion(0x4025, 0x00);
+    assert!(matches!(mapper.mirror_mode(), MirrorMode::Vertical));
+    mapper.write_expansion(0x4025, 0x08);
+    assert!(matches!(mapper.mirror_mode(), MirrorMode::Horizontal));
+}
+
+#[test]
+fn test_fds_timer_irq() {
+    let mut mapper = fds_disk();
+    mapper.write_expansion(0x4023, 0x01);
+    mapper.write_expansion(0x4020, 10);
+    mapper.write_expansion(0x4021, 0);
+    mapper.write_expansion(0x4022, 0x02);
+
+    mapper.clock_cpu(10);
+    assert!(!mapper.irq_triggered());
+    mapper.clock_cpu(1);
+    assert!(mapper.irq_triggered());
+    assert_eq!(mapper.read_expansion(0x4030).map(|s| s & 0x01), Some(0x01));
+    assert!(!mapper.irq_triggered());
+
+    // Only once without repeat.
+    mapper.clock_cpu(100);
+    assert!(!mapper.irq_triggered());
+
+    // Every 11 cycles with it.
+    mapper.write_expansion(0x4022, 0x03);
+    for _ in 0..3 {
+        mapper.clock_cpu(10);
+        assert!(!mapper.irq_triggered());
+        mapper.clock_cpu(1);
+        assert!(mapper.irq_triggered());
+        mapper.read_expansion(0x4030);
+    }
+
+    // Disabling the disk registers stops the timer.
+    mapper.write_expansion(0x4023, 0x00);
+    mapper.clock_cpu(100);
+    assert!(!mapper.irq_triggered());
+}
+
+#[test]
+fn test_fds_disk_read() {
+    let mut mapper = fds_disk();
+    mapper.write_expansion(0x4023, 0x01);
+    assert_eq!(mapper.read_expansion(0x4032).map(|s| s & 0x07), Some(0x02));
+
+    // Motor on, reading, with IRQs.
+    mapper.write_expansion(0x4025, 0xC5);
+
+    // Waits for the disk to spin up, then skips the gap and the start of the block.
+    let mut cycles = 0;
+    while !mapper.irq_triggered() {
+        mapper.clock_cpu(1);
+        cycles += 1;
+        assert!(cycles < 1_000_000);
+    }
+    assert!(cycles > 50000);
+    assert_eq!(mapper.read_expansion(0x4032).map(|s| s & 0x07), Some(0x00));
+    assert_eq!(mapper.read_expansion(0x4031), Some(0x01));
+    assert!(!mapper.irq_triggered());
+
+    // Then a byte every 150 cycles or so.
+    for &expected in b"*NINTENDO-HVC*" {
+        let mut cycles = 0;
+        while !mapper.irq_triggered() {
+            mapper.clock_cpu(1);
+            cycles += 1;
+        }
+        assert!((150..=151).contains(&cycles));
+        assert_eq!(mapper.read_expansion(0x4031), Some(expected));
+    }
+
+    let state = mapper.freeze();
+    let mut mapper_2 = fds_disk();
+    mapper_2.hydrate(state);
+    mapper_2.clock_cpu(151);
+    assert!(mapper_2.irq_triggered());
+    assert_eq!(mapper_2.read_expansion(0x4031), Some(0x00));
+
+    // Ejecting the disk.
+    mapper.insert_disk(None);
+    mapper.clock_cpu(1);
+    assert_eq!(mapper.read_expansion(0x4032).map(|s| s & 0x07), Some(0x07));
+    assert_eq!(mapper.read_expansion(0x4030).map(|s| s & 0x40), Some(0x40));
+    mapper.insert_disk(Some(1));
+    assert_eq!(mapper.disk_side(), Some(1));
+    assert_eq!(mapper.disk_sides(), 2);
+    mapper.insert_disk(Some(2));
+    assert_eq!(mapper.disk_side(), None);
+}
+
+#[test]
+fn test_fds_audio() {
+    let mut mapper = fds_disk();
+    let output = |mapper: &mut mappers::FDS, n: usize| -> Vec<f32> {
+        (0..n).map(|_| mapper.clock_audio()).collect()
+    };
+
+    // A square wave, at full volume.
+    mapper.write_expansion(0x4023, 0x02);
+    mapper.write_expansion(0x4089, 0x80);
+    for ix in 0..64 {
+        mapper.write_expansion(0x4040 + ix, if ix < 32 { 63 } else { 0 });
+    }
+    assert_eq!(mapper.read_expansion(0x4040), Some(0x7F));
+    mapper.write_expansion(0x4089, 0x00);
+    mapper.write_expansion(0x4080, 0xA0);
+    assert_eq!(mapper.read_expansion(0x4090), Some(0x60));
+
+    // 64 CPU cycles per step, 2048 APU cycles per period.
+    mapper.write_expansion(0x4082, 0x00);
+    mapper.write_expansion(0x4083, 0x04);
+    let samples = output(&mut mapper, 2048 * 4);
+    let peak = samples.iter().fold(0.0f32, |a, &b| a.max(b));
+    assert!(peak > 0.2 && peak < 0.25);
+    assert!(samples.iter().all(|&s| s == 0.0 || s == peak));
+    let edges = samples.windows(2).filter(|w| w[0] < w[1]).count();
+    assert!((3..=4).contains(&edges));
+
+    let state = mapper.freeze();
+    let expected = output(&mut mapper, 1000);
+    let mut mapper_2 = fds_disk();
+    mapper_2.hydrate(state);
+    assert_eq!(output(&mut mapper_2, 1000), expected);
+
+    // The master volume.
+    mapper.write_expansion(0x4089, 0x03);
+    let quiet = output(&mut mapper, 2048)
+        .iter()
+        .fold(0.0f32, |a, &b| a.max(b));
+    assert!((quiet - peak * 0.4).abs() < 0.001);
+
+    // Halting the wave.
+    mapper.write_expansion(0x4083, 0x80);
+    let samples = output(&mut mapper, 2048);
+    assert!(samples.iter().all(|&s| s == samples[0]));
+
+    // The volume envelope, which ramps down from 32 to 0 at the slowest speed.
+    mapper.write_expansion(0x4083, 0x04);
+    mapper.write_expansion(0x4080, 0x00);
+    output(&mut mapper, 2048);
+    assert!(mapper.read_expansion(0x4090).unwrap() & 0x3F < 32);
+    output(&mut mapper, 500000);
+    assert_eq!(mapper.read_expansion(0x4090), Some(0x40));
+    assert!(output(&mut mapper, 2048).iter().all(|&s| s == 0.0));
+}
+
+fn bandai_fcg(mapper_number: u16, submapper: u8) -> (mappers::BandaiFCG, Rc<RefCell<Memory>>) {
+    let (prg_size, chr_mem, prg_ram_size) = match mapper_number {
+        153 => (0x80000, Memory::new_ram(0x2000), 0x2000),
+        159 => (0x40000, banked_rom(0x40000, 0x400), 0x80),
+        _ => (0x40000, banked_rom(0x40000, 0x400), 0x100),
+    };
+    let mut mapper = mappers::BandaiFCG::new(
+        banked_rom(prg_size, 0x4000),
+        chr_mem,
+        mappers::BandaiFCGVariant::from_ines(mapper_number, submapper),
+    );
+    let prg_ram = Rc::new(RefCell::new(Memory::new_ram(prg_ram_size)));
+    assert!(mapper.attach_prg_ram(prg_ram.clone()));
+    (mapper, prg_ram)
+}
+
+#[test]
+fn test_bandai_fcg_banking() {
+    let (mut mapper, _) = bandai_fcg(16, 5);
+    mapper.write_prg(0x8008, 3);
+    mapper.write_prg(0x8003, 77);
+    mapper.write_prg(0x8009, 1);
+    assert_eq!(mapper.read_prg(0x8000), 3);
+    assert_eq!(mapper.read_prg(0xC000), 15);
+    assert_eq!(mapper.read_chr(0x0C00), 77);
+    assert_eq!(mapper.mirror_mode(), MirrorMode::Horizontal);
+
+    // The LZ93D50 ignores $6000, where the FCG has its registers.
+    mapper.write_expansion(0x6008, 4);
+    assert_eq!(mapper.read_prg(0x8000), 3);
+    let (mut mapper, _) = bandai_fcg(16, 4);
+    mapper.write_expansion(0x6008, 4);
+    mapper.write_prg(0x8008, 5);
+    assert_eq!(mapper.read_prg(0x8000), 4);
+
+    // Mapper 153 uses bit 0 of the CHR banks to pick a 256kb half of PRG ROM, and has SRAM.
+    let (mut mapper, prg_ram) = bandai_fcg(153, 0);
+    mapper.write_prg(0x8008, 2);
+    mapper.write_prg(0x8005, 1);
+    assert_eq!(mapper.read_prg(0x8000), 18);
+    assert_eq!(mapper.read_prg(0xC000), 31);
+    mapper.write_chr(0x1C00, 9);
+    assert_eq!(mapper.read_chr(0x1C00), 9);
+
+    mapper.write_expansion(0x6000, 0x55);
+    assert_eq!(mapper.read_expansion(0x6000), None);
+    mapper.write_prg(0x800D, 0x20);
+    mapper.write_expansion(0x6000, 0x55);
+    assert_eq!(mapper.read_expansion(0x6000), Some(0x55));
+    assert_eq!(prg_ram.borrow().get(0), 0x55);
+}
+
+#[test]
+fn test_bandai_fcg_irq() {
+    // The LZ93D50 reloads the counter from the latch when the IRQ is enabled.
+    let (mut mapper, _) = bandai_fcg(16, 5);
+    mapper.write_prg(0x800B, 0x00);
+    mapper.write_prg(0x800C, 0x01);
+    mapper.clock_cpu(1000);
+    mapper.write_prg(0x800A, 0x01);
+    mapper.clock_cpu(0x100);
+    assert!(!mapper.irq_triggered());
+    mapper.clock_cpu(1);
+    assert!(mapper.irq_triggered());
+
+    let state = mapper.freeze();
+    mapper.write_prg(0x800A, 0x00);
+    assert!(!mapper.irq_triggered());
+    mapper.clock_cpu(0x10000);
+    assert!(!mapper.irq_triggered());
+
+    let (mut mapper_2, _) = bandai_fcg(16, 5);
+    mapper_2.hydrate(state);
+    assert!(mapper_2.irq_triggered());
+
+    // The FCG's counter is written directly, and keeps its count when enabled.
+    let (mut mapper, _) = bandai_fcg(16, 4);
+    mapper.write_expansion(0x600B, 0x10);
+    mapper.write_expansion(0x600C, 0x00);
+    mapper.write_expansion(0x600A, 0x01);
+    mapper.clock_cpu(0x10);
+    assert!(!mapper.irq_triggered());
+    mapper.clock_cpu(1);
+    assert!(mapper.irq_triggered());
+}
+
+// Bit-bangs the EEPROM's I2C bus through $800D, and reads its data line from bit 4 of $6000.
+fn i2c(mapper: &mut mappers::BandaiFCG, scl: bool, sda: bool) {
+    mapper.write_prg(0x800D, (scl as u8) << 5 | (sda as u8) << 6);
+}
+
+fn i2c_start(mapper: &mut mappers::BandaiFCG) {
+    i2c(mapper, false, true);
+    i2c(mapper, true, true);
+    i2c(mapper, true, false);
+    i2c(mapper, false, false);
+}
+
+fn i2c_stop(mapper: &mut mappers::BandaiFCG) {
+    i2c(mapper, false, false);
+    i2c(mapper, true, false);
+    i2c(mapper, true, true);
+}
+
+fn i2c_send_bit(mapper: &mut mappers::BandaiFCG, bit: bool) {
+    i2c(mapper, false, bit);
+    i2c(mapper, true, bit);
+    i2c(mapper, false, bit);
+}
+
+fn i2c_receive_bit(mapper: &mut mappers::BandaiFCG) -> bool {
+    i2c(mapper, false, true);
+    i2c(mapper, true, true);
+    let bit = mapper.read_expansion(0x6000).unwrap() & 0x10 != 0;
+    i2c(mapper, false, true);
+    bit
+}
+
+// Returns whether the EEPROM acknowledged the byte.
+fn i2c_send(mapper: &mut mappers::BandaiFCG, byte: u8, lsb_first: bool) -> bool {
+    for ix in 0..8 {
+        let shift = if lsb_first { ix } else { 7 - ix };
+        i2c_send_bit(mapper, (byte >> shift) & 0x01 != 0);
+    }
+    !i2c_receive_bit(mapper)
+}
+
+fn i2c_receive(mapper: &mut mappers::BandaiFCG, lsb_first: bool, ack: bool) -> u8 {
+    let mut byte = 0;
+    for ix in 0..8 {
+        let shift = if lsb_first { ix } else { 7 - ix };
+        byte |= (i2c_receive_bit(mapper) as u8) << shift;
+    }
+    i2c_send_bit(mapper, !ack);
+    byte
+}
+
+#[test]
+fn test_bandai_eeprom_24c02() {
+    let (mut mapper, prg_ram) = bandai_fcg(16, 5);
+
+    // Nothing answers at other device addresses.
+    i2c_start(&mut mapper);
+    assert!(!i2c_send(&mut mapper, 0xB0, false));
+    i2c_stop(&mut mapper);
+
+    i2c_start(&mut mapper);
+    assert!(i2c_send(&mut mapper, 0xA0, false));
+    assert!(i2c_send(&mut mapper, 0x16, false));
+    assert!(i2c_send(&mut mapper, 0x12, false));
+    assert!(i2c_send(&mut mapper, 0x34, false));
+    // Writes wrap around within the 8 byte page.
+    assert!(i2c_send(&mut mapper, 0x56, false));
+    i2c_stop(&mut mapper);
+    assert_eq!(prg_ram.borrow().get(0x16), 0x12);
+    assert_eq!(prg_ram.borrow().get(0x17), 0x34);
+    assert_eq!(prg_ram.borrow().get(0x10), 0x56);
+
+    // A random read sets the address with a write, then restarts to read.
+    i2c_start(&mut mapper);
+    assert!(i2c_send(&mut mapper, 0xA0, false));
+    assert!(i2c_send(&mut mapper, 0x16, false));
+    i2c_start(&mut mapper);
+    assert!(i2c_send(&mut mapper, 0xA1, false));
+    assert_eq!(i2c_receive(&mut mapper, false, true), 0x12);
+    assert_eq!(i2c_receive(&mut mapper, false, false), 0x34);
+    i2c_stop(&mut mapper);
+
+    // The bus state survives a save state.
+    i2c_start(&mut mapper);
+    assert!(i2c_send(&mut mapper, 0xA0, false));
+    let state = mapper.freeze();
+    let (mut mapper_2, prg_ram_2) = bandai_fcg(16, 5);
+    mapper_2.hydrate(state);
+    assert!(i2c_send(&mut mapper_2, 0x20, false));
+    assert!(i2c_send(&mut mapper_2, 0x78, false));
+    i2c_stop(&mut mapper_2);
+    assert_eq!(prg_ram_2.borrow().get(0x20), 0x78);
+}
+
+#[test]
+fn test_bandai_eeprom_24c01() {
+    let (mut mapper, prg_ram) = bandai_fcg(159, 0);
+
+    // No device address, and bits go least significant first, with the read bit last.
+    i2c_start(&mut mapper);
+    assert!(i2c_send(&mut mapper, 0x05, true));
+    assert!(i2c_send(&mut mapper, 0x5A, true));
+    assert!(i2c_send(&mut mapper, 0xC3, true));
+    i2c_stop(&mut mapper);
+    assert_eq!(prg_ram.borrow().get(0x05), 0x5A);
+    assert_eq!(prg_ram.borrow().get(0x06), 0xC3);
+
+    i2c_start(&mut mapper);
+    assert!(i2c_send(&mut mapper, 0x85, true));
+    assert_eq!(i2c_receive(&mut mapper, true, true), 0x5A);
+    assert_eq!(i2c_receive(&mut mapper, true, false), 0xC3);
+    i2c_stop(&mut mapper);
+}
+
+// Checks that a save state taken now gives the same reads from another copy of the mapper.
+fn assert_state_restores<M: Mapper>(mapper: &mut M, mut mapper_2: M, addresses: &[u16]) {
+    mapper_2.hydrate(mapper.freeze());
+    for &address in addresses {
+        if address >= 0x8000 {
+            assert_eq!(mapper_2.read_prg(address), mapper.read_prg(address));
+        } else {
+            assert_eq!(mapper_2.read_chr(address), mapper.read_chr(address));
+        }
+    }
+    assert_eq!(mapper_2.mirror_mode(), mapper.mirror_mode());
+}
+
+#[test]
+fn test_gxrom() {
+    let new = || {
+        mappers::GXROM::new(
+            banked_rom(0x20000, 0x8000),
+            banked_rom(0x8000, 0x2000),
+            MirrorMode::Vertical,
+        )
+    };
+    let mut mapper = new();
+    mapper.write_prg(0x8000, 0x21);
+    assert_eq!(mapper.read_prg(0x8000), 2);
+    assert_eq!(mapper.read_prg(0xFFFF), 2);
+    assert_eq!(mapper.read_chr(0x0000), 1);
+    assert_state_restores(&mut mapper, new(), &[0x8000, 0x0000]);
+}
+
+#[test]
+fn test_bnrom() {
+    let new = || {
+        mappers::BNROM::new(
+            banked_rom(0x20000, 0x8000),
+            Memory::new_ram(0x2000),
+            MirrorMode::Vertical,
+            false,
+        )
+    };
+    let mut mapper = new();
+    assert!(!mapper.attach_prg_ram(Rc::new(RefCell::new(Memory::new_ram(0x2000)))));
+    mapper.write_prg(0x8000, 3);
+    assert_eq!(mapper.read_prg(0x8000), 3);
+    mapper.write_chr(0x1234, 5);
+    assert_eq!(mapper.read_chr(0x1234), 5);
+    assert_state_restores(&mut mapper, new(), &[0x8000, 0x1234]);
+
+    // NINA-001 has its registers on top of PRG RAM.
+    let new = || {
+        mappers::BNROM::new(
+            banked_rom(0x10000, 0x8000),
+            banked_rom(0x10000, 0x1000),
+            MirrorMode::Vertical,
+            true,
+        )
+    };
+    let mut mapper = new();
+    let prg_ram = Rc::new(RefCell::new(Memory::new_ram(0x2000)));
+    assert!(mapper.attach_prg_ram(prg_ram.clone()));
+    mapper.write_prg(0x8000, 1);
+    assert_eq!(mapper.read_prg(0x8000), 0);
+    mapper.write_expansion(0x7FFD, 1);
+    mapper.write_expansion(0x7FFE, 5);
+    mapper.write_expansion(0x7FFF, 9);
+    mapper.write_expansion(0x6000, 0xAA);
+    assert_eq!(mapper.read_prg(0x8000), 1);
+    assert_eq!(mapper.read_chr(0x0000), 5);
+    assert_eq!(mapper.read_chr(0x1000), 9);
+    assert_eq!(mapper.read_expansion(0x6000), Some(0xAA));
+    assert_eq!(prg_ram.borrow().get(0x1FFF), 9);
+    assert_state_restores(&mut mapper, new(), &[0x8000, 0x0000, 0x1000]);
+}
+
+#[test]
+fn test_camerica() {
+    let new = || {
+        mappers::Camerica::new(
+            banked_rom(0x40000, 0x4000),
+            Memory::new_ram(0x2000),
+            MirrorMode::Horizontal,
+        )
+    };
+    let mut mapper = new();
+    mapper.write_prg(0xC000, 5);
+    assert_eq!(mapper.read_prg(0x8000), 5);
+    assert_eq!(mapper.read_prg(0xC000), 15);
+    assert_eq!(mapper.mirror_mode(), MirrorMode::Horizontal);
+
+    // Fire Hawk's single-screen mirroring.
+    mapper.write_prg(0x9000, 0x10);
+    assert_eq!(mapper.mirror_mode(), MirrorMode::SingleUpper);
+    assert_state_restores(&mut mapper, new(), &[0x8000, 0xC000]);
+}
+
+#[test]
+fn test_bandai_74161() {
+    let new = |single_screen| {
+        mappers::Bandai74161::new(
+            banked_rom(0x20000, 0x4000),
+            banked_rom(0x20000, 0x2000),
+            MirrorMode::Vertical,
+            single_screen,
+        )
+    };
+    let mut mapper = new(false);
+    mapper.write_prg(0x8000, 0x53);
+    assert_eq!(mapper.read_prg(0x8000), 5);
+    assert_eq!(mapper.read_prg(0xC000), 7);
+    assert_eq!(mapper.read_chr(0x0000), 3);
+    assert_eq!(mapper.mirror_mode(), MirrorMode::Vertical);
+    assert_state_restores(&mut mapper, new(false), &[0x8000, 0x0000]);
+
+    // Mapper 152 takes bit 7 for mirroring.
+    let mut mapper = new(true);
+    mapper.write_prg(0x8000, 0xD3);
+    assert_eq!(mapper.read_prg(0x8000), 5);
+    assert_eq!(mapper.mirror_mode(), MirrorMode::SingleUpper);
+    assert_state_restores(&mut mapper, new(true), &[0x8000, 0x0000]);
+}
+
+#[test]
+fn test_jaleco_jf16() {
+    let new = |submapper| {
+        mappers::JalecoJF16::new(
+            banked_rom(0x20000, 0x4000),
+            banked_rom(0x20000, 0x2000),
+            submapper,
+        )
+    };
+    let mut mapper = new(0);
+    mapper.write_prg(0x8000, 0x5A);
+    assert_eq!(mapper.read_prg(0x8000), 2);
+    assert_eq!(mapper.read_prg(0xC000), 7);
+    assert_eq!(mapper.read_chr(0x0000), 5);
+    assert_eq!(mapper.mirror_mode(), MirrorMode::SingleUpper);
+    assert_state_restores(&mut mapper, new(0), &[0x8000, 0x0000]);
+
+    // Holy Diver's board has horizontal and vertical mirroring.
+    let mut mapper = new(3);
+    mapper.write_prg(0x8000, 0x08);
+    assert_eq!(mapper.mirror_mode(), MirrorMode::Vertical);
+    mapper.write_prg(0x8000, 0x00);
+    assert_eq!(mapper.mirror_mode(), MirrorMode::Horizontal);
+}
+
+#[test]
+fn test_jaleco_jf05() {
+    let new = || {
+        mappers::JalecoJF05::new(
+            banked_rom(0x8000, 0x4000),
+            banked_rom(0x8000, 0x2000),
+            MirrorMode::Vertical,
+        )
+    };
+    let mut mapper = new();
+    assert!(mapper.attach_prg_ram(Rc::new(RefCell::new(Memory::new_ram(0x2000)))));
+    assert_eq!(mapper.read_prg(0xC000), 1);
+
+    // The bank number's bits are swapped.
+    mapper.write_expansion(0x6000, 0x01);
+    assert_eq!(mapper.read_chr(0x0000), 2);
+    mapper.write_expansion(0x6000, 0x02);
+    assert_eq!(mapper.read_chr(0x0000), 1);
+    assert_state_restores(&mut mapper, new(), &[0x0000]);
+}
+
+#[test]
+fn test_jaleco_jf11() {
+    let new = || {
+        mappers::JalecoJF11::new(
+            banked_rom(0x20000, 0x8000),
+            banked_rom(0x20000, 0x2000),
+            MirrorMode::Vertical,
+        )
+    };
+    let mut mapper = new();
+    assert!(mapper.attach_prg_ram(Rc::new(RefCell::new(Memory::new_ram(0x2000)))));
+    mapper.write_expansion(0x7000, 0x2B);
+    assert_eq!(mapper.read_prg(0x8000), 2);
+    assert_eq!(mapper.read_chr(0x0000), 11);
+    assert_state_restores(&mut mapper, new(), &[0x8000, 0x0000]);
+}
+
+#[test]
+fn test_unrom_180() {
+    let new = || {
+        mappers::UNROM180::new(
+            banked_rom(0x20000, 0x4000),
+            Memory::new_ram(0x2000),
+            MirrorMode::Vertical,
+        )
+    };
+    let mut mapper = new();
+    mapper.write_prg(0x8000, 6);
+    assert_eq!(mapper.read_prg(0x8000), 0);
+    assert_eq!(mapper.read_prg(0xC000), 6);
+    assert_state_restores(&mut mapper, new(), &[0x8000, 0xC000]);
+}
+
+#[test]
+fn test_sunsoft1() {
+    let new = || {
+        mappers::Sunsoft1::new(
+            banked_rom(0x8000, 0x4000),
+            banked_rom(0x8000, 0x1000),
+            MirrorMode::Vertical,
+        )
+    };
+    let mut mapper = new();
+    assert!(mapper.attach_prg_ram(Rc::new(RefCell::new(Memory::new_ram(0x2000)))));
+    assert_eq!(mapper.read_chr(0x1000), 4);
+
+    // The upper bank always comes from the second half.
+    mapper.write_expansion(0x6000, 0x13);
+    assert_eq!(mapper.read_chr(0x0000), 3);
+    assert_eq!(mapper.read_chr(0x1000), 5);
+    assert_state_restores(&mut mapper, new(), &[0x0000, 0x1000]);
+}
+
+#[test]
+fn test_quattro() {
+    let new = |submapper| {
+        mappers::Quattro::new(
+            banked_rom(0x40000, 0x4000),
+            Memory::new_ram(0x2000),
+            MirrorMode::Vertical,
+            submapper,
+        )
+    };
+    let mut mapper = new(0);
+    mapper.write_prg(0x8000, 0x10);
+    mapper.write_prg(0xC000, 0x01);
+    assert_eq!(mapper.read_prg(0x8000), 9);
+    assert_eq!(mapper.read_prg(0xC000), 11);
+    assert_state_restores(&mut mapper, new(0), &[0x8000, 0xC000]);
+
+    // The Aladdin Deck Enhancer swaps the block bits.
+    let mut mapper = new(1);
+    mapper.write_prg(0x8000, 0x10);
+    assert_eq!(mapper.read_prg(0x8000), 4);
+}
@@ -1,6 +1,15 @@
 // -- Mapper tests using Holy Diver Batman test roms.
 // -- Test each mapper once normally, and then once with a savestate.
 
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::emulator::NES;
+use crate::emulator::ines::ROM;
+use crate::emulator::io;
+use crate::emulator::io::event::EventBus;
+use crate::emulator::test::run_blargg_test_rom;
+
 macro_rules! test_mapper {
     ($name:ident, $rom:expr_2021, $cycles:expr_2021) => {
         mod $name {
@@ -40,11 +49,231 @@ macro_rules! test_mapper {
 
 test_mapper!(nrom, "M0_P32K_C8K_V", 100_000_000);
 
-// Note that test status here is 1000.
-// This means that bit 4 of $Exxx doesn't disable WRAM.
-// Right now our mappers aren't hooked up to WRAM, so accepting this as-is.
 test_mapper!(mmc1, "M1_P128K_C128K", 500_000_000);
+test_mapper!(mmc1_skrom, "M1_P128K_C128K_S8K", 500_000_000);
+test_mapper!(mmc1_sjrom, "M1_P128K_C32K_W8K", 500_000_000);
+test_mapper!(mmc1_surom, "M1_P512K_S8K", 500_000_000);
+test_mapper!(mmc1_sxrom, "M1_P512K_S32K", 500_000_000);
 test_mapper!(uxrom, "M2_P128K_V", 150_000_000);
 test_mapper!(cnrom, "M3_P32K_C32K_H", 100_000_000);
 test_mapper!(mmc3, "M4_P256K_C256K", 200_000_000);
 test_mapper!(axrom, "M7_P128K", 120_000_000);
+
+// -- VRC2 and VRC4 tests using generated ROMs, since there are no test ROMs for these boards.
+// -- Each ROM goes through the registers using its board's wiring of the CPU address lines, and
+// -- reports its result like blargg's test ROMs.
+
+// Where the generated ROMs jump to report which check failed, and their interrupt handlers.
+const VRC_FAIL: u16 = 0xFF00;
+const VRC_IRQ: u16 = 0xFF80;
+const VRC_NMI: u16 = 0xFF90;
+
+// Appends 6502 instructions to a program.
+struct Program(Vec<u8>);
+
+impl Program {
+    fn write(&mut self, address: u16, byte: u8) {
+        // LDA #byte, STA address
+        self.0
+            .extend([0xA9, byte, 0x8D, address as u8, (address >> 8) as u8]);
+    }
+
+    fn read(&mut self, address: u16) {
+        // LDA address
+        self.0.extend([0xAD, address as u8, (address >> 8) as u8]);
+    }
+
+    // Reports the check as failed unless A holds the byte.
+    fn expect(&mut self, byte: u8, check: u8) {
+        // LDY #check, CMP #byte, BEQ +3, JMP VRC_FAIL
+        self.0.extend([0xA0, check, 0xC9, byte, 0xF0, 0x03, 0x4C]);
+        self.0.extend([VRC_FAIL as u8, (VRC_FAIL >> 8) as u8]);
+    }
+
+    fn read_chr(&mut self, address: u16) {
+        self.read(0x2002);
+        self.write(0x2006, (address >> 8) as u8);
+        self.write(0x2006, address as u8);
+        // The first read comes from PPUDATA's buffer.
+        self.read(0x2007);
+        self.read(0x2007);
+    }
+}
+
+// Builds an NES 2.0 ROM for the board with the chip's A0 and A1 wired to the given CPU address
+// lines.  Each 8kb PRG bank and 1kb CHR bank is filled with its own bank number.
+fn vrc_test_rom(mapper_number: u16, submapper: u8, a0: u16, a1: u16) -> Vec<u8> {
+    let vrc2 = matches!((mapper_number, submapper), (22, _) | (23, 3) | (25, 3));
+    // VRC2a ignores the lowest bit of CHR banks.
+    let chr_shift = if mapper_number == 22 { 1 } else { 0 };
+
+    let mut p = Program(vec![]);
+    // SEI, CLD, LDX #$FF, TXS
+    p.0.extend([0x78, 0xD8, 0xA2, 0xFF, 0x9A]);
+    p.write(0x6000, 0x80);
+    p.write(0x0000, 0x00);
+    // Only the mapper should raise IRQs.
+    p.write(0x4017, 0x40);
+
+    // PRG banks.
+    p.write(0x8000, 5);
+    p.write(0xA000, 9);
+    p.read(0x8000);
+    p.expect(5, 1);
+    p.read(0xA000);
+    p.expect(9, 2);
+
+    // VRC4 can swap the first bank with the second last.
+    if !vrc2 {
+        p.write(0x9000 | a1, 0x02);
+        p.read(0xC000);
+        p.expect(5, 3);
+        p.read(0x8000);
+        p.expect(14, 4);
+        p.write(0x9000 | a1, 0x00);
+    }
+
+    // CHR banks, a nibble at a time, once the PPU has warmed up.
+    for _ in 0..2 {
+        // BIT $2002, BPL -5
+        p.0.extend([0x2C, 0x02, 0x20, 0x10, 0xFB]);
+    }
+    let (bank_0, bank_1) = (0x25u8, 0x4Au8);
+    p.write(0xB000, (bank_0 << chr_shift) & 0x0F);
+    p.write(0xB000 | a0, (bank_0 << chr_shift) >> 4);
+    p.write(0xB000 | a1, (bank_1 << chr_shift) & 0x0F);
+    p.write(0xB000 | a0 | a1, (bank_1 << chr_shift) >> 4);
+    p.read_chr(0x0000);
+    p.expect(bank_0, 5);
+    p.read_chr(0x0400);
+    p.expect(bank_1, 6);
+
+    // The VRC4 IRQ counter, set to go off straight away.
+    if !vrc2 {
+        p.write(0xF000, 0x0F);
+        p.write(0xF000 | a0, 0x0F);
+        p.write(0xF000 | a1, 0x06);
+        // CLI, LDX #0, then wait for the handler to set $00:
+        // LDA $00, BNE +6, DEX, BNE -7, JMP VRC_FAIL
+        p.0.extend([0x58, 0xA2, 0x00, 0xA0, 7]);
+        p.0.extend([0xA5, 0x00, 0xD0, 0x06, 0xCA, 0xD0, 0xF9, 0x4C]);
+        p.0.extend([VRC_FAIL as u8, (VRC_FAIL >> 8) as u8]);
+        // SEI
+        p.0.push(0x78);
+    }
+
+    for (ix, byte) in b"Passed\n\0".iter().enumerate() {
+        p.write(0x6004 + ix as u16, *byte);
+    }
+    p.write(0x6000, 0x00);
+    let end = 0xE000 + p.0.len() as u16;
+    // JMP end
+    p.0.extend([0x4C, end as u8, (end >> 8) as u8]);
+
+    let mut data = vec![b'N', b'E', b'S', 0x1A, 8, 32];
+    data.push(((mapper_number & 0x0F) << 4) as u8);
+    data.push((mapper_number & 0xF0) as u8 | 0x08);
+    data.push((submapper << 4) | (mapper_number >> 8) as u8);
+    // 8kb of PRG RAM for the results.
+    data.extend([0x00, 0x07, 0x00, 0x00, 0x00, 0x00, 0x00]);
+
+    let mut prg = vec![];
+    for bank in 0..16u8 {
+        prg.extend([bank; 0x2000]);
+    }
+    let last_bank = &mut prg[0x1E000..];
+    last_bank[..p.0.len()].copy_from_slice(&p.0);
+    let offset = |address: u16| (address - 0xE000) as usize;
+    // STY $6000, JMP *
+    last_bank[offset(VRC_FAIL)..offset(VRC_FAIL) + 6]
+        .copy_from_slice(&[0x8C, 0x00, 0x60, 0x4C, 0x03, 0xFF]);
+    // PHA, LDA #1, STA $00, acknowledge, PLA, RTI
+    let acknowledge = 0xF000 | a0 | a1;
+    last_bank[offset(VRC_IRQ)..offset(VRC_IRQ) + 9].copy_from_slice(&[
+        0x48,
+        0xA9,
+        0x01,
+        0x85,
+        0x00,
+        0x8D,
+        acknowledge as u8,
+        (acknowledge >> 8) as u8,
+        0x68,
+    ]);
+    last_bank[offset(VRC_IRQ) + 9] = 0x40;
+    last_bank[offset(VRC_NMI)] = 0x40;
+    last_bank[0x1FFA..].copy_from_slice(&[
+        VRC_NMI as u8,
+        (VRC_NMI >> 8) as u8,
+        0x00,
+        0xE0,
+        VRC_IRQ as u8,
+        (VRC_IRQ >> 8) as u8,
+    ]);
+    data.extend(prg);
+
+    for bank in 0..=255u8 {
+        data.extend([bank; 0x400]);
+    }
+    data
+}
+
+fn test_vrc(mapper_number: u16, submapper: u8, a0: u16, a1: u16) {
+    let rom = ROM::from_bytes(vrc_test_rom(mapper_number, submapper, a0, a1)).unwrap();
+    let mut nes = NES::new(
+        Rc::new(RefCell::new(EventBus::new())),
+        Rc::new(RefCell::new(io::Screen::new())),
+        io::nop::DummyAudio {},
+        rom,
+    )
+    .unwrap();
+    let (status, output) = run_blargg_test_rom(&mut nes, 10_000_000);
+
+    assert_eq!(status, 0x00);
+    assert_eq!(output, "Passed\n");
+}
+
+#[test]
+fn test_vrc2a() {
+    test_vrc(22, 0, 0x02, 0x01);
+}
+
+#[test]
+fn test_vrc2b() {
+    test_vrc(23, 3, 0x01, 0x02);
+}
+
+#[test]
+fn test_vrc2c() {
+    test_vrc(25, 3, 0x02, 0x01);
+}
+
+#[test]
+fn test_vrc4a() {
+    test_vrc(21, 1, 0x02, 0x04);
+}
+
+#[test]
+fn test_vrc4b() {
+    test_vrc(25, 1, 0x02, 0x01);
+}
+
+#[test]
+fn test_vrc4c() {
+    test_vrc(21, 2, 0x40, 0x80);
+}
+
+#[test]
+fn test_vrc4d() {
+    test_vrc(25, 2, 0x08, 0x04);
+}
+
+#[test]
+fn test_vrc4e() {
+    test_vrc(23, 2, 0x04, 0x08);
+}
+
+#[test]
+fn test_vrc4f() {
+    test_vrc(23, 1, 0x01, 0x02);
+}
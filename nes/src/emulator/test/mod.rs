@@ -1,11 +1,19 @@
+mod apu;
+mod debugger;
 mod image_capture;
 mod instr_misc;
 mod instr_test_v5;
 mod instr_timing;
+mod mapper_banking;
 mod mappers;
 mod nestest;
+mod nsf;
+mod open_bus;
 mod ppu_sprite_hit;
 mod ppu_sprite_overflow;
+mod rewind;
+mod rom_loading;
+mod sram;
 
 use std::cell::RefCell;
 use std::env;
@@ -31,12 +39,12 @@ fn run_for(nes: &mut NES, cycles: u64) {
 }
 
 fn prepare_ete_test<P: AsRef<Path>>(path: P) -> (NES, Rc<RefCell<EventBus>>, ImageCapture) {
-    let rom = ines::ROM::load(path);
+    let rom = ines::ROM::load(path).unwrap();
     let event_bus = Rc::new(RefCell::new(EventBus::new()));
     let output = Rc::new(RefCell::new(io::Screen::new()));
     let audio = io::nop::DummyAudio {};
     let image = ImageCapture::new(output.clone());
-    let nes = NES::new(event_bus.clone(), output, audio, rom);
+    let nes = NES::new(event_bus.clone(), output, audio, rom).unwrap();
     (nes, event_bus, image)
 }
 
@@ -55,10 +63,10 @@ fn load_and_run_blargg_test_rom_with_cycles<P: AsRef<Path>>(
 fn run_blargg_test_rom(nes: &mut NES, max_cycles: u64) -> (u8, String) {
     let mut cycles = 0;
     // Run until the status byte says the test is running.
-    let mut status = nes.cpu.borrow_mut().load_memory(0x6000);
+    let mut status = nes.cpu.borrow_mut().peek_memory(0x6000);
     while status != 0x80 {
         cycles += nes.tick();
-        status = nes.cpu.borrow_mut().load_memory(0x6000);
+        status = nes.cpu.borrow_mut().peek_memory(0x6000);
 
         if cycles > 20_000_000 {
             panic!(
@@ -71,7 +79,7 @@ fn run_blargg_test_rom(nes: &mut NES, max_cycles: u64) -> (u8, String) {
     // Run until completion.
     while status == 0x80 {
         cycles += nes.tick();
-        status = nes.cpu.borrow_mut().load_memory(0x6000);
+        status = nes.cpu.borrow_mut().peek_memory(0x6000);
 
         cycles += 1;
         if cycles > max_cycles {
@@ -93,7 +101,7 @@ fn collect_test_output(nes: &mut NES) -> String {
     // Collect output.
     let mut text_buf = vec![];
     for ix in 0..1000 {
-        let byte = nes.cpu.borrow_mut().load_memory(0x6004 + ix);
+        let byte = nes.cpu.borrow_mut().peek_memory(0x6004 + ix);
         if byte == 0x00 {
             break;
         } else {
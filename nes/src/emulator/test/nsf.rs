@@ -0,0 +1,237 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::emulator::NES;
+use crate::emulator::ines::{RomError, Timing};
+use crate::emulator::io;
+use crate::emulator::io::event::EventBus;
+use crate::emulator::mappers;
+use crate::emulator::memory::{Mapper, Memory};
+use crate::emulator::nsf::{EXPANSION_FDS, NSF};
+use crate::emulator::test::run_for;
+
+// Init stores the song and region at $00 and $02, and play counts its calls at $01.
+const INIT_AND_PLAY: [u8; 0x14] = [
+    0x85, 0x00, // STA $00
+    0x86, 0x02, // STX $02
+    0x60, // RTS
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //
+    0xE6, 0x01, // INC $01
+    0x60, // RTS
+    0,
+];
+
+// Builds an NSF with 3 songs, starting at the second.
+fn build_nsf(load_address: u16, banks: [u8; 8], region: u8, expansion: u8, data: &[u8]) -> Vec<u8> {
+    let mut nsf = vec![b'N', b'E', b'S', b'M', 0x1A, 0x01, 3, 2];
+    nsf.extend_from_slice(&load_address.to_le_bytes());
+    nsf.extend_from_slice(&0x8000u16.to_le_bytes());
+    nsf.extend_from_slice(&0x8010u16.to_le_bytes());
+    nsf.resize(0x0E, 0);
+    nsf.extend_from_slice(b"Title");
+    nsf.resize(0x2E, 0);
+    nsf.extend_from_slice(b"Artist");
+    nsf.resize(0x6E, 0);
+    nsf.extend_from_slice(&16639u16.to_le_bytes());
+    nsf.extend_from_slice(&banks);
+    nsf.extend_from_slice(&19997u16.to_le_bytes());
+    nsf.push(region);
+    nsf.push(expansion);
+    nsf.resize(0x80, 0);
+    nsf.extend_from_slice(data);
+    nsf
+}
+
+fn chunk(id: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut chunk = (data.len() as u32).to_le_bytes().to_vec();
+    chunk.extend_from_slice(id);
+    chunk.extend_from_slice(data);
+    chunk
+}
+
+fn start(nsf: NSF) -> NES {
+    NES::new(
+        Rc::new(RefCell::new(EventBus::new())),
+        Rc::new(RefCell::new(io::Screen::new())),
+        io::nop::DummyAudio {},
+        nsf,
+    )
+    .unwrap()
+}
+
+// 16 banks, each filled with its own bank number.
+fn banked_data() -> Vec<u8> {
+    (0..0x10000).map(|ix| (ix / 0x1000) as u8).collect()
+}
+
+#[test]
+fn test_nsf_header() {
+    let nsf = NSF::from_bytes(build_nsf(0x8000, [0; 8], 0, 0, &INIT_AND_PLAY)).unwrap();
+    assert_eq!(nsf.songs(), 3);
+    assert_eq!(nsf.starting_song(), 1);
+    assert_eq!(nsf.load_address(), 0x8000);
+    assert_eq!(nsf.init_address(), 0x8000);
+    assert_eq!(nsf.play_address(), 0x8010);
+    assert_eq!(nsf.title(), "Title");
+    assert_eq!(nsf.artist(), "Artist");
+    assert_eq!(nsf.copyright(), "");
+    assert_eq!(nsf.timing(), Timing::NTSC);
+    assert_eq!(nsf.play_speed_us(Timing::NTSC), 16639);
+    assert_eq!(nsf.play_speed_us(Timing::PAL), 19997);
+    assert!(!nsf.is_bankswitched());
+    assert_eq!(nsf.data(), &INIT_AND_PLAY);
+
+    let nsf = NSF::from_bytes(build_nsf(0x8000, [0, 1, 0, 0, 0, 0, 0, 0], 3, 0x24, &[0])).unwrap();
+    assert!(nsf.is_bankswitched());
+    assert_eq!(nsf.timing(), Timing::MultiRegion);
+    assert_eq!(nsf.expansion(), 0x24);
+
+    let mut data = build_nsf(0x8000, [0; 8], 0, 0, &[]);
+    assert!(matches!(
+        NSF::from_bytes(data.clone()),
+        Err(RomError::BadNsf(_))
+    ));
+    data.truncate(0x40);
+    assert!(matches!(
+        NSF::from_bytes(data),
+        Err(RomError::TruncatedHeader)
+    ));
+}
+
+#[test]
+fn test_nsfe() {
+    let mut info = vec![];
+    for word in [0xC000u16, 0xC000, 0xC010] {
+        info.extend_from_slice(&word.to_le_bytes());
+    }
+    info.extend_from_slice(&[0x01, 0x00, 4, 3]);
+
+    let mut data = b"NSFE".to_vec();
+    data.extend(chunk(b"INFO", &info));
+    data.extend(chunk(b"DATA", &INIT_AND_PLAY));
+    data.extend(chunk(b"BANK", &[0, 1]));
+    data.extend(chunk(b"RATE", &[0x10, 0x27, 0x20, 0x4E]));
+    data.extend(chunk(b"auth", b"Title\0Artist\0Copyright\0Ripper\0"));
+    data.extend(chunk(b"tlbl", b"One\0\0Three\0"));
+    data.extend(chunk(b"misc", b"skipped"));
+    data.extend(chunk(b"NEND", &[]));
+
+    let nsf = NSF::from_bytes(data.clone()).unwrap();
+    assert_eq!(nsf.songs(), 4);
+    assert_eq!(nsf.starting_song(), 3);
+    assert_eq!(nsf.load_address(), 0xC000);
+    assert_eq!(nsf.play_address(), 0xC010);
+    assert_eq!(nsf.timing(), Timing::PAL);
+    assert_eq!(nsf.play_speed_us(Timing::NTSC), 10000);
+    assert_eq!(nsf.play_speed_us(Timing::PAL), 20000);
+    assert!(nsf.is_bankswitched());
+    assert_eq!(nsf.title(), "Title");
+    assert_eq!(nsf.copyright(), "Copyright");
+    assert_eq!(nsf.track_label(0), Some("One"));
+    assert_eq!(nsf.track_label(1), None);
+    assert_eq!(nsf.track_label(2), Some("Three"));
+    assert_eq!(nsf.track_label(3), None);
+
+    // Chunks which can't be skipped.
+    let mut bad = data[..data.len() - 8].to_vec();
+    bad.extend(chunk(b"XTRA", &[]));
+    assert!(matches!(NSF::from_bytes(bad), Err(RomError::BadNsf(_))));
+
+    let mut no_data = b"NSFE".to_vec();
+    no_data.extend(chunk(b"INFO", &info));
+    assert!(matches!(NSF::from_bytes(no_data), Err(RomError::BadNsf(_))));
+}
+
+#[test]
+fn test_nsf_player() {
+    let nsf = NSF::from_bytes(build_nsf(0x8000, [0; 8], 0, 0, &INIT_AND_PLAY)).unwrap();
+    let mut nes = start(nsf);
+    assert_eq!(nes.songs(), 3);
+    assert_eq!(nes.song(), Some(1));
+
+    // Play is called at 60.1Hz.
+    let second = nes.region().master_clock_hz();
+    run_for(&mut nes, second);
+    assert_eq!(nes.ram.borrow().get(0x00), 1);
+    assert_eq!(nes.ram.borrow().get(0x02), 0);
+    assert!((59..=61).contains(&nes.ram.borrow().get(0x01)));
+
+    // Selecting a song starts over.
+    nes.select_song(2);
+    assert_eq!(nes.song(), Some(2));
+    run_for(&mut nes, second / 2);
+    assert_eq!(nes.ram.borrow().get(0x00), 2);
+    assert!((29..=31).contains(&nes.ram.borrow().get(0x01)));
+
+    // PAL music is called at 50Hz.
+    let nsf = NSF::from_bytes(build_nsf(0x8000, [0; 8], 1, 0, &INIT_AND_PLAY)).unwrap();
+    let mut nes = start(nsf);
+    let second = nes.region().master_clock_hz();
+    run_for(&mut nes, second);
+    assert_eq!(nes.ram.borrow().get(0x02), 1);
+    assert!((49..=51).contains(&nes.ram.borrow().get(0x01)));
+}
+
+#[test]
+fn test_nsf_bankswitching() {
+    let data = banked_data();
+    let nsf = NSF::from_bytes(build_nsf(0x8000, [0, 1, 2, 3, 4, 5, 6, 7], 0, 0, &data));
+    let mut mapper = mappers::NSFPlayer::new(nsf.unwrap());
+    let prg_ram = Rc::new(RefCell::new(Memory::new_ram(0x2000)));
+    assert!(mapper.attach_prg_ram(prg_ram));
+
+    assert_eq!(mapper.read_prg(0x8000), 0);
+    assert_eq!(mapper.read_prg(0xF000), 7);
+    mapper.write_expansion(0x5FF8, 12);
+    mapper.write_expansion(0x5FFF, 15);
+    assert_eq!(mapper.read_prg(0x8FFF), 12);
+    assert_eq!(mapper.read_prg(0xF000), 15);
+
+    // The vectors point at the driver.
+    assert_eq!(mapper.read_prg(0xFFFC), 0x00);
+    assert_eq!(mapper.read_prg(0xFFFD), 0x41);
+    assert_eq!(mapper.read_expansion(0x4100), Some(0x78));
+
+    // RAM at $6000.
+    mapper.write_expansion(0x6000, 0xAB);
+    assert_eq!(mapper.read_expansion(0x6000), Some(0xAB));
+
+    // Unbanked music is loaded at the load address.
+    let nsf = NSF::from_bytes(build_nsf(0xC000, [0; 8], 0, 0, &data[0x3000..]));
+    let mut mapper = mappers::NSFPlayer::new(nsf.unwrap());
+    assert_eq!(mapper.read_prg(0xBFFF), 0);
+    assert_eq!(mapper.read_prg(0xC000), 3);
+    assert_eq!(mapper.read_prg(0xF000), 6);
+}
+
+#[test]
+fn test_nsf_fds() {
+    let data = banked_data();
+    let nsf = NSF::from_bytes(build_nsf(
+        0x8000,
+        [0, 1, 2, 3, 4, 5, 6, 7],
+        0,
+        EXPANSION_FDS,
+        &data,
+    ));
+    let mut mapper = mappers::NSFPlayer::new(nsf.unwrap());
+    let prg_ram = Rc::new(RefCell::new(Memory::new_ram(0x8000)));
+    assert!(mapper.attach_prg_ram(prg_ram));
+
+    // $6000-$DFFF is RAM, loaded from the banks.
+    assert_eq!(mapper.read_expansion(0x6000), Some(6));
+    assert_eq!(mapper.read_expansion(0x7000), Some(7));
+    assert_eq!(mapper.read_prg(0x8000), 0);
+    mapper.write_prg(0x8000, 0xAB);
+    assert_eq!(mapper.read_prg(0x8000), 0xAB);
+    mapper.write_expansion(0x5FF8, 9);
+    assert_eq!(mapper.read_prg(0x8000), 9);
+    mapper.write_expansion(0x5FF6, 10);
+    assert_eq!(mapper.read_expansion(0x6FFF), Some(10));
+    assert_eq!(mapper.read_prg(0xE000), 6);
+
+    // The FDS sound registers.
+    mapper.write_expansion(0x4089, 0x80);
+    mapper.write_expansion(0x4040, 0x3F);
+    assert_eq!(mapper.read_expansion(0x4040), Some(0x7F));
+}
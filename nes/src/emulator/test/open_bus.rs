@@ -0,0 +1,119 @@
+// -- Reads from addresses which nothing drives should see the last value on the data bus.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::emulator::NES;
+use crate::emulator::ines::ROM;
+use crate::emulator::io;
+use crate::emulator::io::event::EventBus;
+use crate::emulator::test::run_for;
+
+// Builds an NROM cartridge which runs the program at $8000, then loops forever.
+fn run_program(program: &[u8]) -> NES {
+    let mut data = vec![b'N', b'E', b'S', 0x1A, 1, 1, 0x00, 0x00];
+    data.resize(16 + 0x4000 + 0x2000, 0);
+    let prg = &mut data[16..16 + 0x4000];
+    prg[..program.len()].copy_from_slice(program);
+    let end = 0x8000 + program.len() as u16;
+    prg[program.len()..program.len() + 3].copy_from_slice(&[0x4C, end as u8, (end >> 8) as u8]);
+    prg[0x3FFC..0x3FFE].copy_from_slice(&[0x00, 0x80]);
+
+    let mut nes = NES::new(
+        Rc::new(RefCell::new(EventBus::new())),
+        Rc::new(RefCell::new(io::Screen::new())),
+        io::nop::DummyAudio {},
+        ROM::from_bytes(data).unwrap(),
+    )
+    .unwrap();
+    run_for(&mut nes, 10_000);
+    nes
+}
+
+#[test]
+fn test_open_bus() {
+    let nes = run_program(&[
+        // The last byte read is the high byte of the address.
+        0xAD, 0x00, 0x50, // LDA $5000
+        0x85, 0x00, // STA $00
+        0xAD, 0x00, 0x40, // LDA $4000
+        0x85, 0x01, // STA $01
+        0xAD, 0x18, 0x40, // LDA $4018
+        0x85, 0x02, // STA $02
+        // Indexing doesn't change the high byte.
+        0xA2, 0x34, // LDX #$34
+        0xBD, 0x00, 0x5F, // LDA $5F00,X
+        0x85, 0x03, // STA $03
+    ]);
+    let ram = nes.ram.borrow();
+    assert_eq!(ram.get(0x00), 0x50);
+    assert_eq!(ram.get(0x01), 0x40);
+    assert_eq!(ram.get(0x02), 0x40);
+    assert_eq!(ram.get(0x03), 0x5F);
+}
+
+#[test]
+fn test_controller_open_bus() {
+    let nes = run_program(&[
+        // Only the low 5 bits come from the controllers.
+        0xAD, 0x16, 0x40, // LDA $4016
+        0x85, 0x00, // STA $00
+        0xAD, 0x17, 0x40, // LDA $4017
+        0x85, 0x01, // STA $01
+        // With indirect addressing the last byte read is still the high byte of the address.
+        0xA9, 0x16, // LDA #$16
+        0x85, 0x10, // STA $10
+        0xA9, 0x40, // LDA #$40
+        0x85, 0x11, // STA $11
+        0xA0, 0x00, // LDY #$00
+        0xB1, 0x10, // LDA ($10),Y
+        0x85, 0x02, // STA $02
+    ]);
+    let ram = nes.ram.borrow();
+    assert_eq!(ram.get(0x00), 0x40);
+    assert_eq!(ram.get(0x01), 0x40);
+    assert_eq!(ram.get(0x02), 0x40);
+}
+
+#[test]
+fn test_execute_open_bus() {
+    let nes = run_program(&[
+        // Push a return address and status for RTI.
+        0xA9, 0x80, // LDA #$80
+        0x48, // PHA
+        0xA9, 0x0A, // LDA #$0A
+        0x48, // PHA
+        0x08, // PHP
+        // The last byte read is the high byte of the address, $40, which is RTI.
+        0x4C, 0x18, 0x40, // JMP $4018
+        0xA9, 0x01, // LDA #$01
+        0x85, 0x00, // STA $00
+    ]);
+    assert_eq!(nes.ram.borrow().get(0x00), 0x01);
+}
+
+#[test]
+fn test_debug_read_leaves_open_bus() {
+    let mut nes = run_program(&[
+        0xA9, 0x12, // LDA #$12
+        0x85, 0x00, // STA $00
+    ]);
+    // The program ends looping on a JMP, so the last byte read is $80.
+    assert_eq!(nes.debug_read(0x0000), 0x12);
+    assert_eq!(nes.debug_read(0x5000), 0x80);
+}
+
+#[test]
+fn test_apu_status_open_bus() {
+    let nes = run_program(&[
+        // Leave $FF on the PPU's latch.
+        0xA9, 0xFF, // LDA #$FF
+        0x8D, 0x03, 0x20, // STA $2003
+        // Crossing a page reads $3F15 first, which puts the PPU's latch on the bus.
+        0xA2, 0x20, // LDX #$20
+        0xBD, 0xF5, 0x3F, // LDA $3FF5,X
+        0x85, 0x00, // STA $00
+    ]);
+    // Only bit 5 comes from the bus, as all the channels are silent.
+    assert_eq!(nes.ram.borrow().get(0x00), 0x20);
+}
@@ -0,0 +1,100 @@
+use std::env;
+
+use crate::emulator::NES;
+use crate::emulator::rewind::Rewinder;
+use crate::emulator::state::SaveState;
+use crate::emulator::test::image_capture::ImageCapture;
+use crate::emulator::test::{file_digest, prepare_ete_test, run_for, test_resource_path};
+
+// 341 dots * 262 scanlines * 4 master cycles per dot.
+const FRAME_CYCLES: u64 = 357_368;
+
+fn snapshot(nes: &mut NES) -> Vec<u8> {
+    bincode::serialize(&nes.freeze()).unwrap()
+}
+
+fn image_digest(image: &ImageCapture) -> String {
+    let mut path = env::temp_dir();
+    path.push("rewind_test.bmp");
+    image.save_bmp(&path);
+    file_digest(path)
+}
+
+#[test]
+fn test_rewind_restores_snapshots_in_reverse() {
+    let path = test_resource_path("mappers/M4_P256K_C256K.nes");
+    let (mut nes, _, _) = prepare_ete_test(&path);
+    let mut rewinder = Rewinder::new(usize::MAX, 1, 8);
+
+    let mut snapshots = vec![];
+    for _ in 0..30 {
+        rewinder.frame(&mut nes);
+        snapshots.push(snapshot(&mut nes));
+        run_for(&mut nes, FRAME_CYCLES);
+    }
+    assert_eq!(rewinder.len(), 30);
+
+    for expected in snapshots.iter().rev() {
+        assert!(rewinder.rewind(&mut nes));
+        assert_eq!(&snapshot(&mut nes), expected);
+    }
+    assert!(!rewinder.rewind(&mut nes));
+    assert!(rewinder.is_empty());
+    assert_eq!(rewinder.used_bytes(), 0);
+}
+
+#[test]
+fn test_rewind_and_replay() {
+    let path = test_resource_path("mappers/M4_P256K_C256K.nes");
+    let (mut nes, _, image) = prepare_ete_test(&path);
+    let mut rewinder = Rewinder::new(usize::MAX, 1, 8);
+
+    for _ in 0..30 {
+        rewinder.frame(&mut nes);
+        run_for(&mut nes, FRAME_CYCLES);
+    }
+    let expected = image_digest(&image);
+
+    for _ in 0..10 {
+        assert!(rewinder.rewind(&mut nes));
+    }
+    for _ in 0..10 {
+        rewinder.frame(&mut nes);
+        run_for(&mut nes, FRAME_CYCLES);
+    }
+    assert_eq!(image_digest(&image), expected);
+    assert_eq!(rewinder.len(), 30);
+}
+
+#[test]
+fn test_capture_interval() {
+    let path = test_resource_path("mappers/M4_P256K_C256K.nes");
+    let (mut nes, _, _) = prepare_ete_test(&path);
+    let mut rewinder = Rewinder::new(usize::MAX, 3, 8);
+
+    for _ in 0..9 {
+        rewinder.frame(&mut nes);
+        run_for(&mut nes, FRAME_CYCLES);
+    }
+    assert_eq!(rewinder.len(), 3);
+}
+
+#[test]
+fn test_memory_budget() {
+    let path = test_resource_path("mappers/M4_P256K_C256K.nes");
+    let (mut nes, _, _) = prepare_ete_test(&path);
+    let budget = snapshot(&mut nes).len() * 3;
+    let mut rewinder = Rewinder::new(budget, 1, 4);
+
+    let mut last_snapshot = vec![];
+    for _ in 0..40 {
+        last_snapshot = snapshot(&mut nes);
+        rewinder.frame(&mut nes);
+        run_for(&mut nes, FRAME_CYCLES);
+        assert!(rewinder.used_bytes() <= budget);
+    }
+    assert!(rewinder.len() < 40);
+
+    assert!(rewinder.rewind(&mut nes));
+    assert_eq!(snapshot(&mut nes), last_snapshot);
+}
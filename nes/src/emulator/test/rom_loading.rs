@@ -0,0 +1,502 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use crate::emulator::NES;
+use crate::emulator::fds::{BIOS_SIZE, Disk, SIDE_SIZE};
+use crate::emulator::ines::{ConsoleType, ROM, RomError, Timing};
+use crate::emulator::io;
+use crate::emulator::io::event::EventBus;
+use crate::emulator::mappers::{MMC1Variant, MapperRegistry};
+use crate::emulator::memory::{Mapper, Memory};
+use crate::emulator::ppu::MirrorMode;
+use crate::emulator::region::Region;
+use crate::emulator::state::{CustomMapperState, MapperState, SaveState};
+use crate::emulator::test::test_resource_path;
+
+// Builds an iNES image with the given header flags and zeroed PRG/CHR data.
+fn build_rom(prg_banks: u8, chr_banks: u8, flags_6: u8, flags_7: u8) -> Vec<u8> {
+    let mut data = vec![
+        b'N', b'E', b'S', 0x1A, prg_banks, chr_banks, flags_6, flags_7,
+    ];
+    data.resize(16, 0);
+    data.resize(
+        16 + (prg_banks as usize) * 0x4000 + (chr_banks as usize) * 0x2000,
+        0,
+    );
+    data
+}
+
+// Builds an NES 2.0 image from bytes 8-15 of the header.
+fn build_nes2_rom(prg_banks: u8, chr_banks: u8, flags_6: u8, flags_7: u8, ext: [u8; 8]) -> Vec<u8> {
+    let mut data = build_rom(prg_banks, chr_banks, flags_6, (flags_7 & 0xF3) | 0x08);
+    data[8..16].copy_from_slice(&ext);
+    data
+}
+
+#[test]
+fn test_load_valid_rom() {
+    let rom = ROM::from_bytes(build_rom(2, 1, 0x00, 0x00)).unwrap();
+    assert_eq!(rom.mapper_number(), 0);
+    assert!(rom.get_mapper().is_ok());
+}
+
+#[test]
+fn test_load_missing_file() {
+    let res = ROM::load(test_resource_path("does_not_exist.nes"));
+    assert!(matches!(res, Err(RomError::Io(_))));
+}
+
+#[test]
+fn test_truncated_header() {
+    let res = ROM::from_bytes(vec![b'N', b'E', b'S', 0x1A, 0x01]);
+    assert!(matches!(res, Err(RomError::TruncatedHeader)));
+}
+
+#[test]
+fn test_bad_magic() {
+    let mut data = build_rom(1, 1, 0x00, 0x00);
+    data[3] = 0x00;
+    let res = ROM::from_bytes(data);
+    assert!(matches!(res, Err(RomError::BadMagic)));
+}
+
+#[test]
+fn test_trainer() {
+    let trainer: Vec<u8> = (0..512).map(|ix| ix as u8).collect();
+    let mut data = build_rom(1, 1, 0x04, 0x00);
+    data.splice(16..16, trainer.iter().cloned());
+    data[16 + 512] = 0xAB;
+    let rom = ROM::from_bytes(data).unwrap();
+
+    assert_eq!(rom.trainer(), Some(trainer.as_slice()));
+    assert_eq!(rom.prg_rom().get(0), 0xAB);
+
+    // The trainer should be copied to $7000.
+    let nes = NES::new(
+        Rc::new(RefCell::new(EventBus::new())),
+        Rc::new(RefCell::new(io::Screen::new())),
+        io::nop::DummyAudio {},
+        rom,
+    )
+    .unwrap();
+    assert_eq!(
+        &nes.sram.borrow().as_slice()[0x1000..0x1200],
+        trainer.as_slice()
+    );
+}
+
+#[test]
+fn test_truncated_trainer() {
+    let mut data = build_rom(1, 1, 0x04, 0x00);
+    data.truncate(16 + 256);
+    let res = ROM::from_bytes(data);
+    assert!(matches!(res, Err(RomError::TruncatedTrainer)));
+}
+
+#[test]
+fn test_four_screen() {
+    let rom = ROM::from_bytes(build_rom(1, 1, 0x09, 0x00)).unwrap();
+    assert!(matches!(rom.mirror_mode(), MirrorMode::FourScreen));
+    assert_eq!(rom.nametable_ram_size_bytes(), 0x1000);
+}
+
+#[test]
+fn test_missing_prg_rom() {
+    let res = ROM::from_bytes(build_rom(0, 1, 0x00, 0x00));
+    assert!(matches!(res, Err(RomError::MissingPrgRom)));
+}
+
+#[test]
+fn test_truncated_prg_rom() {
+    let mut data = build_rom(2, 0, 0x00, 0x00);
+    data.truncate(16 + 0x4000);
+    let res = ROM::from_bytes(data);
+    assert!(matches!(
+        res,
+        Err(RomError::TruncatedPrgRom {
+            expected: 0x8000,
+            actual: 0x4000,
+        })
+    ));
+}
+
+#[test]
+fn test_truncated_chr_rom() {
+    let mut data = build_rom(1, 1, 0x00, 0x00);
+    data.truncate(16 + 0x4000 + 0x1000);
+    let res = ROM::from_bytes(data);
+    assert!(matches!(
+        res,
+        Err(RomError::TruncatedChrRom {
+            expected: 0x2000,
+            actual: 0x1000,
+        })
+    ));
+}
+
+#[test]
+fn test_unsupported_mapper() {
+    let rom = ROM::from_bytes(build_rom(1, 1, 0xF0, 0xF0)).unwrap();
+    assert!(matches!(
+        rom.get_mapper(),
+        Err(RomError::UnsupportedMapper(255))
+    ));
+}
+
+#[test]
+fn test_load_mappers() {
+    for mapper_number in [
+        16u8, 19, 21, 22, 23, 24, 25, 26, 34, 66, 69, 70, 71, 76, 78, 85, 87, 88, 95, 118, 119,
+        140, 152, 153, 159, 180, 184, 206, 232,
+    ] {
+        let rom =
+            ROM::from_bytes(build_rom(2, 1, mapper_number << 4, mapper_number & 0xF0)).unwrap();
+        assert_eq!(rom.mapper_number(), mapper_number as u16);
+        assert!(rom.get_mapper().is_ok());
+    }
+}
+
+// A board from outside the mappers module, with a single PRG bank register.
+struct CustomMapper {
+    prg_rom: Memory,
+    prg_bank: u8,
+}
+
+impl Mapper for CustomMapper {
+    fn read_chr(&mut self, _address: u16) -> u8 {
+        0
+    }
+
+    fn write_chr(&mut self, _address: u16, _byte: u8) {}
+
+    fn read_prg(&mut self, address: u16) -> u8 {
+        let offset = (self.prg_bank as usize) * 0x4000 + (address & 0x3FFF) as usize;
+        self.prg_rom.get(offset % self.prg_rom.len())
+    }
+
+    fn write_prg(&mut self, _address: u16, byte: u8) {
+        self.prg_bank = byte;
+    }
+
+    fn mirror_mode(&self) -> MirrorMode {
+        MirrorMode::Vertical
+    }
+}
+
+impl SaveState<'static, MapperState> for CustomMapper {
+    fn freeze(&mut self) -> MapperState {
+        MapperState::Custom(CustomMapperState {
+            id: 255,
+            data: vec![self.prg_bank],
+        })
+    }
+
+    fn hydrate(&mut self, state: MapperState) {
+        match state {
+            MapperState::Custom(s) if s.id == 255 => self.prg_bank = s.data[0],
+            _ => panic!("Incompatible mapper state for custom mapper: {:?}", state),
+        }
+    }
+}
+
+fn custom_registry() -> Arc<MapperRegistry> {
+    let mut registry = MapperRegistry::new();
+    registry.register(255, |rom| {
+        Ok(Rc::new(RefCell::new(CustomMapper {
+            prg_rom: rom.prg_rom(),
+            prg_bank: 0,
+        })))
+    });
+    // Submapper 1 gets an error instead.
+    registry.register_submapper(255, 1, |rom| {
+        Err(RomError::UnsupportedMapper(rom.mapper_number()))
+    });
+    Arc::new(registry)
+}
+
+#[test]
+fn test_mapper_registry() {
+    let registry = custom_registry();
+    assert!(registry.supports(4, 0));
+    assert!(registry.supports(255, 0));
+    assert!(!MapperRegistry::new().supports(255, 0));
+    assert!(!MapperRegistry::empty().supports(0, 0));
+
+    let mut data = build_rom(2, 1, 0xF0, 0xF0);
+    data[16 + 0x4000] = 0xAB;
+    let rom = ROM::from_bytes(data)
+        .unwrap()
+        .with_mapper_registry(registry.clone());
+    let mapper = rom.get_mapper().unwrap();
+    mapper.borrow_mut().write_prg(0x8000, 1);
+    assert_eq!(mapper.borrow_mut().read_prg(0x8000), 0xAB);
+
+    // Built-in mappers are still there.
+    let rom = ROM::from_bytes(build_rom(2, 1, 0x10, 0x00))
+        .unwrap()
+        .with_mapper_registry(registry.clone());
+    assert!(rom.get_mapper().is_ok());
+
+    let rom = ROM::from_bytes(build_nes2_rom(
+        2,
+        1,
+        0xF0,
+        0xF0,
+        [0x10, 0, 0, 0, 0, 0, 0, 0],
+    ))
+    .unwrap()
+    .with_mapper_registry(registry);
+    assert_eq!(rom.submapper(), 1);
+    assert!(matches!(
+        rom.get_mapper(),
+        Err(RomError::UnsupportedMapper(255))
+    ));
+}
+
+#[test]
+fn test_custom_mapper_state() {
+    let rom = ROM::from_bytes(build_rom(2, 1, 0xF0, 0xF0))
+        .unwrap()
+        .with_mapper_registry(custom_registry());
+    let mut nes = NES::new(
+        Rc::new(RefCell::new(EventBus::new())),
+        Rc::new(RefCell::new(io::Screen::new())),
+        io::nop::DummyAudio {},
+        rom,
+    )
+    .unwrap();
+
+    let state = nes.freeze();
+    match state.mapper {
+        MapperState::Custom(ref s) => assert_eq!(s.id, 255),
+        _ => panic!("Expected custom mapper state"),
+    }
+    let bytes = bincode::serialize(&state).unwrap();
+    nes.hydrate(bincode::deserialize(&bytes).unwrap());
+}
+
+#[test]
+fn test_ines_defaults() {
+    let rom = ROM::from_bytes(build_rom(1, 0, 0x12, 0x00)).unwrap();
+    assert!(!rom.is_nes2());
+    assert_eq!(rom.mapper_number(), 1);
+    assert_eq!(rom.submapper(), 0);
+    assert_eq!(rom.prg_ram_size_bytes(), 0x2000);
+    assert_eq!(rom.prg_nvram_size_bytes(), 0x2000);
+    assert_eq!(rom.chr_ram_size_bytes(), 0x2000);
+    assert_eq!(rom.timing(), Timing::NTSC);
+    assert_eq!(rom.console_type(), ConsoleType::NES);
+}
+
+#[test]
+fn test_ines_mmc1_sxrom() {
+    // iNES 1.0 headers can't give SXROM's PRG RAM size, so 512kb boards are assumed to have it.
+    let rom = ROM::from_bytes(build_rom(32, 0, 0x12, 0x00)).unwrap();
+    assert_eq!(rom.prg_ram_size_bytes(), 0x8000);
+    assert_eq!(
+        MMC1Variant::from_ines(
+            rom.submapper(),
+            rom.prg_rom_size_bytes(),
+            rom.prg_ram_size_bytes()
+        ),
+        MMC1Variant::SXROM
+    );
+
+    // NES 2.0 headers say which it is.
+    let rom = ROM::from_bytes(build_nes2_rom(
+        32,
+        0,
+        0x12,
+        0x08,
+        [0x00, 0x00, 0x70, 0x07, 0x00, 0x00, 0x00, 0x00],
+    ))
+    .unwrap();
+    assert_eq!(
+        MMC1Variant::from_ines(
+            rom.submapper(),
+            rom.prg_rom_size_bytes(),
+            rom.prg_ram_size_bytes()
+        ),
+        MMC1Variant::SUROM
+    );
+}
+
+#[test]
+fn test_bandai_eeprom_sizes() {
+    // The EEPROM is saved like SRAM, even without the battery flag.
+    let rom = ROM::from_bytes(build_rom(2, 1, 0x00, 0x10)).unwrap();
+    assert_eq!(rom.prg_ram_size_bytes(), 0x100);
+    assert!(rom.has_eeprom());
+    let rom = ROM::from_bytes(build_rom(2, 1, 0xF0, 0x90)).unwrap();
+    assert_eq!(rom.prg_ram_size_bytes(), 0x80);
+    assert!(rom.has_eeprom());
+
+    // FCG boards have no EEPROM, and mapper 153 has SRAM instead.
+    let rom = ROM::from_bytes(build_nes2_rom(
+        2,
+        1,
+        0x00,
+        0x10,
+        [0x40, 0, 0, 0, 0, 0, 0, 0],
+    ))
+    .unwrap();
+    assert!(!rom.has_eeprom());
+    let rom = ROM::from_bytes(build_rom(2, 0, 0x90, 0x90)).unwrap();
+    assert!(!rom.has_eeprom());
+}
+
+#[test]
+fn test_ines_ignores_dirty_header() {
+    let mut data = build_rom(1, 1, 0x40, 0x40);
+    data[12..16].copy_from_slice(b"Dude");
+    let rom = ROM::from_bytes(data).unwrap();
+    assert_eq!(rom.mapper_number(), 4);
+}
+
+#[test]
+fn test_nes2_header() {
+    let rom = ROM::from_bytes(build_nes2_rom(
+        2,
+        0,
+        0x50,
+        0x41,
+        [0x31, 0x00, 0x70, 0x07, 0x01, 0x00, 0x00, 0x02],
+    ))
+    .unwrap();
+    assert!(rom.is_nes2());
+    assert_eq!(rom.mapper_number(), 0x145);
+    assert_eq!(rom.submapper(), 3);
+    assert_eq!(rom.prg_rom_size_bytes(), 0x8000);
+    assert_eq!(rom.chr_rom_size_bytes(), 0);
+    assert_eq!(rom.prg_ram_size_bytes(), 0x2000);
+    assert_eq!(rom.prg_nvram_size_bytes(), 0x2000);
+    assert_eq!(rom.chr_ram_size_bytes(), 0x2000);
+    assert_eq!(rom.chr_nvram_size_bytes(), 0);
+    assert_eq!(rom.timing(), Timing::PAL);
+    assert_eq!(rom.console_type(), ConsoleType::VsSystem);
+    assert_eq!(rom.expansion_device(), 0x02);
+}
+
+#[test]
+fn test_nes2_no_ram() {
+    let rom = ROM::from_bytes(build_nes2_rom(1, 1, 0x00, 0x03, [0x00; 8])).unwrap();
+    assert_eq!(rom.prg_ram_size_bytes(), 0);
+    assert_eq!(rom.chr_ram_size_bytes(), 0);
+    assert_eq!(rom.console_type(), ConsoleType::Extended(0));
+}
+
+#[test]
+fn test_nes2_exponent_rom_size() {
+    // 2^14 * 3 = 48KB of PRG ROM.
+    let mut data = build_nes2_rom(
+        0,
+        0,
+        0x00,
+        0x00,
+        [0x00, 0x0F, 0x00, 0x07, 0x03, 0x00, 0x00, 0x00],
+    );
+    data[4] = (14 << 2) | 0x01;
+    data.resize(16 + 0xC000, 0);
+    let rom = ROM::from_bytes(data).unwrap();
+    assert_eq!(rom.prg_rom_size_bytes(), 0xC000);
+    assert_eq!(rom.timing(), Timing::Dendy);
+    assert_eq!(rom.prg_rom().len(), 0xC000);
+}
+
+#[test]
+fn test_region() {
+    for (timing, region) in [
+        (0, Region::NTSC),
+        (1, Region::PAL),
+        (2, Region::NTSC),
+        (3, Region::Dendy),
+    ] {
+        let rom = ROM::from_bytes(build_nes2_rom(
+            1,
+            1,
+            0x00,
+            0x00,
+            [0x00, 0x00, 0x07, 0x00, timing, 0x00, 0x00, 0x00],
+        ))
+        .unwrap();
+        let nes = NES::new(
+            Rc::new(RefCell::new(EventBus::new())),
+            Rc::new(RefCell::new(io::Screen::new())),
+            io::nop::DummyAudio {},
+            rom,
+        )
+        .unwrap();
+        assert_eq!(nes.region(), region);
+    }
+}
+
+// Builds a .fds image with just a disk info block on each side.
+fn build_disk(sides: usize, header: bool) -> Vec<u8> {
+    let mut data = vec![];
+    if header {
+        data.extend_from_slice(&[b'F', b'D', b'S', 0x1A, sides as u8]);
+        data.resize(16, 0);
+    }
+    for side in 0..sides {
+        let start = data.len();
+        data.extend_from_slice(b"\x01*NINTENDO-HVC*");
+        data.push(side as u8);
+        data.resize(start + SIDE_SIZE, 0);
+    }
+    data
+}
+
+#[test]
+fn test_load_fds_disk() {
+    for header in [true, false] {
+        let disk = Disk::from_bytes(build_disk(2, header), vec![0; BIOS_SIZE]).unwrap();
+        assert_eq!(disk.sides().len(), 2);
+        assert_eq!(disk.sides()[1][15], 1);
+        assert_eq!(disk.bios().len(), BIOS_SIZE);
+    }
+
+    // Disk System games start with 32KB of RAM, and a disk in the drive.
+    let disk = Disk::from_bytes(build_disk(2, true), vec![0; BIOS_SIZE]).unwrap();
+    let mut nes = NES::new(
+        Rc::new(RefCell::new(EventBus::new())),
+        Rc::new(RefCell::new(io::Screen::new())),
+        io::nop::DummyAudio {},
+        disk,
+    )
+    .unwrap();
+    assert_eq!(nes.sram.borrow().len(), 0x8000);
+    nes.debug_write(0xDFFF, 0xAB);
+    assert_eq!(nes.sram.borrow().get(0x7FFF), 0xAB);
+    assert!(!nes.has_battery());
+
+    assert_eq!(nes.disk_sides(), 2);
+    assert_eq!(nes.disk_side(), Some(0));
+    nes.insert_disk(None);
+    assert_eq!(nes.disk_side(), None);
+    nes.insert_disk(Some(1));
+    assert_eq!(nes.disk_side(), Some(1));
+}
+
+#[test]
+fn test_fds_bad_bios() {
+    let res = Disk::from_bytes(build_disk(1, false), vec![0; 0x1000]);
+    assert!(matches!(res, Err(RomError::BadBiosSize(0x1000))));
+
+    let res = Disk::load(
+        test_resource_path("does_not_exist.fds"),
+        test_resource_path("does_not_exist.rom"),
+    );
+    assert!(matches!(res, Err(RomError::Io(_))));
+}
+
+#[test]
+fn test_fds_bad_disk_image() {
+    let mut data = build_disk(2, true);
+    data[16 + SIDE_SIZE + 1] = b'X';
+    let res = Disk::from_bytes(data, vec![0; BIOS_SIZE]);
+    assert!(matches!(res, Err(RomError::BadDiskImage)));
+
+    let res = Disk::from_bytes(vec![b'F', b'D', b'S', 0x1A], vec![0; BIOS_SIZE]);
+    assert!(matches!(res, Err(RomError::TruncatedHeader)));
+}
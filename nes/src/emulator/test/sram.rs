@@ -0,0 +1,39 @@
+use crate::emulator::state::SaveState;
+use crate::emulator::test::prepare_ete_test;
+use crate::emulator::test::run_for;
+use crate::emulator::test::test_resource_path;
+
+#[test]
+fn test_no_battery() {
+    let path = test_resource_path("mappers/M1_P128K_C128K.nes");
+    let (mut nes, _, _) = prepare_ete_test(&path);
+    assert!(!nes.has_battery());
+
+    run_for(&mut nes, 10_000_000);
+    assert!(nes.flush_sram().is_none());
+}
+
+#[test]
+fn test_battery_save_roundtrip() {
+    let path = test_resource_path("mappers/M1_P128K_C128K_S8K.nes");
+    let (mut nes, _, _) = prepare_ete_test(&path);
+    assert!(nes.has_battery());
+
+    // The test rom writes to PRG RAM, so there should be something to flush, but only once.
+    run_for(&mut nes, 10_000_000);
+    let data = nes.flush_sram().expect("SRAM should have been written");
+    assert_eq!(data.len(), 0x2000);
+    assert!(nes.flush_sram().is_none());
+
+    // Loading the save into a fresh emulator restores SRAM, and doesn't count as a change.
+    let (mut nes_2, _, _) = prepare_ete_test(&path);
+    nes_2.load_sram(&data);
+    assert_eq!(nes_2.sram.borrow().as_slice(), data.as_slice());
+    assert!(nes_2.flush_sram().is_none());
+
+    // Neither does loading a save state.
+    let (mut nes_3, _, _) = prepare_ete_test(&path);
+    nes_3.hydrate(nes.freeze());
+    assert_eq!(nes_3.sram.borrow().as_slice(), data.as_slice());
+    assert!(nes_3.flush_sram().is_none());
+}
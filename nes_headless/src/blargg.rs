@@ -0,0 +1,36 @@
+use nes::emulator::NES;
+
+// blargg's test ROMs report their progress in PRG RAM:
+// $6000       = status.  $80 while running, $81 if the console needs resetting, otherwise the
+//               result code, where 0 means success.
+// $6001-$6003 = DE B0 61, to signal the rest of the data is valid.
+// $6004-      = zero-terminated output text.
+pub const STATUS_RUNNING: u8 = 0x80;
+pub const STATUS_NEEDS_RESET: u8 = 0x81;
+
+const SIGNATURE: [u8; 3] = [0xDE, 0xB0, 0x61];
+
+pub fn has_signature(nes: &NES) -> bool {
+    let mut cpu = nes.cpu.borrow_mut();
+    SIGNATURE
+        .iter()
+        .enumerate()
+        .all(|(ix, byte)| cpu.peek_memory(0x6001 + ix as u16) == *byte)
+}
+
+pub fn status(nes: &NES) -> u8 {
+    nes.cpu.borrow_mut().peek_memory(0x6000)
+}
+
+pub fn output(nes: &NES) -> String {
+    let mut cpu = nes.cpu.borrow_mut();
+    let mut text_buf = vec![];
+    for ix in 0..0x1000 {
+        let byte = cpu.peek_memory(0x6004 + ix);
+        if byte == 0x00 {
+            break;
+        }
+        text_buf.push(byte);
+    }
+    String::from_utf8_lossy(&text_buf).into_owned()
+}
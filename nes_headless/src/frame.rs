@@ -0,0 +1,64 @@
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use flate2::Compression;
+use flate2::Crc;
+use flate2::write::ZlibEncoder;
+use md5::{Digest, Md5};
+
+// Frames are 24-bit RGB, as rendered by io::Screen.
+pub const WIDTH: usize = 256;
+pub const HEIGHT: usize = 240;
+
+pub fn md5_hex(frame: &[u8]) -> String {
+    let mut hasher = Md5::new();
+    hasher.input(frame);
+    hasher
+        .result()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+pub fn write_ppm<P: AsRef<Path>>(path: P, frame: &[u8]) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    write!(file, "P6\n{} {}\n255\n", WIDTH, HEIGHT)?;
+    file.write_all(frame)?;
+    file.flush()
+}
+
+pub fn write_png<P: AsRef<Path>>(path: P, frame: &[u8]) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(b"\x89PNG\r\n\x1a\n")?;
+
+    // Header: dimensions, 8-bit depth, truecolour, default compression/filter, no interlacing.
+    let mut header = vec![];
+    header.extend_from_slice(&(WIDTH as u32).to_be_bytes());
+    header.extend_from_slice(&(HEIGHT as u32).to_be_bytes());
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+    write_png_chunk(&mut file, b"IHDR", &header)?;
+
+    // Each row is prefixed with its filter type, which is always 0 (none).
+    let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+    for row in frame.chunks(WIDTH * 3) {
+        encoder.write_all(&[0])?;
+        encoder.write_all(row)?;
+    }
+    write_png_chunk(&mut file, b"IDAT", &encoder.finish()?)?;
+
+    write_png_chunk(&mut file, b"IEND", &[])?;
+    file.flush()
+}
+
+fn write_png_chunk<W: Write>(out: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    let mut crc = Crc::new();
+    crc.update(kind);
+    crc.update(data);
+
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+    out.write_all(&crc.sum().to_be_bytes())
+}
@@ -0,0 +1,274 @@
+pub mod blargg;
+pub mod frame;
+pub mod script;
+pub mod wav;
+
+use std::cell::RefCell;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::rc::Rc;
+
+use nes::emulator::NES;
+use nes::emulator::io;
+use nes::emulator::io::event::EventBus;
+use nes::emulator::media::{self, Media};
+use nes::emulator::region::Region;
+
+use crate::script::InputScript;
+
+const USAGE: &str = "Usage: nes_headless <rom> [options]
+
+Runs a ROM, disk image or NSF without a window or audio device, then reports on the final frame.
+
+Options:
+    --frames N       Run for N frames (default 60, or 10000 with --blargg)
+    --cycles N       Run for N master clock cycles
+    --input FILE     Play back a scripted input file on controller 1
+    --region REGION  Override the region from the ROM header (ntsc, pal or dendy)
+    --png FILE       Save the final frame as a PNG
+    --ppm FILE       Save the final frame as a PPM
+    --md5            Print the MD5 of the final frame's RGB data
+    --wav FILE       Save everything the APU played as a WAV
+    --track N        Play track N of an NSF, counting from 1
+    --fds-bios FILE  The Disk System BIOS (default disksys.rom next to the disk image)
+    --blargg         Stop when a blargg test ROM finishes, print its output, and exit with its
+                     result code";
+
+const WAV_SAMPLE_RATE: u32 = 44_100;
+
+// How long to wait before resetting the console when a blargg test asks for it.
+const BLARGG_RESET_DELAY_FRAMES: u64 = 6;
+
+struct Options {
+    rom_path: String,
+    frames: Option<u64>,
+    cycles: Option<u64>,
+    input_path: Option<String>,
+    region: Option<Region>,
+    png_path: Option<String>,
+    ppm_path: Option<String>,
+    md5: bool,
+    wav_path: Option<String>,
+    track: Option<usize>,
+    fds_bios_path: Option<String>,
+    blargg: bool,
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        rom_path: String::new(),
+        frames: None,
+        cycles: None,
+        input_path: None,
+        region: None,
+        png_path: None,
+        ppm_path: None,
+        md5: false,
+        wav_path: None,
+        track: None,
+        fds_bios_path: None,
+        blargg: false,
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .cloned()
+                .ok_or(format!("{} requires a value", arg))
+        };
+
+        match arg.as_str() {
+            "--frames" => options.frames = Some(parse_number(arg, &value()?)?),
+            "--cycles" => options.cycles = Some(parse_number(arg, &value()?)?),
+            "--input" => options.input_path = Some(value()?),
+            "--region" => options.region = Some(value()?.parse()?),
+            "--png" => options.png_path = Some(value()?),
+            "--ppm" => options.ppm_path = Some(value()?),
+            "--md5" => options.md5 = true,
+            "--wav" => options.wav_path = Some(value()?),
+            "--track" => match parse_number(arg, &value()?)? {
+                0 => return Err(String::from("--track counts from 1")),
+                track => options.track = Some(track as usize),
+            },
+            "--fds-bios" => options.fds_bios_path = Some(value()?),
+            "--blargg" => options.blargg = true,
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+            _ if options.rom_path.is_empty() => options.rom_path = arg.clone(),
+            _ => return Err(format!("unexpected argument {}", arg)),
+        }
+    }
+
+    if options.rom_path.is_empty() {
+        return Err(String::from("you must pass in a path to a ROM file"));
+    }
+
+    if options.frames.is_none() && options.cycles.is_none() {
+        options.frames = Some(if options.blargg { 10_000 } else { 60 });
+    }
+
+    Ok(options)
+}
+
+fn parse_number(arg: &str, value: &str) -> Result<u64, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{} expects a number, got \"{}\"", arg, value))
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(cause) => {
+            eprintln!("{}\n\n{}", cause, USAGE);
+            process::exit(2);
+        }
+    };
+
+    process::exit(match run(&options) {
+        Ok(code) => code,
+        Err(cause) => {
+            eprintln!("{}", cause);
+            1
+        }
+    });
+}
+
+// Returns the exit code.
+fn run(options: &Options) -> Result<i32, String> {
+    let fds_bios_path = match options.fds_bios_path {
+        Some(ref path) => PathBuf::from(path),
+        None => Path::new(&options.rom_path).with_file_name("disksys.rom"),
+    };
+    let rom = media::load(&options.rom_path, &fds_bios_path)
+        .map_err(|e| format!("Couldn't load {}: {}", options.rom_path, e))?;
+    let region = options.region.unwrap_or(Region::from(rom.timing()));
+
+    let mut script = match options.input_path {
+        None => None,
+        Some(ref path) => {
+            Some(InputScript::load(path).map_err(|e| format!("Couldn't load {}: {}", path, e))?)
+        }
+    };
+
+    let event_bus = Rc::new(RefCell::new(EventBus::new()));
+    let screen = Rc::new(RefCell::new(io::Screen::new()));
+    let audio = Rc::new(RefCell::new(io::SimpleAudioOut::new(
+        WAV_SAMPLE_RATE as f32,
+    )));
+    audio.borrow_mut().set_region(region);
+    audio.borrow_mut().set_enabled(options.wav_path.is_some());
+    let mut nes = NES::new_with_region(event_bus, screen.clone(), audio.clone(), rom, region)
+        .map_err(|e| format!("Couldn't start {}: {}", options.rom_path, e))?;
+
+    if let Some(track) = options.track {
+        if track > nes.songs() {
+            return Err(format!("There is no track {} to play", track));
+        }
+        nes.select_song(track - 1);
+    }
+
+    // Frames are counted as the PPU finishes rendering them.
+    let mut frames: u64 = 0;
+    let mut cycles: u64 = 0;
+    let mut last_scanline = nes.ppu.borrow().scanline;
+    let mut blargg_reset_frame: Option<u64> = None;
+
+    // Audio is resampled a frame at a time, keeping in step with the master clock overall.
+    let mut samples: Vec<f32> = vec![];
+    let mut audio_cycles: u64 = 0;
+    apply_input(&mut nes, &mut script, frames);
+
+    while options.frames.is_none_or(|max| frames < max)
+        && options.cycles.is_none_or(|max| cycles < max)
+    {
+        cycles += nes.tick();
+
+        let scanline = nes.ppu.borrow().scanline;
+        if scanline == 240 && last_scanline != 240 {
+            frames += 1;
+            apply_input(&mut nes, &mut script, frames);
+
+            let due = cycles * WAV_SAMPLE_RATE as u64 / region.master_clock_hz();
+            let num_samples = due.saturating_sub(samples.len() as u64);
+            audio
+                .borrow_mut()
+                .consume(cycles - audio_cycles, num_samples, |data| {
+                    samples.extend_from_slice(data)
+                });
+            audio_cycles = cycles;
+
+            if options.blargg && blargg::has_signature(&nes) {
+                match blargg::status(&nes) {
+                    blargg::STATUS_RUNNING => (),
+                    blargg::STATUS_NEEDS_RESET => match blargg_reset_frame {
+                        None => blargg_reset_frame = Some(frames + BLARGG_RESET_DELAY_FRAMES),
+                        Some(reset_frame) if frames >= reset_frame => {
+                            nes.reset();
+                            blargg_reset_frame = None;
+                        }
+                        Some(_) => (),
+                    },
+                    _ => break,
+                }
+            }
+        }
+        last_scanline = scanline;
+    }
+
+    println!("Ran {} frames ({} cycles)", frames, cycles);
+
+    let mut frame_data = vec![0; frame::WIDTH * frame::HEIGHT * 3];
+    screen
+        .borrow()
+        .do_render(|buffer| frame_data.copy_from_slice(buffer));
+
+    if let Some(ref path) = options.png_path {
+        frame::write_png(path, &frame_data)
+            .map_err(|e| format!("Couldn't write {}: {}", path, e))?;
+    }
+
+    if let Some(ref path) = options.ppm_path {
+        frame::write_ppm(path, &frame_data)
+            .map_err(|e| format!("Couldn't write {}: {}", path, e))?;
+    }
+
+    if let Some(ref path) = options.wav_path {
+        wav::write_wav(path, WAV_SAMPLE_RATE, &samples)
+            .map_err(|e| format!("Couldn't write {}: {}", path, e))?;
+    }
+
+    if options.md5 {
+        println!("MD5: {}", frame::md5_hex(&frame_data));
+    }
+
+    if options.blargg {
+        if !blargg::has_signature(&nes) {
+            return Err(String::from("No blargg test output found at $6000"));
+        }
+
+        let status = blargg::status(&nes);
+        println!("{}", blargg::output(&nes));
+        if status >= blargg::STATUS_RUNNING {
+            return Err(format!("Test still running (status ${:02X})", status));
+        }
+        println!("Result: {}", status);
+        return Ok(status as i32);
+    }
+
+    Ok(0)
+}
+
+fn apply_input(nes: &mut NES, script: &mut Option<InputScript>, frame: u64) {
+    let buttons = match script.as_mut().and_then(|s| s.buttons_for_frame(frame)) {
+        None => return,
+        Some(buttons) => buttons,
+    };
+
+    let mut joy1 = nes.joy1.borrow_mut();
+    for button in script::ALL_BUTTONS.iter() {
+        joy1.set_pressed(*button, buttons.contains(button));
+    }
+}
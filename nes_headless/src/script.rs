@@ -0,0 +1,141 @@
+use std::fs;
+use std::path::Path;
+
+use nes::emulator::controller::Button;
+
+// A scripted sequence of inputs for controller 1.
+//
+// Each line gives a frame number followed by the buttons to hold from that frame onwards, until
+// the next line.  "-" releases all buttons, and anything after a '#' is a comment.  e.g.
+//
+//     # Press start to get past the title screen, then walk right.
+//     60   start
+//     62   -
+//     120  right b
+pub struct InputScript {
+    entries: Vec<(u64, Vec<Button>)>,
+    next_ix: usize,
+}
+
+impl InputScript {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<InputScript, String> {
+        let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        InputScript::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<InputScript, String> {
+        let mut entries: Vec<(u64, Vec<Button>)> = Vec::new();
+        for (ix, line) in text.lines().enumerate() {
+            let line_num = ix + 1;
+            let line = line.split('#').next().unwrap_or("");
+            let mut words = line.split_whitespace();
+
+            let frame = match words.next() {
+                None => continue,
+                Some(word) => word
+                    .parse::<u64>()
+                    .map_err(|_| format!("line {}: invalid frame number \"{}\"", line_num, word))?,
+            };
+
+            if let Some((prev_frame, _)) = entries.last()
+                && frame <= *prev_frame
+            {
+                return Err(format!(
+                    "line {}: frame {} must come after frame {}",
+                    line_num, frame, prev_frame
+                ));
+            }
+
+            let mut buttons = vec![];
+            for word in words {
+                if word == "-" {
+                    continue;
+                }
+                match parse_button(word) {
+                    None => return Err(format!("line {}: unknown button \"{}\"", line_num, word)),
+                    Some(button) => buttons.push(button),
+                }
+            }
+
+            entries.push((frame, buttons));
+        }
+
+        Ok(InputScript {
+            entries,
+            next_ix: 0,
+        })
+    }
+
+    // Should be called once for each frame, in order.
+    // Returns the buttons to hold from this frame onwards, if they change on this frame.
+    pub fn buttons_for_frame(&mut self, frame: u64) -> Option<&[Button]> {
+        match self.entries.get(self.next_ix) {
+            Some((entry_frame, _)) if *entry_frame <= frame => {
+                self.next_ix += 1;
+                Some(&self.entries[self.next_ix - 1].1)
+            }
+            _ => None,
+        }
+    }
+}
+
+pub const ALL_BUTTONS: [Button; 8] = [
+    Button::A,
+    Button::B,
+    Button::Select,
+    Button::Start,
+    Button::Up,
+    Button::Down,
+    Button::Left,
+    Button::Right,
+];
+
+fn parse_button(name: &str) -> Option<Button> {
+    match name.to_ascii_lowercase().as_str() {
+        "a" => Some(Button::A),
+        "b" => Some(Button::B),
+        "select" => Some(Button::Select),
+        "start" => Some(Button::Start),
+        "up" => Some(Button::Up),
+        "down" => Some(Button::Down),
+        "left" => Some(Button::Left),
+        "right" => Some(Button::Right),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use nes::emulator::controller::Button;
+
+    use crate::script::InputScript;
+
+    #[test]
+    fn test_parse_script() {
+        let mut script = InputScript::parse(
+            "# Title screen.\n\
+             60 start\n\
+             \n\
+             62 -   # Let go.\n\
+             120 Right B\n",
+        )
+        .unwrap();
+
+        assert_eq!(script.buttons_for_frame(0), None);
+        assert_eq!(script.buttons_for_frame(60), Some(&[Button::Start][..]));
+        assert_eq!(script.buttons_for_frame(61), None);
+        assert_eq!(script.buttons_for_frame(62), Some(&[][..]));
+        assert_eq!(
+            script.buttons_for_frame(120),
+            Some(&[Button::Right, Button::B][..])
+        );
+        assert_eq!(script.buttons_for_frame(121), None);
+    }
+
+    #[test]
+    fn test_parse_script_errors() {
+        assert!(InputScript::parse("sixty start").is_err());
+        assert!(InputScript::parse("60 turbo").is_err());
+        assert!(InputScript::parse("60 start\n30 a").is_err());
+    }
+}
@@ -143,6 +143,12 @@ impl Compositor {
         };
     }
 
+    pub fn set_debug_window_title(&mut self, title: &str) {
+        if let Err(cause) = self.debug_canvas.window_mut().set_title(title) {
+            panic!("failed to set window title: {}", cause);
+        }
+    }
+
     pub fn set_debug(&mut self, mode: DebugMode) {
         if mode == self.debug_mode {
             return;
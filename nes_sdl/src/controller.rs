@@ -1,6 +1,7 @@
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fs::{File, create_dir_all};
+use std::io::{Read, Write};
 use std::path::PathBuf;
 use std::rc::Rc;
 
@@ -11,11 +12,14 @@ use flate2::write::GzEncoder;
 use serde::Serialize;
 use serde_json::Serializer;
 
+use nes::emulator::NES;
+use nes::emulator::debugger::Access;
 use nes::emulator::io::event::{Event, EventHandler, Key};
 use nes::emulator::io::{Screen, SimpleAudioOut};
+use nes::emulator::rewind::Rewinder;
 use nes::emulator::state::SaveState;
-use nes::emulator::{NES, NES_MASTER_CLOCK_HZ};
 
+use crate::debugger::{DebugCommand, HELP, Register};
 use crate::portal::Portal;
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
@@ -31,19 +35,26 @@ pub struct EmulatorState {
     pub is_tracing: bool,
     pub target_hz: u64,
     pub debug_mode: DebugMode,
+    // The NSF song playing, if any.
+    pub song: Option<usize>,
 }
 
 impl EmulatorState {
-    pub fn new() -> EmulatorState {
+    pub fn new(target_hz: u64) -> EmulatorState {
         EmulatorState {
             is_running: true,
             is_tracing: false,
-            target_hz: NES_MASTER_CLOCK_HZ,
+            target_hz,
             debug_mode: DebugMode::APU,
+            song: None,
         }
     }
 }
 
+// Keep about 8 minutes of rewind history at full speed, with a snapshot every frame.
+const REWIND_BUDGET_BYTES: usize = 32 * 1024 * 1024;
+const REWIND_KEYFRAME_INTERVAL: usize = 60;
+
 fn save_state_dir() -> PathBuf {
     let mut path = match dirs::data_dir() {
         Some(path) => path,
@@ -61,6 +72,29 @@ fn save_state_file_path(name: &str) -> PathBuf {
     state_file_path
 }
 
+fn sram_file_path(name: &str) -> PathBuf {
+    let mut sram_file_path = save_state_dir();
+    sram_file_path.push(format!("{}.sav", name));
+    sram_file_path
+}
+
+fn save_sram(data: &[u8], name: &str) -> Result<(), String> {
+    create_dir_all(save_state_dir()).map_err(|e| e.to_string())?;
+    let mut sram_file = File::create(sram_file_path(name)).map_err(|e| e.to_string())?;
+    sram_file.write_all(data).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn load_sram(nes: &mut NES, name: &str) -> Result<(), String> {
+    let mut sram_file = File::open(sram_file_path(name)).map_err(|e| e.to_string())?;
+    let mut data = vec![];
+    sram_file
+        .read_to_end(&mut data)
+        .map_err(|e| e.to_string())?;
+    nes.load_sram(&data);
+    Ok(())
+}
+
 fn save_state(nes: &mut NES, name: &str) -> Result<(), String> {
     create_dir_all(save_state_dir()).map_err(|e| e.to_string())?;
     let state_file = File::create(save_state_file_path(name)).map_err(|e| e.to_string())?;
@@ -96,6 +130,20 @@ pub struct Controller {
     audio_output: Rc<RefCell<SimpleAudioOut>>,
     key_states: HashMap<Key, bool>,
     state_portal: Portal<EmulatorState>,
+
+    // Set while stopped in the debugger.
+    paused: bool,
+
+    // Rewind is active while the hotkey is held, and stops at the oldest snapshot.
+    rewinder: Rewinder,
+    rewinding: bool,
+    rewind_exhausted: bool,
+
+    // The Disk System side that was last in the drive, so the next one goes in after ejecting.
+    last_disk_side: usize,
+
+    // Names of the NSF songs, for the track list.
+    track_names: Vec<String>,
 }
 
 impl Controller {
@@ -112,15 +160,117 @@ impl Controller {
             audio_output,
             key_states: HashMap::new(),
             state_portal,
+            paused: false,
+            rewinder: Rewinder::new(REWIND_BUDGET_BYTES, 1, REWIND_KEYFRAME_INTERVAL),
+            rewinding: false,
+            rewind_exhausted: false,
+            last_disk_side: 0,
+            track_names: Vec::new(),
         }
     }
 
     pub fn tick(&mut self) -> u64 {
-        self.nes.tick()
+        if self.is_halted() {
+            return 0;
+        }
+        let cycles = self.nes.tick();
+        self.check_break();
+        cycles
     }
 
     pub fn tick_multi(&mut self, ticks: u32) -> u64 {
-        self.nes.tick_multi(ticks)
+        if self.is_halted() {
+            return 0;
+        }
+        let cycles = self.nes.tick_multi(ticks);
+        self.check_break();
+        cycles
+    }
+
+    // Should be called at the start of each frame, to take or restore rewind snapshots.
+    pub fn start_frame(&mut self) {
+        if self.paused {
+            return;
+        }
+
+        if self.rewinding {
+            self.rewind_exhausted = !self.rewinder.rewind(&mut self.nes);
+        } else {
+            self.rewinder.frame(&mut self.nes);
+        }
+    }
+
+    // Whether emulation is stopped, either in the debugger or because there's nothing left to
+    // rewind.
+    pub fn is_halted(&self) -> bool {
+        self.paused || (self.rewinding && self.rewind_exhausted)
+    }
+
+    fn set_rewinding(&mut self, on: bool) {
+        if on == self.rewinding {
+            return;
+        }
+
+        self.rewinding = on;
+        self.rewind_exhausted = false;
+        if on {
+            // Rewound audio just sounds like noise, so mute it.
+            self.audio_output.borrow_mut().set_enabled(false);
+        } else {
+            let target_hz = self.target_hz();
+            self.set_target_hz(target_hz);
+        }
+        println!("Rewind: {}", if on { "ON" } else { "OFF" });
+    }
+
+    pub fn set_track_names(&mut self, names: Vec<String>) {
+        self.track_names = names;
+    }
+
+    // Moves through the NSF songs, wrapping around at either end.
+    fn change_song(&mut self, offset: isize) {
+        let songs = self.nes.songs() as isize;
+        if songs == 0 {
+            return;
+        }
+
+        let song = self.nes.song().unwrap_or(0) as isize;
+        self.nes
+            .select_song((song + offset).rem_euclid(songs) as usize);
+        self.show_song();
+    }
+
+    pub fn show_song(&self) {
+        let song = self.nes.song();
+        if let Some(name) = song.and_then(|song| self.track_names.get(song)) {
+            println!("Playing {}", name);
+        }
+        self.state_portal.consume(|state| state.song = song);
+    }
+
+    // Ejects the disk, or inserts the next side after the one that was ejected.
+    fn swap_disk(&mut self) {
+        let sides = self.nes.disk_sides();
+        if sides == 0 {
+            return;
+        }
+
+        match self.nes.disk_side() {
+            Some(side) => {
+                self.last_disk_side = side;
+                self.nes.insert_disk(None);
+                println!("Disk ejected");
+            }
+            None => {
+                let side = (self.last_disk_side + 1) % sides;
+                self.nes.insert_disk(Some(side));
+                println!(
+                    "Inserted disk {} side {}",
+                    side / 2 + 1,
+                    ["A", "B"][side % 2]
+                );
+            }
+        }
     }
 
     pub fn is_running(&self) -> bool {
@@ -157,6 +307,34 @@ impl Controller {
         self.nes.reset();
     }
 
+    fn sram_name(&self) -> String {
+        match self.rom_name {
+            Some(ref name) => name.clone(),
+            None => String::from("unknown"),
+        }
+    }
+
+    pub fn load_sram(&mut self) {
+        if !self.nes.has_battery() {
+            return;
+        }
+
+        let name = self.sram_name();
+        match load_sram(&mut self.nes, &name) {
+            Err(cause) => println!("No battery save loaded for {}: {}", name, cause),
+            Ok(_) => println!("Loaded battery save: {}", name),
+        };
+    }
+
+    pub fn flush_sram(&mut self) {
+        if let Some(data) = self.nes.flush_sram() {
+            let name = self.sram_name();
+            if let Err(cause) = save_sram(&data, &name) {
+                println!("Failed to write battery save: {}", cause);
+            }
+        }
+    }
+
     pub fn set_target_hz(&mut self, hz: u64) {
         self.state_portal.consume(|state| state.target_hz = hz);
         self.screen.borrow_mut().set_double_buffering(hz > 200_000);
@@ -198,11 +376,106 @@ impl Controller {
     pub fn debug_print(&mut self, start: u16, len: u16) {
         println!("CPU Memory starting from ${:X}", start);
         for ix in 0..len {
-            print!("{:02X} ", self.nes.cpu.borrow_mut().load_memory(start + ix));
+            print!("{:02X} ", self.nes.cpu.borrow_mut().peek_memory(start + ix));
         }
         println!("");
     }
 
+    fn check_break(&mut self) {
+        if let Some(reason) = self.nes.take_break() {
+            println!("{}", reason);
+            self.pause();
+        }
+    }
+
+    fn pause(&mut self) {
+        self.paused = true;
+        self.nes.cancel_step();
+        println!("{}", self.nes.debug_format_instruction());
+    }
+
+    // Steps run with the emulator unpaused, and pause it again when they finish.
+    fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn handle_debug_command(&mut self, command: DebugCommand) {
+        match command {
+            DebugCommand::Break(pc) => {
+                self.nes.add_breakpoint(pc);
+                println!("Breakpoint at ${:04X}", pc);
+            }
+            DebugCommand::Delete(address) => {
+                let removed_breakpoint = self.nes.remove_breakpoint(address);
+                let removed_watchpoints = self.nes.remove_watchpoints(address);
+                if !removed_breakpoint && !removed_watchpoints {
+                    println!("Nothing to delete at ${:04X}", address);
+                }
+            }
+            DebugCommand::Watch(watchpoint) => self.nes.add_watchpoint(watchpoint),
+            DebugCommand::List => {
+                for pc in self.nes.breakpoints() {
+                    println!("Break at ${:04X}", pc);
+                }
+                for watchpoint in self.nes.watchpoints() {
+                    let access = match watchpoint.access {
+                        Access::Read => "reads",
+                        Access::Write => "writes",
+                        Access::Execute => "execution",
+                    };
+                    println!(
+                        "Watch {} at ${:04X}-${:04X}",
+                        access, watchpoint.start, watchpoint.end
+                    );
+                }
+            }
+            DebugCommand::Pause => self.pause(),
+            DebugCommand::Continue => self.resume(),
+            DebugCommand::Step => {
+                self.nes.step();
+                self.resume();
+            }
+            DebugCommand::Next => {
+                self.nes.step_over();
+                self.resume();
+            }
+            DebugCommand::Finish => {
+                self.nes.step_out();
+                self.resume();
+            }
+            DebugCommand::Scanline(scanline) => {
+                self.nes.run_to_scanline(scanline);
+                self.resume();
+            }
+            DebugCommand::Registers => println!("{}", self.nes.debug_format_instruction()),
+            DebugCommand::SetRegister(register, value) => {
+                let mut cpu = self.nes.cpu.borrow_mut();
+                let mut registers = cpu.registers();
+                match register {
+                    Register::A => registers.a = value as u8,
+                    Register::X => registers.x = value as u8,
+                    Register::Y => registers.y = value as u8,
+                    Register::SP => registers.sp = value as u8,
+                    Register::PC => registers.pc = value,
+                    Register::P => registers.p = value as u8,
+                }
+                cpu.set_registers(registers);
+            }
+            DebugCommand::SetFlag(flag, on) => self.nes.cpu.borrow_mut().set_flag(flag, on),
+            DebugCommand::Memory(start, len) => {
+                for row_start in (0..len).step_by(16) {
+                    let address = start.wrapping_add(row_start);
+                    print!("${:04X}:", address);
+                    for ix in 0..16.min(len - row_start) {
+                        print!(" {:02X}", self.nes.debug_read(address.wrapping_add(ix)));
+                    }
+                    println!();
+                }
+            }
+            DebugCommand::Help => println!("{}", HELP),
+        }
+    }
+
     fn handle_num_key(&mut self, num: u8) {
         let shift_modifier = *self.key_states.get(&Key::Shift).unwrap_or(&false);
         let ctrl_modifier = *self.key_states.get(&Key::Control).unwrap_or(&false);
@@ -228,17 +501,18 @@ impl Controller {
             };
         } else {
             // Set speed.
+            let master_clock_hz = self.nes.region().master_clock_hz();
             let target_hz = match num {
                 1 => 0,          // Paused.
                 2 => 20_000,     // Scanlines.
                 3 => 200_000,    // Frames.
                 4 => 2_000_000,  // 1/10 Slow-mo.
                 5 => 10_000_000, // 1/2 Slow-mo.
-                6 => NES_MASTER_CLOCK_HZ,
-                7 => NES_MASTER_CLOCK_HZ * 2,
-                8 => NES_MASTER_CLOCK_HZ * 3,
-                9 => NES_MASTER_CLOCK_HZ * 4,
-                0 => NES_MASTER_CLOCK_HZ * 5,
+                6 => master_clock_hz,
+                7 => master_clock_hz * 2,
+                8 => master_clock_hz * 3,
+                9 => master_clock_hz * 4,
+                0 => master_clock_hz * 5,
                 _ => panic!("Unexpected num key: {}", num),
             };
             self.set_target_hz(target_hz);
@@ -281,11 +555,18 @@ impl EventHandler for Controller {
                     Key::Num9 => self.handle_num_key(9),
                     Key::Num0 => self.handle_num_key(0),
                     Key::Backspace => self.reset(),
+                    Key::R => self.set_rewinding(true),
+                    Key::D => self.swap_disk(),
+                    Key::Left => self.change_song(-1),
+                    Key::Right => self.change_song(1),
                     _ => (),
                 };
             }
             Event::KeyUp(key) => {
                 self.key_states.insert(key, false);
+                if key == Key::R {
+                    self.set_rewinding(false);
+                }
             }
         };
     }
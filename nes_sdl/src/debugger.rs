@@ -0,0 +1,167 @@
+use std::io::BufRead;
+use std::sync::mpsc::{Receiver, channel};
+
+use nes::emulator::cpu::Flag;
+use nes::emulator::debugger::{Access, Watchpoint};
+
+pub const HELP: &str =
+    "Debugger commands (addresses and values are hex, optionally prefixed with $):
+    break ADDR            (b)  Break when the PC reaches ADDR
+    delete ADDR           (d)  Remove breakpoints and watchpoints at ADDR
+    watch r|w|x ADDR[-END] (w) Break on reads, writes or execution in a range
+    list                  (l)  List breakpoints and watchpoints
+    pause                 (p)  Break now
+    continue              (c)  Resume emulation
+    step                  (s)  Run one instruction
+    next                  (n)  Run one instruction, stepping over subroutine calls
+    finish                (f)  Run until the current subroutine returns
+    scanline N                 Run until the PPU reaches scanline N (decimal)
+    regs                  (r)  Show the registers and next instruction
+    set a|x|y|sp|pc|p VALUE    Set a register
+    set c|z|i|d|b|v|n 0|1      Set a status flag
+    mem ADDR [LEN]        (m)  Dump memory
+    help                  (h)  Show this message";
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Register {
+    A,
+    X,
+    Y,
+    SP,
+    PC,
+    P,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DebugCommand {
+    Break(u16),
+    Delete(u16),
+    Watch(Watchpoint),
+    List,
+    Pause,
+    Continue,
+    Step,
+    Next,
+    Finish,
+    Scanline(u16),
+    Registers,
+    SetRegister(Register, u16),
+    SetFlag(Flag, bool),
+    Memory(u16, u16),
+    Help,
+}
+
+// Reads debugger commands from stdin on a separate thread, so the emulator never blocks on it.
+pub fn spawn_stdin_reader() -> Receiver<DebugCommand> {
+    let (sender, receiver) = channel();
+    std::thread::spawn(move || {
+        let stdin = std::io::stdin();
+        for line in stdin.lock().lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            match parse_command(&line) {
+                Ok(command) => {
+                    if sender.send(command).is_err() {
+                        break;
+                    }
+                }
+                Err(cause) => println!("{}.  Type \"help\" for a list of commands.", cause),
+            }
+        }
+    });
+    receiver
+}
+
+pub fn parse_command(line: &str) -> Result<DebugCommand, String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let arg = |ix: usize| {
+        words
+            .get(ix)
+            .cloned()
+            .ok_or(format!("\"{}\" needs more arguments", words[0]))
+    };
+
+    let command = match words.first().cloned().unwrap_or("") {
+        "break" | "b" => DebugCommand::Break(parse_hex(arg(1)?)?),
+        "delete" | "d" => DebugCommand::Delete(parse_hex(arg(1)?)?),
+        "watch" | "w" => {
+            let access = match arg(1)? {
+                "r" => Access::Read,
+                "w" => Access::Write,
+                "x" => Access::Execute,
+                other => return Err(format!("Unknown access type \"{}\"", other)),
+            };
+            let (start, end) = match arg(2)?.split_once('-') {
+                Some((start, end)) => (parse_hex(start)?, parse_hex(end)?),
+                None => {
+                    let address = parse_hex(arg(2)?)?;
+                    (address, address)
+                }
+            };
+            DebugCommand::Watch(Watchpoint::new(start, end, access))
+        }
+        "list" | "l" => DebugCommand::List,
+        "pause" | "p" => DebugCommand::Pause,
+        "continue" | "c" => DebugCommand::Continue,
+        "step" | "s" => DebugCommand::Step,
+        "next" | "n" => DebugCommand::Next,
+        "finish" | "f" => DebugCommand::Finish,
+        "scanline" => DebugCommand::Scanline(
+            arg(1)?
+                .parse()
+                .map_err(|_| format!("Invalid scanline \"{}\"", words[1]))?,
+        ),
+        "regs" | "r" => DebugCommand::Registers,
+        "set" => {
+            let value = parse_hex(arg(2)?)?;
+            let byte = || {
+                u8::try_from(value)
+                    .map(u16::from)
+                    .map_err(|_| format!("Value \"{}\" doesn't fit in a byte", words[2]))
+            };
+            let bit = || match value {
+                0 | 1 => Ok(value == 1),
+                _ => Err(format!("Value \"{}\" should be 0 or 1", words[2])),
+            };
+            match arg(1)?.to_ascii_lowercase().as_str() {
+                "a" => DebugCommand::SetRegister(Register::A, byte()?),
+                "x" => DebugCommand::SetRegister(Register::X, byte()?),
+                "y" => DebugCommand::SetRegister(Register::Y, byte()?),
+                "sp" => DebugCommand::SetRegister(Register::SP, byte()?),
+                "pc" => DebugCommand::SetRegister(Register::PC, value),
+                "p" => DebugCommand::SetRegister(Register::P, byte()?),
+                "c" => DebugCommand::SetFlag(Flag::C, bit()?),
+                "z" => DebugCommand::SetFlag(Flag::Z, bit()?),
+                "i" => DebugCommand::SetFlag(Flag::I, bit()?),
+                "d" => DebugCommand::SetFlag(Flag::D, bit()?),
+                "b" => DebugCommand::SetFlag(Flag::B, bit()?),
+                "v" => DebugCommand::SetFlag(Flag::V, bit()?),
+                "n" => DebugCommand::SetFlag(Flag::N, bit()?),
+                other => return Err(format!("Unknown register \"{}\"", other)),
+            }
+        }
+        "mem" | "m" => {
+            let len = match words.get(2) {
+                None => 0x10,
+                Some(len) => parse_hex(len)?,
+            };
+            DebugCommand::Memory(parse_hex(arg(1)?)?, len)
+        }
+        "help" | "h" => DebugCommand::Help,
+        other => return Err(format!("Unknown command \"{}\"", other)),
+    };
+
+    Ok(command)
+}
+
+fn parse_hex(text: &str) -> Result<u16, String> {
+    u16::from_str_radix(text.trim_start_matches('$'), 16)
+        .map_err(|_| format!("Invalid hex value \"{}\"", text))
+}
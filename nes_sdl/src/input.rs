@@ -59,6 +59,7 @@ fn convert_sdl_keycode_to_internal(keycode: Keycode) -> Option<Key> {
         Keycode::O => Some(Key::O),
         Keycode::P => Some(Key::P),
         Keycode::Q => Some(Key::Q),
+        Keycode::R => Some(Key::R),
         Keycode::S => Some(Key::S),
         Keycode::T => Some(Key::T),
         Keycode::U => Some(Key::U),
@@ -1,50 +1,102 @@
 pub mod audio;
 pub mod compositor;
 pub mod controller;
+pub mod debugger;
 pub mod governer;
 pub mod input;
 pub mod portal;
 
 use std::cell::RefCell;
 use std::env;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::sync::mpsc::Receiver;
 use std::sync::{Arc, Condvar, Mutex};
 use std::time::Duration;
 
 use nes::emulator::NES;
 use nes::emulator::apu::debug::APUDebug;
-use nes::emulator::ines;
 use nes::emulator::io;
 use nes::emulator::io::event::{Event, EventBus};
+use nes::emulator::media::{self, Media};
+use nes::emulator::nsf::NSF;
 use nes::emulator::ppu::debug::{PPUDebug, PPUDebugRender};
+use nes::emulator::region::Region;
 
 use crate::audio::{AudioQueue, SAMPLE_RATE};
 use crate::compositor::Compositor;
 use crate::controller::{Controller, DebugMode, EmulatorState};
+use crate::debugger::DebugCommand;
 use crate::governer::Governer;
 use crate::input::InputPump;
 use crate::portal::Portal;
 
 pub const RENDER_FPS: u64 = 60;
 
+// How often to write battery-backed SRAM to disk while running.
+pub const SRAM_FLUSH_INTERVAL_FRAMES: u64 = RENDER_FPS * 10;
+
+pub const FDS_BIOS_FILE_NAME: &str = "disksys.rom";
+
 fn main() {
     // -- Handle Args --
 
-    let args: Vec<String> = env::args().collect();
+    // The Famicom Disk System BIOS defaults to disksys.rom next to the disk image.
+    let mut fds_bios_path = None;
+    let args: Vec<String> = env::args()
+        .filter(|arg| match arg.strip_prefix("--fds-bios=") {
+            Some(path) => {
+                fds_bios_path = Some(PathBuf::from(path));
+                false
+            }
+            None => true,
+        })
+        .collect();
 
     let rom_path = match args.get(1) {
-        None => panic!("You must pass in a path to a iNes ROM file."),
+        None => panic!("You must pass in a path to a iNes ROM or FDS disk image file."),
         Some(path) => path,
     };
 
+    // Optionally override the region from the ROM header, e.g. for old iNES dumps of PAL games.
+    let region_override = match args.get(2).map(|name| name.parse::<Region>()) {
+        None => None,
+        Some(Ok(region)) => Some(region),
+        Some(Err(cause)) => {
+            eprintln!("{}", cause);
+            std::process::exit(1);
+        }
+    };
+
     // -- Initialize --
 
-    let rom = ines::ROM::load(rom_path);
+    let fds_bios_path =
+        fds_bios_path.unwrap_or_else(|| Path::new(rom_path).with_file_name(FDS_BIOS_FILE_NAME));
+    let rom = match media::load(rom_path, &fds_bios_path) {
+        Ok(rom) => rom,
+        Err(cause) => {
+            eprintln!("Couldn't load {}: {}", rom_path, cause);
+            std::process::exit(1);
+        }
+    };
     let rom_name = Path::new(rom_path)
         .file_stem()
         .map(|s| s.to_string_lossy().to_string())
         .unwrap_or(String::from("unknown"));
+    let region = region_override.unwrap_or(Region::from(rom.timing()));
+
+    // NSF music shows the APU waveforms with a track list, instead of a picture.
+    let nsf = NSF::load(rom_path).ok();
+    let track_names: Vec<String> = match nsf {
+        None => Vec::new(),
+        Some(ref nsf) => (0..nsf.songs())
+            .map(|song| match nsf.track_label(song) {
+                Some(label) => format!("Track {}/{}: {}", song + 1, nsf.songs(), label),
+                None => format!("Track {}/{}", song + 1, nsf.songs()),
+            })
+            .collect(),
+    };
+    let emu_track_names = track_names.clone();
 
     let sdl_context = sdl2::init().unwrap();
     let video = sdl_context.video().unwrap();
@@ -67,26 +119,44 @@ fn main() {
     let mut audio_queue = AudioQueue::new(audio, audio_portal.clone());
     let mut input = InputPump::new(sdl_context.event_pump().unwrap(), event_portal.clone());
 
-    compositor.set_window_title(&format!("[NES] {}", rom_name));
+    match nsf {
+        Some(ref nsf) if !nsf.title().is_empty() => {
+            compositor.set_window_title(&format!("[NSF] {} - {}", nsf.title(), nsf.artist()))
+        }
+        Some(_) => compositor.set_window_title(&format!("[NSF] {}", rom_name)),
+        None => compositor.set_window_title(&format!("[NES] {}", rom_name)),
+    }
 
-    let state = Portal::new(EmulatorState::new());
+    let state = Portal::new(EmulatorState::new(region.master_clock_hz()));
     let emu_state = state.clone();
 
+    // Debugger commands are typed into the terminal.
+    let debug_commands = debugger::spawn_stdin_reader();
+
     let ui_sync = Arc::new((Mutex::new(()), Condvar::new()));
     let emu_sync = ui_sync.clone();
 
     // -- Run --
-    let _ = std::thread::spawn(std::panic::AssertUnwindSafe(move || {
+    let emu_thread = std::thread::spawn(std::panic::AssertUnwindSafe(move || {
         let event_bus = Rc::new(RefCell::new(EventBus::new()));
         let video_output = Rc::new(RefCell::new(io::Screen::new()));
         let audio_output = Rc::new(RefCell::new(io::SimpleAudioOut::new(SAMPLE_RATE)));
 
-        let nes = NES::new(
+        let nes = match NES::new_with_region(
             event_bus.clone(),
             video_output.clone(),
             audio_output.clone(),
             rom,
-        );
+            region,
+        ) {
+            Ok(nes) => nes,
+            Err(cause) => {
+                eprintln!("Couldn't start {}: {}", rom_name, cause);
+                emu_state.consume(|state| state.is_running = false);
+                return;
+            }
+        };
+        audio_output.borrow_mut().set_region(nes.region());
         let ppu_debug = PPUDebug::new(nes.ppu.clone());
         let apu_debug = APUDebug::new(nes.apu.clone());
 
@@ -97,7 +167,10 @@ fn main() {
             emu_state,
         )));
         controller.borrow_mut().set_rom_name(&rom_name);
+        controller.borrow_mut().load_sram();
         controller.borrow_mut().start();
+        controller.borrow_mut().set_track_names(emu_track_names);
+        controller.borrow().show_song();
         event_bus
             .borrow_mut()
             .register(Box::new(controller.clone()));
@@ -114,6 +187,7 @@ fn main() {
             audio_portal.clone(),
             event_bus.clone(),
             event_portal.clone(),
+            debug_commands,
         );
     }));
 
@@ -124,6 +198,7 @@ fn main() {
             &mut audio_queue,
             &mut input,
             state.clone(),
+            &track_names,
         );
     }));
 
@@ -133,6 +208,10 @@ fn main() {
             println!("Panic in main loop.  Exiting.");
         }
     }
+
+    // Let the emulator thread finish up so that battery saves get written out.
+    state.consume(|state| state.is_running = false);
+    let _ = emu_thread.join();
 }
 
 fn ui_loop(
@@ -141,13 +220,23 @@ fn ui_loop(
     audio_queue: &mut AudioQueue,
     input: &mut InputPump,
     state_portal: Portal<EmulatorState>,
+    track_names: &[String],
 ) {
+    let mut shown_song = None;
     while state_portal.consume(|state| state.is_running) {
         audio_queue.flush();
         compositor.render();
         input.pump();
         compositor.set_debug(state_portal.consume(|state| state.debug_mode));
 
+        let song = state_portal.consume(|state| state.song);
+        if song != shown_song {
+            if let Some(name) = song.and_then(|song| track_names.get(song)) {
+                compositor.set_debug_window_title(name);
+            }
+            shown_song = song;
+        }
+
         let &(ref lock, ref cvar) = &*sync;
         let guard = lock.lock().unwrap();
         let _ = cvar
@@ -169,6 +258,7 @@ fn main_loop(
     audio_portal: Portal<Vec<f32>>,
     event_bus: Rc<RefCell<EventBus>>,
     event_portal: Portal<Vec<Event>>,
+    debug_commands: Receiver<DebugCommand>,
 ) {
     let mut frame_count: u64 = 0;
     let mut agg_cycles: u64 = 0;
@@ -186,7 +276,17 @@ fn main_loop(
                 .for_each(|e| event_bus.borrow_mut().broadcast(e));
         });
 
-        while cycles_this_frame < target_frame_cycles && !governer.taking_too_long() {
+        while let Ok(command) = debug_commands.try_recv() {
+            controller.borrow_mut().handle_debug_command(command);
+        }
+
+        controller.borrow_mut().start_frame();
+
+        // Ticking stops while paused in the debugger, but the UI keeps running.
+        while cycles_this_frame < target_frame_cycles
+            && !governer.taking_too_long()
+            && !controller.borrow().is_halted()
+        {
             // Batching ticks here is a massive perf win since finding the elapsed time is costly.
             cycles_this_frame += controller.borrow_mut().tick_multi(100);
         }
@@ -247,7 +347,14 @@ fn main_loop(
             );
             agg_cycles = 0;
         }
+
+        // Periodically persist battery-backed SRAM in case we don't exit cleanly.
+        if frame_count % SRAM_FLUSH_INTERVAL_FRAMES == 0 {
+            controller.borrow_mut().flush_sram();
+        }
     }
+
+    controller.borrow_mut().flush_sram();
 }
 
 fn copy_buffer(src_buf: &[u8], tgt_buf: &mut [u8]) {
@@ -1,8 +1,6 @@
 const toArrayBuffer = array => array.buffer.slice(array.byteOffset, array.byteLength + array.byteOffset)
 
-const CYCLES_PER_SECOND = 21477272;
 const FRAMES_PER_SECOND = (1000 / 16);
-const CYCLES_PER_FRAME = CYCLES_PER_SECOND / FRAMES_PER_SECOND;
 
 import("nes_web")
     .then(({ Emulator, Event, Key }) => {
@@ -37,11 +35,18 @@ import("nes_web")
 
             var running = true;
 
-            nes = Emulator.new(new Uint8Array(buf));
+            try {
+                nes = Emulator.new(new Uint8Array(buf));
+            } catch (e) {
+                console.error("Failed to load ROM:", e);
+                return;
+            }
+
+            const cyclesPerFrame = BigInt(Math.round(Number(nes.master_clock_hz()) / FRAMES_PER_SECOND));
 
             function step() {
                 var cycles = BigInt(0);
-                while (cycles <= CYCLES_PER_FRAME) {
+                while (cycles <= cyclesPerFrame) {
                     cycles += nes.run(100);
                 }
 
@@ -20,20 +20,25 @@ pub struct Emulator {
 
 #[wasm_bindgen]
 impl Emulator {
-    pub fn new(rom_data: Vec<u8>) -> Emulator {
+    pub fn new(rom_data: Vec<u8>) -> Result<Emulator, JsError> {
         let event_bus = Rc::new(RefCell::new(EventBus::new()));
         let video_out = Rc::new(RefCell::new(io::Screen::new()));
         let audio_out = Rc::new(RefCell::new(io::SimpleAudioOut::new(48_000.0)));
-        let rom = ines::ROM::from_bytes(rom_data);
+        let rom = ines::ROM::from_bytes(rom_data)?;
 
-        let nes = NES::new(event_bus.clone(), video_out.clone(), audio_out.clone(), rom);
+        let nes = NES::new(event_bus.clone(), video_out.clone(), audio_out.clone(), rom)?;
+        audio_out.borrow_mut().set_region(nes.region());
 
-        Emulator {
+        Ok(Emulator {
             nes,
             event_bus,
             video_out,
             audio_out,
-        }
+        })
+    }
+
+    pub fn master_clock_hz(&self) -> u64 {
+        self.nes.region().master_clock_hz()
     }
 
     pub fn run(&mut self, ticks: u32) -> u64 {